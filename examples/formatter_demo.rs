@@ -15,6 +15,8 @@ fn main() {
             message: "Add beautiful output formatting".to_string(),
             is_head: true,
             branch: Some("main".to_string()),
+            signature: None,
+            parents: Vec::new(),
         },
         CommitInfo {
             hash: "f7h8i9j0k1l2m3n4".to_string(),
@@ -23,6 +25,8 @@ fn main() {
             message: "Implement Unicode formatter".to_string(),
             is_head: false,
             branch: None,
+            signature: None,
+            parents: Vec::new(),
         },
         CommitInfo {
             hash: "m3n4o5p6q7r8s9t0".to_string(),
@@ -31,6 +35,8 @@ fn main() {
             message: "Initial project setup".to_string(),
             is_head: false,
             branch: None,
+            signature: None,
+            parents: Vec::new(),
         },
     ];
 
@@ -86,6 +92,7 @@ fn main() {
             DiffLine::Removed("    deprecated_call();".to_string()),
             DiffLine::Context("}".to_string()),
         ],
+        binary: false,
     }];
     println!("{}\n", formatter.format_diff(&diff_hunks));
 
@@ -112,6 +119,8 @@ fn main() {
         message: "Test commit".to_string(),
         is_head: true,
         branch: Some("ascii-test".to_string()),
+        signature: None,
+        parents: Vec::new(),
     }];
     println!("{}\n", ascii_formatter.format_log(&simple_commits));
 
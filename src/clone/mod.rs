@@ -1,6 +1,6 @@
 pub mod parallel;
 
-pub use parallel::ParallelCloner;
+pub use parallel::{CloneStats, ParallelCloner};
 
 /// Clone configuration
 pub struct CloneConfig {
@@ -8,15 +8,30 @@ pub struct CloneConfig {
     pub path: String,
     pub num_workers: usize,
     pub chunk_size: usize,
+    /// Draw progress bars while downloading
+    pub progress: bool,
+    /// Branch to check out instead of the remote's default
+    pub branch: Option<String>,
+    /// Fetch only the checked-out branch and its history
+    pub single_branch: bool,
 }
 
 impl CloneConfig {
+    /// The branch to ask the server for, if only one is wanted
+    pub fn requested_branch(&self) -> Option<&str> {
+        self.single_branch
+            .then(|| self.branch.as_deref().unwrap_or("HEAD"))
+    }
+
     pub fn new(url: &str, path: &str) -> Self {
         CloneConfig {
             url: url.to_string(),
             path: path.to_string(),
             num_workers: num_cpus::get(),
             chunk_size: 64 * 1024, // 64KB chunks
+            progress: false,
+            branch: None,
+            single_branch: false,
         }
     }
 }
@@ -1,79 +1,222 @@
+use std::collections::HashSet;
+use std::fs;
 use std::sync::Arc;
-use tokio::task::JoinHandle;
-use futures::stream::{self, StreamExt};
+use std::time::{Duration, Instant};
+
 use super::CloneConfig;
+use crate::core::error::{Error, Result};
+use crate::core::repo::Repository;
+use crate::core::resume::OperationProgress;
+use crate::remote::client::{repo_url, RemoteClient, Transfer, TransferCallback};
+use crate::remote::objects::ObjectSet;
+use crate::remote::parallel_fetch::{PackBatchDownloader, ParallelFetchConfig};
+use crate::remote::protocol::CloneResponse;
+use crate::remote::sync::{format_bytes, transfer_callback};
+use crate::remote::Remote;
+use crate::ui::ProgressReporter;
+
+/// Totals for a finished clone
+#[derive(Debug, Clone)]
+pub struct CloneStats {
+    pub commits: usize,
+    pub objects: usize,
+    pub bytes: u64,
+    pub elapsed: Duration,
+    /// Whether file contents were downloaded in parallel chunks
+    pub parallel: bool,
+}
+
+impl CloneStats {
+    /// Aggregate throughput in bytes per second
+    pub fn throughput(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs == 0.0 {
+            0.0
+        } else {
+            self.bytes as f64 / secs
+        }
+    }
+}
 
-/// Parallel cloner for downloading pack files concurrently
+/// Parallel cloner: fetches history in one request, then downloads file
+/// contents concurrently across `num_workers`
 pub struct ParallelCloner {
     config: CloneConfig,
+    on_progress: Option<TransferCallback>,
 }
 
 impl ParallelCloner {
     pub fn new(config: CloneConfig) -> Self {
-        ParallelCloner { config }
+        ParallelCloner {
+            config,
+            on_progress: None,
+        }
     }
 
-    /// Clone repository with parallel pack file downloads
-    pub async fn clone(&self) -> Result<(), String> {
-        println!("Cloning {} to {}", self.config.url, self.config.path);
-        
-        // Step 1: Fetch pack manifest from server
-        let packs = self.fetch_manifest().await?;
-        println!("Found {} pack files", packs.len());
-        
-        // Step 2: Download packs in parallel
-        self.download_packs_parallel(&packs).await?;
-        
-        println!("Clone complete!");
-        Ok(())
+    /// Also report progress to `callback`; chunk downloads are reported
+    /// under the "Downloading chunks" phase
+    pub fn with_progress(mut self, callback: TransferCallback) -> Self {
+        self.on_progress = Some(callback);
+        self
     }
 
-    /// Fetch list of available pack files from server
-    async fn fetch_manifest(&self) -> Result<Vec<String>, String> {
-        // In real impl: GET {url}/.mug/manifest.json
-        // For now, placeholder
-        Ok(vec![
-            "pack-001.mug".to_string(),
-            "pack-002.mug".to_string(),
-        ])
+    /// Fetch every object of `remote` into `repo`, returning the remote's
+    /// branches and default branch alongside transfer totals.
+    ///
+    /// Falls back to a single full request when the server does not publish
+    /// a chunk manifest.
+    pub async fn fetch(
+        &self,
+        repo: &Repository,
+        remote: &Remote,
+        token: &str,
+    ) -> Result<(CloneResponse, CloneStats)> {
+        let reporter = self.config.progress.then(ProgressReporter::new);
+        let result = self.fetch_with(repo, remote, token, reporter.as_ref()).await;
+        if let Some(reporter) = &reporter {
+            reporter.finish();
+        }
+        result
     }
 
-    /// Download multiple packs concurrently
-    async fn download_packs_parallel(&self, packs: &[String]) -> Result<(), String> {
-        let tasks: Vec<JoinHandle<Result<(), String>>> = packs
-            .iter()
-            .take(self.config.num_workers)
-            .map(|pack| {
-                let url = self.config.url.clone();
-                let path = self.config.path.clone();
-                let pack_name = pack.clone();
-                
-                tokio::spawn(async move {
-                    Self::download_pack(&url, &path, &pack_name).await
-                })
-            })
-            .collect();
-
-        for task in tasks {
-            task.await.map_err(|e| e.to_string())??;
+    async fn fetch_with(
+        &self,
+        repo: &Repository,
+        remote: &Remote,
+        token: &str,
+        reporter: Option<&ProgressReporter>,
+    ) -> Result<(CloneResponse, CloneStats)> {
+        let started = Instant::now();
+        let mut client = RemoteClient::new()?;
+        let base_url = repo_url(&remote.url);
+        let mut downloader = PackBatchDownloader::new(ParallelFetchConfig {
+            max_concurrent_downloads: self.config.num_workers.max(1),
+            auth_token: Some(token.to_string()).filter(|t| !t.is_empty()),
+            ..ParallelFetchConfig::default()
+        });
+        let drawn = reporter.map(transfer_callback);
+        let on_progress = self.on_progress.clone();
+        if drawn.is_some() || on_progress.is_some() {
+            let (drawn, on_progress) = (drawn.clone(), on_progress.clone());
+            client = client.with_progress(Arc::new(move |phase, progress| {
+                for callback in drawn.iter().chain(on_progress.iter()) {
+                    callback(phase, progress);
+                }
+            }));
+        }
+        if reporter.is_some() || on_progress.is_some() {
+            let reporter = reporter.cloned();
+            downloader = downloader.with_progress(Arc::new(move |progress| {
+                if let Some(reporter) = &reporter {
+                    let detail = format!(
+                        "{}/{} chunks, {}",
+                        progress.downloaded_chunks,
+                        progress.total_chunks,
+                        format_bytes(progress.bytes_downloaded as usize)
+                    );
+                    reporter.update(
+                        "Downloading chunks",
+                        progress.downloaded_chunks as u64,
+                        progress.total_chunks as u64,
+                        &detail,
+                    );
+                }
+                if let Some(on_progress) = &on_progress {
+                    let progress = OperationProgress {
+                        processed: progress.downloaded_chunks as u64,
+                        total: Some(progress.total_chunks as u64),
+                        bytes_processed: progress.bytes_downloaded,
+                        total_bytes: Some(progress.total_bytes),
+                    };
+                    on_progress("Downloading chunks", &progress);
+                }
+            }));
+        }
+
+        let manifest = match downloader.fetch_manifest(&base_url).await {
+            Ok(manifest) => manifest,
+            Err(_) => {
+                let Transfer { response, bytes } = client
+                    .clone(remote, token, true, self.config.requested_branch())
+                    .await?;
+                let repo = repo.adopt_hash_algorithm(response.hash_algorithm)?;
+                let objects = Self::store(&repo, &response)?;
+                let stats = CloneStats {
+                    commits: response.commits.len(),
+                    objects,
+                    bytes: bytes as u64,
+                    elapsed: started.elapsed(),
+                    parallel: false,
+                };
+                return Ok((response, stats));
+            }
+        };
+
+        let Transfer { response, bytes } = client
+            .clone(remote, token, false, self.config.requested_branch())
+            .await?;
+        let repo = &repo.adopt_hash_algorithm(response.hash_algorithm)?;
+        downloader = downloader.with_hash_algorithm(response.hash_algorithm);
+
+        // The manifest covers every branch; only download the files this
+        // clone's trees refer to
+        let mut manifest = manifest;
+        if self.config.single_branch {
+            let wanted: HashSet<&str> = response
+                .trees
+                .iter()
+                .flat_map(|tree| &tree.entries)
+                .filter(|entry| !entry.is_dir)
+                .map(|entry| entry.hash.as_str())
+                .collect();
+            manifest.retain_chunks(|chunk| wanted.contains(chunk.hash.as_str()));
+        }
+
+        // Download file contents into a scratch directory, then import them
+        let scratch = repo.root_path().join(".mug").join("tmp").join("clone");
+        fs::create_dir_all(&scratch)?;
+        let results = downloader
+            .download_pack(&manifest, &base_url, &scratch.to_string_lossy())
+            .await?;
+
+        let mut chunk_bytes = 0;
+        for result in &results {
+            if !result.success {
+                return Err(Error::Custom(format!(
+                    "Failed to download {}: {}",
+                    result.chunk_hash,
+                    result.error.as_deref().unwrap_or("unknown error")
+                )));
+            }
+            let content = fs::read(scratch.join(&result.chunk_hash))?;
+            repo.get_store().store_received(&result.chunk_hash, &content)?;
+            chunk_bytes += result.bytes_downloaded;
+        }
+        fs::remove_dir_all(&scratch)?;
+        if let Some(tmp) = scratch.parent() {
+            let _ = fs::remove_dir(tmp);
         }
 
-        Ok(())
+        let objects = Self::store(repo, &response)? + results.len();
+        let stats = CloneStats {
+            commits: response.commits.len(),
+            objects,
+            bytes: bytes as u64 + chunk_bytes,
+            elapsed: started.elapsed(),
+            parallel: true,
+        };
+        Ok((response, stats))
     }
 
-    /// Download single pack file
-    async fn download_pack(
-        url: &str,
-        path: &str,
-        pack_name: &str,
-    ) -> Result<(), String> {
-        let pack_url = format!("{}/{}", url, pack_name);
-        println!("Downloading {}", pack_name);
-        
-        // In real impl: fetch from pack_url with progress
-        // For now, placeholder
-        
-        Ok(())
+    /// Store the objects carried by a clone response, returning how many
+    fn store(repo: &Repository, response: &CloneResponse) -> Result<usize> {
+        let objects = ObjectSet {
+            commits: response.commits.clone(),
+            trees: response.trees.clone(),
+            blobs: response.blobs.clone(),
+        };
+        objects.store(repo)?;
+        Ok(objects.commits.len() + objects.trees.len() + objects.blobs.len())
     }
 }
 
@@ -87,4 +230,16 @@ mod tests {
         assert_eq!(config.url, "https://example.com/repo");
         assert_eq!(config.num_workers, num_cpus::get());
     }
+
+    #[test]
+    fn test_clone_stats_throughput() {
+        let stats = CloneStats {
+            commits: 1,
+            objects: 3,
+            bytes: 2048,
+            elapsed: Duration::from_secs(2),
+            parallel: true,
+        };
+        assert_eq!(stats.throughput(), 1024.0);
+    }
 }
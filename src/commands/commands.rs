@@ -1,32 +1,138 @@
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
 use rayon::prelude::*;
 use regex::Regex;
 
-use crate::core::error::Result;
-use crate::core::repo::Repository;
+use crate::core::commit::CommitLog;
+use crate::core::crypto::SignatureStatus;
+use crate::core::diff;
+use crate::core::error::{Error, Result};
+use crate::core::file_mode;
+use crate::core::hash::short_hash;
+use crate::core::index::Index;
+use crate::core::repo::{format_log_entry, Repository};
+use crate::core::store::{Tree, TreeEntry};
+use crate::ui::formatter::DiffHunk;
 
-pub fn remove_files(repo: &Repository, paths: &[&str]) -> Result<()> {
-    paths.par_iter().try_for_each(|path| {
-        fs::remove_file(path)?;
-        repo.remove(path)?;
-        Ok(())
-    })
-}
+/// Stop tracking files, staging their removal. Unless `cached` is set the
+/// working copies are deleted too.
+pub fn remove_files(repo: &Repository, paths: &[&str], cached: bool) -> Result<()> {
+    let tracked = repo.tracked_files()?;
+    if let Some(path) = paths.iter().find(|path| !tracked.contains_key(**path)) {
+        return Err(Error::Custom(format!("Not under version control: {}", path)));
+    }
 
-pub fn mv_file(repo: &Repository, from: &str, to: &str) -> Result<()> {
-    fs::rename(from, to)?;
-    repo.remove(from)?;
-    repo.add(to)?;
+    let head = match repo.head_commit_id()? {
+        Some(id) => repo.snapshot(&id)?,
+        None => std::collections::HashMap::new(),
+    };
+    let mut index = Index::new(repo.get_db().clone())?;
+    for path in paths {
+        if head.contains_key(*path) {
+            index.stage_removal(path)?;
+        } else {
+            index.remove(path)?;
+        }
+    }
+    index.flush()?;
+
+    if !cached {
+        paths.par_iter().try_for_each(|path| -> Result<()> {
+            let file_path = repo.root_path().join(path);
+            if file_path.exists() {
+                fs::remove_file(file_path)?;
+            }
+            Ok(())
+        })?;
+    }
     Ok(())
 }
 
+/// Move or rename a tracked file or directory, staging the removal of the
+/// old paths and the same blobs under the new ones. Moving onto an existing
+/// directory moves into it; an existing file is only replaced with `force`.
+pub fn mv_file(repo: &Repository, from: &str, to: &str, force: bool) -> Result<()> {
+    let root = repo.root_path();
+    let from = from.trim_end_matches('/');
+    let mut to = to.trim_end_matches('/').to_string();
+    if root.join(&to).is_dir() {
+        let name = Path::new(from)
+            .file_name()
+            .ok_or_else(|| Error::Custom(format!("Invalid source path: {}", from)))?;
+        to = Path::new(&to).join(name).to_string_lossy().to_string();
+    }
+
+    let tracked = repo.tracked_files()?;
+    let prefix = format!("{}/", from);
+    let moved: Vec<(&String, &String)> = tracked
+        .iter()
+        .filter(|(path, _)| path.as_str() == from || path.starts_with(&prefix))
+        .collect();
+    if moved.is_empty() {
+        return Err(Error::Custom(format!("Not under version control: {}", from)));
+    }
+
+    let dest = root.join(&to);
+    if dest.exists() {
+        if !force || dest.is_dir() {
+            return Err(Error::Custom(format!(
+                "Destination exists: {} (use --force to overwrite)",
+                to
+            )));
+        }
+        fs::remove_file(&dest)?;
+    }
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(root.join(from), &dest)?;
+
+    let head = match repo.head_commit_id()? {
+        Some(id) => repo.snapshot(&id)?,
+        None => std::collections::HashMap::new(),
+    };
+    let mut index = Index::new(repo.get_db().clone())?;
+    for (path, hash) in moved {
+        let new_path = format!("{}{}", to, &path[from.len()..]);
+        if head.contains_key(path) {
+            index.stage_removal(path)?;
+        } else {
+            index.remove(path)?;
+        }
+        index.add(new_path, hash.clone())?;
+    }
+    index.flush()
+}
+
+/// Unstage files and put back their HEAD contents. A directory restores
+/// everything under it; paths outside a sparse checkout stay off disk.
 pub fn restore_files(repo: &Repository, paths: &[&str]) -> Result<()> {
     paths.par_iter().try_for_each(|path| {
         repo.remove(path)?;
-        Ok(())
-    })
+        Ok::<_, Error>(())
+    })?;
+
+    let (head, modes) = match repo.head_commit_id()? {
+        Some(id) => repo.snapshot_with_modes(&id)?,
+        None => Default::default(),
+    };
+    let materialized = repo.materialized()?;
+    let mut large_files = None;
+    for (path, hash) in &head {
+        let selected = paths
+            .iter()
+            .any(|p| *p == "." || path == p || path.starts_with(&format!("{}/", p)));
+        if !selected || !materialized(path) {
+            continue;
+        }
+        let mode = modes.get(path).copied().unwrap_or(file_mode::REGULAR);
+        let content = repo.file_content(hash, &mut large_files)?;
+        file_mode::check_no_symlink_parents(repo.root_path(), path)?;
+        file_mode::write(&repo.root_path().join(path), &content, mode)?;
+    }
+    Ok(())
 }
 
 pub fn grep(repo_path: &Path, pattern: &str) -> Result<Vec<String>> {
@@ -40,7 +146,8 @@ pub fn grep(repo_path: &Path, pattern: &str) -> Result<Vec<String>> {
         .filter(|e| !e.path().to_string_lossy().contains(".mug"))
         .par_bridge()
         .filter_map(|entry| {
-            if let Ok(content) = fs::read_to_string(entry.path()) {
+            let content = fs::read(entry.path()).ok().filter(|data| !diff::is_binary(data))?;
+            if let Ok(content) = String::from_utf8(content) {
                 let matches: Vec<String> = content
                     .lines()
                     .enumerate()
@@ -72,33 +179,432 @@ pub fn grep(repo_path: &Path, pattern: &str) -> Result<Vec<String>> {
     Ok(results)
 }
 
-pub fn show_commit(repo: &Repository, commit_id: &str) -> Result<String> {
-    let log = repo.log()?;
-    for entry in log {
-        if entry.contains(commit_id) {
-            return Ok(entry);
+/// Commit details followed by the files it changed, or with `stat` a
+/// `diff --stat` summary. Changes are against the first parent; merge
+/// commits also list both parents.
+pub fn show_commit(repo: &Repository, commit_id: &str, stat: bool) -> Result<String> {
+    let commit_id = repo.resolve_rev(commit_id)?;
+    let commit_log = CommitLog::new(repo.get_db().clone());
+    let commit = commit_log.get_commit(&commit_id)?;
+    let entry = format_log_entry(&commit);
+
+    let mut output = match (&commit.parent, &commit.merge_parent) {
+        (Some(first), Some(second)) => {
+            let (header, rest) = entry.split_once('\n').unwrap_or((&entry, ""));
+            format!(
+                "{}\nMerge: {} {}\n{}",
+                header,
+                short_hash(first),
+                short_hash(second),
+                rest
+            )
+        }
+        _ => entry,
+    };
+
+    let status = commit_log.verify_signature(&commit)?;
+    if status != SignatureStatus::Unsigned {
+        output.push_str(&format!("Signature: {}\n", status.describe()));
+    }
+
+    let parent = match &commit.parent {
+        Some(parent) => repo.snapshot(parent)?,
+        None => std::collections::HashMap::new(),
+    };
+    let mut changes = repo.diff_snapshots(&parent, &repo.snapshot(&commit_id)?)?;
+    if changes.is_empty() {
+        return Ok(output);
+    }
+
+    output.push('\n');
+    if commit.merge_parent.is_some() {
+        output.push_str(&format!(
+            "Changes against first parent {}:\n",
+            short_hash(commit.parent.as_deref().unwrap_or_default())
+        ));
+    }
+    if stat {
+        repo.count_diff_lines(&mut changes)?;
+        for line in diff::format_stat(&changes) {
+            output.push_str(&line);
+            output.push('\n');
+        }
+    } else {
+        for change in &changes {
+            let line = match (&change.renamed_from, change.old_hash.is_empty(), change.new_hash.is_empty()) {
+                (Some(old), _, _) => format!("R  {} => {}", old, change.path),
+                (None, true, _) => format!("A  {}", change.path),
+                (None, _, true) => format!("D  {}", change.path),
+                _ => format!("M  {}", change.path),
+            };
+            output.push_str(&line);
+            output.push('\n');
+        }
+    }
+    Ok(output)
+}
+
+/// One author's commits, as listed by `shortlog`
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AuthorSummary {
+    pub author: String,
+    /// Subject lines, oldest first
+    pub subjects: Vec<String>,
+}
+
+/// Parse a `--since`/`--until` bound: an RFC 3339 time, or a `YYYY-MM-DD`
+/// date meaning the start of that day (or its end, for `end_of_day`)
+pub fn parse_date_bound(value: &str, end_of_day: bool) -> Result<chrono::DateTime<chrono::Utc>> {
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&chrono::Utc));
+    }
+    let date = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+        Error::Custom(format!("Invalid date '{}' (expected YYYY-MM-DD or RFC 3339)", value))
+    })?;
+    let time = if end_of_day {
+        date.and_hms_opt(23, 59, 59)
+    } else {
+        date.and_hms_opt(0, 0, 0)
+    };
+    Ok(time.unwrap_or_default().and_utc())
+}
+
+/// Commits reachable from HEAD grouped by author, most commits first,
+/// optionally limited to those made between `since` and `until`
+pub fn shortlog(repo: &Repository, since: Option<&str>, until: Option<&str>) -> Result<Vec<AuthorSummary>> {
+    let since = since.map(|s| parse_date_bound(s, false)).transpose()?;
+    let until = until.map(|u| parse_date_bound(u, true)).transpose()?;
+
+    let mut authors: Vec<AuthorSummary> = Vec::new();
+    for info in repo.log_structured()?.into_iter().rev() {
+        let date = chrono::DateTime::parse_from_rfc3339(&info.date)
+            .map_err(|e| Error::Custom(format!("Invalid commit date '{}': {}", info.date, e)))?;
+        if since.is_some_and(|since| date < since) || until.is_some_and(|until| date > until) {
+            continue;
+        }
+
+        let subject = info.message.lines().next().unwrap_or_default().to_string();
+        match authors.iter_mut().find(|a| a.author == info.author) {
+            Some(summary) => summary.subjects.push(subject),
+            None => authors.push(AuthorSummary {
+                author: info.author,
+                subjects: vec![subject],
+            }),
+        }
+    }
+
+    authors.sort_by(|a, b| {
+        b.subjects
+            .len()
+            .cmp(&a.subjects.len())
+            .then_with(|| a.author.cmp(&b.author))
+    });
+    Ok(authors)
+}
+
+/// Type of a stored object
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectKind {
+    Blob,
+    Tree,
+    Commit,
+}
+
+impl ObjectKind {
+    fn parse(kind: &str) -> Result<Self> {
+        match kind {
+            "blob" => Ok(ObjectKind::Blob),
+            "tree" => Ok(ObjectKind::Tree),
+            "commit" => Ok(ObjectKind::Commit),
+            other => Err(Error::Custom(format!(
+                "Unknown object type '{}' (expected blob, tree or commit)",
+                other
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for ObjectKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ObjectKind::Blob => write!(f, "blob"),
+            ObjectKind::Tree => write!(f, "tree"),
+            ObjectKind::Commit => write!(f, "commit"),
+        }
+    }
+}
+
+/// An object read back by `cat_object`
+#[derive(Debug, Clone)]
+pub struct CatObject {
+    pub kind: ObjectKind,
+    /// Size in bytes of the stored object
+    pub size: u64,
+    /// Raw bytes for a blob, an entry listing for a tree, fields for a commit
+    pub content: Vec<u8>,
+}
+
+/// Read an object by hash, detecting its type unless `kind` is given.
+/// Commits may be named by any revision `resolve_rev` accepts.
+pub fn cat_object(repo: &Repository, hash: &str, kind: Option<&str>) -> Result<CatObject> {
+    let kind = match kind {
+        Some(kind) => ObjectKind::parse(kind)?,
+        None => detect_kind(repo, hash)?,
+    };
+    let store = repo.get_store();
+
+    match kind {
+        ObjectKind::Blob => {
+            let blob = store.get_blob(hash).map_err(|_| Error::ObjectNotFound(hash.to_string()))?;
+            Ok(CatObject {
+                kind,
+                size: blob.size,
+                content: blob.content,
+            })
+        }
+        ObjectKind::Tree => {
+            let tree = read_tree(repo, hash)?;
+            let size = serde_json::to_vec(&tree)?.len() as u64;
+            Ok(CatObject {
+                kind,
+                size,
+                content: format_tree_entries(&tree, "").into_bytes(),
+            })
+        }
+        ObjectKind::Commit => {
+            let commit_id = repo.resolve_rev(hash)?;
+            let commit = CommitLog::new(repo.get_db().clone()).get_commit(&commit_id)?;
+            let size = serde_json::to_vec(&commit)?.len() as u64;
+
+            let mut fields = format!("id {}\ntree {}\n", commit.id, commit.tree_hash);
+            for parent in commit.parents() {
+                fields.push_str(&format!("parent {}\n", parent));
+            }
+            fields.push_str(&format!("author {}\n", commit.author));
+            fields.push_str(&format!("date {}\n", commit.timestamp.to_rfc3339()));
+            if commit.signature.is_some() {
+                fields.push_str("signed yes\n");
+            }
+            fields.push_str(&format!("\n{}\n", commit.message));
+            Ok(CatObject {
+                kind,
+                size,
+                content: fields.into_bytes(),
+            })
+        }
+    }
+}
+
+/// Work out whether `hash` names a tree, a blob or a commit
+fn detect_kind(repo: &Repository, hash: &str) -> Result<ObjectKind> {
+    if repo.get_store().has_object(hash) {
+        // Trees are stored as JSON carrying their own hash
+        return Ok(match repo.get_store().get_tree(hash) {
+            Ok(tree) if tree.hash == hash => ObjectKind::Tree,
+            _ => ObjectKind::Blob,
+        });
+    }
+    repo.resolve_rev(hash)
+        .map(|_| ObjectKind::Commit)
+        .map_err(|_| Error::ObjectNotFound(hash.to_string()))
+}
+
+fn read_tree(repo: &Repository, hash: &str) -> Result<Tree> {
+    match repo.get_store().get_tree(hash) {
+        Ok(tree) if tree.hash == hash => Ok(tree),
+        _ => Err(Error::Custom(format!("{} is not a tree object", hash))),
+    }
+}
+
+/// One `<mode> <type> <hash>\t<path>` line per entry
+fn format_tree_entries(tree: &Tree, prefix: &str) -> String {
+    tree.entries
+        .iter()
+        .map(|entry| format!("{}\n", format_tree_entry(entry, prefix)))
+        .collect()
+}
+
+fn format_tree_entry(entry: &TreeEntry, prefix: &str) -> String {
+    let (mode, kind) = if entry.is_dir {
+        ("040000".to_string(), ObjectKind::Tree)
+    } else {
+        (format!("{:06o}", entry.mode), ObjectKind::Blob)
+    };
+    format!("{} {} {}\t{}{}", mode, kind, entry.hash, prefix, entry.name)
+}
+
+/// List the tree recorded by a revision, descending into subtrees when
+/// `recursive` is set (subtree entries themselves are then omitted)
+pub fn ls_tree(repo: &Repository, rev: &str, recursive: bool) -> Result<Vec<String>> {
+    let commit_id = repo.resolve_rev(rev)?;
+    let commit = CommitLog::new(repo.get_db().clone()).get_commit(&commit_id)?;
+
+    let mut lines = Vec::new();
+    list_tree(repo, &commit.tree_hash, "", recursive, &mut lines)?;
+    Ok(lines)
+}
+
+fn list_tree(
+    repo: &Repository,
+    hash: &str,
+    prefix: &str,
+    recursive: bool,
+    lines: &mut Vec<String>,
+) -> Result<()> {
+    let tree = read_tree(repo, hash)?;
+    for entry in &tree.entries {
+        if recursive && entry.is_dir {
+            let prefix = format!("{}{}/", prefix, entry.name);
+            list_tree(repo, &entry.hash, &prefix, recursive, lines)?;
+        } else {
+            lines.push(format_tree_entry(entry, prefix));
         }
     }
-    Err(crate::core::error::Error::Custom(format!(
-        "Commit {} not found",
-        commit_id
-    )))
+    Ok(())
 }
 
 pub fn diff_commits(
-    _repo: &Repository,
+    repo: &Repository,
     from: Option<&str>,
     to: Option<&str>,
 ) -> Result<Vec<String>> {
-    let _from = from.unwrap_or("HEAD");
-    let _to = to.unwrap_or("HEAD");
+    let from = repo.resolve_rev(from.unwrap_or("HEAD"))?;
+    let to = repo.resolve_rev(to.unwrap_or("HEAD"))?;
+
+    let changes = repo.diff_snapshots(&repo.snapshot(&from)?, &repo.snapshot(&to)?)?;
 
     let mut diffs = Vec::new();
-    diffs.push("Diff between commits (simplified)".to_string());
+    diffs.push(format!("Diff between {} and {}", short_hash(&from), short_hash(&to)));
+    for change in changes {
+        diffs.push(match (&change.renamed_from, change.old_hash.is_empty(), change.new_hash.is_empty()) {
+            (Some(old_path), _, _) => format!("R  {} -> {}", old_path, change.path),
+            (None, true, _) => format!("A  {}", change.path),
+            (None, _, true) => format!("D  {}", change.path),
+            _ => format!("M  {}", change.path),
+        });
+    }
 
     Ok(diffs)
 }
 
+/// `diff --stat` between two revisions, each defaulting to HEAD
+pub fn diff_stat(repo: &Repository, from: Option<&str>, to: Option<&str>) -> Result<Vec<String>> {
+    let from = repo.resolve_rev(from.unwrap_or("HEAD"))?;
+    let to = repo.resolve_rev(to.unwrap_or("HEAD"))?;
+
+    let mut changes = repo.diff_snapshots(&repo.snapshot(&from)?, &repo.snapshot(&to)?)?;
+    repo.count_diff_lines(&mut changes)?;
+    Ok(diff::format_stat(&changes))
+}
+
+/// Content hunks between two revisions for `diff --word-diff`, with
+/// changed lines reduced to the words that differ
+pub fn word_diff(repo: &Repository, from: Option<&str>, to: Option<&str>) -> Result<Vec<DiffHunk>> {
+    let from = repo.resolve_rev(from.unwrap_or("HEAD"))?;
+    let to = repo.resolve_rev(to.unwrap_or("HEAD"))?;
+
+    let mut changes = repo.diff_snapshots(&repo.snapshot(&from)?, &repo.snapshot(&to)?)?;
+    repo.count_diff_lines(&mut changes)?;
+    let content = |hash: &str| -> Result<String> {
+        if hash.is_empty() {
+            return Ok(String::new());
+        }
+        let blob = repo.get_store().get_blob(hash)?;
+        Ok(String::from_utf8_lossy(&blob.content).into_owned())
+    };
+
+    changes
+        .into_iter()
+        .map(|change| {
+            let lines = if change.binary {
+                Vec::new()
+            } else {
+                diff::word_diff_lines(&content(&change.old_hash)?, &content(&change.new_hash)?)
+            };
+            Ok(DiffHunk {
+                file: change.path,
+                added: change.lines_added,
+                removed: change.lines_removed,
+                lines,
+                binary: change.binary,
+            })
+        })
+        .collect()
+}
+
+/// Unchanged lines kept around each change by `patch_lines`
+const PATCH_CONTEXT_LINES: usize = 3;
+
+/// Line diff between two snapshots: a `---`/`+++` header per changed file,
+/// then its changed lines and their context, with `...` between hunks
+pub fn patch_lines(
+    repo: &Repository,
+    from: &HashMap<String, String>,
+    to: &HashMap<String, String>,
+) -> Result<Vec<String>> {
+    let mut large_files = None;
+    let mut content = |hash: &str| -> Result<Vec<u8>> {
+        if hash.is_empty() {
+            return Ok(Vec::new());
+        }
+        repo.file_content(hash, &mut large_files)
+    };
+
+    let mut lines = Vec::new();
+    for change in repo.diff_snapshots(from, to)? {
+        let (old, new) = (content(&change.old_hash)?, content(&change.new_hash)?);
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+        lines.push(format!("--- {}", change.renamed_from.as_deref().unwrap_or(&change.path)));
+        lines.push(format!("+++ {}", change.path));
+        if diff::is_binary(&old) || diff::is_binary(&new) {
+            lines.push("Binary files differ".to_string());
+            continue;
+        }
+
+        let diff_lines: Vec<String> = diff::text_diff(
+            &String::from_utf8_lossy(&old),
+            &String::from_utf8_lossy(&new),
+        )
+        .into_iter()
+        .map(|line| line.trim_end_matches('\n').to_string())
+        .collect();
+        let changed: Vec<usize> = (0..diff_lines.len())
+            .filter(|&i| !diff_lines[i].starts_with(' '))
+            .collect();
+        let mut last_shown = None;
+        for (i, line) in diff_lines.iter().enumerate() {
+            if !changed.iter().any(|&c| c.abs_diff(i) <= PATCH_CONTEXT_LINES) {
+                continue;
+            }
+            if last_shown.is_some_and(|last: usize| i > last + 1) {
+                lines.push("...".to_string());
+            }
+            lines.push(line.clone());
+            last_shown = Some(i);
+        }
+    }
+    Ok(lines)
+}
+
+/// `patch_lines` for the changes staged in the index against HEAD
+pub fn staged_diff(repo: &Repository) -> Result<Vec<String>> {
+    let head = match repo.head_commit_id()? {
+        Some(id) => repo.snapshot(&id)?,
+        None => HashMap::new(),
+    };
+    let mut staged = head.clone();
+    for entry in Index::new(repo.get_db().clone())?.entries() {
+        if entry.is_removal() {
+            staged.remove(&entry.path);
+        } else {
+            staged.insert(entry.path, entry.hash);
+        }
+    }
+    patch_lines(repo, &head, &staged)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -114,4 +620,236 @@ mod tests {
         let result = grep(Path::new("."), "(?P<invalid");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_grep_skips_binary_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(dir.path().join("notes.txt"), "find the needle\n").unwrap();
+        fs::write(dir.path().join("data.bin"), b"needle\0\x01\x02").unwrap();
+
+        let results = grep(dir.path(), "needle").unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].contains("notes.txt"));
+    }
+
+    #[test]
+    fn test_show_tagged_commit() {
+        use crate::core::tag::TagManager;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        fs::write(dir.path().join("file.txt"), "hello").unwrap();
+        repo.add("file.txt").unwrap();
+        let commit_id = repo.commit("Tester".to_string(), "Release".to_string()).unwrap();
+
+        let head = repo.resolve_rev("HEAD").unwrap();
+        TagManager::new(repo.get_db().clone())
+            .create("v1.0".to_string(), head)
+            .unwrap();
+
+        let tag = TagManager::new(repo.get_db().clone()).get("v1.0").unwrap().unwrap();
+        assert_eq!(tag.commit_id, commit_id);
+        assert_eq!(repo.resolve_rev("refs/tags/v1.0").unwrap(), commit_id);
+
+        let shown = show_commit(&repo, "v1.0", false).unwrap();
+        assert!(shown.contains(&short_hash(&commit_id)));
+        assert!(shown.contains("Release"));
+    }
+
+    #[test]
+    fn test_show_lists_changes_against_first_parent() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let commit = |path: &str, content: &str, message: &str| {
+            fs::write(dir.path().join(path), content).unwrap();
+            repo.add(path).unwrap();
+            repo.commit("Tester".to_string(), message.to_string()).unwrap()
+        };
+
+        commit("a.txt", "a\n", "Base");
+        repo.create_branch("feature".to_string()).unwrap();
+        let main = commit("a.txt", "a\nmain\n", "Main");
+        repo.checkout("feature".to_string()).unwrap();
+        let feature = commit("b.txt", "b\n", "Feature");
+        repo.checkout("main".to_string()).unwrap();
+        crate::core::merge::merge(&repo, "feature", crate::core::merge::MergeStrategy::Recursive)
+            .unwrap();
+
+        let shown = show_commit(&repo, "HEAD", false).unwrap();
+        assert!(shown.contains(&format!("Merge: {} {}", short_hash(&main), short_hash(&feature))));
+        assert!(shown.contains("A  b.txt"));
+        assert!(!shown.contains("a.txt"));
+
+        let shown = show_commit(&repo, "HEAD~1", true).unwrap();
+        assert!(!shown.contains("Merge:"));
+        assert!(shown.contains(" a.txt | +1 -0 +"));
+        assert!(shown.contains(" 1 file changed, 1 insertion(+), 0 deletions(-)"));
+    }
+
+    #[test]
+    fn test_staged_diff_trims_unchanged_lines() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let lines: Vec<String> = (1..=20).map(|i| i.to_string()).collect();
+        fs::write(dir.path().join("a.txt"), lines.join("\n") + "\n").unwrap();
+        repo.add("a.txt").unwrap();
+        repo.commit("Tester".to_string(), "Base".to_string()).unwrap();
+
+        fs::write(dir.path().join("a.txt"), lines.join("\n").replace("\n2\n", "\ntwo\n") + "\n").unwrap();
+        fs::write(dir.path().join("b.txt"), "new\n").unwrap();
+        repo.add("a.txt").unwrap();
+        repo.add("b.txt").unwrap();
+
+        let diff = staged_diff(&repo).unwrap();
+        assert_eq!(&diff[..3], &["--- a.txt", "+++ a.txt", "  1"]);
+        assert!(diff.contains(&"- 2".to_string()));
+        assert!(diff.contains(&"+ two".to_string()));
+        assert!(diff.contains(&"  5".to_string()));
+        assert!(!diff.contains(&"  6".to_string()));
+        assert!(diff.ends_with(&["".to_string(), "--- b.txt".to_string(), "+++ b.txt".to_string(), "+ new".to_string()]));
+    }
+
+    #[test]
+    fn test_shortlog_groups_by_author() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        for (i, author) in ["Ana", "Ben", "Ana"].iter().enumerate() {
+            fs::write(dir.path().join("file.txt"), i.to_string()).unwrap();
+            repo.add("file.txt").unwrap();
+            repo.commit(author.to_string(), format!("Change {}\n\nDetails", i)).unwrap();
+        }
+
+        let authors = shortlog(&repo, None, None).unwrap();
+        assert_eq!(authors.len(), 2);
+        assert_eq!(authors[0].author, "Ana");
+        assert_eq!(authors[0].subjects, vec!["Change 0", "Change 2"]);
+        assert_eq!(authors[1].author, "Ben");
+
+        assert!(shortlog(&repo, Some("2999-01-01"), None).unwrap().is_empty());
+        assert!(shortlog(&repo, None, Some("2000-01-01")).unwrap().is_empty());
+        assert!(shortlog(&repo, Some("yesterday"), None).is_err());
+    }
+
+    #[test]
+    fn test_cat_object_detects_type() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        fs::write(dir.path().join("file.txt"), "hello").unwrap();
+        repo.add("file.txt").unwrap();
+        let commit_id = repo.commit("Tester".to_string(), "Initial".to_string()).unwrap();
+        let commit = CommitLog::new(repo.get_db().clone()).get_commit(&commit_id).unwrap();
+        let blob_hash = crate::core::hash::hash_bytes(b"hello");
+
+        let blob = cat_object(&repo, &blob_hash, None).unwrap();
+        assert_eq!(blob.kind, ObjectKind::Blob);
+        assert_eq!(blob.size, 5);
+        assert_eq!(blob.content, b"hello");
+
+        let tree = cat_object(&repo, &commit.tree_hash, None).unwrap();
+        assert_eq!(tree.kind, ObjectKind::Tree);
+        let listing = String::from_utf8(tree.content).unwrap();
+        assert_eq!(listing, format!("100644 blob {}\tfile.txt\n", blob_hash));
+
+        let shown = cat_object(&repo, "HEAD", None).unwrap();
+        assert_eq!(shown.kind, ObjectKind::Commit);
+        let fields = String::from_utf8(shown.content).unwrap();
+        assert!(fields.contains(&format!("tree {}", commit.tree_hash)));
+        assert!(fields.contains("Initial"));
+
+        assert!(cat_object(&repo, &blob_hash, Some("tree")).is_err());
+        assert!(cat_object(&repo, "missing", None).is_err());
+    }
+
+    #[test]
+    fn test_ls_tree_recurses_into_subtrees() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let blob = repo.get_store().store_blob(b"fn main() {}").unwrap();
+        let sub = repo
+            .get_store()
+            .store_tree(vec![TreeEntry {
+                name: "main.rs".to_string(),
+                hash: blob.clone(),
+                is_dir: false,
+                mode: file_mode::REGULAR,
+            }])
+            .unwrap();
+        let root = repo
+            .get_store()
+            .store_tree(vec![TreeEntry {
+                name: "src".to_string(),
+                hash: sub.clone(),
+                is_dir: true,
+                mode: file_mode::REGULAR,
+            }])
+            .unwrap();
+        let commit = CommitLog::new(repo.get_db().clone())
+            .create_commit(root, "Tester".to_string(), "Nested".to_string(), None)
+            .unwrap();
+
+        assert_eq!(
+            ls_tree(&repo, &commit, false).unwrap(),
+            vec![format!("040000 tree {}\tsrc", sub)]
+        );
+        assert_eq!(
+            ls_tree(&repo, &commit, true).unwrap(),
+            vec![format!("100644 blob {}\tsrc/main.rs", blob)]
+        );
+    }
+
+    #[test]
+    fn test_mv_file_records_rename() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        fs::create_dir_all(dir.path().join("docs")).unwrap();
+        fs::write(dir.path().join("notes.txt"), "some notes\n").unwrap();
+        fs::write(dir.path().join("docs/guide.md"), "guide\n").unwrap();
+        fs::write(dir.path().join("other.txt"), "other\n").unwrap();
+        repo.add("notes.txt").unwrap();
+        repo.add("docs/guide.md").unwrap();
+        repo.add("other.txt").unwrap();
+        let first = repo.commit("Tester".to_string(), "Initial".to_string()).unwrap();
+
+        mv_file(&repo, "notes.txt", "renamed.txt", false).unwrap();
+        mv_file(&repo, "docs", "manual", false).unwrap();
+        assert!(dir.path().join("manual/guide.md").exists());
+        assert!(!dir.path().join("notes.txt").exists());
+        assert!(mv_file(&repo, "renamed.txt", "other.txt", false).is_err());
+        assert!(mv_file(&repo, "missing.txt", "x.txt", false).is_err());
+
+        let second = repo.commit("Tester".to_string(), "Move".to_string()).unwrap();
+        let mut files: Vec<String> = repo.snapshot(&second).unwrap().into_keys().collect();
+        files.sort();
+        assert_eq!(files, vec!["manual/guide.md", "other.txt", "renamed.txt"]);
+
+        let diff = diff_commits(&repo, Some(&first), Some(&second)).unwrap();
+        assert!(diff.contains(&"R  notes.txt -> renamed.txt".to_string()));
+        assert!(diff.contains(&"R  docs/guide.md -> manual/guide.md".to_string()));
+
+        mv_file(&repo, "renamed.txt", "other.txt", true).unwrap();
+        assert_eq!(fs::read_to_string(dir.path().join("other.txt")).unwrap(), "some notes\n");
+    }
+
+    #[test]
+    fn test_rm_cached_keeps_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        fs::write(dir.path().join("secret.env"), "TOKEN=1").unwrap();
+        fs::write(dir.path().join("old.txt"), "old").unwrap();
+        repo.add("secret.env").unwrap();
+        repo.add("old.txt").unwrap();
+        repo.commit("Tester".to_string(), "Initial".to_string()).unwrap();
+
+        remove_files(&repo, &["secret.env"], true).unwrap();
+        remove_files(&repo, &["old.txt"], false).unwrap();
+        assert!(dir.path().join("secret.env").exists());
+        assert!(!dir.path().join("old.txt").exists());
+
+        let lines: Vec<String> = repo.status().unwrap().porcelain().iter().map(|e| e.to_string()).collect();
+        assert_eq!(lines, vec!["D  old.txt", "D  secret.env", "?? secret.env"]);
+
+        let commit = repo.commit("Tester".to_string(), "Untrack".to_string()).unwrap();
+        assert!(repo.snapshot(&commit).unwrap().is_empty());
+        assert!(remove_files(&repo, &["secret.env"], true).is_err());
+    }
 }
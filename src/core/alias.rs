@@ -0,0 +1,126 @@
+/// Command aliases from `alias.<name>` config, expanded before the command
+/// line is parsed
+use crate::core::error::{Error, Result};
+
+/// Config key holding the expansion of alias `name`
+pub fn config_key(name: &str) -> String {
+    format!("alias.{}", name)
+}
+
+/// Split an alias value into arguments. Whitespace separates arguments
+/// except inside single or double quotes; a backslash escapes the next
+/// character outside single quotes.
+pub fn split(value: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote = None;
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('\''), c) => current.get_or_insert_with(String::new).push(c),
+            (_, '\\') => {
+                let escaped = chars
+                    .next()
+                    .ok_or_else(|| Error::Custom(format!("Alias '{}' ends with a backslash", value)))?;
+                current.get_or_insert_with(String::new).push(escaped);
+            }
+            (Some(_), c) => current.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => args.extend(current.take()),
+            (None, c) => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(Error::Custom(format!("Alias '{}' has an unclosed quote", value)));
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+/// Replace an aliased command in `args` (program name first) with its
+/// expansion, repeatedly, so aliases may refer to other aliases. Built-in
+/// commands are never aliased. `lookup` gives an alias's value.
+pub fn expand(
+    mut args: Vec<String>,
+    is_builtin: impl Fn(&str) -> bool,
+    lookup: impl Fn(&str) -> Result<Option<String>>,
+) -> Result<Vec<String>> {
+    let mut seen: Vec<String> = Vec::new();
+    loop {
+        // The command is the first argument that is not a global flag
+        let Some(position) = args.iter().skip(1).position(|arg| !arg.starts_with('-')).map(|i| i + 1) else {
+            return Ok(args);
+        };
+        let name = args[position].clone();
+        if is_builtin(&name) {
+            return Ok(args);
+        }
+        let Some(value) = lookup(&name)? else {
+            return Ok(args);
+        };
+        if seen.contains(&name) {
+            seen.push(name);
+            return Err(Error::Custom(format!("Alias loop: {}", seen.join(" -> "))));
+        }
+        let expansion = split(&value)?;
+        if expansion.is_empty() {
+            return Err(Error::Custom(format!("Alias '{}' is empty", name)));
+        }
+        args.splice(position..=position, expansion);
+        seen.push(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    fn expand_with(line: &str, aliases: &[(&str, &str)]) -> Result<Vec<String>> {
+        let aliases: HashMap<&str, &str> = aliases.iter().copied().collect();
+        expand(
+            args(line),
+            |name| ["checkout", "status", "log"].contains(&name),
+            |name| Ok(aliases.get(name).map(|value| value.to_string())),
+        )
+    }
+
+    #[test]
+    fn test_split_honours_quotes() {
+        assert_eq!(split("  log --oneline ").unwrap(), args("log --oneline"));
+        assert_eq!(
+            split(r#"commit -m "two words" 'it''s' a\ b """#).unwrap(),
+            vec!["commit", "-m", "two words", "its", "a b", ""]
+        );
+        assert!(split("log 'open").is_err());
+    }
+
+    #[test]
+    fn test_expand_aliases_with_arguments_and_chains() {
+        let aliases = [("co", "checkout"), ("lg", "log --oneline"), ("l", "lg -n 5"), ("status", "log")];
+        assert_eq!(expand_with("mug co main", &aliases).unwrap(), args("mug checkout main"));
+        assert_eq!(
+            expand_with("mug --json l --all", &aliases).unwrap(),
+            args("mug --json log --oneline -n 5 --all")
+        );
+        // Built-in commands and unknown names are left for clap
+        assert_eq!(expand_with("mug status", &aliases).unwrap(), args("mug status"));
+        assert_eq!(expand_with("mug nope", &aliases).unwrap(), args("mug nope"));
+        assert_eq!(expand_with("mug --help", &aliases).unwrap(), args("mug --help"));
+    }
+
+    #[test]
+    fn test_expand_refuses_loops() {
+        let err = expand_with("mug a", &[("a", "b -x"), ("b", "a")]).unwrap_err();
+        assert!(err.to_string().contains("a -> b -> a"));
+        assert!(expand_with("mug e", &[("e", "  ")]).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use chrono::{Datelike, Timelike};
+
+use crate::core::commit::CommitLog;
+use crate::core::error::{Error, Result};
+use crate::core::file_mode;
+use crate::core::repo::Repository;
+
+/// Archive file format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Tar,
+    Zip,
+}
+
+impl FromStr for ArchiveFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "tar" => Ok(ArchiveFormat::Tar),
+            "zip" => Ok(ArchiveFormat::Zip),
+            _ => Err(Error::Custom(format!(
+                "Unknown archive format: {} (expected tar or zip)",
+                s
+            ))),
+        }
+    }
+}
+
+/// Write the files of `rev` to `output` as a tar or zip archive, returning
+/// how many were written. Files are read and written one at a time.
+pub fn write_archive(
+    repo: &Repository,
+    rev: &str,
+    format: ArchiveFormat,
+    output: &Path,
+) -> Result<usize> {
+    let commit_id = repo.resolve_rev(rev)?;
+    let commit = CommitLog::new(repo.get_db().clone()).get_commit(&commit_id)?;
+    let (files, modes) = repo.snapshot_with_modes(&commit_id)?;
+    let mut files: Vec<_> = files.into_iter().collect();
+    files.sort();
+    let mode = |path: &str| modes.get(path).copied().unwrap_or(file_mode::REGULAR);
+
+    let out = BufWriter::new(File::create(output)?);
+    let mut large_files = None;
+    match format {
+        ArchiveFormat::Tar => {
+            let mut tar = tar::Builder::new(out);
+            for (path, hash) in &files {
+                let content = repo.file_content(hash, &mut large_files)?;
+                let mut header = tar::Header::new_gnu();
+                header.set_mtime(commit.timestamp.timestamp().max(0) as u64);
+                if mode(path) == file_mode::SYMLINK {
+                    header.set_entry_type(tar::EntryType::Symlink);
+                    header.set_size(0);
+                    header.set_mode(0o777);
+                    tar.append_link(&mut header, path, String::from_utf8_lossy(&content).as_ref())?;
+                    continue;
+                }
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_size(content.len() as u64);
+                header.set_mode(mode(path) & 0o777);
+                header.set_cksum();
+                tar.append_data(&mut header, path, content.as_slice())?;
+            }
+            tar.into_inner()?.flush()?;
+        }
+        ArchiveFormat::Zip => {
+            let zip_error = |e: zip::result::ZipError| Error::Custom(format!("Zip error: {}", e));
+            let time = commit.timestamp;
+            let modified = zip::DateTime::from_date_and_time(
+                time.year().clamp(1980, 2107) as u16,
+                time.month() as u8,
+                time.day() as u8,
+                time.hour() as u8,
+                time.minute() as u8,
+                time.second() as u8,
+            )
+            .unwrap_or_default();
+
+            let mut zip = zip::ZipWriter::new(out);
+            for (path, hash) in &files {
+                let content = repo.file_content(hash, &mut large_files)?;
+                let options = zip::write::SimpleFileOptions::default()
+                    .compression_method(zip::CompressionMethod::Deflated)
+                    .unix_permissions(mode(path) & 0o777)
+                    .last_modified_time(modified);
+                if mode(path) == file_mode::SYMLINK {
+                    let target = String::from_utf8_lossy(&content).to_string();
+                    zip.add_symlink(path.as_str(), target, options).map_err(zip_error)?;
+                    continue;
+                }
+                zip.start_file(path.as_str(), options).map_err(zip_error)?;
+                zip.write_all(&content)?;
+            }
+            zip.finish().map_err(zip_error)?.flush()?;
+        }
+    }
+
+    Ok(files.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Read;
+    use tempfile::TempDir;
+
+    fn repo_with_files() -> (TempDir, Repository) {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("README.md"), "# Project\n").unwrap();
+        fs::write(dir.path().join("src/main.rs"), "fn main() {}\n").unwrap();
+        repo.add("README.md").unwrap();
+        repo.add("src/main.rs").unwrap();
+        repo.commit("Tester".to_string(), "Initial".to_string()).unwrap();
+        (dir, repo)
+    }
+
+    #[test]
+    fn test_tar_archive_has_every_file() {
+        let (dir, repo) = repo_with_files();
+        let output = dir.path().join("out.tar");
+        assert_eq!(write_archive(&repo, "HEAD", ArchiveFormat::Tar, &output).unwrap(), 2);
+
+        let mut archive = tar::Archive::new(File::open(&output).unwrap());
+        let mut entries = Vec::new();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let mut content = String::new();
+            entry.read_to_string(&mut content).unwrap();
+            let path = entry.path().unwrap().to_string_lossy().to_string();
+            entries.push((path, entry.header().mode().unwrap(), content));
+        }
+        assert_eq!(
+            entries,
+            vec![
+                ("README.md".to_string(), 0o644, "# Project\n".to_string()),
+                ("src/main.rs".to_string(), 0o644, "fn main() {}\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_zip_archive_has_every_file() {
+        let (dir, repo) = repo_with_files();
+        let output = dir.path().join("out.zip");
+        write_archive(&repo, "HEAD", ArchiveFormat::Zip, &output).unwrap();
+
+        let mut archive = zip::ZipArchive::new(File::open(&output).unwrap()).unwrap();
+        assert_eq!(archive.len(), 2);
+        let mut file = archive.by_name("src/main.rs").unwrap();
+        let mut content = String::new();
+        file.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "fn main() {}\n");
+        assert_eq!(file.unix_mode().map(|mode| mode & 0o777), Some(0o644));
+    }
+}
@@ -1,7 +1,24 @@
+use crate::core::crypto::CryptoKey;
 use crate::core::database::MugDb;
-use crate::core::error::Result;
+use crate::core::error::{Error, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long a client has to answer a key challenge
+const CHALLENGE_TTL: Duration = Duration::from_secs(60);
+
+/// How long a session token issued for a signed challenge stays valid
+const SESSION_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Bytes a client signs to answer the challenge `nonce`. The prefix keeps
+/// these signatures from ever being valid for anything else.
+pub fn challenge_message(nonce: &str) -> Vec<u8> {
+    format!("mug-auth:{}", nonce).into_bytes()
+}
 
 /// Authentication credentials
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -72,10 +89,42 @@ impl AuthManager {
     }
 }
 
+/// Replace `path` with `data` through a temporary file readable only by
+/// the owner, since the stores hold bearer secrets
+fn write_private(path: &Path, data: &[u8]) -> Result<()> {
+    let temp = path.with_extension("json.tmp");
+    if temp.exists() {
+        fs::remove_file(&temp)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&temp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
 /// Server-side auth store
 pub struct ServerAuth {
     // Map of token -> (username, permissions)
     tokens: HashMap<String, TokenInfo>,
+    // Map of registered public key -> (name, permissions)
+    keys: HashMap<String, TokenInfo>,
+    // Outstanding challenges: nonce -> (public key, issued at)
+    challenges: HashMap<String, (String, Instant)>,
+    // Sessions from answered challenges: token -> (public key, issued at)
+    sessions: HashMap<String, (String, Instant)>,
+    // Backing file for tokens, if persisted; keys live beside it
+    path: Option<PathBuf>,
+    // Modification times and sizes of the tokens and keys files when last
+    // read
+    loaded: [Option<(SystemTime, u64)>; 2],
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -91,13 +140,149 @@ pub enum Permission {
     Admin(String), // Full access to repo
 }
 
+/// Access level on a repository. Each role includes the ones before it:
+/// read fetches, write pushes, and admin also deletes branches and
+/// force-pushes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Read,
+    Write,
+    Admin,
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Role::Read => write!(f, "read"),
+            Role::Write => write!(f, "write"),
+            Role::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "read" => Ok(Role::Read),
+            "write" => Ok(Role::Write),
+            "admin" => Ok(Role::Admin),
+            _ => Err(Error::Custom(format!(
+                "Unknown access level '{}' (expected read, write or admin)",
+                s
+            ))),
+        }
+    }
+}
+
+impl Permission {
+    /// Build a permission from an access level name ("read", "write" or "admin")
+    pub fn for_access(access: &str, repo: &str) -> Result<Self> {
+        Ok(Self::new(access.parse()?, repo))
+    }
+
+    pub fn new(role: Role, repo: &str) -> Self {
+        match role {
+            Role::Read => Permission::Read(repo.to_string()),
+            Role::Write => Permission::Write(repo.to_string()),
+            Role::Admin => Permission::Admin(repo.to_string()),
+        }
+    }
+
+    pub fn role(&self) -> Role {
+        match self {
+            Permission::Read(_) => Role::Read,
+            Permission::Write(_) => Role::Write,
+            Permission::Admin(_) => Role::Admin,
+        }
+    }
+
+    pub fn repo(&self) -> &str {
+        match self {
+            Permission::Read(repo) | Permission::Write(repo) | Permission::Admin(repo) => repo,
+        }
+    }
+}
+
+impl Default for ServerAuth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ServerAuth {
+    /// In-memory store; tokens are lost when it is dropped
     pub fn new() -> Self {
         Self {
             tokens: HashMap::new(),
+            keys: HashMap::new(),
+            challenges: HashMap::new(),
+            sessions: HashMap::new(),
+            path: None,
+            loaded: [None, None],
         }
     }
 
+    /// Open a store persisted as JSON at `path`, creating it on first save.
+    /// Registered keys are kept in `keys.json` in the same directory.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut auth = Self {
+            path: Some(path.as_ref().to_path_buf()),
+            ..Self::new()
+        };
+        auth.reload_if_changed()?;
+        Ok(auth)
+    }
+
+    /// Replace the tokens and keys with the files' contents if they changed
+    /// on disk (e.g. by `serve --add-token`) since they were last read, so
+    /// added, revoked and changed entries all take effect
+    fn reload_if_changed(&mut self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let paths = [path.clone(), Self::keys_path(path)];
+        for (i, path) in paths.iter().enumerate() {
+            let modified = fs::metadata(path).and_then(|m| Ok((m.modified()?, m.len()))).ok();
+            if modified == self.loaded[i] {
+                continue;
+            }
+            let entries = Self::read_tokens(path)?;
+            if i == 0 {
+                self.tokens = entries;
+            } else {
+                self.keys = entries;
+            }
+            self.loaded[i] = modified;
+        }
+        Ok(())
+    }
+
+    fn keys_path(tokens_path: &Path) -> PathBuf {
+        tokens_path.with_file_name("keys.json")
+    }
+
+    fn read_tokens(path: &Path) -> Result<HashMap<String, TokenInfo>> {
+        if !path.exists() {
+            return Ok(HashMap::new());
+        }
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    /// Write the tokens back to the backing file
+    pub fn save(&self) -> Result<()> {
+        if let Some(path) = &self.path {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            write_private(path, &serde_json::to_vec_pretty(&self.tokens)?)?;
+            write_private(&Self::keys_path(path), &serde_json::to_vec_pretty(&self.keys)?)?;
+        }
+        Ok(())
+    }
+
     /// Add a token
     pub fn add_token(&mut self, token: String, username: String, permissions: Vec<Permission>) {
         self.tokens.insert(
@@ -109,21 +294,110 @@ impl ServerAuth {
         );
     }
 
-    /// Verify token and check permission
-    pub fn verify(&self, token: &str, repo: &str, action: &str) -> Result<bool> {
-        match self.tokens.get(token) {
-            Some(info) => {
-                let has_permission = info.permissions.iter().any(|p| match p {
-                    Permission::Admin(r) => r == repo,
-                    Permission::Write(r) if action == "write" => r == repo,
-                    Permission::Read(r) if action == "read" => r == repo,
-                    _ => false,
-                });
+    /// Register a public key (as printed by `mug keys generate`) that can
+    /// authenticate by signing a challenge
+    pub fn add_key(&mut self, public_key: &str, name: String, permissions: Vec<Permission>) -> Result<()> {
+        let decoded = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, public_key)
+            .map_err(|e| Error::Custom(format!("Invalid public key: {}", e)))?;
+        if decoded.len() != 32 {
+            return Err(Error::Custom("Invalid public key: expected 32 bytes".to_string()));
+        }
+        self.keys.insert(
+            public_key.to_string(),
+            TokenInfo {
+                username: name,
+                permissions,
+            },
+        );
+        Ok(())
+    }
+
+    /// A registered key, as keys.json currently has it
+    fn key_info(&mut self, public_key: &str) -> Result<Option<TokenInfo>> {
+        self.reload_if_changed()?;
+        Ok(self.keys.get(public_key).cloned())
+    }
+
+    /// Issue a one-time nonce for a registered key to sign
+    pub fn issue_challenge(&mut self, public_key: &str) -> Result<String> {
+        if self.key_info(public_key)?.is_none() {
+            return Err(Error::Custom("Unknown public key".to_string()));
+        }
+        let now = Instant::now();
+        self.challenges.retain(|_, (_, issued)| now.duration_since(*issued) < CHALLENGE_TTL);
+        let nonce = AuthManager::generate_token();
+        self.challenges.insert(nonce.clone(), (public_key.to_string(), now));
+        Ok(nonce)
+    }
+
+    /// Check a signed challenge and start a session for its key, returning
+    /// a bearer token for the session. Each challenge can be answered once.
+    pub fn answer_challenge(&mut self, public_key: &str, nonce: &str, signature: &str) -> Result<String> {
+        let invalid = || Error::Custom("Invalid or expired challenge".to_string());
+        let (key, issued) = self.challenges.remove(nonce).ok_or_else(invalid)?;
+        if key != public_key || issued.elapsed() >= CHALLENGE_TTL {
+            return Err(invalid());
+        }
+
+        let verifier = CryptoKey {
+            public_key: public_key.to_string(),
+            seed: None,
+        };
+        if !verifier.verify(&challenge_message(nonce), signature)? {
+            return Err(Error::Custom("Signature does not match the key".to_string()));
+        }
+
+        let now = Instant::now();
+        self.sessions.retain(|_, (_, issued)| now.duration_since(*issued) < SESSION_TTL);
+        let token = AuthManager::generate_token();
+        self.sessions.insert(token.clone(), (public_key.to_string(), now));
+        Ok(token)
+    }
+
+    /// Seconds a session token stays valid
+    pub fn session_ttl() -> u64 {
+        SESSION_TTL.as_secs()
+    }
 
-                Ok(has_permission)
+    /// The role a token or session holds on `repo`, if any
+    pub fn role(&mut self, token: &str, repo: &str) -> Result<Option<Role>> {
+        self.reload_if_changed()?;
+        let info = if let Some(info) = self.tokens.get(token) {
+            Some(info.clone())
+        } else if let Some((key, issued)) = self.sessions.get(token) {
+            // Session tokens carry the permissions of the key that signed in
+            if issued.elapsed() < SESSION_TTL {
+                let key = key.clone();
+                self.key_info(&key)?
+            } else {
+                None
             }
-            None => Ok(false),
+        } else {
+            None
+        };
+
+        Ok(info.and_then(|info| {
+            info.permissions
+                .iter()
+                .filter(|p| p.repo() == repo)
+                .map(Permission::role)
+                .max()
+        }))
+    }
+
+    /// Whether `token` is a provisioned token or a live session, whatever
+    /// it grants
+    pub fn knows(&mut self, token: &str) -> Result<bool> {
+        if let Some((_, issued)) = self.sessions.get(token) {
+            return Ok(issued.elapsed() < SESSION_TTL);
         }
+        self.reload_if_changed()?;
+        Ok(self.tokens.contains_key(token))
+    }
+
+    /// Verify token and check it holds at least `required` on `repo`
+    pub fn verify(&mut self, token: &str, repo: &str, required: Role) -> Result<bool> {
+        Ok(self.role(token, repo)?.is_some_and(|role| role >= required))
     }
 
     /// Get token info
@@ -154,8 +428,107 @@ mod tests {
             vec![Permission::Read("repo1".to_string())],
         );
 
-        assert!(auth.verify(&token, "repo1", "read").unwrap());
-        assert!(!auth.verify(&token, "repo1", "write").unwrap());
-        assert!(!auth.verify(&token, "repo2", "read").unwrap());
+        assert!(auth.verify(&token, "repo1", Role::Read).unwrap());
+        assert!(!auth.verify(&token, "repo1", Role::Write).unwrap());
+        assert!(!auth.verify(&token, "repo2", Role::Read).unwrap());
+    }
+
+    #[test]
+    fn test_roles_per_repo() {
+        let mut auth = ServerAuth::new();
+        auth.add_token(
+            "token-123456".to_string(),
+            "maintainer".to_string(),
+            vec![
+                Permission::Read("docs".to_string()),
+                Permission::Admin("app".to_string()),
+            ],
+        );
+
+        assert_eq!(auth.role("token-123456", "app").unwrap(), Some(Role::Admin));
+        assert!(auth.verify("token-123456", "app", Role::Write).unwrap());
+        assert!(auth.verify("token-123456", "docs", Role::Read).unwrap());
+        assert!(!auth.verify("token-123456", "docs", Role::Write).unwrap());
+        assert_eq!(auth.role("token-123456", "other").unwrap(), None);
+        assert!(Role::Read < Role::Write && Role::Write < Role::Admin);
+        assert_eq!("admin".parse::<Role>().unwrap().to_string(), "admin");
+    }
+
+    #[test]
+    fn test_server_auth_persists_tokens() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("tokens.json");
+
+        let mut auth = ServerAuth::open(&path).unwrap();
+        let mut reader = ServerAuth::open(&path).unwrap();
+        auth.add_token(
+            "token-123456".to_string(),
+            "ci".to_string(),
+            vec![Permission::for_access("write", "repo1").unwrap()],
+        );
+        auth.save().unwrap();
+
+        // A store opened earlier sees tokens written since
+        assert!(reader.verify("token-123456", "repo1", Role::Write).unwrap());
+
+        let mut reopened = ServerAuth::open(&path).unwrap();
+        assert!(reopened.verify("token-123456", "repo1", Role::Read).unwrap());
+        assert_eq!(reopened.get_token_info("token-123456").unwrap().username, "ci");
+        assert!(Permission::for_access("owner", "repo1").is_err());
+
+        // Editing the file downgrades or revokes tokens without a restart
+        let mut tokens: HashMap<String, TokenInfo> = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        tokens.get_mut("token-123456").unwrap().permissions = vec![Permission::Read("repo1".to_string())];
+        fs::write(&path, serde_json::to_vec_pretty(&tokens).unwrap()).unwrap();
+        assert!(!reader.verify("token-123456", "repo1", Role::Write).unwrap());
+        assert!(reader.verify("token-123456", "repo1", Role::Read).unwrap());
+        fs::write(&path, "{}").unwrap();
+        assert!(!reader.verify("token-123456", "repo1", Role::Read).unwrap());
+        assert!(!reader.knows("token-123456").unwrap());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            for file in ["tokens.json", "keys.json"] {
+                let mode = fs::metadata(dir.path().join(file)).unwrap().permissions().mode();
+                assert_eq!(mode & 0o777, 0o600, "{}", file);
+            }
+        }
+    }
+
+    #[test]
+    fn test_key_challenge_starts_session() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("tokens.json");
+        let (key, public_key) = CryptoKey::generate().unwrap();
+
+        let mut admin = ServerAuth::open(&path).unwrap();
+        assert!(admin.add_key("not-a-key", "bad".to_string(), vec![]).is_err());
+        admin
+            .add_key(&public_key, "laptop".to_string(), vec![Permission::Write("repo1".to_string())])
+            .unwrap();
+        admin.save().unwrap();
+
+        // The server's store was opened before the key was registered
+        let mut auth = ServerAuth::new();
+        auth.path = Some(path);
+        let (_, stranger) = CryptoKey::generate().unwrap();
+        assert!(auth.issue_challenge(&stranger).is_err());
+
+        let nonce = auth.issue_challenge(&public_key).unwrap();
+        let forged = key.sign(b"something else").unwrap();
+        assert!(auth.answer_challenge(&public_key, &nonce, &forged).is_err());
+        // A failed answer uses up the challenge
+        let signature = key.sign(&challenge_message(&nonce)).unwrap();
+        assert!(auth.answer_challenge(&public_key, &nonce, &signature).is_err());
+
+        let nonce = auth.issue_challenge(&public_key).unwrap();
+        let signature = key.sign(&challenge_message(&nonce)).unwrap();
+        let session = auth.answer_challenge(&public_key, &nonce, &signature).unwrap();
+        assert!(auth.answer_challenge(&public_key, &nonce, &signature).is_err());
+
+        assert!(auth.verify(&session, "repo1", Role::Write).unwrap());
+        assert!(!auth.verify(&session, "repo2", Role::Read).unwrap());
+        assert!(!auth.verify(&nonce, "repo1", Role::Read).unwrap());
     }
 }
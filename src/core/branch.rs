@@ -1,5 +1,6 @@
-use crate::core::database::MugDb;
-use crate::core::error::Result;
+use crate::core::database::{MugDb, Transaction};
+use crate::core::error::{Error, Result};
+use crate::core::reflog::Reflog;
 use serde::{Deserialize, Serialize};
 
 /// A branch reference
@@ -37,9 +38,14 @@ impl BranchManager {
         }
     }
 
-    /// Delete a branch
+    /// Delete a branch, recording its last commit in the reflog so it can
+    /// be recovered
     pub fn delete_branch(&self, name: &str) -> Result<()> {
+        let branch = self
+            .get_branch(name)?
+            .ok_or_else(|| Error::BranchNotFound(name.to_string()))?;
         self.db.delete("BRANCHES", name)?;
+        Reflog::new(self.db.clone()).record(name, &branch.commit_id, "", "branch: deleted")?;
         Ok(())
     }
 
@@ -57,12 +63,46 @@ impl BranchManager {
 
     /// Update a branch to point to a different commit
     pub fn update_branch(&self, name: &str, commit_id: String) -> Result<()> {
+        self.update_branch_with_reason(name, commit_id, "update")
+    }
+
+    /// Update a branch and record `operation` as the reason in the reflog
+    pub fn update_branch_with_reason(
+        &self,
+        name: &str,
+        commit_id: String,
+        operation: &str,
+    ) -> Result<()> {
+        self.db
+            .transaction(|tx| self.update_branch_in(tx, name, commit_id.clone(), operation))
+    }
+
+    /// Update a branch and its reflog as part of a transaction
+    pub fn update_branch_in(
+        &self,
+        tx: &Transaction,
+        name: &str,
+        commit_id: String,
+        operation: &str,
+    ) -> Result<()> {
+        let old_commit = match tx.get("BRANCHES", name)? {
+            Some(data) => serde_json::from_slice::<BranchRef>(&data)?.commit_id,
+            None => String::new(),
+        };
+
         let branch = BranchRef {
             name: name.to_string(),
-            commit_id,
+            commit_id: commit_id.clone(),
         };
         let serialized = serde_json::to_vec(&branch)?;
-        self.db.set("BRANCHES", name, serialized)?;
+        tx.set("BRANCHES", name, serialized)?;
+
+        let reflog = Reflog::new(self.db.clone());
+        reflog.record_in(tx, name, &old_commit, &commit_id, operation)?;
+        if tx.get("HEAD", "HEAD")?.as_deref() == Some(name.as_bytes()) {
+            reflog.record_in(tx, "HEAD", &old_commit, &commit_id, operation)?;
+        }
+
         Ok(())
     }
 
@@ -76,14 +116,46 @@ impl BranchManager {
 
     /// Set the HEAD reference
     pub fn set_head(&self, ref_name: String) -> Result<()> {
-        self.db.set("HEAD", "HEAD", ref_name)?;
+        let old_head = self.get_head()?;
+        let old_commit = match &old_head {
+            Some(head) => self.head_commit_for(head)?,
+            None => String::new(),
+        };
+        let new_commit = self.head_commit_for(&ref_name)?;
+
+        self.db.set("HEAD", "HEAD", ref_name.as_str())?;
+
+        let operation = format!(
+            "checkout: moving from {} to {}",
+            old_head.unwrap_or_default(),
+            ref_name
+        );
+        Reflog::new(self.db.clone()).record("HEAD", &old_commit, &new_commit, &operation)?;
         Ok(())
     }
 
+    /// Resolve a HEAD value (branch name or detached marker) to a commit id
+    fn head_commit_for(&self, head: &str) -> Result<String> {
+        if let Some(commit_id) = head.strip_prefix("detached:") {
+            return Ok(commit_id.to_string());
+        }
+        Ok(self
+            .get_branch(head)?
+            .map(|b| b.commit_id)
+            .unwrap_or_default())
+    }
+
     /// Detach HEAD to a specific commit
     pub fn detach_head(&self, commit_id: String) -> Result<()> {
+        let old_commit = match self.get_head()? {
+            Some(head) => self.head_commit_for(&head)?,
+            None => String::new(),
+        };
         let detached_marker = format!("detached:{}", commit_id);
         self.db.set("HEAD", "HEAD", detached_marker)?;
+
+        let operation = format!("checkout: moving to {}", commit_id);
+        Reflog::new(self.db.clone()).record("HEAD", &old_commit, &commit_id, &operation)?;
         Ok(())
     }
 
@@ -141,4 +213,29 @@ mod tests {
         manager.set_head("main".to_string()).unwrap();
         assert_eq!(manager.get_head().unwrap(), Some("main".to_string()));
     }
+
+    #[test]
+    fn test_update_branch_writes_reflog() {
+        let dir = TempDir::new().unwrap();
+        let db = MugDb::new(dir.path().join("db")).unwrap();
+        let manager = BranchManager::new(db.clone());
+
+        manager
+            .create_branch("main".to_string(), "commit1".to_string())
+            .unwrap();
+        manager.set_head("main".to_string()).unwrap();
+        manager
+            .update_branch_with_reason("main", "commit2".to_string(), "commit: second")
+            .unwrap();
+
+        let reflog = Reflog::new(db);
+        let entries = reflog.entries("main").unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].old_hash, "commit1");
+        assert_eq!(entries[0].new_hash, "commit2");
+
+        let head_entries = reflog.entries("HEAD").unwrap();
+        assert_eq!(head_entries.len(), 2);
+        assert_eq!(head_entries[0].operation, "commit: second");
+    }
 }
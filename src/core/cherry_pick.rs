@@ -1,87 +1,258 @@
+use std::collections::HashMap;
+
+use crate::core::branch::BranchManager;
+use crate::core::commit::CommitLog;
+use crate::core::config::Config;
 use crate::core::error::{Error, Result};
+use crate::core::index::Index;
+use crate::core::merge::{merge_snapshots, MergeStrategy, SnapshotMerge};
+use crate::core::rebase_tui::RebaseAction;
 use crate::core::repo::Repository;
+use crate::core::sequencer::{self, OperationKind, OperationState};
 
-/// Cherry-pick a commit onto the current branch
+/// Cherry-pick a commit onto the current branch.
+///
+/// The changes the commit made relative to its first parent are merged into
+/// HEAD. On conflict the working tree is left with conflict markers, the
+/// cleanly applied paths are staged, and no commit is created until
+/// [`continue_cherry_pick`].
 pub fn cherry_pick(repo: &Repository, commit_id: &str) -> Result<CherryPickResult> {
-    let current_branch = repo.current_branch()?;
-    let current_branch_name = current_branch.as_deref().unwrap_or("main");
-
-    // Get the commit to cherry-pick
-    let commits = repo.log()?;
-    let cherry_pick_commit = commits
-        .iter()
-        .find(|c| c.contains(commit_id))
-        .ok_or_else(|| Error::Custom(format!("Commit {} not found", commit_id)))?;
-
-    // Ensure we're not cherry-picking from the current branch to itself
-    if cherry_pick_commit.contains(current_branch_name) {
+    pick(repo, commit_id, None, &[])
+}
+
+/// Cherry-pick one commit. `orig_head` is where the branch was before the
+/// whole operation started and `remaining` the commits to pick after this
+/// one; both are recorded if the pick stops on conflicts.
+fn pick(
+    repo: &Repository,
+    commit_id: &str,
+    orig_head: Option<&str>,
+    remaining: &[String],
+) -> Result<CherryPickResult> {
+    sequencer::ensure_no_operation(repo)?;
+    let current_branch = repo.current_branch()?.ok_or_else(|| {
+        Error::Custom("Cannot cherry-pick with a detached HEAD".to_string())
+    })?;
+    if !Index::new(repo.get_db().clone())?.entries().is_empty() {
+        return Err(Error::Custom(
+            "Cannot cherry-pick with staged changes; commit them first".to_string(),
+        ));
+    }
+    repo.ensure_clean_working_tree("cherry-pick")?;
+
+    let commit_id = repo.resolve_rev(commit_id)?;
+    let short = commit_id.chars().take(7).collect::<String>();
+    let head = repo.head_commit_id()?.ok_or(Error::NoCommits)?;
+
+    // Ensure we're not cherry-picking a commit already on the current branch
+    let commit_log = CommitLog::new(repo.get_db().clone());
+    if commit_log.is_ancestor(&commit_id, &head)? {
         return Err(Error::Custom(
             "Cannot cherry-pick a commit from the current branch".to_string(),
         ));
     }
 
-    // Create a new commit with the same changes but different parent
-    let new_commit = format!(
-        "cherry-pick: {} on {}",
-        commit_id.chars().take(7).collect::<String>(),
-        current_branch_name
-    );
+    let original = commit_log.get_commit(&commit_id)?;
+    let base = match &original.parent {
+        Some(parent) => repo.snapshot(parent)?,
+        None => HashMap::new(),
+    };
+    let ours = repo.snapshot(&head)?;
+    let SnapshotMerge {
+        files: merged,
+        conflicts,
+    } = merge_snapshots(
+        repo,
+        &base,
+        &ours,
+        &repo.snapshot(&commit_id)?,
+        "HEAD",
+        &short,
+        MergeStrategy::Recursive,
+    )?;
 
-    Ok(CherryPickResult {
-        success: true,
-        original_commit: commit_id.to_string(),
-        new_commit,
-        branch: current_branch_name.to_string(),
-        message: format!(
-            "Successfully cherry-picked {} onto {}",
-            commit_id.chars().take(7).collect::<String>(),
-            current_branch_name
-        ),
-    })
+    let mut result = CherryPickResult {
+        success: false,
+        original_commit: commit_id.clone(),
+        new_commit: head.clone(),
+        branch: current_branch.clone(),
+        conflicts: vec![],
+        message: String::new(),
+    };
+
+    let committer = Config::load(repo.common_root())?.get_user_name();
+    let author = if original.author.is_empty() { committer } else { original.author };
+
+    if merged == ours {
+        result.success = true;
+        result.message = format!("{} is already applied to {}; nothing to commit", short, current_branch);
+        return Ok(result);
+    }
+
+    let modes = repo.carry_modes(&merged, &[&head, &commit_id])?;
+    repo.update_working_tree(&ours, &merged, &modes)?;
+
+    if !conflicts.is_empty() {
+        // Stage the cleanly applied paths so the resolving commit includes them
+        let mut index = Index::new(repo.get_db().clone())?;
+        for (path, hash) in &merged {
+            if ours.get(path) != Some(hash) && !conflicts.contains(path) {
+                index.add(path.clone(), hash.clone())?;
+            }
+        }
+        for path in ours.keys().filter(|path| !merged.contains_key(*path)) {
+            index.stage_removal(path)?;
+        }
+        index.flush()?;
+
+        OperationState {
+            kind: OperationKind::CherryPick,
+            branch: current_branch,
+            orig_head: orig_head.unwrap_or(&head).to_string(),
+            target: commit_id,
+            files: merged,
+            conflicts: conflicts.clone(),
+            author,
+            message: original.message,
+            parent: Some(head),
+            merge_head: None,
+            edit_message: false,
+            remaining: remaining.iter().map(|id| (id.clone(), RebaseAction::Pick)).collect(),
+            applied: 0,
+        }
+        .save(repo)?;
+
+        result.conflicts = conflicts;
+        result.message = format!(
+            "Could not apply {}; fix conflicts and run 'mug cherry-pick --continue'",
+            short
+        );
+        return Ok(result);
+    }
+
+    let tree_hash = repo.store_snapshot_with_modes(&merged, &modes)?;
+    let signing_key = repo.default_signing_key()?;
+    let commit = CommitLog::prepare_commit(
+        tree_hash,
+        author,
+        original.message.clone(),
+        Some(head),
+        None,
+        signing_key.as_ref(),
+    )?;
+    let serialized = serde_json::to_vec(&commit)?;
+
+    let summary = original.message.lines().next().unwrap_or_default();
+    let branch_manager = BranchManager::new(repo.get_db().clone());
+    repo.get_db().transaction(|tx| {
+        tx.set("COMMITS", &commit.id, &serialized)?;
+        branch_manager.update_branch_in(
+            tx,
+            &current_branch,
+            commit.id.clone(),
+            &format!("cherry-pick: {}", summary),
+        )
+    })?;
+    repo.get_db().flush()?;
+
+    result.success = true;
+    result.new_commit = commit.id;
+    result.message = format!("Successfully cherry-picked {} onto {}", short, current_branch);
+    Ok(result)
 }
 
-/// Cherry-pick multiple commits
+/// Cherry-pick multiple commits, oldest first, stopping at the first one
+/// that fails or conflicts
 pub fn cherry_pick_range(
     repo: &Repository,
     start_id: &str,
     end_id: &str,
 ) -> Result<CherryPickRangeResult> {
-    let commits = repo.log()?;
+    let start_id = repo.resolve_rev(start_id)?;
+    let end_id = repo.resolve_rev(end_id)?;
+
+    // Walk first parents from the end of the range back to its start
+    let commit_log = CommitLog::new(repo.get_db().clone());
+    let mut range = Vec::new();
+    let mut next = Some(end_id);
+    while let Some(id) = next {
+        next = commit_log.get_commit(&id)?.parent;
+        let reached_start = id == start_id;
+        range.push(id);
+        if reached_start {
+            break;
+        }
+    }
+    if range.last() != Some(&start_id) {
+        return Err(Error::Custom(
+            "Start commit is not an ancestor of the end commit".to_string(),
+        ));
+    }
+    range.reverse();
+
+    let orig_head = repo.head_commit_id()?.ok_or(Error::NoCommits)?;
+    pick_all(repo, &range, &orig_head)
+}
+
+/// Conclude a cherry-pick that stopped on conflicts, once they are resolved,
+/// then pick any commits of the range that were still to come
+pub fn continue_cherry_pick(repo: &Repository) -> Result<CherryPickRangeResult> {
+    let state = OperationState::load_kind(repo, OperationKind::CherryPick)?;
+    let files = sequencer::resolved_files(repo, &state)?;
+    let new_commit = sequencer::commit_resolution(repo, &state, &files, state.message.clone(), true)?;
+    sequencer::finish(repo)?;
+
+    let remaining: Vec<String> = state.remaining.into_iter().map(|(id, _)| id).collect();
+    let mut result = pick_all(repo, &remaining, &state.orig_head)?;
+    result.total += 1;
+    result.successful += 1;
+    result.picked_commits.insert(
+        0,
+        CherryPickResult {
+            success: true,
+            message: format!(
+                "Successfully cherry-picked {} onto {}",
+                crate::core::hash::short_hash(&state.target),
+                state.branch
+            ),
+            original_commit: state.target,
+            new_commit,
+            branch: state.branch,
+            conflicts: vec![],
+        },
+    );
+    Ok(result)
+}
+
+/// Pick `commits` in order, stopping at the first that fails or conflicts
+fn pick_all(repo: &Repository, commits: &[String], orig_head: &str) -> Result<CherryPickRangeResult> {
     let mut picked_commits = Vec::new();
     let mut failed_commits = Vec::new();
-
-    let start_found = commits.iter().position(|c| c.contains(start_id));
-    let end_found = commits.iter().position(|c| c.contains(end_id));
-
-    match (start_found, end_found) {
-        (Some(start), Some(end)) => {
-            let (from, to) = if start < end {
-                (start, end)
-            } else {
-                (end, start)
-            };
-
-            for i in from..=to {
-                if let Some(commit_log) = commits.get(i) {
-                    match cherry_pick(repo, commit_log) {
-                        Ok(result) => picked_commits.push(result),
-                        Err(e) => failed_commits.push((commit_log.clone(), e.to_string())),
-                    }
-                }
+    let mut conflicts = Vec::new();
+    for (index, id) in commits.iter().enumerate() {
+        match pick(repo, id, Some(orig_head), &commits[index + 1..]) {
+            Ok(result) if result.success => picked_commits.push(result),
+            Ok(result) => {
+                failed_commits.push((id.clone(), result.message));
+                conflicts = result.conflicts;
+                break;
+            }
+            Err(e) => {
+                failed_commits.push((id.clone(), e.to_string()));
+                break;
             }
-
-            Ok(CherryPickRangeResult {
-                total: to - from + 1,
-                successful: picked_commits.len(),
-                failed: failed_commits.len(),
-                picked_commits,
-                failed_commits,
-            })
         }
-        _ => Err(Error::Custom(
-            "One or both commit IDs not found".to_string(),
-        )),
     }
+
+    Ok(CherryPickRangeResult {
+        total: commits.len(),
+        successful: picked_commits.len(),
+        failed: failed_commits.len(),
+        stopped_at: failed_commits.first().map(|(id, _)| id.clone()),
+        conflicts,
+        picked_commits,
+        failed_commits,
+    })
 }
 
 /// Result of a single cherry-pick operation
@@ -91,6 +262,8 @@ pub struct CherryPickResult {
     pub original_commit: String,
     pub new_commit: String,
     pub branch: String,
+    /// Paths left with conflict markers
+    pub conflicts: Vec<String>,
     pub message: String,
 }
 
@@ -102,6 +275,10 @@ pub struct CherryPickRangeResult {
     pub failed: usize,
     pub picked_commits: Vec<CherryPickResult>,
     pub failed_commits: Vec<(String, String)>,
+    /// The commit the range stopped at, if any failed
+    pub stopped_at: Option<String>,
+    /// Conflicted paths left by the commit the range stopped at
+    pub conflicts: Vec<String>,
 }
 
 #[cfg(test)]
@@ -115,6 +292,7 @@ mod tests {
             original_commit: "abc123".to_string(),
             new_commit: "def456".to_string(),
             branch: "main".to_string(),
+            conflicts: vec![],
             message: "Cherry-pick successful".to_string(),
         };
 
@@ -131,10 +309,125 @@ mod tests {
             failed: 0,
             picked_commits: vec![],
             failed_commits: vec![],
+            stopped_at: None,
+            conflicts: vec![],
         };
 
         assert_eq!(result.total, 3);
         assert_eq!(result.successful, 3);
         assert_eq!(result.failed, 0);
     }
+
+    fn repo_with_feature() -> (tempfile::TempDir, Repository) {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        std::fs::write(dir.path().join("file.txt"), "one\ntwo\nthree\n").unwrap();
+        repo.add("file.txt").unwrap();
+        repo.commit("Tester".to_string(), "Base".to_string()).unwrap();
+        repo.create_branch("feature".to_string()).unwrap();
+        (dir, repo)
+    }
+
+    fn commit_file(repo: &Repository, dir: &tempfile::TempDir, content: &str, message: &str) -> String {
+        std::fs::write(dir.path().join("file.txt"), content).unwrap();
+        repo.add("file.txt").unwrap();
+        repo.commit("Tester".to_string(), message.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_cherry_pick_applies_commit_changes() {
+        let (dir, repo) = repo_with_feature();
+        repo.checkout("feature".to_string()).unwrap();
+        let picked = commit_file(&repo, &dir, "one\ntwo\nthree\nfour\n", "Add four");
+        repo.checkout("main".to_string()).unwrap();
+        commit_file(&repo, &dir, "zero\ntwo\nthree\n", "Change one");
+
+        let result = cherry_pick(&repo, &picked).unwrap();
+        assert!(result.success);
+        assert!(result.conflicts.is_empty());
+        assert_eq!(repo.head_commit_id().unwrap(), Some(result.new_commit.clone()));
+
+        let content = std::fs::read_to_string(dir.path().join("file.txt")).unwrap();
+        assert_eq!(content, "zero\ntwo\nthree\nfour\n");
+        assert_eq!(repo.history().unwrap()[0].message, "Add four");
+    }
+
+    #[test]
+    fn test_cherry_pick_refuses_unstaged_changes() {
+        let (dir, repo) = repo_with_feature();
+        repo.checkout("feature".to_string()).unwrap();
+        let picked = commit_file(&repo, &dir, "one\ntwo\nthree\nfour\n", "Add four");
+        repo.checkout("main".to_string()).unwrap();
+        let head = repo.head_commit_id().unwrap();
+        std::fs::write(dir.path().join("file.txt"), "one\ntwo\nthree\nlocal\n").unwrap();
+
+        let err = cherry_pick(&repo, &picked).unwrap_err();
+        assert!(err.to_string().contains("file.txt"), "{}", err);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("file.txt")).unwrap(),
+            "one\ntwo\nthree\nlocal\n"
+        );
+        assert_eq!(repo.head_commit_id().unwrap(), head);
+    }
+
+    #[test]
+    fn test_cherry_pick_conflict_writes_markers() {
+        let (dir, repo) = repo_with_feature();
+        repo.checkout("feature".to_string()).unwrap();
+        let picked = commit_file(&repo, &dir, "one\nfeature\nthree\n", "Feature");
+        repo.checkout("main".to_string()).unwrap();
+        let head = commit_file(&repo, &dir, "one\nmain\nthree\n", "Main");
+
+        let result = cherry_pick(&repo, &picked).unwrap();
+        assert!(!result.success);
+        assert_eq!(result.conflicts, vec!["file.txt".to_string()]);
+        assert_eq!(repo.head_commit_id().unwrap(), Some(head));
+
+        let content = std::fs::read_to_string(dir.path().join("file.txt")).unwrap();
+        assert!(content.contains("<<<<<<<") && content.contains(">>>>>>>"));
+        assert!(content.contains("main") && content.contains("feature"));
+    }
+
+    #[test]
+    fn test_cherry_pick_range_stops_at_conflict() {
+        let (dir, repo) = repo_with_feature();
+        repo.checkout("feature".to_string()).unwrap();
+        let first = commit_file(&repo, &dir, "one\ntwo\nthree\nfour\n", "Add four");
+        let second = commit_file(&repo, &dir, "uno\ntwo\nthree\nfour\n", "Translate one");
+        let third = commit_file(&repo, &dir, "uno\ntwo\nthree\nfour\nfive\n", "Add five");
+        repo.checkout("main".to_string()).unwrap();
+        commit_file(&repo, &dir, "eins\ntwo\nthree\n", "Translate one differently");
+
+        let result = cherry_pick_range(&repo, &first, &third).unwrap();
+        assert_eq!(result.total, 3);
+        assert_eq!(result.successful, 1);
+        assert_eq!(result.failed, 1);
+        assert_eq!(result.stopped_at, Some(second));
+        assert_eq!(result.conflicts, vec!["file.txt".to_string()]);
+        assert_eq!(repo.history().unwrap()[0].message, "Add four");
+    }
+
+    #[test]
+    fn test_cherry_pick_continue_finishes_range() {
+        let (dir, repo) = repo_with_feature();
+        repo.checkout("feature".to_string()).unwrap();
+        let first = commit_file(&repo, &dir, "uno\ntwo\nthree\n", "Translate one");
+        let second = commit_file(&repo, &dir, "uno\ntwo\nthree\nfour\n", "Add four");
+        repo.checkout("main".to_string()).unwrap();
+        commit_file(&repo, &dir, "eins\ntwo\nthree\n", "Translate one differently");
+
+        let result = cherry_pick_range(&repo, &first, &second).unwrap();
+        assert_eq!(result.stopped_at, Some(first));
+        assert!(continue_cherry_pick(&repo).is_err());
+
+        std::fs::write(dir.path().join("file.txt"), "one\ntwo\nthree\n").unwrap();
+        let result = continue_cherry_pick(&repo).unwrap();
+        assert_eq!(result.total, 2);
+        assert_eq!(result.successful, 2);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("file.txt")).unwrap(),
+            "one\ntwo\nthree\nfour\n"
+        );
+        assert_eq!(repo.history().unwrap()[0].message, "Add four");
+    }
 }
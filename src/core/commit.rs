@@ -1,6 +1,8 @@
+use crate::core::crypto::{CryptoKey, KeyManager, SignatureStatus};
 use crate::core::database::MugDb;
 use crate::core::error::Result;
 use chrono::{DateTime, Utc};
+use std::collections::{HashSet, VecDeque};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -21,9 +23,47 @@ pub struct CommitMetadata {
     pub id: String,
     pub tree_hash: String,
     pub parent: Option<String>,
+    /// Second parent of a merge commit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merge_parent: Option<String>,
     pub author: String,
     pub message: String,
     pub timestamp: DateTime<Utc>,
+    /// Base64-encoded Ed25519 signature over the commit content
+    #[serde(default)]
+    pub signature: Option<String>,
+    /// Base64-encoded public key of the signer
+    #[serde(default)]
+    pub signer_key: Option<String>,
+}
+
+impl CommitMetadata {
+    /// The bytes covered by the commit signature
+    pub fn signing_payload(&self) -> Vec<u8> {
+        let merge_line = match &self.merge_parent {
+            Some(merge_parent) => format!("merge {}\n", merge_parent),
+            None => String::new(),
+        };
+        format!(
+            "tree {}\nparent {}\n{}author {}\ntimestamp {}\n\n{}",
+            self.tree_hash,
+            self.parent.as_deref().unwrap_or(""),
+            merge_line,
+            self.author,
+            self.timestamp.to_rfc3339(),
+            self.message
+        )
+        .into_bytes()
+    }
+
+    /// All parents of this commit, first parent first
+    pub fn parents(&self) -> Vec<String> {
+        self.parent
+            .iter()
+            .chain(self.merge_parent.iter())
+            .cloned()
+            .collect()
+    }
 }
 
 pub struct CommitLog {
@@ -43,22 +83,86 @@ impl CommitLog {
         message: String,
         parent: Option<String>,
     ) -> Result<String> {
-        let commit_id = Uuid::new_v4().to_string();
-        let timestamp = chrono::Utc::now();
+        self.create_signed_commit(tree_hash, author, message, parent, None)
+    }
+
+    /// Create a new commit, signing it when a key is given
+    pub fn create_signed_commit(
+        &self,
+        tree_hash: String,
+        author: String,
+        message: String,
+        parent: Option<String>,
+        signing_key: Option<&CryptoKey>,
+    ) -> Result<String> {
+        self.store_commit(tree_hash, author, message, parent, None, signing_key)
+    }
 
-        let commit = CommitMetadata {
-            id: commit_id.clone(),
+    /// Create a merge commit with two parents
+    pub fn create_merge_commit(
+        &self,
+        tree_hash: String,
+        author: String,
+        message: String,
+        parent: String,
+        merge_parent: String,
+        signing_key: Option<&CryptoKey>,
+    ) -> Result<String> {
+        self.store_commit(
+            tree_hash,
+            author,
+            message,
+            Some(parent),
+            Some(merge_parent),
+            signing_key,
+        )
+    }
+
+    fn store_commit(
+        &self,
+        tree_hash: String,
+        author: String,
+        message: String,
+        parent: Option<String>,
+        merge_parent: Option<String>,
+        signing_key: Option<&CryptoKey>,
+    ) -> Result<String> {
+        let commit =
+            Self::prepare_commit(tree_hash, author, message, parent, merge_parent, signing_key)?;
+
+        let serialized = serde_json::to_vec(&commit)?;
+        self.db.set("COMMITS", &commit.id, serialized)?;
+
+        Ok(commit.id)
+    }
+
+    /// Build and sign a new commit without storing it
+    pub fn prepare_commit(
+        tree_hash: String,
+        author: String,
+        message: String,
+        parent: Option<String>,
+        merge_parent: Option<String>,
+        signing_key: Option<&CryptoKey>,
+    ) -> Result<CommitMetadata> {
+        let mut commit = CommitMetadata {
+            id: Uuid::new_v4().to_string(),
             tree_hash,
             parent,
+            merge_parent,
             author,
             message,
-            timestamp,
+            timestamp: chrono::Utc::now(),
+            signature: None,
+            signer_key: None,
         };
 
-        let serialized = serde_json::to_vec(&commit)?;
-        self.db.set("COMMITS", &commit_id, serialized)?;
+        if let Some(key) = signing_key {
+            commit.signature = Some(key.sign(&commit.signing_payload())?);
+            commit.signer_key = Some(key.public_key.clone());
+        }
 
-        Ok(commit_id)
+        Ok(commit)
     }
 
     /// Get a commit by ID
@@ -84,6 +188,54 @@ impl CommitLog {
         Ok(history)
     }
 
+    /// All commits reachable from `start_id` through any parent, including itself
+    pub fn ancestors(&self, start_id: &str) -> Result<HashSet<String>> {
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::from([start_id.to_string()]);
+
+        while let Some(id) = queue.pop_front() {
+            if !seen.insert(id.clone()) {
+                continue;
+            }
+            queue.extend(self.get_commit(&id)?.parents());
+        }
+
+        Ok(seen)
+    }
+
+    /// Check whether `ancestor_id` is reachable from `descendant_id`
+    pub fn is_ancestor(&self, ancestor_id: &str, descendant_id: &str) -> Result<bool> {
+        Ok(self.ancestors(descendant_id)?.contains(ancestor_id))
+    }
+
+    /// Find the nearest common ancestor of two commits
+    pub fn merge_base(&self, a: &str, b: &str) -> Result<Option<String>> {
+        let a_ancestors = self.ancestors(a)?;
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::from([b.to_string()]);
+
+        // Breadth-first from `b` so the closest shared commit is found first
+        while let Some(id) = queue.pop_front() {
+            if a_ancestors.contains(&id) {
+                return Ok(Some(id));
+            }
+            if seen.insert(id.clone()) {
+                queue.extend(self.get_commit(&id)?.parents());
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Check a commit's signature against the local keyring
+    pub fn verify_signature(&self, commit: &CommitMetadata) -> Result<SignatureStatus> {
+        KeyManager::new(self.db.clone()).check(
+            &commit.signing_payload(),
+            commit.signature.as_deref(),
+            commit.signer_key.as_deref(),
+        )
+    }
+
     /// Get the parent of a commit
     pub fn parent(&self, id: &str) -> Result<Option<CommitMetadata>> {
         let commit = self.get_commit(id)?;
@@ -147,4 +299,74 @@ mod tests {
         let history = log.history(id2).unwrap();
         assert_eq!(history.len(), 2);
     }
+
+    #[test]
+    fn test_signed_commit_verification() {
+        let dir = TempDir::new().unwrap();
+        let db = MugDb::new(dir.path().join("db")).unwrap();
+        let (key, _) = CryptoKey::generate().unwrap();
+        KeyManager::new(db.clone()).add(&key).unwrap();
+        let log = CommitLog::new(db);
+
+        let signed_id = log
+            .create_signed_commit(
+                "tree1".to_string(),
+                "User".to_string(),
+                "Signed".to_string(),
+                None,
+                Some(&key),
+            )
+            .unwrap();
+        let signed = log.get_commit(&signed_id).unwrap();
+        assert!(log.verify_signature(&signed).unwrap().is_good());
+
+        let mut tampered = signed.clone();
+        tampered.message = "Forged".to_string();
+        assert!(matches!(
+            log.verify_signature(&tampered).unwrap(),
+            SignatureStatus::Bad(_)
+        ));
+
+        let unsigned_id = log
+            .create_commit("tree2".to_string(), "User".to_string(), "Plain".to_string(), None)
+            .unwrap();
+        let unsigned = log.get_commit(&unsigned_id).unwrap();
+        assert_eq!(
+            log.verify_signature(&unsigned).unwrap(),
+            SignatureStatus::Unsigned
+        );
+    }
+
+    #[test]
+    fn test_merge_base() {
+        let dir = TempDir::new().unwrap();
+        let db = MugDb::new(dir.path().join("db")).unwrap();
+        let log = CommitLog::new(db);
+        let commit = |parent: Option<String>| {
+            log.create_commit("tree".to_string(), "User".to_string(), "msg".to_string(), parent)
+                .unwrap()
+        };
+
+        let root = commit(None);
+        let base = commit(Some(root.clone()));
+        let ours = commit(Some(base.clone()));
+        let theirs = commit(Some(base.clone()));
+
+        assert_eq!(log.merge_base(&ours, &theirs).unwrap(), Some(base.clone()));
+        assert!(log.is_ancestor(&root, &ours).unwrap());
+        assert!(!log.is_ancestor(&ours, &theirs).unwrap());
+
+        let merged = log
+            .create_merge_commit(
+                "tree".to_string(),
+                "User".to_string(),
+                "Merge".to_string(),
+                ours.clone(),
+                theirs.clone(),
+                None,
+            )
+            .unwrap();
+        assert!(log.is_ancestor(&theirs, &merged).unwrap());
+        assert_eq!(log.merge_base(&merged, &theirs).unwrap(), Some(theirs));
+    }
 }
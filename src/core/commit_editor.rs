@@ -1,9 +1,6 @@
 use crate::core::error::Result;
-use crossterm::{
-    event::{self, Event, KeyCode},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode},
-};
+use crate::ui::terminal::TerminalGuard;
+use crossterm::event::{self, Event, KeyCode};
 use ratatui::{
     backend::CrosstermBackend,
     layout::{Alignment, Constraint, Direction, Layout},
@@ -19,6 +16,10 @@ pub struct CommitEditorState {
     cursor_line: usize,
     cursor_col: usize,
     dirty: bool,
+    /// Staged diff shown beside the message, if any
+    diff: Vec<String>,
+    show_diff: bool,
+    diff_scroll: u16,
 }
 
 impl CommitEditorState {
@@ -34,9 +35,28 @@ impl CommitEditorState {
             cursor_line: 0,
             cursor_col: 0,
             dirty: false,
+            diff: Vec::new(),
+            show_diff: false,
+            diff_scroll: 0,
         }
     }
 
+    /// Show `diff` in a pane beside the message
+    pub fn with_diff(mut self, diff: Vec<String>) -> Self {
+        self.show_diff = !diff.is_empty();
+        self.diff = diff;
+        self
+    }
+
+    pub fn toggle_diff(&mut self) {
+        self.show_diff = !self.show_diff && !self.diff.is_empty();
+    }
+
+    pub fn scroll_diff(&mut self, lines: i32) {
+        let max = self.diff.len().saturating_sub(1) as i32;
+        self.diff_scroll = (self.diff_scroll as i32 + lines).clamp(0, max) as u16;
+    }
+
     pub fn insert_char(&mut self, ch: char) {
         if self.cursor_line < self.lines.len() {
             let line = &mut self.lines[self.cursor_line];
@@ -147,20 +167,90 @@ impl CommitEditorState {
     pub fn is_empty(&self) -> bool {
         self.lines.iter().all(|l| l.is_empty())
     }
+
+    /// The message with comment lines stripped, or `None` if nothing but
+    /// comments and whitespace remains
+    pub fn message(&self) -> Option<String> {
+        Some(strip_comments(&self.get_content())).filter(|message| !message.is_empty())
+    }
+}
+
+/// Initial editor content: a blank message followed by commented context
+pub fn commit_template(branch: &str, staged: &[String]) -> String {
+    let mut template = String::from("\n");
+    template.push_str("# Please enter the commit message for your changes. Lines starting\n");
+    template.push_str("# with '#' will be ignored, and an empty message aborts the commit.\n");
+    template.push_str(&format!("#\n# On branch {}\n# Changes to be committed:\n", branch));
+    for path in staged {
+        template.push_str(&format!("#\t{}\n", path));
+    }
+    template
+}
+
+/// Remove comment lines and surrounding blank lines from an edited message
+pub fn strip_comments(message: &str) -> String {
+    message
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
 }
 
+/// Reject messages whose first line is blank
+pub fn validate_message(message: &str) -> Result<()> {
+    match message.lines().next() {
+        Some(subject) if !subject.trim().is_empty() => Ok(()),
+        _ => Err(crate::core::error::Error::Custom(
+            "Aborting commit: the subject line is empty".to_string(),
+        )),
+    }
+}
+
+/// Append a `Signed-off-by` trailer unless the message already has it
+pub fn add_signoff(message: &str, name: &str, email: &str) -> String {
+    let trailer = format!("Signed-off-by: {} <{}>", name, email);
+    let message = message.trim_end();
+    if message.lines().any(|line| line.trim() == trailer) {
+        return message.to_string();
+    }
+
+    // Join an existing trailer block instead of starting a new paragraph
+    let last_paragraph = message.rsplit("
+
+").next().unwrap_or("");
+    let has_trailers = message.contains("
+
+")
+        && last_paragraph.lines().all(|line| {
+            line.split_once(": ")
+                .map(|(key, _)| !key.is_empty() && !key.contains(' '))
+                .unwrap_or(false)
+        });
+    let separator = if has_trailers { "\n" } else { "\n\n" };
+    format!("{}{}{}", message, separator, trailer)
+}
+
+/// Edit a commit message in the terminal. Lines starting with `#` are
+/// comments and are stripped; returns `None` if cancelled or if nothing but
+/// comments remains.
 pub fn run_commit_editor(initial_message: Option<String>) -> Result<Option<String>> {
-    enable_raw_mode().map_err(|e| crate::core::error::Error::Custom(e.to_string()))?;
-    let mut stdout = io::stdout();
-    
-    execute!(stdout, crossterm::cursor::Show)
-        .map_err(|e| crate::core::error::Error::Custom(e.to_string()))?;
+    run_commit_editor_with_diff(initial_message, Vec::new())
+}
 
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)
+/// `run_commit_editor` with `diff` (usually the staged changes) shown in a
+/// pane beside the message, toggled with Ctrl+D
+pub fn run_commit_editor_with_diff(
+    initial_message: Option<String>,
+    diff: Vec<String>,
+) -> Result<Option<String>> {
+    // Restores the terminal however the editor exits
+    let _guard = TerminalGuard::new()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))
         .map_err(|e| crate::core::error::Error::Custom(e.to_string()))?;
 
-    let mut state = CommitEditorState::new(initial_message);
+    let mut state = CommitEditorState::new(initial_message).with_diff(diff);
 
     loop {
         terminal
@@ -171,18 +261,13 @@ pub fn run_commit_editor(initial_message: Option<String>) -> Result<Option<Strin
             .map_err(|e| crate::core::error::Error::Custom(e.to_string()))?
         {
             match key.code {
-                KeyCode::Esc => {
-                    disable_raw_mode().ok();
-                    return Ok(None);
-                }
+                KeyCode::Esc => return Ok(None),
                 KeyCode::Char(c) => {
                     if key.modifiers.contains(crossterm::event::KeyModifiers::CONTROL) {
                         match c {
                             's' => break, // Ctrl+S to save
-                            'c' => {
-                                disable_raw_mode().ok();
-                                return Ok(None);
-                            }
+                            'c' => return Ok(None),
+                            'd' => state.toggle_diff(),
                             _ => {}
                         }
                     } else {
@@ -219,21 +304,18 @@ pub fn run_commit_editor(initial_message: Option<String>) -> Result<Option<Strin
                 KeyCode::Tab => {
                     state.insert_char('\t');
                 }
+                KeyCode::PageDown => {
+                    state.scroll_diff(10);
+                }
+                KeyCode::PageUp => {
+                    state.scroll_diff(-10);
+                }
                 _ => {}
             }
         }
     }
 
-    disable_raw_mode().map_err(|e| crate::core::error::Error::Custom(e.to_string()))?;
-    execute!(io::stdout(), crossterm::cursor::Hide)
-        .map_err(|e| crate::core::error::Error::Custom(e.to_string()))?;
-
-    let content = state.get_content();
-    if content.is_empty() {
-        Ok(None)
-    } else {
-        Ok(Some(content))
-    }
+    Ok(state.message())
 }
 
 fn ui(f: &mut Frame, state: &CommitEditorState) {
@@ -242,6 +324,16 @@ fn ui(f: &mut Frame, state: &CommitEditorState) {
         .margin(1)
         .constraints([Constraint::Min(15), Constraint::Length(8)].as_ref())
         .split(f.size());
+    let panes = if state.show_diff {
+        Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(chunks[0])
+    } else {
+        Layout::default()
+            .constraints([Constraint::Percentage(100)].as_ref())
+            .split(chunks[0])
+    };
 
     let editor_lines: Vec<Line> = state
         .lines
@@ -257,6 +349,8 @@ fn ui(f: &mut Frame, state: &CommitEditorState) {
                         .fg(Color::Black)
                         .bg(Color::White)
                         .add_modifier(Modifier::BOLD)
+                } else if line.starts_with('#') {
+                    Style::default().fg(Color::DarkGray)
                 } else {
                     Style::default().fg(Color::White)
                 };
@@ -288,7 +382,30 @@ fn ui(f: &mut Frame, state: &CommitEditorState) {
         .block(Block::default().title("Commit Message").borders(Borders::ALL))
         .style(Style::default().fg(Color::White));
 
-    f.render_widget(editor, chunks[0]);
+    f.render_widget(editor, panes[0]);
+
+    if state.show_diff {
+        let diff_lines: Vec<Line> = state
+            .diff
+            .iter()
+            .map(|line| {
+                let style = if line.starts_with("+++") || line.starts_with("---") {
+                    Style::default().add_modifier(Modifier::BOLD)
+                } else if line.starts_with('+') {
+                    Style::default().fg(Color::Green)
+                } else if line.starts_with('-') {
+                    Style::default().fg(Color::Red)
+                } else {
+                    Style::default()
+                };
+                Line::from(Span::styled(line.clone(), style))
+            })
+            .collect();
+        let diff = Paragraph::new(diff_lines)
+            .block(Block::default().title("Staged Changes").borders(Borders::ALL))
+            .scroll((state.diff_scroll, 0));
+        f.render_widget(diff, panes[1]);
+    }
 
     let status = if state.dirty { "Modified" } else { "Clean" };
     let status_color = if state.dirty { Color::Yellow } else { Color::Green };
@@ -311,7 +428,11 @@ fn ui(f: &mut Frame, state: &CommitEditorState) {
             Span::styled("↑↓←→", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
             Span::raw(" Navigate  "),
             Span::styled("Home/End", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
-            Span::raw(" Line start/end"),
+            Span::raw(" Line start/end  "),
+            Span::styled("Ctrl+D", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+            Span::raw(" Toggle diff  "),
+            Span::styled("PgUp/PgDn", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+            Span::raw(" Scroll diff"),
         ]),
         Line::from(vec![
             Span::styled("Enter", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
@@ -402,4 +523,65 @@ mod tests {
         assert_eq!(editor.lines[0], "Initial");
         assert_eq!(editor.lines[1], "message");
     }
+
+    #[test]
+    fn test_commit_template_round_trip() {
+        let template = commit_template("main", &["src/lib.rs".to_string()]);
+        assert!(template.contains("# On branch main"));
+        assert!(template.contains("#\tsrc/lib.rs"));
+        assert_eq!(strip_comments(&template), "");
+        assert_eq!(strip_comments(&format!("Fix bug\n\nDetails{}", template)), "Fix bug\n\nDetails");
+    }
+
+    #[test]
+    fn test_message_strips_comments() {
+        let template = commit_template("main", &["src/lib.rs".to_string()]);
+        let mut editor = CommitEditorState::new(Some(template));
+        assert_eq!(editor.message(), None);
+        for c in "Fix bug".chars() {
+            editor.insert_char(c);
+        }
+        assert_eq!(editor.message().as_deref(), Some("Fix bug"));
+    }
+
+    #[test]
+    fn test_diff_pane_toggle() {
+        let mut editor = CommitEditorState::new(None);
+        editor.toggle_diff();
+        assert!(!editor.show_diff);
+
+        let mut editor = editor.with_diff(vec!["--- a".to_string(), "+++ a".to_string()]);
+        assert!(editor.show_diff);
+        editor.toggle_diff();
+        assert!(!editor.show_diff);
+        editor.scroll_diff(10);
+        assert_eq!(editor.diff_scroll, 1);
+        editor.scroll_diff(-10);
+        assert_eq!(editor.diff_scroll, 0);
+    }
+
+    #[test]
+    fn test_validate_message() {
+        assert!(validate_message("Fix bug\n\nDetails").is_ok());
+        assert!(validate_message("").is_err());
+        assert!(validate_message("   \nbody only").is_err());
+    }
+
+    #[test]
+    fn test_add_signoff() {
+        let trailer = "Signed-off-by: Ada <ada@example.com>";
+        assert_eq!(
+            add_signoff("Fix bug", "Ada", "ada@example.com"),
+            format!("Fix bug\n\n{}", trailer)
+        );
+
+        let signed = add_signoff("Fix bug\n\nDetails\n", "Ada", "ada@example.com");
+        assert_eq!(signed, format!("Fix bug\n\nDetails\n\n{}", trailer));
+        assert_eq!(add_signoff(&signed, "Ada", "ada@example.com"), signed);
+
+        assert_eq!(
+            add_signoff("Fix bug\n\nReviewed-by: Bob <bob@example.com>", "Ada", "ada@example.com"),
+            format!("Fix bug\n\nReviewed-by: Bob <bob@example.com>\n{}", trailer)
+        );
+    }
 }
@@ -1,9 +1,12 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use crate::core::error::Result;
+use crate::core::error::{Error, Result};
+
+/// Environment variable overriding where the global config file lives
+pub const GLOBAL_CONFIG_ENV: &str = "MUG_GLOBAL_CONFIG";
 
 /// Repository configuration manager
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,16 +33,29 @@ impl Config {
         }
     }
 
-    /// Loads configuration from .mug/config.json
+    /// Loads configuration from .mug/config.json, taking the user name and
+    /// email from the global config where the repository sets none
     pub fn load(repo_root: &Path) -> Result<Self> {
         let config_path = repo_root.join(".mug").join("config.json");
 
-        if config_path.exists() {
+        let mut config = if config_path.exists() {
             let content = fs::read_to_string(&config_path)?;
             serde_json::from_str(&content)
-                .map_err(|e| crate::core::error::Error::Custom(format!("Failed to parse config: {}", e)))
+                .map_err(|e| crate::core::error::Error::Custom(format!("Failed to parse config: {}", e)))?
         } else {
-            Ok(Config::new())
+            Config::new()
+        };
+        config.merge_global(&GlobalConfig::load()?);
+        Ok(config)
+    }
+
+    /// Fill in settings the repository leaves unset from the global config
+    pub fn merge_global(&mut self, global: &GlobalConfig) {
+        if self.user_name.is_none() {
+            self.user_name = global.get("user.name").map(str::to_string);
+        }
+        if self.user_email.is_none() {
+            self.user_email = global.get("user.email").map(str::to_string);
         }
     }
 
@@ -104,6 +120,247 @@ impl Default for Config {
     }
 }
 
+/// The kind of value a configuration key holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigType {
+    String,
+    /// `true`/`false`, also accepting yes/no, on/off and 1/0
+    Bool,
+    /// A non-negative integer
+    Int,
+    Path,
+}
+
+impl std::fmt::Display for ConfigType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigType::String => write!(f, "string"),
+            ConfigType::Bool => write!(f, "bool"),
+            ConfigType::Int => write!(f, "int"),
+            ConfigType::Path => write!(f, "path"),
+        }
+    }
+}
+
+/// Keys mug reads, with the type of each. `*` stands for a branch or
+/// remote name.
+const KNOWN_KEYS: &[(&str, ConfigType)] = &[
+    ("user.name", ConfigType::String),
+    ("alias.*", ConfigType::String),
+    ("user.email", ConfigType::String),
+    ("user.signingkey", ConfigType::String),
+    ("commit.gpgsign", ConfigType::Bool),
+    ("commit.template", ConfigType::Path),
+    ("core.excludesfile", ConfigType::Path),
+    ("core.ignorecase", ConfigType::Bool),
+    ("core.hashAlgorithm", ConfigType::String),
+    ("diff.renameThreshold", ConfigType::String),
+    ("store.server", ConfigType::String),
+    ("store.threshold", ConfigType::Int),
+    ("store.cacheLimit", ConfigType::Int),
+    ("default_remote", ConfigType::String),
+    ("branch.*.remote", ConfigType::String),
+    ("branch.*.merge", ConfigType::String),
+    ("remote.*.token", ConfigType::String),
+    ("remote.*.key", ConfigType::String),
+];
+
+/// The type of a known key, or `None` for keys mug does not read
+pub fn key_type(key: &str) -> Option<ConfigType> {
+    KNOWN_KEYS.iter().find(|(pattern, _)| key_matches(pattern, key)).map(|&(_, kind)| kind)
+}
+
+fn key_matches(pattern: &str, key: &str) -> bool {
+    match pattern.split_once('*') {
+        Some((prefix, suffix)) => {
+            key.len() > prefix.len() + suffix.len() && key.starts_with(prefix) && key.ends_with(suffix)
+        }
+        None => pattern == key,
+    }
+}
+
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+pub fn parse_int(value: &str) -> Option<u64> {
+    value.trim().parse().ok()
+}
+
+/// Check `value` suits `key` and return it in canonical form (booleans as
+/// `true`/`false`). Unknown keys are refused unless `allow_unknown`, so a
+/// typo is not silently stored.
+pub fn validate(key: &str, value: &str, allow_unknown: bool) -> Result<String> {
+    if key == crate::core::hash::HashAlgorithm::CONFIG_KEY {
+        return Err(Error::Custom(format!(
+            "{} can only be chosen at init (mug init --hash)",
+            key
+        )));
+    }
+    let Some(kind) = key_type(key) else {
+        if allow_unknown {
+            return Ok(value.to_string());
+        }
+        let section = key.split('.').next().unwrap_or(key);
+        let similar: Vec<&str> = KNOWN_KEYS
+            .iter()
+            .map(|&(known, _)| known)
+            .filter(|known| known.split('.').next() == Some(section))
+            .collect();
+        let hint = if similar.is_empty() {
+            String::new()
+        } else {
+            format!(" (known {} keys: {})", section, similar.join(", "))
+        };
+        return Err(Error::Custom(format!(
+            "Unknown config key '{}'{}; use --add to set it anyway",
+            key, hint
+        )));
+    };
+
+    let invalid = |expected: &str| {
+        Error::Custom(format!("Invalid value for {} ({}): '{}' is not {}", key, kind, value, expected))
+    };
+    match kind {
+        ConfigType::Bool => parse_bool(value)
+            .map(|b| b.to_string())
+            .ok_or_else(|| invalid("a boolean")),
+        ConfigType::Int => parse_int(value)
+            .map(|n| n.to_string())
+            .ok_or_else(|| invalid("a non-negative integer")),
+        ConfigType::Path if value.trim().is_empty() => Err(invalid("a path")),
+        _ if key == "diff.renameThreshold" => {
+            crate::core::diff::parse_rename_threshold(value)
+                .map(|_| value.to_string())
+                .ok_or_else(|| invalid("a percentage such as 60%"))
+        }
+        _ => Ok(value.to_string()),
+    }
+}
+
+/// Where a configuration value was set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigOrigin {
+    /// The user's global config file
+    Global(PathBuf),
+    /// A repository's database
+    Repository(PathBuf),
+}
+
+impl std::fmt::Display for ConfigOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigOrigin::Global(path) => write!(f, "global:{}", path.display()),
+            ConfigOrigin::Repository(path) => write!(f, "repo:{}", path.display()),
+        }
+    }
+}
+
+/// A configuration value and the layer it came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigEntry {
+    pub key: String,
+    pub value: String,
+    pub origin: ConfigOrigin,
+    /// Overridden by the same key in a later layer
+    pub shadowed: bool,
+}
+
+/// User-level settings shared by every repository, stored as JSON in
+/// `~/.config/mug/config`. Repository settings override these.
+#[derive(Debug, Clone, Default)]
+pub struct GlobalConfig {
+    /// Backing file, if there is a home directory to keep it in
+    path: Option<PathBuf>,
+    values: BTreeMap<String, String>,
+}
+
+impl GlobalConfig {
+    /// Where the global config lives: `$MUG_GLOBAL_CONFIG`, else
+    /// `$XDG_CONFIG_HOME/mug/config`, else `~/.config/mug/config`
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os(GLOBAL_CONFIG_ENV) {
+            return Some(PathBuf::from(path));
+        }
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_home.join("mug").join("config"))
+    }
+
+    /// Load the global config from its default location; empty if there is
+    /// no file yet
+    pub fn load() -> Result<Self> {
+        match Self::default_path() {
+            Some(path) => Self::open(path),
+            None => Ok(GlobalConfig::default()),
+        }
+    }
+
+    /// Load the global config from `path`; empty if the file does not exist
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let values = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(path)?).map_err(|e| {
+                Error::Custom(format!("Failed to parse {}: {}", path.display(), e))
+            })?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(GlobalConfig {
+            path: Some(path.to_path_buf()),
+            values,
+        })
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    /// Set a value and write the file, creating its directory if needed
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        self.values.insert(key.to_string(), value.to_string());
+        self.save()
+    }
+
+    /// All settings, sorted by key
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// All settings with this file as their origin, sorted by key
+    pub fn config_entries(&self) -> Vec<ConfigEntry> {
+        let path = self.path.clone().unwrap_or_default();
+        self.entries()
+            .map(|(key, value)| ConfigEntry {
+                key: key.to_string(),
+                value: value.to_string(),
+                origin: ConfigOrigin::Global(path.clone()),
+                shadowed: false,
+            })
+            .collect()
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = self.path.as_ref().ok_or_else(|| {
+            Error::Custom("No home directory for the global config; set MUG_GLOBAL_CONFIG".to_string())
+        })?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(&self.values)?)?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -153,4 +410,45 @@ mod tests {
         assert_eq!(loaded.get_user_name(), "Jane Doe");
         assert_eq!(loaded.get_user_email(), "jane@example.com");
     }
+
+    #[test]
+    fn test_validate_known_keys() {
+        assert_eq!(validate("commit.gpgsign", "Yes", false).unwrap(), "true");
+        assert_eq!(validate("commit.gpgsign", "off", false).unwrap(), "false");
+        assert!(validate("commit.gpgsign", "sometimes", false).is_err());
+        assert_eq!(validate("store.threshold", " 1024", false).unwrap(), "1024");
+        assert!(validate("store.threshold", "-1", false).is_err());
+        assert!(validate("diff.renameThreshold", "lots", false).is_err());
+        assert_eq!(validate("branch.feature/x.remote", "origin", false).unwrap(), "origin");
+        assert!(validate("core.hashAlgorithm", "sha256", true).is_err());
+
+        let err = validate("user.nam", "Jane", false).unwrap_err().to_string();
+        assert!(err.contains("user.name") && err.contains("--add"));
+        assert_eq!(validate("user.nam", "Jane", true).unwrap(), "Jane");
+        assert!(validate("branch..remote", "origin", false).is_err());
+    }
+
+    #[test]
+    fn test_global_config_layering() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("home/.config/mug/config");
+
+        let mut global = GlobalConfig::open(&path).unwrap();
+        assert_eq!(global.get("user.name"), None);
+        global.set("user.name", "Global Name").unwrap();
+        global.set("user.email", "global@example.com").unwrap();
+
+        let global = GlobalConfig::open(&path).unwrap();
+        assert_eq!(
+            global.entries().collect::<Vec<_>>(),
+            vec![("user.email", "global@example.com"), ("user.name", "Global Name")]
+        );
+
+        // The repository's own settings win over the global ones
+        let mut config = Config::new();
+        config.set_user_name("Repo Name".to_string());
+        config.merge_global(&global);
+        assert_eq!(config.get_user_name(), "Repo Name");
+        assert_eq!(config.get_user_email(), "global@example.com");
+    }
 }
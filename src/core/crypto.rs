@@ -1,4 +1,5 @@
 /// Cryptographic signing and verification for commits
+use crate::core::database::MugDb;
 use crate::core::error::Result;
 use ed25519_dalek::{SigningKey, VerifyingKey, Signature, Signer, Verifier};
 use rand::thread_rng;
@@ -98,7 +99,7 @@ impl CryptoKey {
         let sig_bytes = base64::decode(signature)
             .map_err(|e| crate::core::error::Error::Custom(format!("Invalid signature: {}", e)))?;
 
-        if sig_bytes.len() != 64 {
+        if sig_bytes.len() != 64 || public_bytes_vec.len() != 32 {
             return Ok(false);
         }
 
@@ -117,6 +118,121 @@ impl CryptoKey {
     }
 }
 
+/// Outcome of checking a signature attached to a commit or tag
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureStatus {
+    /// No signature attached
+    Unsigned,
+    /// Signature is valid and made by a key in the local keyring
+    Good(String),
+    /// Signature is valid but the signer key is not in the local keyring
+    UnknownKey(String),
+    /// Signature does not match the signed content
+    Bad(String),
+}
+
+impl SignatureStatus {
+    pub fn is_good(&self) -> bool {
+        matches!(self, SignatureStatus::Good(_))
+    }
+
+    /// Short marker for list views
+    pub fn indicator(&self) -> &'static str {
+        match self {
+            SignatureStatus::Unsigned => "",
+            SignatureStatus::Good(_) => "✓",
+            SignatureStatus::UnknownKey(_) => "?",
+            SignatureStatus::Bad(_) => "✗",
+        }
+    }
+
+    /// Human-readable description of the check result
+    pub fn describe(&self) -> String {
+        match self {
+            SignatureStatus::Unsigned => "No signature".to_string(),
+            SignatureStatus::Good(key) => format!("Good signature from {}", key),
+            SignatureStatus::UnknownKey(key) => {
+                format!("Valid signature from unknown key {}", key)
+            }
+            SignatureStatus::Bad(key) => format!("BAD signature from {}", key),
+        }
+    }
+}
+
+/// Signing keys stored in the repository database
+pub struct KeyManager {
+    db: MugDb,
+}
+
+impl KeyManager {
+    const CURRENT_KEY: &'static str = "user.signingkey";
+
+    pub fn new(db: MugDb) -> Self {
+        KeyManager { db }
+    }
+
+    /// Store a key and make it the current signing key
+    pub fn add(&self, key: &CryptoKey) -> Result<()> {
+        let serialized = serde_json::to_vec(key)?;
+        self.db.set("keys", &key.public_key, serialized)?;
+        self.db.set("config", Self::CURRENT_KEY, &key.public_key)?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    /// List all stored keys
+    pub fn list(&self) -> Result<Vec<CryptoKey>> {
+        let entries = self.db.scan("keys", "")?;
+        Ok(entries
+            .into_iter()
+            .filter_map(|(_, value)| serde_json::from_slice(&value).ok())
+            .collect())
+    }
+
+    /// Get a stored key by its public key
+    pub fn get(&self, public_key: &str) -> Result<Option<CryptoKey>> {
+        match self.db.get("keys", public_key)? {
+            Some(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Get the current signing key, if one is configured
+    pub fn current(&self) -> Result<Option<CryptoKey>> {
+        match self.db.get("config", Self::CURRENT_KEY)? {
+            Some(public_key) => self.get(&String::from_utf8_lossy(&public_key)),
+            None => Ok(None),
+        }
+    }
+
+    /// Check a signature over `message` made by `signer_key`
+    pub fn check(
+        &self,
+        message: &[u8],
+        signature: Option<&str>,
+        signer_key: Option<&str>,
+    ) -> Result<SignatureStatus> {
+        let (signature, signer_key) = match (signature, signer_key) {
+            (Some(sig), Some(key)) => (sig, key),
+            _ => return Ok(SignatureStatus::Unsigned),
+        };
+
+        let verifier = CryptoKey {
+            public_key: signer_key.to_string(),
+            seed: None,
+        };
+        if !verifier.verify(message, signature).unwrap_or(false) {
+            return Ok(SignatureStatus::Bad(signer_key.to_string()));
+        }
+
+        if self.get(signer_key)?.is_some() {
+            Ok(SignatureStatus::Good(signer_key.to_string()))
+        } else {
+            Ok(SignatureStatus::UnknownKey(signer_key.to_string()))
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -150,4 +266,30 @@ mod tests {
 
         assert!(!verified);
     }
+
+    #[test]
+    fn test_key_manager_current_and_check() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = MugDb::new(dir.path().join("db")).unwrap();
+        let manager = KeyManager::new(db);
+        assert!(manager.current().unwrap().is_none());
+
+        let (key, public) = CryptoKey::generate().unwrap();
+        manager.add(&key).unwrap();
+        assert_eq!(manager.current().unwrap().unwrap().public_key, public);
+
+        let signature = key.sign(b"payload").unwrap();
+        assert!(manager
+            .check(b"payload", Some(&signature), Some(&public))
+            .unwrap()
+            .is_good());
+        assert_eq!(
+            manager.check(b"tampered", Some(&signature), Some(&public)).unwrap(),
+            SignatureStatus::Bad(public.clone())
+        );
+        assert_eq!(
+            manager.check(b"payload", None, None).unwrap(),
+            SignatureStatus::Unsigned
+        );
+    }
 }
@@ -1,23 +1,109 @@
 use crate::core::error::{Error, Result};
-use sled::{Db, Tree};
+use sled::transaction::{
+    ConflictableTransactionError, TransactionError, TransactionalTree, UnabortableTransactionError,
+};
+use sled::{Db, Transactional, Tree};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 
+/// Trees that are always available inside a transaction, even before their
+/// first write
+const CORE_TREES: &[&str] = &[
+    "HEAD", "BRANCHES", "INDEX", "COMMITS", "REFLOG", "refs", "tags", "config", "stash",
+    "OPERATION",
+];
+
+/// Trees holding a working tree's own state; each linked worktree gets its
+/// own copy, while everything else is shared
+const WORKTREE_TREES: &[&str] = &["HEAD", "INDEX", "OPERATION"];
+
+/// How often, and how far apart, opening a database that is still locked
+/// is retried
+const OPEN_RETRIES: u32 = 50;
+const OPEN_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(20);
+
+/// Whether opening failed because another handle holds the database lock.
+/// sled reports this as an `Other` error naming the lock.
+fn is_lock_error(e: &std::io::Error) -> bool {
+    e.kind() == std::io::ErrorKind::WouldBlock || e.to_string().contains("could not acquire lock")
+}
+
+/// The name `tree_name` is stored under for `worktree`
+fn scoped<'a>(worktree: Option<&str>, tree_name: &'a str) -> Cow<'a, str> {
+    match worktree {
+        Some(worktree) if WORKTREE_TREES.contains(&tree_name) => {
+            Cow::Owned(format!("worktrees/{}/{}", worktree, tree_name))
+        }
+        _ => Cow::Borrowed(tree_name),
+    }
+}
+
 /// Lightweight embedded database wrapper around Sled
 #[derive(Clone)]
 pub struct MugDb {
     db: Arc<Db>,
+    /// Linked worktree whose HEAD, index and operation state this handle
+    /// sees; `None` for the main working tree
+    worktree: Option<Arc<str>>,
 }
 
 impl MugDb {
     pub fn new(path: PathBuf) -> Result<Self> {
-        let db = sled::open(&path).map_err(|e| Error::Database(e.to_string()))?;
-        Ok(MugDb { db: Arc::new(db) })
+        // A handle dropped just before, e.g. by the previous server request,
+        // releases its file lock from a background thread, so wait briefly
+        // for it rather than failing
+        let mut attempts = 0;
+        let db = loop {
+            match sled::open(&path) {
+                Err(sled::Error::Io(e)) if is_lock_error(&e) && attempts < OPEN_RETRIES => {
+                    attempts += 1;
+                    std::thread::sleep(OPEN_RETRY_DELAY);
+                }
+                result => break result.map_err(|e| Error::Database(e.to_string()))?,
+            }
+        };
+        Ok(MugDb {
+            db: Arc::new(db),
+            worktree: None,
+        })
+    }
+
+    /// A handle on the same database seeing the per-worktree state of the
+    /// linked worktree `name`, or of the main working tree for `None`
+    pub fn for_worktree(&self, name: Option<&str>) -> MugDb {
+        MugDb {
+            db: self.db.clone(),
+            worktree: name.map(Arc::from),
+        }
+    }
+
+    /// The linked worktree this handle belongs to, if any
+    pub fn worktree(&self) -> Option<&str> {
+        self.worktree.as_deref()
+    }
+
+    /// Delete the per-worktree state of the linked worktree `name`
+    pub fn drop_worktree(&self, name: &str) -> Result<()> {
+        for tree_name in WORKTREE_TREES {
+            self.db
+                .drop_tree(scoped(Some(name), tree_name).as_bytes())
+                .map_err(|e| Error::Database(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    fn open_tree(&self, tree_name: &str) -> Result<Tree> {
+        self.db
+            .open_tree(scoped(self.worktree(), tree_name).as_bytes())
+            .map_err(|e| Error::Database(e.to_string()))
     }
 
     /// Get the tree for storing HEAD ref
     pub fn head_tree(&self) -> Tree {
-        self.db.open_tree("HEAD").unwrap()
+        self.open_tree("HEAD").unwrap()
     }
 
     /// Get the tree for storing branch refs
@@ -27,7 +113,7 @@ impl MugDb {
 
     /// Get the tree for storing index/staging area
     pub fn index_tree(&self) -> Tree {
-        self.db.open_tree("INDEX").unwrap()
+        self.open_tree("INDEX").unwrap()
     }
 
     /// Get the tree for storing commit metadata
@@ -45,10 +131,7 @@ impl MugDb {
 
     /// Get a value from a tree
     pub fn get<K: AsRef<[u8]>>(&self, tree_name: &str, key: K) -> Result<Option<Vec<u8>>> {
-        let tree = self
-            .db
-            .open_tree(tree_name)
-            .map_err(|e| Error::Database(e.to_string()))?;
+        let tree = self.open_tree(tree_name)?;
         tree.get(key)
             .map_err(|e| Error::Database(e.to_string()))
             .map(|opt| opt.map(|v| v.to_vec()))
@@ -61,10 +144,7 @@ impl MugDb {
         key: K,
         value: V,
     ) -> Result<()> {
-        let tree = self
-            .db
-            .open_tree(tree_name)
-            .map_err(|e| Error::Database(e.to_string()))?;
+        let tree = self.open_tree(tree_name)?;
         tree.insert(key, value.as_ref())
             .map_err(|e| Error::Database(e.to_string()))?;
         Ok(())
@@ -72,10 +152,7 @@ impl MugDb {
 
     /// Delete a value from a tree
     pub fn delete<K: AsRef<[u8]>>(&self, tree_name: &str, key: K) -> Result<()> {
-        let tree = self
-            .db
-            .open_tree(tree_name)
-            .map_err(|e| Error::Database(e.to_string()))?;
+        let tree = self.open_tree(tree_name)?;
         tree.remove(key)
             .map_err(|e| Error::Database(e.to_string()))?;
         Ok(())
@@ -87,10 +164,7 @@ impl MugDb {
         tree_name: &str,
         prefix: K,
     ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
-        let tree = self
-            .db
-            .open_tree(tree_name)
-            .map_err(|e| Error::Database(e.to_string()))?;
+        let tree = self.open_tree(tree_name)?;
         let mut results = Vec::new();
         for item in tree.scan_prefix(prefix) {
             let (k, v) = item.map_err(|e| Error::Database(e.to_string()))?;
@@ -101,11 +175,160 @@ impl MugDb {
 
     /// Clear a tree
     pub fn clear_tree(&self, tree_name: &str) -> Result<()> {
-        let tree = self
-            .db
-            .open_tree(tree_name)
-            .map_err(|e| Error::Database(e.to_string()))?;
+        let tree = self.open_tree(tree_name)?;
         tree.clear().map_err(|e| Error::Database(e.to_string()))?;
         Ok(())
     }
+
+    /// Run `f` as a single atomic transaction across every tree.
+    ///
+    /// Either all of its writes are applied or none are; returning an error
+    /// from `f` discards them. The closure may be re-run if another writer
+    /// conflicts with it, so it should not have side effects outside `tx`,
+    /// and it must read through `tx` too: plain `MugDb` calls block until the
+    /// transaction ends.
+    pub fn transaction<F, R>(&self, f: F) -> Result<R>
+    where
+        F: Fn(&Transaction) -> Result<R>,
+    {
+        let mut names: Vec<String> = CORE_TREES
+            .iter()
+            .map(|name| scoped(self.worktree(), name).into_owned())
+            .collect();
+        for name in self.db.tree_names() {
+            let name = String::from_utf8_lossy(&name).to_string();
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        let trees = names
+            .iter()
+            .map(|name| self.db.open_tree(name))
+            .collect::<std::result::Result<Vec<Tree>, _>>()
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        let result = trees.as_slice().transaction(|tx_trees| {
+            let tx = Transaction {
+                trees: names.iter().cloned().zip(tx_trees.iter()).collect(),
+                worktree: self.worktree(),
+                conflict: Cell::new(false),
+            };
+            match f(&tx) {
+                Ok(value) => Ok(value),
+                Err(_) if tx.conflict.get() => Err(ConflictableTransactionError::Conflict),
+                Err(e) => Err(ConflictableTransactionError::Abort(e)),
+            }
+        });
+
+        match result {
+            Ok(value) => Ok(value),
+            Err(TransactionError::Abort(e)) => Err(e),
+            Err(TransactionError::Storage(e)) => Err(Error::Database(e.to_string())),
+        }
+    }
+}
+
+/// Reads and writes staged inside `MugDb::transaction`
+pub struct Transaction<'a> {
+    trees: HashMap<String, &'a TransactionalTree>,
+    worktree: Option<&'a str>,
+    conflict: Cell<bool>,
+}
+
+impl Transaction<'_> {
+    fn tree(&self, tree_name: &str) -> Result<&TransactionalTree> {
+        let scoped_name = scoped(self.worktree, tree_name);
+        self.trees.get(scoped_name.as_ref()).copied().ok_or_else(|| {
+            Error::Database(format!("Tree '{}' is not available in this transaction", tree_name))
+        })
+    }
+
+    fn check<T>(&self, result: std::result::Result<T, UnabortableTransactionError>) -> Result<T> {
+        result.map_err(|e| {
+            if matches!(e, UnabortableTransactionError::Conflict) {
+                self.conflict.set(true);
+            }
+            Error::Database(e.to_string())
+        })
+    }
+
+    /// Get a value from a tree, seeing writes made earlier in the transaction
+    pub fn get<K: AsRef<[u8]>>(&self, tree_name: &str, key: K) -> Result<Option<Vec<u8>>> {
+        let tree = self.tree(tree_name)?;
+        self.check(tree.get(key)).map(|opt| opt.map(|v| v.to_vec()))
+    }
+
+    /// Set a value in a tree
+    pub fn set<K: AsRef<[u8]>, V: AsRef<[u8]>>(
+        &self,
+        tree_name: &str,
+        key: K,
+        value: V,
+    ) -> Result<()> {
+        let tree = self.tree(tree_name)?;
+        self.check(tree.insert(key.as_ref(), value.as_ref()))?;
+        Ok(())
+    }
+
+    /// Delete a value from a tree
+    pub fn delete<K: AsRef<[u8]>>(&self, tree_name: &str, key: K) -> Result<()> {
+        let tree = self.tree(tree_name)?;
+        self.check(tree.remove(key.as_ref()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_transaction_commits_or_discards_all_writes() {
+        let dir = TempDir::new().unwrap();
+        let db = MugDb::new(dir.path().join("db")).unwrap();
+        db.set("INDEX", "a", b"1").unwrap();
+
+        db.transaction(|tx| {
+            tx.set("BRANCHES", "main", b"commit")?;
+            tx.delete("INDEX", "a")?;
+            assert_eq!(tx.get("BRANCHES", "main")?, Some(b"commit".to_vec()));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(db.get("BRANCHES", "main").unwrap(), Some(b"commit".to_vec()));
+        assert_eq!(db.get("INDEX", "a").unwrap(), None);
+
+        let result: Result<()> = db.transaction(|tx| {
+            tx.set("BRANCHES", "main", b"other")?;
+            Err(Error::Custom("stop".to_string()))
+        });
+        assert!(result.is_err());
+        assert_eq!(db.get("BRANCHES", "main").unwrap(), Some(b"commit".to_vec()));
+
+        assert!(db.transaction(|tx| tx.set("missing", "k", b"v")).is_err());
+    }
+
+    #[test]
+    fn test_worktree_handles_scope_head_and_index() {
+        let dir = TempDir::new().unwrap();
+        let db = MugDb::new(dir.path().join("db")).unwrap();
+        let linked = db.for_worktree(Some("wt"));
+        db.set("HEAD", "HEAD", b"main").unwrap();
+        db.set("BRANCHES", "main", b"commit").unwrap();
+        linked
+            .transaction(|tx| {
+                tx.set("HEAD", "HEAD", b"feature")?;
+                tx.set("INDEX", "a", b"1")
+            })
+            .unwrap();
+
+        assert_eq!(db.get("HEAD", "HEAD").unwrap(), Some(b"main".to_vec()));
+        assert_eq!(linked.get("HEAD", "HEAD").unwrap(), Some(b"feature".to_vec()));
+        assert_eq!(linked.get("BRANCHES", "main").unwrap(), Some(b"commit".to_vec()));
+        assert!(db.scan("INDEX", "").unwrap().is_empty());
+
+        db.drop_worktree("wt").unwrap();
+        assert_eq!(linked.get("INDEX", "a").unwrap(), None);
+    }
 }
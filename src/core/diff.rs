@@ -1,12 +1,30 @@
-use similar::TextDiff;
+use similar::{ChangeTag, DiffTag, TextDiff};
 use std::collections::HashMap;
 
+use crate::ui::formatter::{DiffLine, InlineSpan};
+
+/// Minimum similarity for a deleted and an added file to count as a rename
+pub const DEFAULT_RENAME_THRESHOLD: f32 = 0.5;
+
+/// Widest `+++---` bar drawn by `format_stat`
+const STAT_BAR_WIDTH: usize = 40;
+
+/// Unchanged lines shown around each change in a content diff
+const CONTEXT_LINES: usize = 3;
+
+/// How much of a file `is_binary` inspects, as git does
+const BINARY_CHECK_BYTES: usize = 8000;
+
 pub struct Diff {
     pub path: String,
     pub old_hash: String,
     pub new_hash: String,
     pub lines_added: usize,
     pub lines_removed: usize,
+    /// Either side is binary, so there are no lines to count
+    pub binary: bool,
+    /// Previous path when the file was renamed to `path`
+    pub renamed_from: Option<String>,
 }
 
 pub struct DiffStats {
@@ -15,6 +33,12 @@ pub struct DiffStats {
     pub lines_removed: usize,
 }
 
+/// Whether content is binary rather than text: like git, a NUL byte near
+/// the start marks it as binary. Diffs, line counts and grep all use this.
+pub fn is_binary(data: &[u8]) -> bool {
+    data[..data.len().min(BINARY_CHECK_BYTES)].contains(&0)
+}
+
 /// Compare two snapshots and produce a diff
 pub fn diff_snapshots(
     old_tree: &HashMap<String, String>,
@@ -32,6 +56,8 @@ pub fn diff_snapshots(
                     new_hash: new_hash.clone(),
                     lines_added: 0,
                     lines_removed: 0,
+                    binary: false,
+                    renamed_from: None,
                 });
             }
         } else {
@@ -42,6 +68,8 @@ pub fn diff_snapshots(
                 new_hash: new_hash.clone(),
                 lines_added: 0,
                 lines_removed: 0,
+                binary: false,
+                renamed_from: None,
             });
         }
     }
@@ -55,6 +83,8 @@ pub fn diff_snapshots(
                 new_hash: String::new(),
                 lines_added: 0,
                 lines_removed: 0,
+                binary: false,
+                renamed_from: None,
             });
         }
     }
@@ -62,6 +92,97 @@ pub fn diff_snapshots(
     diffs
 }
 
+/// Parse a rename threshold such as `50%`, `50` or `0.5`
+pub fn parse_rename_threshold(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let number: f32 = value.trim_end_matches('%').trim().parse().ok()?;
+    let ratio = if value.ends_with('%') || number > 1.0 {
+        number / 100.0
+    } else {
+        number
+    };
+    (0.0..=1.0).contains(&ratio).then_some(ratio)
+}
+
+/// Line-based similarity of two contents, from 0.0 (unrelated) to 1.0
+pub fn similarity(old: &[u8], new: &[u8]) -> f32 {
+    if old == new {
+        return 1.0;
+    }
+    let old = String::from_utf8_lossy(old);
+    let new = String::from_utf8_lossy(new);
+    TextDiff::from_lines(old.as_ref(), new.as_ref()).ratio()
+}
+
+/// Pair deleted and added files into renames.
+///
+/// Identical content always pairs; otherwise the most similar pairs at or
+/// above `threshold` win. `load` returns a blob's content by hash.
+pub fn detect_renames<F>(diffs: Vec<Diff>, threshold: f32, mut load: F) -> Vec<Diff>
+where
+    F: FnMut(&str) -> Option<Vec<u8>>,
+{
+    let (mut deleted, rest): (Vec<Diff>, Vec<Diff>) =
+        diffs.into_iter().partition(|d| d.new_hash.is_empty());
+    let (mut added, mut result): (Vec<Diff>, Vec<Diff>) =
+        rest.into_iter().partition(|d| d.old_hash.is_empty());
+    deleted.sort_by(|a, b| a.path.cmp(&b.path));
+    added.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut contents: HashMap<String, Option<Vec<u8>>> = HashMap::new();
+    let mut candidates = Vec::new();
+    for (d, old) in deleted.iter().enumerate() {
+        for (a, new) in added.iter().enumerate() {
+            let score = if old.old_hash == new.new_hash {
+                1.0
+            } else {
+                for hash in [&old.old_hash, &new.new_hash] {
+                    if !contents.contains_key(hash) {
+                        contents.insert(hash.clone(), load(hash));
+                    }
+                }
+                match (&contents[&old.old_hash], &contents[&new.new_hash]) {
+                    (Some(old), Some(new)) => similarity(old, new),
+                    _ => 0.0,
+                }
+            };
+            if score >= threshold && score > 0.0 {
+                candidates.push((score, d, a));
+            }
+        }
+    }
+    // Best matches first; ties keep path order
+    candidates.sort_by(|x, y| y.0.total_cmp(&x.0));
+
+    let mut paired_deleted = vec![false; deleted.len()];
+    let mut paired_added = vec![false; added.len()];
+    for (_, d, a) in candidates {
+        if paired_deleted[d] || paired_added[a] {
+            continue;
+        }
+        paired_deleted[d] = true;
+        paired_added[a] = true;
+        result.push(Diff {
+            path: added[a].path.clone(),
+            old_hash: deleted[d].old_hash.clone(),
+            new_hash: added[a].new_hash.clone(),
+            lines_added: 0,
+            lines_removed: 0,
+            binary: false,
+            renamed_from: Some(deleted[d].path.clone()),
+        });
+    }
+
+    let unpaired_deleted = deleted
+        .into_iter()
+        .zip(paired_deleted)
+        .filter(|(_, paired)| !paired);
+    let unpaired_added = added.into_iter().zip(paired_added).filter(|(_, paired)| !paired);
+    result.extend(unpaired_deleted.chain(unpaired_added).map(|(diff, _)| diff));
+    result.sort_by(|a, b| a.path.cmp(&b.path));
+    result
+}
+
 /// Calculate statistics from diffs
 pub fn diff_stats(diffs: &[Diff]) -> DiffStats {
     DiffStats {
@@ -71,6 +192,126 @@ pub fn diff_stats(diffs: &[Diff]) -> DiffStats {
     }
 }
 
+/// Lines added and removed going from `old` to `new`
+pub fn count_lines(old: &[u8], new: &[u8]) -> (usize, usize) {
+    let old = String::from_utf8_lossy(old);
+    let new = String::from_utf8_lossy(new);
+    let diff = TextDiff::from_lines(old.as_ref(), new.as_ref());
+    diff.iter_all_changes()
+        .fold((0, 0), |(added, removed), change| match change.tag() {
+            ChangeTag::Insert => (added + 1, removed),
+            ChangeTag::Delete => (added, removed + 1),
+            ChangeTag::Equal => (added, removed),
+        })
+}
+
+/// A `diff --stat` summary: one line per file with its counts and a bar
+/// scaled to the largest change, then a total line
+pub fn format_stat(diffs: &[Diff]) -> Vec<String> {
+    let names: Vec<String> = diffs
+        .iter()
+        .map(|d| match &d.renamed_from {
+            Some(old) => format!("{} => {}", old, d.path),
+            None => d.path.clone(),
+        })
+        .collect();
+    let name_width = names.iter().map(|n| n.chars().count()).max().unwrap_or(0);
+    let largest = diffs
+        .iter()
+        .map(|d| d.lines_added + d.lines_removed)
+        .max()
+        .unwrap_or(0);
+    let scale = |n: usize| match largest > STAT_BAR_WIDTH {
+        // Keep every non-empty side visible
+        true if n > 0 => (n * STAT_BAR_WIDTH / largest).max(1),
+        true => 0,
+        false => n,
+    };
+
+    let mut lines: Vec<String> = diffs
+        .iter()
+        .zip(&names)
+        .map(|(d, name)| {
+            if d.binary {
+                return format!(" {:<width$} | Bin", name, width = name_width);
+            }
+            format!(
+                " {:<width$} | +{} -{} {}{}",
+                name,
+                d.lines_added,
+                d.lines_removed,
+                "+".repeat(scale(d.lines_added)),
+                "-".repeat(scale(d.lines_removed)),
+                width = name_width
+            )
+        })
+        .collect();
+
+    let stats = diff_stats(diffs);
+    let plural = |n: usize| if n == 1 { "" } else { "s" };
+    lines.push(format!(
+        " {} file{} changed, {} insertion{}(+), {} deletion{}(-)",
+        stats.files_changed,
+        plural(stats.files_changed),
+        stats.lines_added,
+        plural(stats.lines_added),
+        stats.lines_removed,
+        plural(stats.lines_removed)
+    ));
+    lines
+}
+
+/// The lines of a diff from `old` to `new` for `--word-diff`: each removed
+/// line that was replaced is paired with its replacement and reduced to the
+/// words that differ; unmatched lines stay whole
+pub fn word_diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let diff = TextDiff::from_lines(old, new);
+    let (old_lines, new_lines) = (diff.old_slices(), diff.new_slices());
+    let line = |text: &str| text.trim_end_matches(['\n', '\r']).to_string();
+
+    let mut lines = Vec::new();
+    for op in diff.grouped_ops(CONTEXT_LINES).iter().flatten() {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        let removed = &old_lines[old_range];
+        let added = &new_lines[new_range];
+        if tag == DiffTag::Equal {
+            lines.extend(removed.iter().map(|l| DiffLine::Context(line(l))));
+            continue;
+        }
+
+        // Only replaced lines pair up; deletions and insertions stay whole
+        let paired = if tag == DiffTag::Replace {
+            removed.len().min(added.len())
+        } else {
+            0
+        };
+        for (old_line, new_line) in removed.iter().zip(added.iter()).take(paired) {
+            lines.push(DiffLine::Changed(inline_spans(&line(old_line), &line(new_line))));
+        }
+        lines.extend(removed[paired..].iter().map(|l| DiffLine::Removed(line(l))));
+        lines.extend(added[paired..].iter().map(|l| DiffLine::Added(line(l))));
+    }
+    lines
+}
+
+/// Split a changed line into the words kept, removed and added
+fn inline_spans(old: &str, new: &str) -> Vec<InlineSpan> {
+    let mut spans: Vec<InlineSpan> = Vec::new();
+    for change in TextDiff::from_words(old, new).iter_all_changes() {
+        let text = change.value();
+        // Merge runs of the same kind so "[-a b-]" isn't split per word
+        match (spans.last_mut(), change.tag()) {
+            (Some(InlineSpan::Same(last)), ChangeTag::Equal)
+            | (Some(InlineSpan::Added(last)), ChangeTag::Insert)
+            | (Some(InlineSpan::Removed(last)), ChangeTag::Delete) => last.push_str(text),
+            (_, ChangeTag::Equal) => spans.push(InlineSpan::Same(text.to_string())),
+            (_, ChangeTag::Insert) => spans.push(InlineSpan::Added(text.to_string())),
+            (_, ChangeTag::Delete) => spans.push(InlineSpan::Removed(text.to_string())),
+        }
+    }
+    spans
+}
+
 /// Perform a detailed text diff between two content strings
 pub fn text_diff(old_content: &str, new_content: &str) -> Vec<String> {
     let diff = TextDiff::from_lines(old_content, new_content);
@@ -78,13 +319,13 @@ pub fn text_diff(old_content: &str, new_content: &str) -> Vec<String> {
 
     for change in diff.iter_all_changes() {
         match change.tag() {
-            similar::ChangeTag::Delete => {
+            ChangeTag::Delete => {
                 result.push(format!("- {}", change.value()));
             }
-            similar::ChangeTag::Insert => {
+            ChangeTag::Insert => {
                 result.push(format!("+ {}", change.value()));
             }
-            similar::ChangeTag::Equal => {
+            ChangeTag::Equal => {
                 result.push(format!("  {}", change.value()));
             }
         }
@@ -110,4 +351,113 @@ mod tests {
         let diffs = diff_snapshots(&old_tree, &new_tree);
         assert_eq!(diffs.len(), 3); // modified, deleted, new
     }
+
+    #[test]
+    fn test_detect_renames() {
+        let blobs: HashMap<&str, &[u8]> = [
+            ("a", b"one\ntwo\nthree\nfour\n" as &[u8]),
+            ("a2", b"one\ntwo\nthree\nfive\n"),
+            ("b", b"alpha\n"),
+            ("c", b"something else entirely\n"),
+        ]
+        .into_iter()
+        .collect();
+        let tree = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs.iter().map(|(p, h)| (p.to_string(), h.to_string())).collect()
+        };
+        let old_tree = tree(&[("old.txt", "a"), ("same.txt", "b"), ("gone.txt", "b")]);
+        let new_tree = tree(&[("new.txt", "a2"), ("same.txt", "b"), ("moved/same.txt", "b"), ("other.txt", "c")]);
+
+        let load = |hash: &str| blobs.get(hash).map(|b| b.to_vec());
+        let diffs = detect_renames(diff_snapshots(&old_tree, &new_tree), DEFAULT_RENAME_THRESHOLD, load);
+        let summary: Vec<(&str, Option<&str>)> = diffs
+            .iter()
+            .map(|d| (d.path.as_str(), d.renamed_from.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("moved/same.txt", Some("gone.txt")),
+                ("new.txt", Some("old.txt")),
+                ("other.txt", None),
+            ]
+        );
+
+        // A strict threshold only pairs identical content
+        let diffs = detect_renames(diff_snapshots(&old_tree, &new_tree), 1.0, load);
+        assert_eq!(diffs.iter().filter(|d| d.renamed_from.is_some()).count(), 1);
+    }
+
+    #[test]
+    fn test_format_stat() {
+        let diff = |path: &str, added, removed| Diff {
+            path: path.to_string(),
+            old_hash: "old".to_string(),
+            new_hash: "new".to_string(),
+            lines_added: added,
+            lines_removed: removed,
+            binary: false,
+            renamed_from: None,
+        };
+        assert_eq!(count_lines(b"a\nb\nc\n", b"a\nB\nc\nd\n"), (2, 1));
+
+        let lines = format_stat(&[diff("src/main.rs", 3, 2), diff("a.txt", 1, 0)]);
+        assert_eq!(
+            lines,
+            vec![
+                " src/main.rs | +3 -2 +++--",
+                " a.txt       | +1 -0 +",
+                " 2 files changed, 4 insertions(+), 2 deletions(-)",
+            ]
+        );
+
+        let mut image = diff("logo.png", 0, 0);
+        image.binary = true;
+        assert_eq!(format_stat(&[image])[0], " logo.png | Bin");
+
+        // Large changes are scaled down to the bar width
+        let lines = format_stat(&[diff("big", 400, 0), diff("small", 1, 1)]);
+        assert!(lines[0].ends_with(&"+".repeat(STAT_BAR_WIDTH)));
+        assert!(lines[1].ends_with(" +-"));
+    }
+
+    #[test]
+    fn test_word_diff_lines() {
+        let old = "keep\nthe quick brown fox\ngone\n";
+        let new = "keep\nthe slow brown fox\n";
+        let lines = word_diff_lines(old, new);
+        assert_eq!(
+            lines,
+            vec![
+                DiffLine::Context("keep".to_string()),
+                DiffLine::Changed(vec![
+                    InlineSpan::Same("the ".to_string()),
+                    InlineSpan::Removed("quick".to_string()),
+                    InlineSpan::Added("slow".to_string()),
+                    InlineSpan::Same(" brown fox".to_string()),
+                ]),
+                DiffLine::Removed("gone".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_binary() {
+        assert!(!is_binary(b"plain text\n"));
+        assert!(!is_binary("caf\u{e9}".as_bytes()));
+        assert!(is_binary(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"));
+        // Only the start of the file is inspected
+        let mut late = vec![b'a'; BINARY_CHECK_BYTES];
+        late.push(0);
+        assert!(!is_binary(&late));
+    }
+
+    #[test]
+    fn test_parse_rename_threshold() {
+        assert_eq!(parse_rename_threshold("50%"), Some(0.5));
+        assert_eq!(parse_rename_threshold("75"), Some(0.75));
+        assert_eq!(parse_rename_threshold("0.9"), Some(0.9));
+        assert_eq!(parse_rename_threshold("150%"), None);
+        assert_eq!(parse_rename_threshold("high"), None);
+    }
 }
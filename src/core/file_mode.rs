@@ -0,0 +1,159 @@
+/// File modes recorded in the index and trees, and reading and writing
+/// working tree files with them
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::core::error::{Error, Result};
+
+pub const REGULAR: u32 = 0o100644;
+pub const EXECUTABLE: u32 = 0o100755;
+pub const SYMLINK: u32 = 0o120000;
+
+/// Modes of the files in a snapshot that are not regular files, by path
+pub type FileModes = HashMap<String, u32>;
+
+/// Whether anything is at `path`, counting a symlink to a missing target
+pub fn exists(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok()
+}
+
+/// Content and mode of a working tree file. A symlink is not followed; its
+/// content is the path it points to.
+pub fn read(path: &Path) -> Result<(Vec<u8>, u32)> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.file_type().is_symlink() {
+        return Ok((link_target(&fs::read_link(path)?), SYMLINK));
+    }
+    let mode = if is_executable(&metadata) { EXECUTABLE } else { REGULAR };
+    Ok((fs::read(path)?, mode))
+}
+
+/// Write a file of the given mode, replacing whatever is at `path`
+pub fn write(path: &Path, content: &[u8], mode: u32) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Writing over a symlink would change its target instead
+    if fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink() || mode == SYMLINK) {
+        fs::remove_file(path)?;
+    }
+    if mode == SYMLINK {
+        return write_symlink(path, content);
+    }
+    fs::write(path, content)?;
+    set_mode(path, mode)
+}
+
+/// Refuse `path`, relative to `root`, when one of its parent directories is
+/// a symlink: writing or removing through it would reach outside the
+/// working tree
+pub fn check_no_symlink_parents(root: &Path, path: &str) -> Result<()> {
+    let mut dir = root.to_path_buf();
+    let mut parents = path.split('/').collect::<Vec<_>>();
+    parents.pop();
+    for component in parents {
+        dir.push(component);
+        match fs::symlink_metadata(&dir) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                return Err(Error::Custom(format!("'{}' is beyond a symbolic link", path)));
+            }
+            Ok(_) => {}
+            // Nothing can exist below a missing directory
+            Err(_) => break,
+        }
+    }
+    Ok(())
+}
+
+/// Give an existing regular file the executable bit `mode` calls for,
+/// leaving it alone if it already matches
+pub fn set_mode(path: &Path, mode: u32) -> Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.file_type().is_symlink() || is_executable(&metadata) == (mode == EXECUTABLE) {
+        return Ok(());
+    }
+    set_executable(path, metadata, mode == EXECUTABLE)
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+/// Windows has no executable bit
+#[cfg(not(unix))]
+fn is_executable(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+/// Executable for whoever may read the file, or for no one
+#[cfg(unix)]
+fn set_executable(path: &Path, metadata: fs::Metadata, executable: bool) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut permissions = metadata.permissions();
+    let mode = permissions.mode();
+    permissions.set_mode(if executable { mode | (mode & 0o444) >> 2 } else { mode & !0o111 });
+    fs::set_permissions(path, permissions)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path, _metadata: fs::Metadata, _executable: bool) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn link_target(target: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    target.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn link_target(target: &Path) -> Vec<u8> {
+    target.to_string_lossy().replace('\\', "/").into_bytes()
+}
+
+#[cfg(unix)]
+fn write_symlink(path: &Path, target: &[u8]) -> Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    std::os::unix::fs::symlink(std::ffi::OsStr::from_bytes(target), path)?;
+    Ok(())
+}
+
+/// Creating symlinks needs special privileges on Windows, so the link is
+/// written as a regular file holding its target
+#[cfg(not(unix))]
+fn write_symlink(path: &Path, target: &[u8]) -> Result<()> {
+    eprintln!(
+        "warning: {} is a symlink; checked out as a regular file holding its target",
+        path.display()
+    );
+    fs::write(path, target)?;
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symlinks_round_trip_without_following() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let target = dir.path().join("target.txt");
+        fs::write(&target, "target content").unwrap();
+        let link = dir.path().join("link");
+
+        write(&link, b"target.txt", SYMLINK).unwrap();
+        assert_eq!(read(&link).unwrap(), (b"target.txt".to_vec(), SYMLINK));
+
+        // Replacing the link leaves its old target alone
+        write(&link, b"regular", REGULAR).unwrap();
+        assert_eq!(read(&link).unwrap(), (b"regular".to_vec(), REGULAR));
+        assert_eq!(fs::read_to_string(&target).unwrap(), "target content");
+
+        write(&link, b"missing", SYMLINK).unwrap();
+        assert!(exists(&link) && !link.exists());
+    }
+}
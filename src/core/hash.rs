@@ -1,7 +1,128 @@
-use crate::core::error::Result;
+use crate::core::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
 use sha2::{Digest, Sha256};
+use std::fmt;
 use std::fs;
+use std::io::Read;
 use std::path::Path;
+use std::str::FromStr;
+
+/// Object hash algorithm of a repository, chosen with `mug init --hash` and
+/// stored as `core.hashAlgorithm`. Every object id depends on it, so it is
+/// fixed once the repository is created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    /// Hashes blobs the way git does, so migrated files keep their git ids
+    Sha1,
+}
+
+impl HashAlgorithm {
+    pub const CONFIG_KEY: &'static str = "core.hashAlgorithm";
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha1 => "sha1",
+        }
+    }
+
+    /// Whether `id` is an object id of this algorithm: lowercase hex digits
+    /// of the digest's length
+    pub fn is_object_id(&self, id: &str) -> bool {
+        let len = match self {
+            HashAlgorithm::Sha256 => 64,
+            HashAlgorithm::Sha1 => 40,
+        };
+        id.len() == len && id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+    }
+
+    /// Hash raw data, such as a serialized tree
+    pub fn hash_bytes(&self, data: &[u8]) -> String {
+        match self {
+            HashAlgorithm::Sha256 => hash_bytes(data),
+            HashAlgorithm::Sha1 => hex::encode(Sha1::digest(data)),
+        }
+    }
+
+    /// Hash file contents into a blob id
+    pub fn hash_blob(&self, content: &[u8]) -> String {
+        match self {
+            HashAlgorithm::Sha256 => hash_bytes(content),
+            HashAlgorithm::Sha1 => {
+                let mut hasher = Sha1::new();
+                hasher.update(format!("blob {}\0", content.len()));
+                hasher.update(content);
+                hex::encode(hasher.finalize())
+            }
+        }
+    }
+
+    /// Hash `len` bytes of blob contents read from `reader`, without holding
+    /// them all in memory
+    pub fn hash_blob_reader(&self, reader: impl Read, len: u64) -> Result<String> {
+        fn digest<D: Digest>(mut hasher: D, reader: impl Read, len: u64) -> Result<String> {
+            let mut reader = reader.take(len);
+            let mut buf = [0u8; 64 * 1024];
+            let mut read = 0u64;
+            loop {
+                let n = reader.read(&mut buf)?;
+                if n == 0 {
+                    break;
+                }
+                hasher.update(&buf[..n]);
+                read += n as u64;
+            }
+            if read != len {
+                return Err(Error::Custom(format!("Expected {} bytes, read {}", len, read)));
+            }
+            Ok(hex::encode(hasher.finalize()))
+        }
+
+        match self {
+            HashAlgorithm::Sha256 => digest(Sha256::new(), reader, len),
+            HashAlgorithm::Sha1 => {
+                let mut hasher = Sha1::new();
+                hasher.update(format!("blob {}\0", len));
+                digest(hasher, reader, len)
+            }
+        }
+    }
+
+    /// Hash a file's contents into a blob id; a symlink hashes as the path
+    /// it points to
+    pub fn hash_file<P: AsRef<Path>>(&self, path: P) -> Result<String> {
+        Ok(self.hash_blob(&crate::core::file_mode::read(path.as_ref())?.0))
+    }
+
+    pub fn hash_str(&self, s: &str) -> String {
+        self.hash_bytes(s.as_bytes())
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "sha256" | "sha-256" => Ok(HashAlgorithm::Sha256),
+            "sha1" | "sha-1" => Ok(HashAlgorithm::Sha1),
+            _ => Err(Error::Custom(format!(
+                "Unknown hash algorithm: {} (expected sha256 or sha1)",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
 
 /// Hash a byte slice using SHA256
 pub fn hash_bytes(data: &[u8]) -> String {
@@ -42,6 +163,25 @@ mod tests {
         assert_eq!(hash.len(), 64);
     }
 
+    #[test]
+    fn test_hash_algorithm() {
+        assert_eq!("SHA-1".parse::<HashAlgorithm>().unwrap(), HashAlgorithm::Sha1);
+        assert!("md5".parse::<HashAlgorithm>().is_err());
+
+        // Same id as `git hash-object` for "hello\n"
+        assert_eq!(
+            HashAlgorithm::Sha1.hash_blob(b"hello\n"),
+            "ce013625030ba8dba906f756967f9e9ca394464a"
+        );
+        assert_eq!(HashAlgorithm::Sha256.hash_blob(b"test"), hash_bytes(b"test"));
+        for algorithm in [HashAlgorithm::Sha256, HashAlgorithm::Sha1] {
+            assert_eq!(
+                algorithm.hash_blob_reader(&b"hello\n"[..], 6).unwrap(),
+                algorithm.hash_blob(b"hello\n")
+            );
+        }
+    }
+
     #[test]
     fn test_short_hash() {
         let hash = hash_str("test");
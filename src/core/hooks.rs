@@ -58,8 +58,13 @@ impl Hook {
         }
     }
 
-    /// Execute the hook script
+    /// Execute the hook script in the current directory
     pub fn execute(&self, args: &[&str]) -> Result<HookResult> {
+        self.execute_in(Path::new("."), args)
+    }
+
+    /// Execute the hook script with `dir` as its working directory
+    pub fn execute_in(&self, dir: &Path, args: &[&str]) -> Result<HookResult> {
         if !self.enabled {
             return Ok(HookResult::skipped());
         }
@@ -80,7 +85,7 @@ impl Hook {
         }
 
         // Execute the hook
-        let output = Command::new(&self.path).args(args).output().map_err(|e| {
+        let output = Command::new(&self.path).args(args).current_dir(dir).output().map_err(|e| {
             crate::core::error::Error::Custom(format!("Failed to execute hook {}: {}", self.name, e))
         })?;
 
@@ -133,7 +138,7 @@ impl HookResult {
 
 /// Hook manager for managing all hooks in a repository
 pub struct HookManager {
-    #[allow(dead_code)]
+    /// Hooks run from here, whatever directory mug was started in
     repo_root: PathBuf,
     hooks_dir: PathBuf,
 }
@@ -243,13 +248,13 @@ impl HookManager {
         Ok(())
     }
 
-    /// Execute all hooks of a type
+    /// Execute all hooks of a type from the repository root
     pub fn trigger(&self, hook_type: HookType, args: &[&str]) -> Result<Vec<HookResult>> {
         let hooks = self.list_hooks_by_type(hook_type)?;
         let mut results = Vec::new();
 
         for hook in hooks {
-            match hook.execute(args) {
+            match hook.execute_in(&self.repo_root, args) {
                 Ok(result) => {
                     if !result.is_success() {
                         eprintln!("Hook {} failed: {}", hook.name, result.stderr);
@@ -272,8 +277,9 @@ impl HookManager {
         for result in results {
             if !result.is_success() {
                 return Err(crate::core::error::Error::Custom(format!(
-                    "Hook failed: {}",
-                    result.stderr
+                    "{} hook failed: {}",
+                    hook_type.name(),
+                    result.stderr.trim_end()
                 )));
             }
         }
@@ -465,6 +471,22 @@ mod tests {
         assert_eq!(manager.list_hooks().unwrap().len(), 0);
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_trigger_runs_from_repo_root() {
+        let dir = TempDir::new().unwrap();
+        let manager = HookManager::new(dir.path()).unwrap();
+        manager
+            .install("where", HookType::PrePush, "#!/bin/sh\npwd -P")
+            .unwrap();
+
+        let results = manager.trigger(HookType::PrePush, &[]).unwrap();
+        assert_eq!(
+            results[0].stdout.trim(),
+            dir.path().canonicalize().unwrap().to_string_lossy()
+        );
+    }
+
     #[test]
     fn test_parse_hook_filename() {
         assert_eq!(
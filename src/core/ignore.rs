@@ -1,6 +1,7 @@
 use regex::Regex;
 use std::fs;
 use std::path::Path;
+use walkdir::WalkDir;
 
 use crate::core::error::Result;
 
@@ -15,7 +16,9 @@ struct IgnorePattern {
     #[allow(dead_code)]
     pattern: String,
     regex: Regex,
-    negated: bool, // ! prefix means include
+    negated: bool,  // ! prefix means include
+    dir_only: bool, // trailing / matches directories only
+    base: String,   // directory of the .mugignore declaring it, "" for the root
 }
 
 impl IgnoreRules {
@@ -29,8 +32,13 @@ impl IgnoreRules {
     /// Loads ignore rules from a .mugignore file
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
         let mut rules = IgnoreRules::new();
+        rules.load_file(path.as_ref(), "")?;
+        Ok(rules)
+    }
 
-        if let Ok(content) = fs::read_to_string(&path) {
+    /// Append the patterns of an ignore file, matched relative to `base`
+    fn load_file(&mut self, path: &Path, base: &str) -> Result<()> {
+        if let Ok(content) = fs::read_to_string(path) {
             for line in content.lines() {
                 let trimmed = line.trim();
 
@@ -39,23 +47,53 @@ impl IgnoreRules {
                     continue;
                 }
 
-                rules.add_pattern(trimmed)?;
+                self.add_pattern_in(base, trimmed)?;
             }
         }
-
-        Ok(rules)
+        Ok(())
     }
 
-    /// Loads rules from project root .mugignore
+    /// Loads the root .mugignore and every nested .mugignore in the repo
     pub fn load_from_repo(repo_root: &Path) -> Result<Self> {
-        let mugignore_path = repo_root.join(".mugignore");
-        Self::from_file(mugignore_path)
+        Self::load(repo_root, None)
+    }
+
+    /// Loads the global excludes file, then the repo's .mugignore files from
+    /// the root down, so the deepest matching pattern takes precedence
+    pub fn load(repo_root: &Path, global: Option<&Path>) -> Result<Self> {
+        let mut rules = IgnoreRules::new();
+        if let Some(global) = global {
+            rules.load_file(global, "")?;
+        }
+
+        let mut files: Vec<String> = WalkDir::new(repo_root)
+            .into_iter()
+            .filter_entry(|e| e.file_name() != ".mug" && e.file_name() != ".git")
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file() && e.file_name() == ".mugignore")
+            .filter_map(|e| {
+                let dir = e.path().parent()?.strip_prefix(repo_root).ok()?;
+                Some(dir.to_string_lossy().replace('\\', "/"))
+            })
+            .collect();
+        files.sort_by_key(|dir| (Path::new(dir).components().count(), dir.clone()));
+
+        for dir in files {
+            rules.load_file(&repo_root.join(&dir).join(".mugignore"), &dir)?;
+        }
+        Ok(rules)
     }
 
     /// Adds a pattern to the rules
     pub fn add_pattern(&mut self, pattern: &str) -> Result<()> {
+        self.add_pattern_in("", pattern)
+    }
+
+    /// Adds a pattern declared by the .mugignore in directory `base`
+    fn add_pattern_in(&mut self, base: &str, pattern: &str) -> Result<()> {
         let negated = pattern.starts_with('!');
         let pattern_str = if negated { &pattern[1..] } else { pattern };
+        let dir_only = pattern_str.ends_with('/');
 
         let regex = self.pattern_to_regex(pattern_str)?;
 
@@ -63,51 +101,87 @@ impl IgnoreRules {
             pattern: pattern_str.to_string(),
             regex,
             negated,
+            dir_only,
+            base: base.trim_matches('/').to_string(),
         });
 
         Ok(())
     }
 
-    /// Converts .mugignore pattern to regex
-    /// Supports: *.ext, dir/, exact paths, ** for recursive
+    /// Converts a gitignore-style .mugignore pattern to a regex over
+    /// `/`-separated relative paths.
+    ///
+    /// `*` and `?` stay within one path component, `**` spans directories,
+    /// and a pattern with a leading or inner `/` is anchored at the root;
+    /// otherwise it matches at any depth.
     fn pattern_to_regex(&self, pattern: &str) -> Result<Regex> {
+        let pattern = pattern.trim_end_matches('/');
         if pattern.is_empty() {
             return Err(crate::core::error::Error::Custom("Empty pattern".to_string()));
         }
 
-        // Convert glob to regex
-        let pattern = pattern.trim_end_matches('/');
-
-        let regex_pattern = if pattern == "**" {
-            ".*".to_string()
-        } else if pattern.starts_with("**/") {
-            // Match any depth
-            format!("(^|.*/){}$", regex::escape(&pattern[3..]))
-        } else if pattern.ends_with("/**") {
-            // Match directory and everything in it
-            format!("^{}(/.*)?$", regex::escape(&pattern[..pattern.len() - 3]))
-        } else if pattern.contains('*') {
-            // Simple glob conversion
-            let escaped = regex::escape(pattern)
-                .replace("\\*", ".*")
-                .replace("\\?", ".");
-            format!("^{}$", escaped)
-        } else {
-            // Exact match or directory prefix
-            format!("^{}(/.*)?$", regex::escape(pattern))
-        };
+        let anchored = pattern.contains('/');
+        let pattern = pattern.trim_start_matches('/');
+
+        let mut regex_pattern = String::from(if anchored { "^" } else { "^(.*/)?" });
+        let mut rest = pattern;
+        while let Some(c) = rest.chars().next() {
+            if let Some(tail) = rest.strip_prefix("**/") {
+                regex_pattern.push_str("(.*/)?");
+                rest = tail;
+            } else if let Some(tail) = rest.strip_prefix("**") {
+                regex_pattern.push_str(".*");
+                rest = tail;
+            } else {
+                match c {
+                    '*' => regex_pattern.push_str("[^/]*"),
+                    '?' => regex_pattern.push_str("[^/]"),
+                    _ => regex_pattern.push_str(&regex::escape(&c.to_string())),
+                }
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        regex_pattern.push('$');
 
         Regex::new(&regex_pattern)
             .map_err(|e| crate::core::error::Error::Custom(format!("Invalid regex pattern: {}", e)))
     }
 
-    /// Checks if a path should be ignored
+    /// Checks if a file path should be ignored
     pub fn should_ignore(&self, path: &str) -> bool {
-        let mut ignored = false;
+        self.is_ignored(path, false)
+    }
 
+    /// Checks if a directory path should be ignored
+    pub fn should_ignore_dir(&self, path: &str) -> bool {
+        self.is_ignored(path, true)
+    }
+
+    /// Evaluate every pattern against the path and its parent directories;
+    /// the last matching pattern wins, so a later `!pattern` re-includes
+    fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        let path = path.replace('\\', "/");
+        let path = path.trim_matches('/');
+
+        let mut ignored = false;
         for pattern in &self.patterns {
-            if pattern.regex.is_match(path) {
-                ignored = !pattern.negated; // negated patterns re-include
+            let path = if pattern.base.is_empty() {
+                path
+            } else {
+                match path
+                    .strip_prefix(pattern.base.as_str())
+                    .and_then(|rest| rest.strip_prefix('/'))
+                {
+                    Some(rest) => rest,
+                    None => continue,
+                }
+            };
+            let matches_self = (is_dir || !pattern.dir_only) && pattern.regex.is_match(path);
+            let matches_parent = path
+                .match_indices('/')
+                .any(|(i, _)| pattern.regex.is_match(&path[..i]));
+            if matches_self || matches_parent {
+                ignored = !pattern.negated;
             }
         }
 
@@ -253,4 +327,75 @@ mod tests {
         assert!(content.contains("node_modules/"));
         assert!(content.contains("target/"));
     }
+
+    #[test]
+    fn test_negation_overrides_earlier_ignore() {
+        let mut rules = IgnoreRules::new();
+        rules.add_pattern("logs/").unwrap();
+        rules.add_pattern("!logs/keep.log").unwrap();
+        rules.add_pattern("*.tmp").unwrap();
+        rules.add_pattern("!special.tmp").unwrap();
+        rules.add_pattern("special.tmp").unwrap();
+
+        assert!(rules.should_ignore("logs/today.log"));
+        assert!(!rules.should_ignore("logs/keep.log"));
+        // The last matching pattern wins
+        assert!(rules.should_ignore("special.tmp"));
+        assert!(rules.should_ignore("nested/dir/a.tmp"));
+    }
+
+    #[test]
+    fn test_directory_pattern_excludes_tree() {
+        let mut rules = IgnoreRules::new();
+        rules.add_pattern("build/").unwrap();
+
+        assert!(rules.should_ignore("build/out.o"));
+        assert!(rules.should_ignore("build/deep/nested/file"));
+        assert!(rules.should_ignore("src/build/gen.rs"));
+        assert!(rules.should_ignore_dir("build"));
+        // A plain file named like the directory is kept
+        assert!(!rules.should_ignore("build"));
+        assert!(!rules.should_ignore("builder/main.rs"));
+    }
+
+    #[test]
+    fn test_anchored_and_recursive_globs() {
+        let mut rules = IgnoreRules::new();
+        rules.add_pattern("/config.local").unwrap();
+        rules.add_pattern("docs/**/*.pdf").unwrap();
+        rules.add_pattern("src/*.bak").unwrap();
+
+        assert!(rules.should_ignore("config.local"));
+        assert!(!rules.should_ignore("app/config.local"));
+        assert!(rules.should_ignore("docs/a.pdf"));
+        assert!(rules.should_ignore("docs/x/y/a.pdf"));
+        assert!(!rules.should_ignore("other/docs/a.pdf"));
+        assert!(rules.should_ignore("src/main.bak"));
+        assert!(!rules.should_ignore("src/nested/main.bak"));
+    }
+
+    #[test]
+    fn test_nested_ignore_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(dir.path().join(".mugignore"), "*.log\nout/\n").unwrap();
+        fs::create_dir_all(dir.path().join("app/src")).unwrap();
+        fs::write(dir.path().join("app/.mugignore"), "!keep.log\n/local.cfg\n").unwrap();
+        let global = dir.path().join("global-ignore");
+        fs::write(&global, "*.swp\n!root.log\n").unwrap();
+
+        let rules = IgnoreRules::load(dir.path(), Some(&global)).unwrap();
+        assert!(rules.should_ignore("debug.log"));
+        assert!(rules.should_ignore("app/debug.log"));
+        // The subdirectory file re-includes what the root ignores
+        assert!(!rules.should_ignore("app/keep.log"));
+        assert!(!rules.should_ignore("app/src/keep.log"));
+        assert!(rules.should_ignore("keep.log"));
+        // Anchored to the directory holding the .mugignore
+        assert!(rules.should_ignore("app/local.cfg"));
+        assert!(!rules.should_ignore("local.cfg"));
+        assert!(!rules.should_ignore("app/src/local.cfg"));
+        // Global rules apply everywhere but the repo's files override them
+        assert!(rules.should_ignore("app/src/main.rs.swp"));
+        assert!(rules.should_ignore("root.log"));
+    }
 }
@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::core::database::MugDb;
 use crate::core::error::Result;
+use crate::core::file_mode;
 
 /// Represents a single entry in the git index (staging area)
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -16,6 +17,13 @@ pub struct IndexEntry {
     pub mode: u32,
 }
 
+impl IndexEntry {
+    /// Whether this entry stages the deletion of a tracked path
+    pub fn is_removal(&self) -> bool {
+        self.hash.is_empty()
+    }
+}
+
 /// Manages the git staging area (index) with persistence in the database
 pub struct Index {
     db: MugDb,
@@ -49,6 +57,16 @@ impl Index {
     /// # Returns
     /// Returns `Ok(())` on success, or an error if database operations fail
     pub fn add(&mut self, path: String, hash: String) -> Result<()> {
+        self.add_with_mode(path, hash, file_mode::REGULAR)
+    }
+
+    /// Adds an executable file to the index with executable mode
+    pub fn add_executable(&mut self, path: String, hash: String) -> Result<()> {
+        self.add_with_mode(path, hash, file_mode::EXECUTABLE)
+    }
+
+    /// Stages a file with the given mode, such as `file_mode::SYMLINK`
+    pub fn add_with_mode(&mut self, path: String, hash: String, mode: u32) -> Result<()> {
         // Validate inputs
         if path.is_empty() {
             return Err(crate::core::error::Error::Custom(
@@ -61,11 +79,10 @@ impl Index {
             ));
         }
 
-        // Create the index entry
         let entry = IndexEntry {
             path: path.clone(),
             hash,
-            mode: 0o100644, // Regular file mode
+            mode,
         };
 
         // Update in-memory cache
@@ -78,27 +95,6 @@ impl Index {
         Ok(())
     }
 
-    /// Adds an executable file to the index with executable mode
-    pub fn add_executable(&mut self, path: String, hash: String) -> Result<()> {
-        if path.is_empty() || hash.is_empty() {
-            return Err(crate::core::error::Error::Custom(
-                "Path and hash cannot be empty".to_string(),
-            ));
-        }
-
-        let entry = IndexEntry {
-            path: path.clone(),
-            hash,
-            mode: 0o100755, // Executable file mode
-        };
-
-        self.entries.insert(path.clone(), entry.clone());
-        let serialized = serde_json::to_vec(&entry)?;
-        self.db.set("INDEX", &path, serialized)?;
-
-        Ok(())
-    }
-
     /// Removes a file from the index (unstages it)
     ///
     /// # Arguments
@@ -109,6 +105,18 @@ impl Index {
         Ok(())
     }
 
+    /// Stages the deletion of a tracked path, so the next commit drops it
+    pub fn stage_removal(&mut self, path: &str) -> Result<()> {
+        let entry = IndexEntry {
+            path: path.to_string(),
+            hash: String::new(),
+            mode: 0,
+        };
+        self.entries.insert(path.to_string(), entry.clone());
+        self.db.set("INDEX", path, serde_json::to_vec(&entry)?)?;
+        Ok(())
+    }
+
     /// Retrieves an entry from the index
     ///
     /// # Arguments
@@ -367,4 +375,15 @@ mod tests {
             assert!(index.contains("file2.txt"));
         }
     }
+
+    #[test]
+    fn test_index_stage_removal() {
+        let dir = TempDir::new().unwrap();
+        let db = MugDb::new(dir.path().join("db")).unwrap();
+        let mut index = Index::new(db.clone()).unwrap();
+
+        index.stage_removal("gone.txt").unwrap();
+        let reloaded = Index::new(db).unwrap();
+        assert!(reloaded.get("gone.txt").unwrap().is_removal());
+    }
 }
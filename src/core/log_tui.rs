@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crossterm::event::{self, Event, KeyCode};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    Frame, Terminal,
+};
+
+use crate::commands::{patch_lines, show_commit};
+use crate::core::commit::CommitLog;
+use crate::core::error::{Error, Result};
+use crate::core::hash::short_hash;
+use crate::core::repo::Repository;
+use crate::ui::formatter::CommitInfo;
+use crate::ui::terminal::TerminalGuard;
+
+/// Commits loaded at a time as the selection nears the end of the list
+const PAGE_SIZE: usize = 200;
+
+/// What the user chose to do on leaving the browser
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogAction {
+    /// Check out this branch or commit
+    Checkout(String),
+    /// Print `show` for this commit
+    Show(String),
+}
+
+/// Commit list state, loading history lazily along first parents
+pub struct LogBrowser<'a> {
+    repo: &'a Repository,
+    commit_log: CommitLog,
+    pub commits: Vec<CommitInfo>,
+    /// Next commit to load, if history continues
+    next: Option<String>,
+    page_size: usize,
+    pub selected: usize,
+    /// Lines scrolled down in the detail pane
+    pub detail_scroll: u16,
+    details: HashMap<String, Vec<String>>,
+    status: String,
+}
+
+impl<'a> LogBrowser<'a> {
+    pub fn new(repo: &'a Repository) -> Result<Self> {
+        Self::with_page_size(repo, PAGE_SIZE)
+    }
+
+    fn with_page_size(repo: &'a Repository, page_size: usize) -> Result<Self> {
+        let mut browser = LogBrowser {
+            repo,
+            commit_log: CommitLog::new(repo.get_db().clone()),
+            commits: Vec::new(),
+            next: Some(repo.head_commit_id()?.ok_or(Error::NoCommits)?),
+            page_size,
+            selected: 0,
+            detail_scroll: 0,
+            details: HashMap::new(),
+            status: String::new(),
+        };
+        browser.load_page()?;
+        Ok(browser)
+    }
+
+    /// Load the next page of history, if any remains
+    fn load_page(&mut self) -> Result<()> {
+        let branch = self.repo.current_branch()?;
+        for _ in 0..self.page_size {
+            let Some(id) = self.next.take() else { break };
+            let commit = self.commit_log.get_commit(&id)?;
+            let is_head = self.commits.is_empty();
+            self.next = commit.parent.clone();
+            self.commits.push(CommitInfo {
+                parents: commit.parents(),
+                hash: commit.id,
+                author: commit.author,
+                date: commit.timestamp.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                message: commit.message,
+                is_head,
+                branch: if is_head { branch.clone() } else { None },
+                signature: None,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn select_next(&mut self) -> Result<()> {
+        // Keep a page's worth of margin so scrolling never hits the end
+        if self.selected + 1 >= self.commits.len().saturating_sub(self.page_size / 2) {
+            self.load_page()?;
+        }
+        if self.selected + 1 < self.commits.len() {
+            self.selected += 1;
+            self.detail_scroll = 0;
+        }
+        Ok(())
+    }
+
+    pub fn select_prev(&mut self) {
+        if self.selected > 0 {
+            self.selected -= 1;
+            self.detail_scroll = 0;
+        }
+    }
+
+    pub fn selected_commit(&self) -> Option<&CommitInfo> {
+        self.commits.get(self.selected)
+    }
+
+    /// Details and diff of the selected commit, computed once per commit
+    pub fn details(&mut self) -> Result<&[String]> {
+        let Some(hash) = self.selected_commit().map(|c| c.hash.clone()) else {
+            return Ok(&[]);
+        };
+        if !self.details.contains_key(&hash) {
+            let lines = commit_details(self.repo, &hash)?;
+            self.details.insert(hash.clone(), lines);
+        }
+        Ok(&self.details[&hash])
+    }
+}
+
+/// `show --stat` output for a commit followed by its diff against its first
+/// parent, trimmed to the changed lines and their context
+fn commit_details(repo: &Repository, hash: &str) -> Result<Vec<String>> {
+    let mut lines: Vec<String> = show_commit(repo, hash, true)?.lines().map(str::to_string).collect();
+
+    let commit = CommitLog::new(repo.get_db().clone()).get_commit(hash)?;
+    let parent = match &commit.parent {
+        Some(parent) => repo.snapshot(parent)?,
+        None => HashMap::new(),
+    };
+    let patch = patch_lines(repo, &parent, &repo.snapshot(hash)?)?;
+    if !patch.is_empty() {
+        lines.push(String::new());
+        lines.extend(patch);
+    }
+    Ok(lines)
+}
+
+/// Copy text to the terminal's clipboard with an OSC 52 escape sequence
+fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    use base64::Engine;
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", encoded)?;
+    stdout.flush()
+}
+
+/// Run the commit browser; returns the action chosen, or `None` on quit
+pub fn run_log_browser(repo: &Repository) -> Result<Option<LogAction>> {
+    let mut browser = LogBrowser::new(repo)?;
+    let tui_error = |e: io::Error| Error::Custom(e.to_string());
+
+    // Restores the terminal however the browser exits
+    let _guard = TerminalGuard::new()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout())).map_err(tui_error)?;
+    loop {
+        let details = browser.details()?.to_vec();
+        terminal.draw(|f| ui(f, &browser, &details)).map_err(tui_error)?;
+
+        if let Event::Key(key) = event::read().map_err(tui_error)? {
+            let Some(commit) = browser.selected_commit() else { continue };
+            let hash = commit.hash.clone();
+            // The branch tip checks out the branch rather than detaching HEAD
+            let checkout = commit.branch.clone().unwrap_or_else(|| hash.clone());
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(None),
+                KeyCode::Down | KeyCode::Char('j') => browser.select_next()?,
+                KeyCode::Up | KeyCode::Char('k') => browser.select_prev(),
+                KeyCode::PageDown | KeyCode::Char('J') => {
+                    browser.detail_scroll = browser.detail_scroll.saturating_add(10)
+                }
+                KeyCode::PageUp | KeyCode::Char('K') => {
+                    browser.detail_scroll = browser.detail_scroll.saturating_sub(10)
+                }
+                KeyCode::Char('y') => {
+                    copy_to_clipboard(&hash).map_err(tui_error)?;
+                    browser.status = format!("Copied {}", short_hash(&hash));
+                }
+                KeyCode::Char('c') => return Ok(Some(LogAction::Checkout(checkout))),
+                KeyCode::Enter | KeyCode::Char('s') => return Ok(Some(LogAction::Show(hash))),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn ui(f: &mut Frame, browser: &LogBrowser, details: &[String]) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(5), Constraint::Length(3)].as_ref())
+        .split(f.size());
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+        .split(rows[0]);
+
+    let items: Vec<ListItem> = browser
+        .commits
+        .iter()
+        .map(|commit| {
+            let mut spans = vec![
+                Span::styled(short_hash(&commit.hash), Style::default().fg(Color::Yellow)),
+                Span::raw(" "),
+            ];
+            if let Some(branch) = &commit.branch {
+                spans.push(Span::styled(
+                    format!("[{}] ", branch),
+                    Style::default().fg(Color::Green).add_modifier(Modifier::BOLD),
+                ));
+            }
+            spans.push(Span::raw(commit.message.lines().next().unwrap_or_default().to_string()));
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    let more = if browser.next.is_some() { "+" } else { "" };
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(format!("Commits ({}{})", browser.commits.len(), more))
+                .borders(Borders::ALL),
+        )
+        .highlight_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD));
+    let mut state = ListState::default();
+    state.select(Some(browser.selected));
+    f.render_stateful_widget(list, columns[0], &mut state);
+
+    let detail_lines: Vec<Line> = details
+        .iter()
+        .map(|line| {
+            let style = if line.starts_with("+++") || line.starts_with("---") {
+                Style::default().add_modifier(Modifier::BOLD)
+            } else if line.starts_with('+') {
+                Style::default().fg(Color::Green)
+            } else if line.starts_with('-') {
+                Style::default().fg(Color::Red)
+            } else {
+                Style::default()
+            };
+            Line::from(Span::styled(line.clone(), style))
+        })
+        .collect();
+    let detail = Paragraph::new(detail_lines)
+        .block(Block::default().title("Details").borders(Borders::ALL))
+        .scroll((browser.detail_scroll, 0));
+    f.render_widget(detail, columns[1]);
+
+    let key = |k: &'static str| Span::styled(k, Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD));
+    let help = Paragraph::new(Line::from(vec![
+        key("j/k"),
+        Span::raw(" Move  "),
+        key("J/K"),
+        Span::raw(" Scroll details  "),
+        key("y"),
+        Span::raw(" Copy hash  "),
+        key("c"),
+        Span::raw(" Checkout  "),
+        key("Enter"),
+        Span::raw(" Show  "),
+        key("q"),
+        Span::raw(" Quit  "),
+        Span::styled(browser.status.clone(), Style::default().fg(Color::Green)),
+    ]))
+    .block(Block::default().borders(Borders::ALL));
+    f.render_widget(help, rows[1]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo_with_history(commits: usize) -> (tempfile::TempDir, Repository) {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        for i in 0..commits {
+            std::fs::write(dir.path().join("file.txt"), format!("line\n{}\n", i)).unwrap();
+            repo.add("file.txt").unwrap();
+            repo.commit("Tester".to_string(), format!("Commit {}", i)).unwrap();
+        }
+        (dir, repo)
+    }
+
+    #[test]
+    fn test_browser_loads_history_lazily() {
+        let (_dir, repo) = repo_with_history(7);
+        let mut browser = LogBrowser::with_page_size(&repo, 4).unwrap();
+        assert_eq!(browser.commits.len(), 4);
+        assert_eq!(browser.commits[0].message, "Commit 6");
+        assert_eq!(browser.commits[0].branch.as_deref(), Some("main"));
+
+        for _ in 0..10 {
+            browser.select_next().unwrap();
+        }
+        assert_eq!(browser.commits.len(), 7);
+        assert_eq!(browser.selected_commit().unwrap().message, "Commit 0");
+        browser.select_prev();
+        assert_eq!(browser.selected_commit().unwrap().message, "Commit 1");
+    }
+
+    #[test]
+    fn test_details_include_diff() {
+        let (_dir, repo) = repo_with_history(2);
+        let mut browser = LogBrowser::new(&repo).unwrap();
+        let details = browser.details().unwrap().to_vec();
+        assert!(details.iter().any(|l| l.contains("Commit 1")));
+        assert!(details.contains(&"--- file.txt".to_string()));
+        assert!(details.contains(&"- 0".to_string()));
+        assert!(details.contains(&"+ 1".to_string()));
+    }
+}
@@ -1,5 +1,16 @@
-use crate::core::error::Result;
+use std::collections::{BTreeSet, HashMap};
+use std::str::FromStr;
+
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+use crate::core::branch::BranchManager;
+use crate::core::commit::CommitLog;
+use crate::core::config::Config;
+use crate::core::error::{Error, Result};
+use crate::core::index::Index;
+use crate::core::merge_tui::{run_merge_conflict_resolver, ConflictHunk, HunkResolution};
 use crate::core::repo::Repository;
+use crate::core::sequencer::{self, OperationKind, OperationState};
 
 /// Merge strategy for combining branches
 #[derive(Debug, Clone, Copy)]
@@ -14,6 +25,20 @@ pub enum MergeStrategy {
     Theirs,
 }
 
+impl FromStr for MergeStrategy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "simple" => Ok(MergeStrategy::Simple),
+            "recursive" => Ok(MergeStrategy::Recursive),
+            "ours" => Ok(MergeStrategy::Ours),
+            "theirs" => Ok(MergeStrategy::Theirs),
+            _ => Err(Error::Custom(format!("Unknown merge strategy: {}", s))),
+        }
+    }
+}
+
 /// Result of a merge operation
 #[derive(Debug, Clone)]
 pub struct MergeResult {
@@ -22,12 +47,381 @@ pub struct MergeResult {
     pub message: String,
 }
 
+/// A region of a three-way text merge
+#[derive(Debug, Clone, PartialEq)]
+pub enum MergeChunk {
+    /// Lines both sides agree on
+    Clean(Vec<String>),
+    /// Lines changed differently on both sides
+    Conflict {
+        base: Vec<String>,
+        ours: Vec<String>,
+        theirs: Vec<String>,
+    },
+}
+
+/// Outcome of merging one file's text
+#[derive(Debug, Clone)]
+pub struct TextMerge {
+    pub chunks: Vec<MergeChunk>,
+}
+
+impl TextMerge {
+    pub fn has_conflicts(&self) -> bool {
+        self.conflict_count() > 0
+    }
+
+    pub fn conflict_count(&self) -> usize {
+        self.chunks
+            .iter()
+            .filter(|c| matches!(c, MergeChunk::Conflict { .. }))
+            .count()
+    }
+
+    /// Render the merged text, writing conflict markers around conflicting hunks
+    pub fn render(&self, ours_label: &str, theirs_label: &str) -> String {
+        let mut output = String::new();
+        for chunk in &self.chunks {
+            match chunk {
+                MergeChunk::Clean(lines) => output.extend(lines.iter().map(|l| l.as_str())),
+                MergeChunk::Conflict { ours, theirs, .. } => {
+                    output.push_str(&format!("<<<<<<< {}\n", ours_label));
+                    push_lines_terminated(&mut output, ours);
+                    output.push_str("=======\n");
+                    push_lines_terminated(&mut output, theirs);
+                    output.push_str(&format!(">>>>>>> {}\n", theirs_label));
+                }
+            }
+        }
+        output
+    }
+
+    /// Render the merged text, taking one side for every conflicting hunk
+    pub fn resolve(&self, prefer_ours: bool) -> String {
+        let mut output = String::new();
+        for chunk in &self.chunks {
+            let lines = match chunk {
+                MergeChunk::Clean(lines) => lines,
+                MergeChunk::Conflict { ours, .. } if prefer_ours => ours,
+                MergeChunk::Conflict { theirs, .. } => theirs,
+            };
+            output.extend(lines.iter().map(|l| l.as_str()));
+        }
+        output
+    }
+}
+
+fn push_lines_terminated(output: &mut String, lines: &[String]) {
+    for line in lines {
+        output.push_str(line);
+    }
+    if !output.ends_with('\n') {
+        output.push('\n');
+    }
+}
+
+/// A replacement of `base[start..end]` with `lines`
+struct Edit {
+    start: usize,
+    end: usize,
+    lines: Vec<String>,
+}
+
+/// Contiguous edits that turn `base` into `other`
+fn line_edits(base: &[&str], other: &[&str]) -> Vec<Edit> {
+    let mut edits: Vec<Edit> = Vec::new();
+
+    for op in capture_diff_slices(Algorithm::Myers, base, other) {
+        let (old_start, old_len, new_start, new_len) = match op {
+            DiffOp::Equal { .. } => continue,
+            DiffOp::Delete {
+                old_index,
+                old_len,
+                new_index,
+            } => (old_index, old_len, new_index, 0),
+            DiffOp::Insert {
+                old_index,
+                new_index,
+                new_len,
+            } => (old_index, 0, new_index, new_len),
+            DiffOp::Replace {
+                old_index,
+                old_len,
+                new_index,
+                new_len,
+            } => (old_index, old_len, new_index, new_len),
+        };
+        let lines: Vec<String> = other[new_start..new_start + new_len]
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+
+        match edits.last_mut() {
+            Some(last) if last.end == old_start => {
+                last.end += old_len;
+                last.lines.extend(lines);
+            }
+            _ => edits.push(Edit {
+                start: old_start,
+                end: old_start + old_len,
+                lines,
+            }),
+        }
+    }
+
+    edits
+}
+
+/// Apply `edits` (all inside `start..end`) to that region of `base`
+fn apply_edits(base: &[&str], start: usize, end: usize, edits: &[Edit]) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut pos = start;
+    for edit in edits {
+        result.extend(base[pos..edit.start].iter().map(|l| l.to_string()));
+        result.extend(edit.lines.iter().cloned());
+        pos = edit.end;
+    }
+    result.extend(base[pos..end].iter().map(|l| l.to_string()));
+    result
+}
+
+/// Three-way merge of text, line by line.
+///
+/// Only regions that both sides changed (and changed differently) conflict.
+pub fn merge_text(base: &str, ours: &str, theirs: &str) -> TextMerge {
+    let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+    let ours_lines: Vec<&str> = ours.split_inclusive('\n').collect();
+    let theirs_lines: Vec<&str> = theirs.split_inclusive('\n').collect();
+
+    let ours_edits = line_edits(&base_lines, &ours_lines);
+    let theirs_edits = line_edits(&base_lines, &theirs_lines);
+
+    let mut chunks = Vec::new();
+    let mut clean: Vec<String> = Vec::new();
+    let (mut i, mut j, mut pos) = (0, 0, 0);
+
+    loop {
+        let start = match (ours_edits.get(i), theirs_edits.get(j)) {
+            (None, None) => break,
+            (Some(o), None) => o.start,
+            (None, Some(t)) => t.start,
+            (Some(o), Some(t)) => o.start.min(t.start),
+        };
+
+        // Grow the region while edits from either side overlap or touch it
+        let (first_ours, first_theirs) = (i, j);
+        let mut end = start;
+        loop {
+            let mut grew = false;
+            while let Some(o) = ours_edits.get(i).filter(|o| o.start <= end) {
+                end = end.max(o.end);
+                i += 1;
+                grew = true;
+            }
+            while let Some(t) = theirs_edits.get(j).filter(|t| t.start <= end) {
+                end = end.max(t.end);
+                j += 1;
+                grew = true;
+            }
+            if !grew {
+                break;
+            }
+        }
+
+        clean.extend(base_lines[pos..start].iter().map(|l| l.to_string()));
+        let ours_region = apply_edits(&base_lines, start, end, &ours_edits[first_ours..i]);
+        let theirs_region = apply_edits(&base_lines, start, end, &theirs_edits[first_theirs..j]);
+
+        if first_theirs == j {
+            clean.extend(ours_region);
+        } else if first_ours == i || ours_region == theirs_region {
+            clean.extend(theirs_region);
+        } else {
+            if !clean.is_empty() {
+                chunks.push(MergeChunk::Clean(std::mem::take(&mut clean)));
+            }
+            chunks.push(MergeChunk::Conflict {
+                base: base_lines[start..end].iter().map(|l| l.to_string()).collect(),
+                ours: ours_region,
+                theirs: theirs_region,
+            });
+        }
+        pos = end;
+    }
+
+    clean.extend(base_lines[pos..].iter().map(|l| l.to_string()));
+    if !clean.is_empty() {
+        chunks.push(MergeChunk::Clean(clean));
+    }
+
+    TextMerge { chunks }
+}
+
+const CONTEXT_LINES: usize = 3;
+
+/// Extract the conflict hunks from a file written with conflict markers
+pub fn conflict_hunks(file_path: &str, content: &str) -> Vec<ConflictHunk> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut hunks = Vec::new();
+    let mut i = 0;
+    let mut previous_end = 0;
+
+    while i < lines.len() {
+        if !lines[i].starts_with("<<<<<<< ") {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        let mut current_lines = Vec::new();
+        let mut incoming_lines = Vec::new();
+        i += 1;
+        while i < lines.len() && lines[i] != "=======" {
+            current_lines.push(lines[i].to_string());
+            i += 1;
+        }
+        i += 1;
+        while i < lines.len() && !lines[i].starts_with(">>>>>>> ") {
+            incoming_lines.push(lines[i].to_string());
+            i += 1;
+        }
+
+        // Context never reaches into the previous or next conflict block
+        let before_start = start.saturating_sub(CONTEXT_LINES).max(previous_end);
+        let after_start = (i + 1).min(lines.len());
+        let after_end = lines[after_start..]
+            .iter()
+            .take(CONTEXT_LINES)
+            .position(|l| l.starts_with("<<<<<<< "))
+            .map_or((after_start + CONTEXT_LINES).min(lines.len()), |n| after_start + n);
+        hunks.push(ConflictHunk {
+            file_path: file_path.to_string(),
+            current_lines,
+            incoming_lines,
+            context_before: lines[before_start..start].iter().map(|l| l.to_string()).collect(),
+            context_after: lines[after_start..after_end]
+                .iter()
+                .map(|l| l.to_string())
+                .collect(),
+        });
+        i += 1;
+        previous_end = i;
+    }
+
+    hunks
+}
+
+/// Replace each conflict block in `content` with its chosen resolution.
+///
+/// `HunkResolution::Skip` leaves the markers in place. Returns the new
+/// content and whether any conflict remains.
+pub fn apply_hunk_resolutions(content: &str, resolutions: &[HunkResolution]) -> (String, bool) {
+    let mut output = Vec::new();
+    let mut unresolved = false;
+    let mut hunk = 0;
+    let mut lines = content.lines();
+
+    while let Some(line) = lines.next() {
+        if !line.starts_with("<<<<<<< ") {
+            output.push(line.to_string());
+            continue;
+        }
+
+        let mut block = vec![line.to_string()];
+        let mut current = Vec::new();
+        let mut incoming = Vec::new();
+        let mut in_incoming = false;
+        for line in lines.by_ref() {
+            block.push(line.to_string());
+            if line.starts_with(">>>>>>> ") {
+                break;
+            } else if line == "=======" {
+                in_incoming = true;
+            } else if in_incoming {
+                incoming.push(line.to_string());
+            } else {
+                current.push(line.to_string());
+            }
+        }
+
+        match resolutions.get(hunk).copied().unwrap_or(HunkResolution::Skip) {
+            HunkResolution::Current => output.extend(current),
+            HunkResolution::Incoming => output.extend(incoming),
+            HunkResolution::Both => {
+                output.extend(current);
+                output.extend(incoming);
+            }
+            HunkResolution::Skip => {
+                output.extend(block);
+                unresolved = true;
+            }
+        }
+        hunk += 1;
+    }
+
+    let mut result = output.join("\n");
+    if content.ends_with('\n') {
+        result.push('\n');
+    }
+    (result, unresolved)
+}
+
+/// Launch the conflict resolver for the given paths, write the chosen
+/// resolutions back, and stage every file that no longer has conflicts.
+///
+/// Returns the paths that still contain conflict markers.
+pub fn resolve_conflicts_interactively(
+    repo: &Repository,
+    conflicts: &[String],
+) -> Result<Vec<String>> {
+    let mut contents = Vec::new();
+    let mut hunks = Vec::new();
+    for path in conflicts {
+        let content = std::fs::read_to_string(repo.root_path().join(path)).unwrap_or_default();
+        hunks.extend(conflict_hunks(path, &content));
+        contents.push((path.clone(), content));
+    }
+
+    if hunks.is_empty() {
+        return Ok(conflicts.to_vec());
+    }
+
+    let resolved = match run_merge_conflict_resolver(hunks)? {
+        Some(resolved) => resolved,
+        None => return Ok(conflicts.to_vec()),
+    };
+
+    let mut remaining = Vec::new();
+    for (path, content) in contents {
+        let resolutions: Vec<HunkResolution> = resolved
+            .iter()
+            .filter(|(hunk, _)| hunk.file_path == path)
+            .map(|(_, resolution)| *resolution)
+            .collect();
+        if resolutions.is_empty() {
+            remaining.push(path);
+            continue;
+        }
+
+        let (new_content, unresolved) = apply_hunk_resolutions(&content, &resolutions);
+        std::fs::write(repo.root_path().join(&path), new_content)?;
+        if unresolved {
+            remaining.push(path);
+        } else {
+            repo.add(&path)?;
+        }
+    }
+
+    Ok(remaining)
+}
+
 /// Performs a merge of source branch into current branch
 pub fn merge(
     repo: &Repository,
     source_branch: &str,
     strategy: MergeStrategy,
 ) -> Result<MergeResult> {
+    sequencer::ensure_no_operation(repo)?;
     let current = repo.current_branch()?;
     let current_branch = current.as_deref().unwrap_or("main");
 
@@ -39,120 +433,302 @@ pub fn merge(
         });
     }
 
-    // Get commit logs for both branches
-    let commits = repo.log()?;
-
-    // Check if source branch exists
-    let source_exists = commits.iter().any(|c| c.contains(source_branch));
+    let branch_manager = BranchManager::new(repo.get_db().clone());
+    // Remote-tracking branches such as `origin/main` merge like local ones
+    let source_commit = match branch_manager
+        .get_branch(source_branch)?
+        .map(|b| b.commit_id)
+        .filter(|id| !id.is_empty())
+    {
+        Some(id) => id,
+        None => repo
+            .resolve_rev(source_branch)
+            .map_err(|_| Error::BranchNotFound(source_branch.to_string()))?,
+    };
+    let head_commit = repo.head_commit_id()?.ok_or(Error::NoCommits)?;
 
-    if !source_exists {
-        return Err(crate::core::error::Error::BranchNotFound(
-            source_branch.to_string(),
+    if !Index::new(repo.get_db().clone())?.is_empty() {
+        return Err(Error::Custom(
+            "Cannot merge with staged changes; commit or stash them first".to_string(),
         ));
     }
 
-    match strategy {
-        MergeStrategy::Simple => {
-            // Simple merge: check if it's a fast-forward
-            simple_merge(repo, source_branch, current_branch)
-        }
-        MergeStrategy::Recursive => {
-            // Three-way merge algorithm (simplified)
-            three_way_merge(repo, source_branch, current_branch)
-        }
-        MergeStrategy::Ours | MergeStrategy::Theirs => {
-            // Strategy merges: take one side
-            strategy_merge(repo, source_branch, current_branch, strategy)
-        }
+    let commit_log = CommitLog::new(repo.get_db().clone());
+    if commit_log.is_ancestor(&source_commit, &head_commit)? {
+        return Ok(MergeResult {
+            merged: true,
+            conflicts: vec![],
+            message: format!("Already up to date with {}", source_branch),
+        });
     }
-}
 
-/// Attempt a fast-forward merge
-fn simple_merge(repo: &Repository, source: &str, current: &str) -> Result<MergeResult> {
-    let commits = repo.log()?;
+    if commit_log.is_ancestor(&head_commit, &source_commit)? {
+        return fast_forward(repo, source_branch, current_branch, &head_commit, &source_commit);
+    }
 
-    // Check if current is an ancestor of source (fast-forward possible)
-    let current_idx = commits.iter().position(|c| c.contains(current));
-    let source_idx = commits.iter().position(|c| c.contains(source));
+    three_way_merge(
+        repo,
+        source_branch,
+        current_branch,
+        &head_commit,
+        &source_commit,
+        strategy,
+    )
+}
 
-    match (current_idx, source_idx) {
-        (Some(c), Some(s)) if s < c => {
-            // Source is ahead: fast-forward is possible
-            Ok(MergeResult {
-                merged: true,
-                conflicts: vec![],
-                message: format!("Fast-forward merge of {} into {}", source, current),
-            })
-        }
-        (Some(c), Some(s)) if c < s => {
-            // Current is ahead: no merge needed
-            Ok(MergeResult {
-                merged: true,
-                conflicts: vec![],
-                message: format!("Already up to date with {}", source),
-            })
-        }
-        _ => {
-            // Requires three-way merge
-            three_way_merge(repo, source, current)
-        }
-    }
+/// Conclude a merge that stopped on conflicts, once they are resolved
+pub fn continue_merge(repo: &Repository) -> Result<MergeResult> {
+    let state = OperationState::load_kind(repo, OperationKind::Merge)?;
+    let files = sequencer::resolved_files(repo, &state)?;
+    sequencer::commit_resolution(repo, &state, &files, state.message.clone(), true)?;
+    sequencer::finish(repo)?;
+
+    Ok(MergeResult {
+        merged: true,
+        conflicts: vec![],
+        message: format!("Merged {} into {}", state.target, state.branch),
+    })
 }
 
-/// Three-way merge algorithm (simplified)
-fn three_way_merge(repo: &Repository, source: &str, current: &str) -> Result<MergeResult> {
-    let index = Index::new(repo.get_db().clone())?;
-    let entries = index.entries();
+/// Move the current branch forward to the source commit
+fn fast_forward(
+    repo: &Repository,
+    source: &str,
+    current: &str,
+    head_commit: &str,
+    source_commit: &str,
+) -> Result<MergeResult> {
+    let ours = repo.snapshot(head_commit)?;
+    let (theirs, modes) = repo.snapshot_with_modes(source_commit)?;
+    repo.update_working_tree(&ours, &theirs, &modes)?;
 
-    // Simplified: assume no conflicts if file count is similar
-    let has_conflicts = entries.len() > 10; // Arbitrary threshold for demo
+    let branch_manager = BranchManager::new(repo.get_db().clone());
+    branch_manager.update_branch_with_reason(
+        current,
+        source_commit.to_string(),
+        &format!("merge {}: Fast-forward", source),
+    )?;
+    repo.get_db().flush()?;
 
     Ok(MergeResult {
-        merged: !has_conflicts,
-        conflicts: if has_conflicts {
-            vec!["Merge conflicts detected in multiple files".to_string()]
-        } else {
-            vec![]
-        },
-        message: if has_conflicts {
-            format!("Merge {} into {} with conflicts", source, current)
-        } else {
-            format!("Merged {} into {}", source, current)
-        },
+        merged: true,
+        conflicts: vec![],
+        message: format!("Fast-forward merge of {} into {}", source, current),
     })
 }
 
-/// Strategy-based merge (ours/theirs)
-fn strategy_merge(
-    _repo: &Repository,
+/// Outcome of merging a single path
+enum FileMerge {
+    Clean(Option<String>),
+    Conflict(Option<String>),
+}
+
+/// A merged file snapshot and the paths that were left with conflicts
+#[derive(Debug, Clone)]
+pub struct SnapshotMerge {
+    pub files: HashMap<String, String>,
+    pub conflicts: Vec<String>,
+}
+
+/// Three-way merge of two file snapshots against their common base.
+///
+/// Conflicting text files are stored with conflict markers labelled with
+/// `ours_label` and `theirs_label`.
+pub fn merge_snapshots(
+    repo: &Repository,
+    base: &HashMap<String, String>,
+    ours: &HashMap<String, String>,
+    theirs: &HashMap<String, String>,
+    ours_label: &str,
+    theirs_label: &str,
+    strategy: MergeStrategy,
+) -> Result<SnapshotMerge> {
+    let paths: BTreeSet<&String> = base.keys().chain(ours.keys()).chain(theirs.keys()).collect();
+    let mut files = HashMap::new();
+    let mut conflicts = Vec::new();
+
+    for path in paths {
+        let (b, o, t) = (base.get(path), ours.get(path), theirs.get(path));
+        let outcome = if o == t || t == b {
+            FileMerge::Clean(o.cloned())
+        } else if o == b {
+            FileMerge::Clean(t.cloned())
+        } else {
+            merge_file(repo, b, o, t, ours_label, theirs_label, strategy)?
+        };
+
+        match outcome {
+            FileMerge::Clean(Some(hash)) => {
+                files.insert(path.clone(), hash);
+            }
+            FileMerge::Clean(None) => {}
+            FileMerge::Conflict(hash) => {
+                conflicts.push(path.clone());
+                if let Some(hash) = hash {
+                    files.insert(path.clone(), hash);
+                }
+            }
+        }
+    }
+
+    Ok(SnapshotMerge { files, conflicts })
+}
+
+/// Three-way merge using the nearest common ancestor as base
+fn three_way_merge(
+    repo: &Repository,
     source: &str,
     current: &str,
+    head_commit: &str,
+    source_commit: &str,
     strategy: MergeStrategy,
 ) -> Result<MergeResult> {
-    let msg = match strategy {
-        MergeStrategy::Ours => {
-            format!(
-                "Merged {} into {} (keeping current changes)",
-                source, current
-            )
+    let commit_log = CommitLog::new(repo.get_db().clone());
+    let base = match commit_log.merge_base(head_commit, source_commit)? {
+        Some(base_id) => repo.snapshot(&base_id)?,
+        None => HashMap::new(),
+    };
+    let ours = repo.snapshot(head_commit)?;
+    let theirs = repo.snapshot(source_commit)?;
+
+    let SnapshotMerge {
+        files: merged,
+        conflicts,
+    } = merge_snapshots(repo, &base, &ours, &theirs, current, source, strategy)?;
+
+    let modes = repo.carry_modes(&merged, &[head_commit, source_commit])?;
+    repo.update_working_tree(&ours, &merged, &modes)?;
+
+    if !conflicts.is_empty() {
+        // Stage the cleanly merged paths so the resolving commit includes them
+        let mut index = Index::new(repo.get_db().clone())?;
+        for (path, hash) in &merged {
+            if ours.get(path) != Some(hash) && !conflicts.contains(path) {
+                index.add(path.clone(), hash.clone())?;
+            }
         }
-        MergeStrategy::Theirs => {
-            format!(
-                "Merged {} into {} (accepting incoming changes)",
-                source, current
-            )
+        index.flush()?;
+
+        OperationState {
+            kind: OperationKind::Merge,
+            branch: current.to_string(),
+            orig_head: head_commit.to_string(),
+            target: source.to_string(),
+            files: merged,
+            conflicts: conflicts.clone(),
+            author: Config::load(repo.common_root())?.get_user_name(),
+            message: format!("Merge branch '{}' into {}", source, current),
+            parent: Some(head_commit.to_string()),
+            merge_head: Some(source_commit.to_string()),
+            edit_message: false,
+            remaining: vec![],
+            applied: 0,
         }
-        _ => "Merge completed".to_string(),
-    };
+        .save(repo)?;
+
+        return Ok(MergeResult {
+            merged: false,
+            conflicts,
+            message: format!(
+                "Automatic merge of {} into {} failed; fix conflicts and run 'mug merge --continue'",
+                source, current
+            ),
+        });
+    }
+
+    let tree_hash = repo.store_snapshot_with_modes(&merged, &modes)?;
+    let author = Config::load(repo.common_root())?.get_user_name();
+    let message = format!("Merge branch '{}' into {}", source, current);
+    let signing_key = repo.default_signing_key()?;
+    let commit = CommitLog::prepare_commit(
+        tree_hash,
+        author,
+        message.clone(),
+        Some(head_commit.to_string()),
+        Some(source_commit.to_string()),
+        signing_key.as_ref(),
+    )?;
+    let serialized = serde_json::to_vec(&commit)?;
+
+    // Store the merge commit and move the branch together
+    let branch_manager = BranchManager::new(repo.get_db().clone());
+    repo.get_db().transaction(|tx| {
+        tx.set("COMMITS", &commit.id, &serialized)?;
+        branch_manager.update_branch_in(
+            tx,
+            current,
+            commit.id.clone(),
+            &format!("merge {}: {}", source, message),
+        )
+    })?;
+    repo.get_db().flush()?;
 
     Ok(MergeResult {
         merged: true,
         conflicts: vec![],
-        message: msg,
+        message: format!("Merged {} into {}", source, current),
     })
 }
 
-use crate::core::index::Index;
+/// Merge one path that both sides changed
+fn merge_file(
+    repo: &Repository,
+    base: Option<&String>,
+    ours: Option<&String>,
+    theirs: Option<&String>,
+    current: &str,
+    source: &str,
+    strategy: MergeStrategy,
+) -> Result<FileMerge> {
+    let (ours_hash, theirs_hash) = match (ours, theirs) {
+        (Some(o), Some(t)) => (o, t),
+        // Modified on one side, deleted on the other
+        _ => {
+            return Ok(match strategy {
+                MergeStrategy::Ours => FileMerge::Clean(ours.cloned()),
+                MergeStrategy::Theirs => FileMerge::Clean(theirs.cloned()),
+                _ => FileMerge::Conflict(ours.or(theirs).cloned()),
+            })
+        }
+    };
+
+    let store = repo.get_store();
+    let read_text = |hash: &String| -> Result<Option<String>> {
+        Ok(String::from_utf8(store.get_blob(hash)?.content).ok())
+    };
+    let base_text = match base {
+        Some(hash) => read_text(hash)?,
+        None => Some(String::new()),
+    };
+
+    let (base_text, ours_text, theirs_text) =
+        match (base_text, read_text(ours_hash)?, read_text(theirs_hash)?) {
+            (Some(b), Some(o), Some(t)) => (b, o, t),
+            // Binary content cannot be merged line by line
+            _ => {
+                return Ok(match strategy {
+                    MergeStrategy::Theirs => FileMerge::Clean(Some(theirs_hash.clone())),
+                    MergeStrategy::Ours => FileMerge::Clean(Some(ours_hash.clone())),
+                    _ => FileMerge::Conflict(Some(ours_hash.clone())),
+                })
+            }
+        };
+
+    let text_merge = merge_text(&base_text, &ours_text, &theirs_text);
+    let (content, conflicted) = match strategy {
+        _ if !text_merge.has_conflicts() => (text_merge.resolve(true), false),
+        MergeStrategy::Ours => (text_merge.resolve(true), false),
+        MergeStrategy::Theirs => (text_merge.resolve(false), false),
+        _ => (text_merge.render(current, source), true),
+    };
+
+    let hash = store.store_blob(content.as_bytes())?;
+    if conflicted {
+        Ok(FileMerge::Conflict(Some(hash)))
+    } else {
+        Ok(FileMerge::Clean(Some(hash)))
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -176,4 +752,190 @@ mod tests {
         assert_eq!(format!("{:?}", MergeStrategy::Ours), "Ours");
         assert_eq!(format!("{:?}", MergeStrategy::Theirs), "Theirs");
     }
+
+    #[test]
+    fn test_merge_strategy_parsing() {
+        assert!(matches!("ours".parse::<MergeStrategy>().unwrap(), MergeStrategy::Ours));
+        assert!(matches!(
+            "recursive".parse::<MergeStrategy>().unwrap(),
+            MergeStrategy::Recursive
+        ));
+        assert!("octopus".parse::<MergeStrategy>().is_err());
+    }
+
+    #[test]
+    fn test_merge_text_disjoint_changes() {
+        let base = "a\nb\nc\nd\n";
+        let ours = "A\nb\nc\nd\n";
+        let theirs = "a\nb\nc\nD\n";
+
+        let result = merge_text(base, ours, theirs);
+        assert!(!result.has_conflicts());
+        assert_eq!(result.resolve(true), "A\nb\nc\nD\n");
+    }
+
+    #[test]
+    fn test_merge_text_conflicting_changes() {
+        let base = "a\nb\nc\n";
+        let ours = "a\nours\nc\n";
+        let theirs = "a\ntheirs\nc\n";
+
+        let result = merge_text(base, ours, theirs);
+        assert_eq!(result.conflict_count(), 1);
+        assert_eq!(
+            result.render("main", "feature"),
+            "a\n<<<<<<< main\nours\n=======\ntheirs\n>>>>>>> feature\nc\n"
+        );
+        assert_eq!(result.resolve(false), "a\ntheirs\nc\n");
+    }
+
+    #[test]
+    fn test_merge_text_identical_changes() {
+        let result = merge_text("a\n", "b\n", "b\n");
+        assert!(!result.has_conflicts());
+        assert_eq!(result.resolve(true), "b\n");
+    }
+
+    fn diverged_repo(ours: &str, theirs: &str) -> (tempfile::TempDir, Repository) {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let file = dir.path().join("file.txt");
+
+        std::fs::write(&file, "a\nb\nc\n").unwrap();
+        repo.add("file.txt").unwrap();
+        repo.commit("Tester".to_string(), "Base".to_string()).unwrap();
+        repo.create_branch("feature".to_string()).unwrap();
+
+        std::fs::write(&file, ours).unwrap();
+        repo.add("file.txt").unwrap();
+        repo.commit("Tester".to_string(), "Ours".to_string()).unwrap();
+
+        repo.checkout("feature".to_string()).unwrap();
+        std::fs::write(&file, theirs).unwrap();
+        repo.add("file.txt").unwrap();
+        repo.commit("Tester".to_string(), "Theirs".to_string()).unwrap();
+
+        repo.checkout("main".to_string()).unwrap();
+        std::fs::write(&file, ours).unwrap();
+        (dir, repo)
+    }
+
+    #[test]
+    fn test_three_way_merge_creates_merge_commit() {
+        let (dir, repo) = diverged_repo("A\nb\nc\n", "a\nb\nC\n");
+
+        let result = merge(&repo, "feature", MergeStrategy::Recursive).unwrap();
+        assert!(result.merged);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("file.txt")).unwrap(),
+            "A\nb\nC\n"
+        );
+
+        let head = repo.history().unwrap().remove(0);
+        assert!(head.merge_parent.is_some());
+    }
+
+    #[test]
+    fn test_three_way_merge_reports_conflicts() {
+        let (dir, repo) = diverged_repo("a\nours\nc\n", "a\ntheirs\nc\n");
+
+        let result = merge(&repo, "feature", MergeStrategy::Recursive).unwrap();
+        assert!(!result.merged);
+        assert_eq!(result.conflicts, vec!["file.txt".to_string()]);
+        let content = std::fs::read_to_string(dir.path().join("file.txt")).unwrap();
+        assert!(content.contains("<<<<<<< main"));
+        assert!(content.contains(">>>>>>> feature"));
+    }
+
+    #[test]
+    fn test_merge_continue_after_resolving() {
+        let (dir, repo) = diverged_repo("a\nours\nc\n", "a\ntheirs\nc\n");
+        let file = dir.path().join("file.txt");
+
+        merge(&repo, "feature", MergeStrategy::Recursive).unwrap();
+        assert!(merge(&repo, "feature", MergeStrategy::Recursive).is_err());
+        assert!(continue_merge(&repo).is_err());
+
+        std::fs::write(&file, "a\nboth\nc\n").unwrap();
+        let result = continue_merge(&repo).unwrap();
+        assert!(result.merged);
+
+        let head = repo.history().unwrap().remove(0);
+        assert!(head.merge_parent.is_some());
+        assert_eq!(head.message, "Merge branch 'feature' into main");
+        assert_eq!(repo.snapshot(&head.id).unwrap()["file.txt"], repo.get_store().store_blob(b"a\nboth\nc\n").unwrap());
+        assert!(OperationState::load(&repo).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_merge_refuses_to_overwrite_unstaged_changes() {
+        // Fast-forward
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let file = dir.path().join("a.txt");
+        std::fs::write(&file, "one\n").unwrap();
+        repo.add("a.txt").unwrap();
+        repo.commit("Tester".to_string(), "Base".to_string()).unwrap();
+        repo.create_branch("other".to_string()).unwrap();
+        repo.checkout("other".to_string()).unwrap();
+        std::fs::write(&file, "two\n").unwrap();
+        repo.add("a.txt").unwrap();
+        repo.commit("Tester".to_string(), "Other".to_string()).unwrap();
+        repo.checkout("main".to_string()).unwrap();
+        let main = repo.head_commit_id().unwrap();
+
+        std::fs::write(&file, "one\nlocal\n").unwrap();
+        let err = merge(&repo, "other", MergeStrategy::Recursive).unwrap_err();
+        assert!(err.to_string().contains("a.txt"), "{}", err);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "one\nlocal\n");
+        assert_eq!(repo.head_commit_id().unwrap(), main);
+
+        // Three-way
+        let (dir, repo) = diverged_repo("A\nb\nc\n", "a\nb\nC\n");
+        let file = dir.path().join("file.txt");
+        std::fs::write(&file, "A\nb\nc\nlocal\n").unwrap();
+        assert!(merge(&repo, "feature", MergeStrategy::Recursive).is_err());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "A\nb\nc\nlocal\n");
+        assert!(OperationState::load(&repo).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_theirs_strategy_resolves_conflicts() {
+        let (dir, repo) = diverged_repo("a\nours\nc\n", "a\ntheirs\nc\n");
+
+        let result = merge(&repo, "feature", MergeStrategy::Theirs).unwrap();
+        assert!(result.merged);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("file.txt")).unwrap(),
+            "a\ntheirs\nc\n"
+        );
+    }
+
+    #[test]
+    fn test_conflict_hunks_and_resolutions() {
+        let content = "a\n<<<<<<< main\nours\n=======\ntheirs\n>>>>>>> feature\nb\n<<<<<<< main\nx\n=======\ny\n>>>>>>> feature\n";
+
+        let hunks = conflict_hunks("file.txt", content);
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(hunks[0].current_lines, vec!["ours"]);
+        assert_eq!(hunks[0].incoming_lines, vec!["theirs"]);
+        assert_eq!(hunks[0].context_before, vec!["a"]);
+        assert_eq!(hunks[0].context_after, vec!["b"]);
+        assert_eq!(hunks[1].context_before, vec!["b"]);
+        assert!(hunks[1].context_after.is_empty());
+
+        let (resolved, unresolved) = apply_hunk_resolutions(
+            content,
+            &[HunkResolution::Incoming, HunkResolution::Both],
+        );
+        assert!(!unresolved);
+        assert_eq!(resolved, "a\ntheirs\nb\nx\ny\n");
+
+        let (partial, unresolved) = apply_hunk_resolutions(
+            content,
+            &[HunkResolution::Current, HunkResolution::Skip],
+        );
+        assert!(unresolved);
+        assert!(partial.starts_with("a\nours\nb\n<<<<<<< main\n"));
+    }
 }
@@ -1,9 +1,6 @@
 use crate::core::error::Result;
-use crossterm::{
-    event::{self, Event, KeyCode},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode},
-};
+use crate::ui::terminal::TerminalGuard;
+use crossterm::event::{self, Event, KeyCode};
 use ratatui::{
     backend::CrosstermBackend,
     layout::{Alignment, Constraint, Direction, Layout},
@@ -136,18 +133,15 @@ impl MergeConflictState {
     }
 }
 
-pub fn run_merge_conflict_resolver(hunks: Vec<ConflictHunk>) -> Result<Vec<(ConflictHunk, HunkResolution)>> {
-    enable_raw_mode().map_err(|e| crate::core::error::Error::Custom(e.to_string()))?;
-    let mut stdout = io::stdout();
-    
-    execute!(stdout, crossterm::cursor::Hide)
-        .map_err(|e| crate::core::error::Error::Custom(e.to_string()))?;
-
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)
+/// Run the resolver; returns `None` if the user cancelled
+pub fn run_merge_conflict_resolver(hunks: Vec<ConflictHunk>) -> Result<Option<Vec<(ConflictHunk, HunkResolution)>>> {
+    // Restores the terminal however the TUI exits
+    let _guard = TerminalGuard::new()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))
         .map_err(|e| crate::core::error::Error::Custom(e.to_string()))?;
 
     let mut state = MergeConflictState::new(hunks);
+    let mut cancelled = false;
 
     loop {
         terminal
@@ -159,6 +153,7 @@ pub fn run_merge_conflict_resolver(hunks: Vec<ConflictHunk>) -> Result<Vec<(Conf
         {
             match key.code {
                 KeyCode::Char('q') | KeyCode::Esc => {
+                    cancelled = true;
                     break;
                 }
                 KeyCode::Up | KeyCode::Char('k') => {
@@ -204,11 +199,10 @@ pub fn run_merge_conflict_resolver(hunks: Vec<ConflictHunk>) -> Result<Vec<(Conf
         }
     }
 
-    disable_raw_mode().map_err(|e| crate::core::error::Error::Custom(e.to_string()))?;
-    execute!(io::stdout(), crossterm::cursor::Show)
-        .map_err(|e| crate::core::error::Error::Custom(e.to_string()))?;
-
-    Ok(state.hunks)
+    if cancelled {
+        return Ok(None);
+    }
+    Ok(Some(state.hunks))
 }
 
 fn ui(f: &mut Frame, state: &MergeConflictState) {
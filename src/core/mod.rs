@@ -1,26 +1,34 @@
+pub mod alias;
+pub mod archive;
 pub mod attributes;
 pub mod auth;
 pub mod bisect;
 pub mod branch;
 pub mod cherry_pick;
 pub mod commit;
+pub mod commit_editor;
 pub mod config;
 pub mod crypto;
 pub mod database;
 pub mod depot;
 pub mod diff;
 pub mod error;
+pub mod file_mode;
 pub mod hash;
 pub mod hooks;
 pub mod ignore;
 pub mod index;
+pub mod log_tui;
 pub mod merge;
+pub mod merge_tui;
 pub mod partial_fetch;
 pub mod rebase;
 pub mod rebase_tui;
+pub mod reflog;
 pub mod repo;
 pub mod reset;
 pub mod resume;
+pub mod sequencer;
 pub mod shallow;
 pub mod sparse;
 pub mod stash;
@@ -30,5 +38,6 @@ pub mod store_manager;
 pub mod tag;
 pub mod temporal;
 pub mod workspace;
+pub mod worktree;
 
 pub use error::{Error, Result};
@@ -1,5 +1,14 @@
-use crate::core::error::Result;
+use std::collections::HashMap;
+
+use crate::core::branch::BranchManager;
+use crate::core::commit::CommitLog;
+use crate::core::commit_editor::run_commit_editor;
+use crate::core::error::{Error, Result};
+use crate::core::index::Index;
+use crate::core::merge::{merge_snapshots, MergeStrategy};
+use crate::core::rebase_tui::{run_interactive_rebase, RebaseAction};
 use crate::core::repo::Repository;
+use crate::core::sequencer::{self, OperationKind, OperationState};
 
 /// Represents a single commit to be rebased
 #[derive(Debug, Clone)]
@@ -45,181 +54,268 @@ pub fn rebase(
         });
     }
 
-    // Get commits on current branch that are not on target
-    let current_commits = get_commits_for_rebase(repo, current_branch)?;
+    let commits = rebase_commits(repo, target_branch)?;
 
-    match strategy {
-        RebaseStrategy::Rebase => {
-            simple_rebase(repo, target_branch, current_branch, current_commits)
-        }
-        RebaseStrategy::Interactive => {
-            interactive_rebase(repo, target_branch, current_branch, current_commits)
-        }
-    }
+    let plan = match strategy {
+        RebaseStrategy::Rebase => commits
+            .into_iter()
+            .map(|commit| (commit, RebaseAction::Pick))
+            .collect(),
+        RebaseStrategy::Interactive if commits.is_empty() => Vec::new(),
+        RebaseStrategy::Interactive => match run_interactive_rebase(commits)? {
+            Some(plan) => plan,
+            None => {
+                return Ok(RebaseResult {
+                    success: false,
+                    applied: 0,
+                    conflicts: vec![],
+                    message: "Rebase cancelled".to_string(),
+                })
+            }
+        },
+    };
+
+    apply_rebase_plan(repo, target_branch, plan)
 }
 
-/// Get commits that need to be rebased
-fn get_commits_for_rebase(repo: &Repository, _branch: &str) -> Result<Vec<RebaseCommit>> {
-    let commits = repo.log()?;
-    let mut rebase_commits = Vec::new();
-
-    // Parse commit log and extract commits for this branch
-    for commit_line in commits.iter() {
-        if commit_line.contains("commit ") {
-            if let Some(hash) = commit_line.split_whitespace().nth(1) {
-                // Simple parsing: extract hash and basic info
-                rebase_commits.push(RebaseCommit {
-                    hash: hash.to_string(),
-                    message: String::new(),
-                    author: String::new(),
-                });
-            }
+/// Commits on the current branch that are not on the target, oldest first
+pub fn rebase_commits(repo: &Repository, target_branch: &str) -> Result<Vec<RebaseCommit>> {
+    let target = target_commit(repo, target_branch)?;
+    let head = repo.head_commit_id()?.ok_or(Error::NoCommits)?;
+
+    let commit_log = CommitLog::new(repo.get_db().clone());
+    let upstream = commit_log.ancestors(&target)?;
+
+    let mut commits = Vec::new();
+    let mut next = Some(head);
+    while let Some(id) = next {
+        if upstream.contains(&id) {
+            break;
         }
+        let commit = commit_log.get_commit(&id)?;
+        next = commit.parent.clone();
+        commits.push(RebaseCommit {
+            hash: commit.id,
+            message: commit.message,
+            author: commit.author,
+        });
     }
 
-    Ok(rebase_commits)
+    commits.reverse();
+    Ok(commits)
 }
 
-/// Simple rebase: apply all commits onto target branch
-fn simple_rebase(
+/// Replay commits onto the target branch, applying each commit's action.
+///
+/// The current branch is only moved once every commit has been applied.
+/// On conflict it is left untouched, the working tree shows the conflicted
+/// result, and the rebase can be resumed with [`continue_rebase`].
+pub fn apply_rebase_plan(
     repo: &Repository,
     target_branch: &str,
-    _current_branch: &str,
-    commits: Vec<RebaseCommit>,
+    plan: Vec<(RebaseCommit, RebaseAction)>,
 ) -> Result<RebaseResult> {
-    if commits.is_empty() {
-        return Ok(RebaseResult {
-            success: true,
-            applied: 0,
-            conflicts: vec![],
-            message: "No commits to rebase".to_string(),
-        });
+    sequencer::ensure_no_operation(repo)?;
+    let current_branch = repo.current_branch()?.ok_or_else(|| {
+        Error::Custom("Cannot rebase with a detached HEAD".to_string())
+    })?;
+    if !Index::new(repo.get_db().clone())?.entries().is_empty() {
+        return Err(Error::Custom(
+            "Cannot rebase with staged changes; commit them first".to_string(),
+        ));
     }
+    repo.ensure_clean_working_tree("rebase")?;
 
-    // Create new commits on top of target branch
-    let mut applied = 0;
-    let mut conflicts = Vec::new();
-
-    for commit in commits.iter() {
-        // In a real implementation, we would:
-        // 1. Get the diff of the commit
-        // 2. Apply it on top of target branch
-        // 3. Create new commit with same message/author
-        // 4. Detect conflicts if patches don't apply cleanly
+    let target = target_commit(repo, target_branch)?;
+    let head = repo.head_commit_id()?.ok_or(Error::NoCommits)?;
+    let commit_log = CommitLog::new(repo.get_db().clone());
 
-        match apply_commit_on_branch(repo, target_branch, commit) {
-            Ok(_) => {
-                applied += 1;
-            }
-            Err(e) => {
-                conflicts.push(format!("Conflict applying {}: {}", commit.hash, e));
+    if commit_log.is_ancestor(&target, &head)? && plan.iter().all(|(_, a)| *a == RebaseAction::Pick) {
+        let up_to_date = match plan.first() {
+            Some((first, _)) => {
+                commit_log.get_commit(&first.hash)?.parent.as_deref() == Some(target.as_str())
             }
+            None => true,
+        };
+        if up_to_date {
+            return Ok(RebaseResult {
+                success: true,
+                applied: 0,
+                conflicts: vec![],
+                message: format!("Current branch {} is up to date", current_branch),
+            });
         }
     }
 
-    let success = conflicts.is_empty();
-    let message = if success {
-        format!("Successfully rebased {} commits onto {}", applied, target_branch)
+    let progress = RebaseProgress {
+        branch: current_branch,
+        target: target_branch.to_string(),
+        orig_head: head.clone(),
+        tip: target,
+        applied: 0,
+    };
+    let plan = plan.into_iter().map(|(commit, action)| (commit.hash, action)).collect();
+    replay(repo, progress, &repo.snapshot(&head)?, plan)
+}
+
+/// Resume a rebase that stopped on conflicts, once they are resolved
+pub fn continue_rebase(repo: &Repository) -> Result<RebaseResult> {
+    let state = OperationState::load_kind(repo, OperationKind::Rebase)?;
+    let files = sequencer::resolved_files(repo, &state)?;
+    let message = if state.edit_message {
+        run_commit_editor(Some(state.message.clone()))?
+            .unwrap_or_else(|| state.message.clone())
     } else {
-        format!(
-            "Rebase partially complete: {} applied, {} conflicts",
-            applied,
-            conflicts.len()
-        )
+        state.message.clone()
     };
+    let tip = sequencer::commit_resolution(repo, &state, &files, message, false)?;
+    sequencer::finish(repo)?;
 
-    Ok(RebaseResult {
-        success,
-        applied,
-        conflicts,
-        message,
-    })
+    let progress = RebaseProgress {
+        branch: state.branch,
+        target: state.target,
+        orig_head: state.orig_head,
+        tip,
+        applied: state.applied,
+    };
+    replay(repo, progress, &files, state.remaining)
 }
 
-/// Interactive rebase with user-specified actions
-fn interactive_rebase(
+/// Where a rebase has got to
+struct RebaseProgress {
+    branch: String,
+    /// Branch being rebased onto
+    target: String,
+    orig_head: String,
+    /// Last commit created so far
+    tip: String,
+    applied: usize,
+}
+
+/// Apply `plan` on top of `progress.tip`, then move the branch and update
+/// the working tree from `worktree`, the files it currently holds
+fn replay(
     repo: &Repository,
-    target_branch: &str,
-    _current_branch: &str,
-    commits: Vec<RebaseCommit>,
+    mut progress: RebaseProgress,
+    worktree: &HashMap<String, String>,
+    plan: Vec<(String, RebaseAction)>,
 ) -> Result<RebaseResult> {
-    // Launch TUI for interactive rebase
-    let commits_with_actions = crate::core::rebase_tui::run_interactive_rebase(commits)?;
-
-    // Execute rebase with selected actions
-    let mut applied = 0;
-    let mut conflicts = Vec::new();
-
-    for (commit, action) in commits_with_actions.iter() {
-        match action {
-            crate::core::rebase_tui::RebaseAction::Pick => {
-                match apply_commit_on_branch(repo, target_branch, commit) {
-                    Ok(_) => applied += 1,
-                    Err(e) => conflicts.push(format!("Conflict applying {}: {}", commit.hash, e)),
-                }
-            }
-            crate::core::rebase_tui::RebaseAction::Squash => {
-                // Squash: apply and mark for squashing
-                match apply_commit_on_branch(repo, target_branch, commit) {
-                    Ok(_) => applied += 1,
-                    Err(e) => conflicts.push(format!("Conflict squashing {}: {}", commit.hash, e)),
-                }
-            }
-            crate::core::rebase_tui::RebaseAction::Reword => {
-                // Reword: apply but message will be edited
-                match apply_commit_on_branch(repo, target_branch, commit) {
-                    Ok(_) => {
-                        applied += 1;
-                        println!("Reword: {}", commit.message);
-                    }
-                    Err(e) => conflicts.push(format!("Conflict rewording {}: {}", commit.hash, e)),
-                }
-            }
-            crate::core::rebase_tui::RebaseAction::Drop => {
-                // Drop: skip this commit
+    let commit_log = CommitLog::new(repo.get_db().clone());
+    let signing_key = repo.default_signing_key()?;
+
+    for (index, (hash, action)) in plan.iter().enumerate() {
+        if *action == RebaseAction::Drop {
+            continue;
+        }
+
+        let original = commit_log.get_commit(hash)?;
+        let base = match &original.parent {
+            Some(parent) => repo.snapshot(parent)?,
+            None => HashMap::new(),
+        };
+        let short = &hash[..7.min(hash.len())];
+        let merged = merge_snapshots(
+            repo,
+            &base,
+            &repo.snapshot(&progress.tip)?,
+            &repo.snapshot(hash)?,
+            &progress.target,
+            short,
+            MergeStrategy::Recursive,
+        )?;
+
+        let squash = *action == RebaseAction::Squash && progress.applied > 0;
+        let (author, message, parent) = if squash {
+            let previous = commit_log.get_commit(&progress.tip)?;
+            (
+                previous.author,
+                format!("{}\n\n{}", previous.message, original.message),
+                previous.parent,
+            )
+        } else {
+            (original.author, original.message, Some(progress.tip.clone()))
+        };
+
+        let modes = repo.carry_modes(&merged.files, &[&progress.tip, hash])?;
+        if !merged.conflicts.is_empty() {
+            repo.update_working_tree(worktree, &merged.files, &modes)?;
+            OperationState {
+                kind: OperationKind::Rebase,
+                branch: progress.branch.clone(),
+                orig_head: progress.orig_head,
+                target: progress.target,
+                files: merged.files,
+                conflicts: merged.conflicts.clone(),
+                author,
+                message,
+                parent,
+                merge_head: None,
+                edit_message: *action == RebaseAction::Reword,
+                remaining: plan[index + 1..].to_vec(),
+                applied: progress.applied + usize::from(!squash),
             }
+            .save(repo)?;
+
+            return Ok(RebaseResult {
+                success: false,
+                applied: progress.applied,
+                conflicts: merged
+                    .conflicts
+                    .iter()
+                    .map(|path| format!("Conflict applying {}: {}", short, path))
+                    .collect(),
+                message: format!(
+                    "Rebase stopped; fix conflicts and run 'mug rebase --continue', \
+                     or 'mug rebase --abort' to leave {} unchanged",
+                    progress.branch
+                ),
+            });
         }
+
+        let tree_hash = repo.store_snapshot_with_modes(&merged.files, &modes)?;
+        let message = if *action == RebaseAction::Reword {
+            run_commit_editor(Some(message.clone()))?
+                .unwrap_or(message)
+        } else {
+            message
+        };
+        if !squash {
+            progress.applied += 1;
+        }
+        progress.tip =
+            commit_log.create_signed_commit(tree_hash, author, message, parent, signing_key.as_ref())?;
     }
 
-    let success = conflicts.is_empty();
-    let message = if success {
-        format!("Successfully rebased {} commits onto {}", applied, target_branch)
-    } else {
-        format!(
-            "Rebase partially complete: {} applied, {} conflicts",
-            applied,
-            conflicts.len()
-        )
-    };
+    let (files, modes) = repo.snapshot_with_modes(&progress.tip)?;
+    repo.update_working_tree(worktree, &files, &modes)?;
+    BranchManager::new(repo.get_db().clone()).update_branch_with_reason(
+        &progress.branch,
+        progress.tip,
+        &format!("rebase: {} onto {}", progress.branch, progress.target),
+    )?;
+    repo.get_db().flush()?;
 
     Ok(RebaseResult {
-        success,
-        applied,
-        conflicts,
-        message,
+        success: true,
+        applied: progress.applied,
+        conflicts: vec![],
+        message: format!(
+            "Successfully rebased {} commits onto {}",
+            progress.applied, progress.target
+        ),
     })
 }
 
-/// Apply a single commit onto a branch
-fn apply_commit_on_branch(
-    _repo: &Repository,
-    _target_branch: &str,
-    commit: &RebaseCommit,
-) -> Result<String> {
-    // In a real implementation:
-    // 1. Get the diff/patch for this commit
-    // 2. Apply patch to target branch state
-    // 3. Create new commit with same message/author
-    // 4. Return new commit hash or error if conflicts
-
-    // For now, return success with placeholder new hash
-    Ok(format!("{}_rebased", &commit.hash[..8.min(commit.hash.len())]))
+fn target_commit(repo: &Repository, target_branch: &str) -> Result<String> {
+    BranchManager::new(repo.get_db().clone())
+        .get_branch(target_branch)?
+        .map(|branch| branch.commit_id)
+        .ok_or_else(|| Error::BranchNotFound(target_branch.to_string()))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::rebase_tui::RebaseAction;
 
     #[test]
     fn test_rebase_result_creation() {
@@ -256,4 +352,140 @@ mod tests {
         ];
         assert_eq!(actions.len(), 4);
     }
+
+    fn diverged_repo() -> (tempfile::TempDir, Repository) {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let commit = |path: &str, content: &str, message: &str| {
+            std::fs::write(dir.path().join(path), content).unwrap();
+            repo.add(path).unwrap();
+            repo.commit("Tester".to_string(), message.to_string()).unwrap();
+        };
+
+        commit("base.txt", "base\n", "Base");
+        repo.create_branch("feature".to_string()).unwrap();
+        commit("main.txt", "main\n", "Main work");
+
+        repo.checkout("feature".to_string()).unwrap();
+        commit("one.txt", "one\n", "First");
+        commit("two.txt", "two\n", "Second");
+        (dir, repo)
+    }
+
+    #[test]
+    fn test_rebase_replays_commits_onto_target() {
+        let (dir, repo) = diverged_repo();
+
+        let result = rebase(&repo, "main", RebaseStrategy::Rebase).unwrap();
+        assert!(result.success);
+        assert_eq!(result.applied, 2);
+
+        let history = repo.history().unwrap();
+        let messages: Vec<_> = history.iter().map(|c| c.message.as_str()).collect();
+        assert_eq!(messages, vec!["Second", "First", "Main work", "Base"]);
+        assert!(dir.path().join("main.txt").exists());
+
+        let again = rebase(&repo, "main", RebaseStrategy::Rebase).unwrap();
+        assert_eq!(again.applied, 0);
+        assert!(again.message.contains("up to date"));
+    }
+
+    #[test]
+    fn test_rebase_plan_squash_and_drop() {
+        let (dir, repo) = diverged_repo();
+        let commits = rebase_commits(&repo, "main").unwrap();
+        assert_eq!(commits.len(), 2);
+
+        let plan = vec![
+            (commits[0].clone(), RebaseAction::Pick),
+            (commits[1].clone(), RebaseAction::Squash),
+        ];
+        let result = apply_rebase_plan(&repo, "main", plan).unwrap();
+        assert_eq!(result.applied, 1);
+
+        let head = repo.history().unwrap().remove(0);
+        assert_eq!(head.message, "First\n\nSecond");
+        let snapshot = repo.snapshot(&head.id).unwrap();
+        assert!(snapshot.contains_key("one.txt") && snapshot.contains_key("two.txt"));
+
+        let plan = vec![(rebase_commits(&repo, "main").unwrap().remove(0), RebaseAction::Drop)];
+        let result = apply_rebase_plan(&repo, "main", plan).unwrap();
+        assert_eq!(result.applied, 0);
+        assert_eq!(repo.history().unwrap()[0].message, "Main work");
+        assert!(!dir.path().join("one.txt").exists());
+    }
+
+    #[test]
+    fn test_rebase_refuses_unstaged_changes() {
+        let (dir, repo) = diverged_repo();
+        let head = repo.head_commit_id().unwrap();
+        std::fs::write(dir.path().join("one.txt"), "one\nlocal\n").unwrap();
+
+        let err = rebase(&repo, "main", RebaseStrategy::Rebase).unwrap_err();
+        assert!(err.to_string().contains("one.txt"), "{}", err);
+        assert_eq!(std::fs::read_to_string(dir.path().join("one.txt")).unwrap(), "one\nlocal\n");
+        assert_eq!(repo.head_commit_id().unwrap(), head);
+    }
+
+    #[test]
+    fn test_rebase_conflict_leaves_branch_unchanged() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let file = dir.path().join("file.txt");
+
+        std::fs::write(&file, "base\n").unwrap();
+        repo.add("file.txt").unwrap();
+        repo.commit("Tester".to_string(), "Base".to_string()).unwrap();
+        repo.create_branch("feature".to_string()).unwrap();
+        std::fs::write(&file, "main\n").unwrap();
+        repo.add("file.txt").unwrap();
+        repo.commit("Tester".to_string(), "Main".to_string()).unwrap();
+
+        repo.checkout("feature".to_string()).unwrap();
+        std::fs::write(&file, "feature\n").unwrap();
+        repo.add("file.txt").unwrap();
+        let before = repo.commit("Tester".to_string(), "Feature".to_string()).unwrap();
+
+        let result = rebase(&repo, "main", RebaseStrategy::Rebase).unwrap();
+        assert!(!result.success);
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(repo.head_commit_id().unwrap(), Some(before));
+    }
+
+    #[test]
+    fn test_rebase_continue_after_resolving() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let commit = |path: &str, content: &str, message: &str| {
+            std::fs::write(dir.path().join(path), content).unwrap();
+            repo.add(path).unwrap();
+            repo.commit("Tester".to_string(), message.to_string()).unwrap()
+        };
+
+        commit("file.txt", "base\n", "Base");
+        repo.create_branch("feature".to_string()).unwrap();
+        commit("file.txt", "main\n", "Main");
+        repo.checkout("feature".to_string()).unwrap();
+        commit("file.txt", "feature\n", "Feature");
+        commit("other.txt", "other\n", "Other");
+
+        let result = rebase(&repo, "main", RebaseStrategy::Rebase).unwrap();
+        assert!(!result.success);
+        let content = std::fs::read_to_string(dir.path().join("file.txt")).unwrap();
+        assert!(content.contains("<<<<<<< main"));
+        assert!(rebase(&repo, "main", RebaseStrategy::Rebase).is_err());
+
+        std::fs::write(dir.path().join("file.txt"), "main and feature\n").unwrap();
+        let result = continue_rebase(&repo).unwrap();
+        assert!(result.success);
+        assert_eq!(result.applied, 2);
+
+        let messages: Vec<_> = repo.history().unwrap().into_iter().map(|c| c.message).collect();
+        assert_eq!(messages, vec!["Other", "Feature", "Main", "Base"]);
+        assert!(dir.path().join("other.txt").exists());
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("file.txt")).unwrap(),
+            "main and feature\n"
+        );
+    }
 }
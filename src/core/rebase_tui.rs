@@ -1,10 +1,7 @@
 use crate::core::error::Result;
 use crate::core::rebase::RebaseCommit;
-use crossterm::{
-    event::{self, Event, KeyCode},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode},
-};
+use crate::ui::terminal::TerminalGuard;
+use crossterm::event::{self, Event, KeyCode};
 use ratatui::{
     backend::CrosstermBackend,
     layout::{Alignment, Constraint, Direction, Layout},
@@ -16,7 +13,7 @@ use ratatui::{
 use std::io;
 
 /// Action for each commit in interactive rebase
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum RebaseAction {
     Pick,
     Squash,
@@ -97,21 +94,15 @@ impl RebaseState {
     }
 }
 
-/// Run interactive rebase TUI
-pub fn run_interactive_rebase(commits: Vec<RebaseCommit>) -> Result<Vec<(RebaseCommit, RebaseAction)>> {
-    // Setup terminal
-    enable_raw_mode().map_err(|e| crate::core::error::Error::Custom(e.to_string()))?;
-    let mut stdout = io::stdout();
-    
-    // Alternative approach without EnterAltScreen/LeaveAltScreen
-    execute!(stdout, crossterm::cursor::Hide)
-        .map_err(|e| crate::core::error::Error::Custom(e.to_string()))?;
-
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)
+/// Run interactive rebase TUI; returns `None` if the user cancelled
+pub fn run_interactive_rebase(commits: Vec<RebaseCommit>) -> Result<Option<Vec<(RebaseCommit, RebaseAction)>>> {
+    // Restores the terminal however the TUI exits
+    let _guard = TerminalGuard::new()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))
         .map_err(|e| crate::core::error::Error::Custom(e.to_string()))?;
 
     let mut state = RebaseState::new(commits);
+    let mut cancelled = false;
 
     loop {
         terminal
@@ -123,6 +114,7 @@ pub fn run_interactive_rebase(commits: Vec<RebaseCommit>) -> Result<Vec<(RebaseC
         {
             match key.code {
                 KeyCode::Char('q') | KeyCode::Esc => {
+                    cancelled = true;
                     break;
                 }
                 KeyCode::Up | KeyCode::Char('k') => {
@@ -157,12 +149,10 @@ pub fn run_interactive_rebase(commits: Vec<RebaseCommit>) -> Result<Vec<(RebaseC
         }
     }
 
-    // Restore terminal
-    disable_raw_mode().map_err(|e| crate::core::error::Error::Custom(e.to_string()))?;
-    execute!(io::stdout(), crossterm::cursor::Show)
-        .map_err(|e| crate::core::error::Error::Custom(e.to_string()))?;
-
-    Ok(state.commits)
+    if cancelled {
+        return Ok(None);
+    }
+    Ok(Some(state.commits))
 }
 
 fn ui(f: &mut Frame, state: &RebaseState) {
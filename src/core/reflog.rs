@@ -0,0 +1,226 @@
+use serde::{Deserialize, Serialize};
+
+use crate::core::database::{MugDb, Transaction};
+use crate::core::error::Result;
+
+const REFLOG_TREE: &str = "REFLOG";
+
+/// A single recorded movement of a reference
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReflogEntry {
+    pub ref_name: String,
+    pub old_hash: String,
+    pub new_hash: String,
+    pub operation: String,
+    pub author: String,
+    pub timestamp: String,
+}
+
+/// Append-only log of reference updates, keyed by ref name
+pub struct Reflog {
+    db: MugDb,
+}
+
+impl Reflog {
+    pub fn new(db: MugDb) -> Self {
+        Reflog { db }
+    }
+
+    /// Record a reference moving from `old_hash` to `new_hash`
+    pub fn record(
+        &self,
+        ref_name: &str,
+        old_hash: &str,
+        new_hash: &str,
+        operation: &str,
+    ) -> Result<()> {
+        let author = self.db.get("config", "user.name")?;
+        let (nanos, serialized) = Self::entry(ref_name, old_hash, new_hash, operation, author)?;
+        let key = Self::free_key(ref_name, nanos, |key| Ok(self.db.get(REFLOG_TREE, key)?.is_some()))?;
+        self.db.set(REFLOG_TREE, key, serialized)
+    }
+
+    /// Record a reference movement as part of a transaction
+    pub fn record_in(
+        &self,
+        tx: &Transaction,
+        ref_name: &str,
+        old_hash: &str,
+        new_hash: &str,
+        operation: &str,
+    ) -> Result<()> {
+        // Plain reads would block on the transaction's lock, so read through `tx`
+        let author = tx.get("config", "user.name")?;
+        let (nanos, serialized) = Self::entry(ref_name, old_hash, new_hash, operation, author)?;
+        let key = Self::free_key(ref_name, nanos, |key| Ok(tx.get(REFLOG_TREE, key)?.is_some()))?;
+        tx.set(REFLOG_TREE, key, serialized)
+    }
+
+    /// Build the timestamp in nanoseconds and serialized value of a new entry
+    fn entry(
+        ref_name: &str,
+        old_hash: &str,
+        new_hash: &str,
+        operation: &str,
+        author: Option<Vec<u8>>,
+    ) -> Result<(i64, Vec<u8>)> {
+        let now = chrono::Local::now();
+        let entry = ReflogEntry {
+            ref_name: ref_name.to_string(),
+            old_hash: old_hash.to_string(),
+            new_hash: new_hash.to_string(),
+            operation: operation.to_string(),
+            author: author
+                .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+                .or_else(|| {
+                    let global = crate::core::config::GlobalConfig::load().ok()?;
+                    global.get("user.name").map(str::to_string)
+                })
+                .unwrap_or_else(|| "MUG User".to_string()),
+            timestamp: now.to_rfc3339(),
+        };
+
+        Ok((now.timestamp_nanos_opt().unwrap_or_default(), serde_json::to_vec(&entry)?))
+    }
+
+    /// The key for an entry recorded at `nanos`, moved later a nanosecond
+    /// at a time while `taken`, so two updates in one clock tick both keep
+    /// their entry. Zero-padded nanoseconds keep the keys of one ref in
+    /// chronological order.
+    fn free_key(ref_name: &str, mut nanos: i64, taken: impl Fn(&str) -> Result<bool>) -> Result<String> {
+        loop {
+            let key = format!("{}\0{:020}", ref_name, nanos);
+            if !taken(&key)? {
+                return Ok(key);
+            }
+            nanos += 1;
+        }
+    }
+
+    /// Get all entries for a ref, newest first
+    pub fn entries(&self, ref_name: &str) -> Result<Vec<ReflogEntry>> {
+        let prefix = format!("{}\0", ref_name);
+        let mut entries: Vec<ReflogEntry> = self
+            .db
+            .scan(REFLOG_TREE, prefix)?
+            .into_iter()
+            .filter_map(|(_, value)| serde_json::from_slice(&value).ok())
+            .collect();
+
+        entries.reverse();
+        Ok(entries)
+    }
+
+    /// Delete entries recorded before `cutoff`, for one ref or all of them.
+    /// Returns how many were removed.
+    pub fn expire(&self, ref_name: Option<&str>, cutoff: chrono::DateTime<chrono::Local>) -> Result<usize> {
+        let prefix = ref_name.map(|name| format!("{}\0", name)).unwrap_or_default();
+        let cutoff = cutoff.timestamp_nanos_opt().unwrap_or_default();
+
+        let mut removed = 0;
+        for (key, _) in self.db.scan(REFLOG_TREE, prefix)? {
+            let recorded = String::from_utf8_lossy(&key)
+                .rsplit('\0')
+                .next()
+                .and_then(|nanos| nanos.parse::<i64>().ok());
+            if recorded.is_some_and(|nanos| nanos < cutoff) {
+                self.db.delete(REFLOG_TREE, &key)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Names of all refs that have reflog entries
+    pub fn refs(&self) -> Result<Vec<String>> {
+        let mut names: Vec<String> = self
+            .db
+            .scan(REFLOG_TREE, "")?
+            .into_iter()
+            .filter_map(|(key, _)| {
+                let key = String::from_utf8_lossy(&key).to_string();
+                key.split('\0').next().map(|s| s.to_string())
+            })
+            .collect();
+
+        names.dedup();
+        Ok(names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_reflog_newest_first() {
+        let dir = TempDir::new().unwrap();
+        let db = MugDb::new(dir.path().join("db")).unwrap();
+        let reflog = Reflog::new(db);
+
+        reflog.record("main", "", "commit1", "commit: first").unwrap();
+        reflog
+            .record("main", "commit1", "commit2", "commit: second")
+            .unwrap();
+
+        let entries = reflog.entries("main").unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].new_hash, "commit2");
+        assert_eq!(entries[0].old_hash, "commit1");
+        assert_eq!(entries[1].operation, "commit: first");
+    }
+
+    #[test]
+    fn test_reflog_is_keyed_by_ref() {
+        let dir = TempDir::new().unwrap();
+        let db = MugDb::new(dir.path().join("db")).unwrap();
+        let reflog = Reflog::new(db);
+
+        reflog.record("main", "", "commit1", "commit").unwrap();
+        reflog.record("dev", "", "commit2", "commit").unwrap();
+
+        assert_eq!(reflog.entries("main").unwrap().len(), 1);
+        assert_eq!(reflog.entries("dev").unwrap().len(), 1);
+        assert_eq!(reflog.refs().unwrap(), vec!["dev", "main"]);
+    }
+
+    #[test]
+    fn test_reflog_keeps_updates_in_one_clock_tick() {
+        let dir = TempDir::new().unwrap();
+        let db = MugDb::new(dir.path().join("db")).unwrap();
+        let reflog = Reflog::new(db.clone());
+
+        let taken = |key: &str| Ok(key.ends_with(&format!("{:020}", 7)));
+        assert_eq!(Reflog::free_key("main", 7, taken).unwrap(), format!("main\0{:020}", 8));
+
+        db.transaction(|tx| {
+            for i in 0..50 {
+                reflog.record_in(tx, "main", "", &format!("commit{}", i), "commit")?;
+            }
+            Ok(())
+        })
+        .unwrap();
+        let entries = reflog.entries("main").unwrap();
+        assert_eq!(entries.len(), 50);
+        assert_eq!(entries[0].new_hash, "commit49");
+    }
+
+    #[test]
+    fn test_reflog_expire_removes_old_entries() {
+        let dir = TempDir::new().unwrap();
+        let db = MugDb::new(dir.path().join("db")).unwrap();
+        let reflog = Reflog::new(db);
+
+        reflog.record("main", "", "commit1", "commit").unwrap();
+        reflog.record("dev", "", "commit2", "commit").unwrap();
+
+        let past = chrono::Local::now() - chrono::Duration::days(1);
+        assert_eq!(reflog.expire(None, past).unwrap(), 0);
+
+        let future = chrono::Local::now() + chrono::Duration::seconds(1);
+        assert_eq!(reflog.expire(Some("main"), future).unwrap(), 1);
+        assert!(reflog.entries("main").unwrap().is_empty());
+        assert_eq!(reflog.entries("dev").unwrap().len(), 1);
+    }
+}
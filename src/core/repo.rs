@@ -1,23 +1,45 @@
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
 
 use walkdir::WalkDir;
 
 use crate::core::branch::BranchManager;
-use crate::core::commit::CommitLog;
+use crate::core::commit::{CommitLog, CommitMetadata};
+use crate::core::config::{self, ConfigEntry, ConfigOrigin, GlobalConfig};
+use crate::core::crypto::{CryptoKey, KeyManager};
 use crate::core::database::MugDb;
+use crate::core::diff::{self, Diff};
 use crate::core::error::{Error, Result};
-use crate::core::hash;
+use crate::core::file_mode::{self, FileModes};
+use crate::core::hash::{self, HashAlgorithm};
+use crate::core::hooks::{HookManager, HookType};
 use crate::core::ignore::IgnoreRules;
 use crate::core::index::Index;
+use crate::core::reflog::Reflog;
+use crate::core::sparse::SparseConfig;
 use crate::core::status::Status;
-use crate::core::store::{ObjectStore, TreeEntry};
-
+use crate::core::store::{blob_matches, check_tree_entries, ObjectStore, TreeEntry};
+use crate::core::store_manager::{LargeFilePointer, StoreConfig, StoreManager};
+use crate::core::tag::TagManager;
+use crate::pack::pack_builder::{DEFAULT_PACK_SIZE, PACK_MANIFEST};
+use crate::pack::PackBuilder;
+use crate::ui::formatter::CommitInfo;
+
+/// Clones share the same database handle, so they can be used side by side
+#[derive(Clone)]
 pub struct Repository {
     root: PathBuf,
     mug_dir: PathBuf,
+    /// Root of the main working tree, whose `.mug` holds the objects and
+    /// database; differs from `root` in a linked worktree
+    common_root: PathBuf,
     db: MugDb,
     store: ObjectStore,
+    /// Where files over the large file threshold go
+    store_config: StoreConfig,
+    /// Whether commit hooks run; `--no-verify` turns them off
+    hooks: bool,
 }
 
 impl Repository {
@@ -27,6 +49,12 @@ impl Repository {
 
     /// Initialize a new MUG repository
     pub fn init<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::init_with_hash(path, HashAlgorithm::default())
+    }
+
+    /// Initialize a new MUG repository whose objects are addressed with
+    /// `algorithm`. The choice is fixed for the life of the repository.
+    pub fn init_with_hash<P: AsRef<Path>>(path: P, algorithm: HashAlgorithm) -> Result<Self> {
         let root = path.as_ref().to_path_buf();
         let mug_dir = root.join(Self::MUG_DIR);
         let objects_dir = root.join(Self::OBJECTS_DIR);
@@ -43,6 +71,7 @@ impl Repository {
         let branch_manager = BranchManager::new(db.clone());
         branch_manager.create_branch("main".to_string(), String::new())?;
         branch_manager.set_head("main".to_string())?;
+        db.set("config", HashAlgorithm::CONFIG_KEY, algorithm.as_str().as_bytes())?;
 
         db.flush()?;
 
@@ -53,10 +82,13 @@ impl Repository {
         }
 
         Ok(Repository {
+            common_root: root.clone(),
             root,
             mug_dir,
             db,
-            store: ObjectStore::new(objects_dir)?,
+            store: ObjectStore::new(objects_dir)?.with_algorithm(algorithm),
+            store_config: StoreConfig::default(),
+            hooks: true,
         })
     }
 
@@ -64,24 +96,165 @@ impl Repository {
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
         let root = path.as_ref().to_path_buf();
         let mug_dir = root.join(Self::MUG_DIR);
-        let objects_dir = root.join(Self::OBJECTS_DIR);
-        let db_dir = root.join(Self::DB_DIR);
 
         if !mug_dir.exists() {
             return Err(Error::NotARepository);
         }
 
-        let db = MugDb::new(db_dir)?;
-        let store = ObjectStore::new(objects_dir)?;
+        // A linked worktree keeps only its link in .mug and shares the rest
+        // with the main working tree
+        let link = crate::core::worktree::read_link(&mug_dir)?;
+        let common_root = match &link {
+            Some(link) => link.common_root.clone(),
+            None => root.clone(),
+        };
+        if !common_root.join(Self::DB_DIR).exists() {
+            return Err(Error::NotARepository);
+        }
+        let objects_dir = common_root.join(Self::OBJECTS_DIR);
+
+        let db = MugDb::new(common_root.join(Self::DB_DIR))?
+            .for_worktree(link.as_ref().map(|link| link.name.as_str()));
+        // Repositories from before core.hashAlgorithm existed are SHA-256
+        let algorithm = match db.get("config", HashAlgorithm::CONFIG_KEY)? {
+            Some(value) => String::from_utf8_lossy(&value).parse()?,
+            None => HashAlgorithm::default(),
+        };
+        let store = ObjectStore::new(objects_dir)?.with_algorithm(algorithm);
+        let store_config = Self::load_store_config(&db)?;
 
         Ok(Repository {
             root,
             mug_dir,
+            common_root,
             db,
             store,
+            store_config,
+            hooks: true,
         })
     }
 
+    /// Open the repository containing `start`, searching up through its
+    /// parent directories for a `.mug` directory
+    pub fn discover<P: AsRef<Path>>(start: P) -> Result<Self> {
+        let start = fs::canonicalize(start.as_ref())?;
+        let root = start
+            .ancestors()
+            .find(|dir| Self::is_repo(dir))
+            .ok_or(Error::NotARepository)?;
+        Self::open(root)
+    }
+
+    /// Turn a path given relative to the current directory into the
+    /// repository-relative form used by the index
+    pub fn relative_path(&self, path: &str) -> Result<String> {
+        let root = fs::canonicalize(&self.root)?;
+        let mut full = fs::canonicalize(std::env::current_dir()?)?;
+        for component in Path::new(path).components() {
+            match component {
+                std::path::Component::CurDir => {}
+                std::path::Component::ParentDir => {
+                    full.pop();
+                }
+                other => full.push(other),
+            }
+        }
+
+        let relative = full
+            .strip_prefix(&root)
+            .map_err(|_| Error::Custom(format!("{} is outside the repository", path)))?;
+        let parts: Vec<_> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect();
+        Ok(if parts.is_empty() { ".".to_string() } else { parts.join("/") })
+    }
+
+    /// A handle on the linked worktree `name` checked out at `root`,
+    /// sharing this handle's database
+    pub(crate) fn linked(&self, root: &Path, name: Option<&str>) -> Self {
+        let mut repo = self.clone();
+        repo.root = root.to_path_buf();
+        repo.mug_dir = root.join(Self::MUG_DIR);
+        repo.db = self.db.for_worktree(name);
+        repo
+    }
+
+    /// Enable or disable the pre-/post-commit and pre-/post-push hooks run
+    /// by this handle
+    pub fn with_hooks(mut self, enabled: bool) -> Self {
+        self.hooks = enabled;
+        self
+    }
+
+    /// Large file settings saved in the repository config, over the defaults
+    fn load_store_config(db: &MugDb) -> Result<StoreConfig> {
+        let get = |key: &str| -> Result<Option<String>> {
+            Ok(db
+                .get("config", key)?
+                .map(|value| String::from_utf8_lossy(&value).to_string()))
+        };
+        let bytes = |key: &str, value: String| {
+            value
+                .parse::<usize>()
+                .map_err(|_| Error::Custom(format!("Invalid {}: {}", key, value)))
+        };
+
+        let mut config = StoreConfig {
+            central_server: get(StoreConfig::SERVER_KEY)?,
+            ..StoreConfig::default()
+        };
+        if let Some(value) = get(StoreConfig::THRESHOLD_KEY)? {
+            config.large_file_threshold_bytes = bytes(StoreConfig::THRESHOLD_KEY, value)?;
+        }
+        if let Some(value) = get(StoreConfig::CACHE_LIMIT_KEY)? {
+            config.cache_size_bytes = bytes(StoreConfig::CACHE_LIMIT_KEY, value)?;
+        }
+        Ok(config)
+    }
+
+    /// Use `config` for large file storage
+    pub fn with_store_config(mut self, config: StoreConfig) -> Self {
+        self.store_config = config;
+        self
+    }
+
+    /// The large file store for this repository, with its cache under the
+    /// repository root
+    pub fn store_manager(&self) -> StoreManager {
+        StoreManager::for_repo(&self.common_root, self.store_config.clone())
+    }
+
+    /// The algorithm object ids are computed with
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.store.algorithm()
+    }
+
+    /// A handle addressing objects with `algorithm`, recording it as the
+    /// repository's own. Only allowed before the first commit, so a fresh
+    /// clone can match its remote.
+    pub fn adopt_hash_algorithm(&self, algorithm: HashAlgorithm) -> Result<Self> {
+        if algorithm == self.hash_algorithm() {
+            return Ok(self.clone());
+        }
+        if !self.db.commits_tree().is_empty() {
+            return Err(Error::Custom(format!(
+                "{} is fixed once a repository has commits",
+                HashAlgorithm::CONFIG_KEY
+            )));
+        }
+        self.db
+            .set("config", HashAlgorithm::CONFIG_KEY, algorithm.as_str().as_bytes())?;
+        let mut repo = self.clone();
+        repo.store = repo.store.with_algorithm(algorithm);
+        Ok(repo)
+    }
+
+    /// Whether hooks run for operations on this handle
+    pub fn hooks_enabled(&self) -> bool {
+        self.hooks
+    }
+
     /// Check if a repository exists at path
     pub fn is_repo<P: AsRef<Path>>(path: P) -> bool {
         path.as_ref().join(Self::MUG_DIR).exists()
@@ -90,15 +263,21 @@ impl Repository {
     /// Stage a file
     pub fn add(&self, path: &str) -> Result<()> {
         let file_path = self.root.join(path);
-        if !file_path.exists() {
+        if !file_mode::exists(&file_path) {
             return Err(Error::Custom(format!("File not found: {}", path)));
         }
+        self.check_case_collisions(&[path])?;
 
-        let hash = hash::hash_file(&file_path)?;
-        self.store.store_file(&file_path)?;
+        let (content, mode) = file_mode::read(&file_path)?;
+        let mut manager = self.store_manager();
+        let hash = if manager.is_large(content.len()) {
+            self.store_large_file(&mut manager, &content)?
+        } else {
+            self.store.store_blob(&content)?
+        };
 
         let mut index = Index::new(self.db.clone())?;
-        index.add(path.to_string(), hash)?;
+        index.add_with_mode(path.to_string(), hash, mode)?;
 
         Ok(())
     }
@@ -116,11 +295,16 @@ impl Repository {
             .map(|e| e.path)
             .collect();
 
-        // Collect all file paths first
+        // Collect all file paths first, pruning ignored directories
+        let ignore_rules = self.ignore_rules()?;
         let files: Vec<_> = WalkDir::new(&self.root)
             .into_iter()
+            .filter_entry(|e| {
+                let rel_path = e.path().strip_prefix(&self.root).unwrap_or(e.path());
+                !e.file_type().is_dir() || !ignore_rules.should_ignore_dir(&rel_path.to_string_lossy())
+            })
             .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_file())
+            .filter(|e| e.file_type().is_file() || e.file_type().is_symlink())
             .filter(|e| !e.path().to_string_lossy().contains(".mug"))
             .filter_map(|e| {
                 let path = e.path();
@@ -130,20 +314,29 @@ impl Repository {
                     None
                 }
             })
+            .filter(|(_, rel_path)| !ignore_rules.should_ignore(rel_path))
             .collect();
 
-        // Process files in parallel
+        let paths: Vec<&str> = files.iter().map(|(_, path)| path.as_str()).collect();
+        self.check_case_collisions(&paths)?;
+
+        // Process files in parallel; large files are uploaded one at a time
+        // afterwards
+        let mut manager = self.store_manager();
         let file_entries: Result<Vec<_>> = files
             .par_iter()
             .map(|(path, path_str)| {
                 // Read file once and use for both hashing and storing
-                let content = std::fs::read(path)?;
-                let hash = hash::hash_bytes(&content);
-                self.store.store_blob(&content)?;
+                let (content, mode) = file_mode::read(path)?;
+                let hash = if manager.is_large(content.len()) {
+                    None
+                } else {
+                    Some(self.store.store_blob(&content)?)
+                };
                 
                 // Check if this is a new file
                 let is_new = !existing_paths.contains(path_str);
-                Ok((path_str.clone(), hash, is_new))
+                Ok((path_str.clone(), hash, mode, is_new))
             })
             .collect();
 
@@ -153,8 +346,15 @@ impl Repository {
         let mut mut_index = Index::new(self.db.clone())?;
         let mut added_count = 0;
         
-        for (path_str, hash, is_new) in entries {
-            mut_index.add(path_str, hash)?;
+        for (path_str, hash, mode, is_new) in entries {
+            let hash = match hash {
+                Some(hash) => hash,
+                None => {
+                    let content = fs::read(self.root.join(&path_str))?;
+                    self.store_large_file(&mut manager, &content)?
+                }
+            };
+            mut_index.add_with_mode(path_str, hash, mode)?;
             if is_new {
                 added_count += 1;
             }
@@ -166,6 +366,69 @@ impl Repository {
         Ok(added_count)
     }
 
+    /// Upload a large file to the central server and store a pointer to it
+    fn store_large_file(&self, manager: &mut StoreManager, content: &[u8]) -> Result<String> {
+        let hash = self.store.algorithm().hash_blob(content);
+        manager.store_large(&hash, content)?;
+        self.store.store_pointer(&LargeFilePointer {
+            hash: hash.clone(),
+            size: content.len() as u64,
+        })?;
+        Ok(hash)
+    }
+
+    /// Untracked paths among `paths` that differ only in case from a
+    /// tracked path or an earlier path in the list, each paired with the
+    /// path it collides with
+    pub fn case_collisions(&self, paths: &[&str]) -> Result<Vec<(String, String)>> {
+        let tracked = self.tracked_files()?;
+        let mut seen: HashMap<String, String> = tracked
+            .keys()
+            .map(|path| (path.to_lowercase(), path.clone()))
+            .collect();
+        let mut collisions = Vec::new();
+        for path in paths.iter().filter(|path| !tracked.contains_key(**path)) {
+            match seen.get(&path.to_lowercase()) {
+                Some(existing) if existing != path => {
+                    collisions.push((path.to_string(), existing.clone()))
+                }
+                Some(_) => {}
+                None => {
+                    seen.insert(path.to_lowercase(), path.to_string());
+                }
+            }
+        }
+        Ok(collisions)
+    }
+
+    /// Refuse to stage paths that collide by case when `core.ignorecase` is
+    /// set, as both could never be checked out; otherwise warn. Unset, it
+    /// defaults to on for macOS and Windows.
+    fn check_case_collisions(&self, paths: &[&str]) -> Result<()> {
+        let collisions = self.case_collisions(paths)?;
+        if collisions.is_empty() {
+            return Ok(());
+        }
+
+        let ignore_case = self
+            .get_config_bool("core.ignorecase")?
+            .unwrap_or(cfg!(any(target_os = "macos", windows)));
+        let lines: Vec<String> = collisions
+            .iter()
+            .map(|(path, existing)| format!("{} collides with {}", path, existing))
+            .collect();
+        if ignore_case {
+            return Err(Error::Custom(format!(
+                "Paths differ only in case (core.ignorecase is on):\n  {}",
+                lines.join("\n  ")
+            )));
+        }
+        for line in lines {
+            eprintln!("warning: {}; they will clash on case-insensitive filesystems", line);
+        }
+        Ok(())
+    }
+
     /// Remove a file from staging
     pub fn remove(&self, path: &str) -> Result<()> {
         let mut index = Index::new(self.db.clone())?;
@@ -173,132 +436,819 @@ impl Repository {
         Ok(())
     }
 
+    /// Ignore rules from `core.excludesfile` and every .mugignore in the repo
+    pub fn ignore_rules(&self) -> Result<IgnoreRules> {
+        let global = self.get_config("core.excludesfile")?.map(|path| {
+            match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+                (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+                _ => self.root.join(path),
+            }
+        });
+        IgnoreRules::load(&self.root, global.as_deref())
+    }
+
+    /// Files tracked at HEAD with staged changes applied (path -> blob hash)
+    pub fn tracked_files(&self) -> Result<HashMap<String, String>> {
+        let mut files = match self.head_commit_id()? {
+            Some(id) => self.snapshot(&id)?,
+            None => HashMap::new(),
+        };
+        for entry in Index::new(self.db.clone())?.entries() {
+            if entry.is_removal() {
+                files.remove(&entry.path);
+            } else {
+                files.insert(entry.path, entry.hash);
+            }
+        }
+        Ok(files)
+    }
+
     /// Get repository status
     pub fn status(&self) -> Result<Status> {
         let index = Index::new(self.db.clone())?;
-        Status::from_index_and_wd(&index, &self.root)
+        let mut head = match self.head_commit_id()? {
+            Some(id) => self.snapshot(&id)?,
+            None => HashMap::new(),
+        };
+        // Files a sparse checkout leaves off disk are not deletions
+        let materialized = self.materialized()?;
+        head.retain(|path, _| materialized(path) || self.root.join(path).exists());
+        let mut status = Status::from_head_index_and_wd(
+            head,
+            &index,
+            &self.root,
+            self.ignore_rules()?,
+            self.hash_algorithm(),
+        )?;
+        status.detect_renames(self.rename_threshold()?, |hash| {
+            self.store.get_blob(hash).ok().map(|blob| blob.content)
+        });
+        Ok(status)
+    }
+
+    /// Refuse to start `operation` while tracked files have unstaged
+    /// changes, which writing its results over them would lose
+    pub fn ensure_clean_working_tree(&self, operation: &str) -> Result<()> {
+        let modified = self.status()?.report("").modified;
+        if modified.is_empty() {
+            return Ok(());
+        }
+        Err(Error::Custom(format!(
+            "Cannot {} with unstaged changes; commit or stash them first:\n  {}",
+            operation,
+            modified.join("\n  ")
+        )))
     }
 
-    /// Create a commit
+    /// Create a commit, signing it when `commit.gpgsign` is enabled
     pub fn commit(&self, author: String, message: String) -> Result<String> {
+        let signing_key = self.default_signing_key()?;
+        self.create_commit_from_index(author, message, signing_key, false)
+    }
+
+    /// Replace the HEAD commit with one on the same parent, taking the
+    /// HEAD tree with the staged changes applied. Keeps the old message
+    /// when `message` is `None`.
+    pub fn amend(&self, author: String, message: Option<String>) -> Result<String> {
+        let head = self
+            .head_commit_id()?
+            .ok_or_else(|| Error::Custom("Cannot amend: there are no commits yet".to_string()))?;
+        let message = match message {
+            Some(message) => message,
+            None => CommitLog::new(self.db.clone()).get_commit(&head)?.message,
+        };
+        let signing_key = self.default_signing_key()?;
+        self.create_commit_from_index(author, message, signing_key, true)
+    }
+
+    /// Create a commit, signing it with the current key when `sign` is set
+    pub fn commit_with_signing(&self, author: String, message: String, sign: bool) -> Result<String> {
+        let signing_key = if sign {
+            let key = KeyManager::new(self.db.clone()).current()?.ok_or_else(|| {
+                Error::Custom(
+                    "Commit signing requested but no signing key is configured".to_string(),
+                )
+            })?;
+            Some(key)
+        } else {
+            None
+        };
+        self.create_commit_from_index(author, message, signing_key, false)
+    }
+
+    /// The key commits should be signed with when `commit.gpgsign` is enabled
+    pub fn default_signing_key(&self) -> Result<Option<CryptoKey>> {
+        if self.get_config_bool("commit.gpgsign")? != Some(true) {
+            return Ok(None);
+        }
+        KeyManager::new(self.db.clone()).current()?.map(Some).ok_or_else(|| {
+            Error::Custom("commit.gpgsign is set but no signing key is configured".to_string())
+        })
+    }
+
+    fn create_commit_from_index(
+        &self,
+        author: String,
+        message: String,
+        signing_key: Option<CryptoKey>,
+        amend: bool,
+    ) -> Result<String> {
         let index = Index::new(self.db.clone())?;
 
-        if index.is_empty() {
+        if index.is_empty() && !amend {
             return Err(Error::Custom(
                 "Nothing to commit. Stage files with 'mug add'.".to_string(),
             ));
         }
 
-        // Build tree from index entries
-        let mut tree_entries = Vec::new();
+        // pre-commit hooks get the message file and staged paths, and can abort
+        let hooks = self.hooks.then(|| HookManager::new(self.common_root())).transpose()?;
+        if let Some(hooks) = &hooks {
+            let message_path = self.mug_dir.join("COMMIT_EDITMSG");
+            fs::write(&message_path, &message)?;
+            let mut hook_args = vec![message_path.to_string_lossy().to_string()];
+            hook_args.extend(index.paths());
+            let hook_args: Vec<&str> = hook_args.iter().map(String::as_str).collect();
+            hooks.trigger_strict(HookType::PreCommit, &hook_args)?;
+        }
+
+        // Get parent commit; amending reuses the parents of the commit it replaces
+        let branch_manager = BranchManager::new(self.db.clone());
+        let current_branch = branch_manager.get_head()?;
+        let head_commit_id = self.head_commit_id()?;
+        let (parent_commit_id, merge_parent) = match (&head_commit_id, amend) {
+            (Some(head), true) => {
+                let replaced = CommitLog::new(self.db.clone()).get_commit(head)?;
+                (replaced.parent, replaced.merge_parent)
+            }
+            (None, true) => return Err(Error::NoCommits),
+            (head, false) => (head.clone(), None),
+        };
+
+        // Build tree from the HEAD snapshot with staged entries applied on top
+        let (mut snapshot, mut modes) = match &head_commit_id {
+            Some(head_id) => self.snapshot_with_modes(head_id)?,
+            None => (HashMap::new(), FileModes::new()),
+        };
         for entry in index.entries() {
-            tree_entries.push(TreeEntry {
-                name: entry.path,
-                hash: entry.hash,
-                is_dir: false,
-            });
+            modes.remove(&entry.path);
+            if entry.is_removal() {
+                snapshot.remove(&entry.path);
+            } else {
+                if entry.mode != file_mode::REGULAR {
+                    modes.insert(entry.path.clone(), entry.mode);
+                }
+                snapshot.insert(entry.path, entry.hash);
+            }
+        }
+        let tree_hash = self.store_snapshot_with_modes(&snapshot, &modes)?;
+
+        let commit = CommitLog::prepare_commit(
+            tree_hash,
+            author,
+            message.clone(),
+            parent_commit_id,
+            merge_parent,
+            signing_key.as_ref(),
+        )?;
+        let commit_id = commit.id.clone();
+        let serialized = serde_json::to_vec(&commit)?;
+        let staged: Vec<String> = index.entries().into_iter().map(|e| e.path).collect();
+
+        // Store the commit, move the branch and clear the staging area together
+        self.db.transaction(|tx| {
+            tx.set("COMMITS", &commit_id, &serialized)?;
+
+            let summary = message.lines().next().unwrap_or("");
+            let operation = if amend {
+                format!("commit (amend): {}", summary)
+            } else {
+                format!("commit: {}", summary)
+            };
+            match current_branch.as_deref() {
+                // A detached HEAD moves to the new commit directly
+                Some(head) if head.starts_with("detached:") => {
+                    tx.set("HEAD", "HEAD", format!("detached:{}", commit_id))?;
+                    Reflog::new(self.db.clone()).record_in(
+                        tx,
+                        "HEAD",
+                        head_commit_id.as_deref().unwrap_or_default(),
+                        &commit_id,
+                        &operation,
+                    )?;
+                }
+                Some(branch_name) => {
+                    branch_manager.update_branch_in(
+                        tx,
+                        branch_name,
+                        commit_id.clone(),
+                        &operation,
+                    )?;
+                }
+                None => {}
+            }
+
+            for path in &staged {
+                tx.delete("INDEX", path)?;
+            }
+            Ok(())
+        })?;
+
+        self.db.flush()?;
+
+        // The commit is made; a failing post-commit hook only warns
+        if let Some(hooks) = &hooks {
+            hooks.trigger(HookType::PostCommit, &[&commit_id])?;
         }
 
-        let tree_hash = self.store.store_tree(tree_entries)?;
+        Ok(commit_id)
+    }
 
-        // Get parent commit
+    /// Commit id HEAD points at, following the current branch or a detached HEAD
+    pub fn head_commit_id(&self) -> Result<Option<String>> {
         let branch_manager = BranchManager::new(self.db.clone());
-        let current_branch = branch_manager.get_head()?;
+        let head = match branch_manager.get_head()? {
+            Some(head) => head,
+            None => return Ok(None),
+        };
 
-        let parent_commit_id = if let Some(ref branch_name) = current_branch {
-            if let Some(branch) = branch_manager.get_branch(branch_name)? {
-                if !branch.commit_id.is_empty() {
-                    Some(branch.commit_id)
-                } else {
-                    None
+        if let Some(commit_id) = head.strip_prefix("detached:") {
+            return Ok(Some(commit_id.to_string()));
+        }
+
+        Ok(branch_manager
+            .get_branch(&head)?
+            .map(|b| b.commit_id)
+            .filter(|id| !id.is_empty()))
+    }
+
+    /// Full file snapshot (path -> blob hash) recorded by a commit
+    pub fn snapshot(&self, commit_id: &str) -> Result<HashMap<String, String>> {
+        Ok(self.snapshot_with_modes(commit_id)?.0)
+    }
+
+    /// Files recorded by a commit along with the modes of those that are
+    /// not regular files
+    pub fn snapshot_with_modes(&self, commit_id: &str) -> Result<(HashMap<String, String>, FileModes)> {
+        let commit = CommitLog::new(self.db.clone()).get_commit(commit_id)?;
+        let mut files = HashMap::new();
+        let mut modes = FileModes::new();
+        self.flatten_tree(&commit.tree_hash, "", &mut files, &mut modes)?;
+        Ok((files, modes))
+    }
+
+    /// Modes of the files in a commit that are not regular files
+    pub fn file_modes(&self, commit_id: &str) -> Result<FileModes> {
+        Ok(self.snapshot_with_modes(commit_id)?.1)
+    }
+
+    /// Modes for the files of `files`, a snapshot built from other commits
+    /// such as a merge result: each file keeps its mode from the first of
+    /// `sources` that has the same content at that path
+    pub fn carry_modes(&self, files: &HashMap<String, String>, sources: &[&str]) -> Result<FileModes> {
+        let mut modes = FileModes::new();
+        let mut decided = std::collections::HashSet::new();
+        for source in sources.iter().filter(|id| !id.is_empty()) {
+            let (source_files, source_modes) = self.snapshot_with_modes(source)?;
+            for (path, hash) in files {
+                if source_files.get(path) == Some(hash) && decided.insert(path.clone()) {
+                    if let Some(&mode) = source_modes.get(path) {
+                        modes.insert(path.clone(), mode);
+                    }
                 }
+            }
+        }
+        Ok(modes)
+    }
+
+    /// Collect the files of a tree and its subtrees under `prefix`
+    fn flatten_tree(
+        &self,
+        tree_hash: &str,
+        prefix: &str,
+        files: &mut HashMap<String, String>,
+        modes: &mut FileModes,
+    ) -> Result<()> {
+        let tree = self.store.get_tree(tree_hash)?;
+        check_tree_entries(&tree.entries)?;
+        for entry in tree.entries {
+            let path = format!("{}{}", prefix, entry.name);
+            if entry.is_dir {
+                self.flatten_tree(&entry.hash, &format!("{}/", path), files, modes)?;
             } else {
-                None
+                if entry.mode != file_mode::REGULAR {
+                    modes.insert(path.clone(), entry.mode);
+                }
+                files.insert(path, entry.hash);
+            }
+        }
+        Ok(())
+    }
+
+    /// Store a file snapshot as nested trees, one per directory, and return
+    /// the root tree hash
+    pub fn store_snapshot(&self, snapshot: &HashMap<String, String>) -> Result<String> {
+        self.store_snapshot_with_modes(snapshot, &FileModes::new())
+    }
+
+    /// Store a file snapshot whose files not in `modes` are regular files
+    pub fn store_snapshot_with_modes(
+        &self,
+        snapshot: &HashMap<String, String>,
+        modes: &FileModes,
+    ) -> Result<String> {
+        let files: Vec<(&str, &str, u32)> = snapshot
+            .iter()
+            .map(|(path, hash)| {
+                let mode = modes.get(path).copied().unwrap_or(file_mode::REGULAR);
+                (path.as_str(), hash.as_str(), mode)
+            })
+            .collect();
+        self.store_dir(files)
+    }
+
+    fn store_dir(&self, files: Vec<(&str, &str, u32)>) -> Result<String> {
+        let mut subdirs: BTreeMap<&str, Vec<(&str, &str, u32)>> = BTreeMap::new();
+        let mut tree_entries = Vec::new();
+
+        for (path, hash, mode) in files {
+            match path.split_once('/') {
+                Some((dir, rest)) => subdirs.entry(dir).or_default().push((rest, hash, mode)),
+                None => tree_entries.push(TreeEntry {
+                    name: path.to_string(),
+                    hash: hash.to_string(),
+                    is_dir: false,
+                    mode,
+                }),
+            }
+        }
+        for (dir, files) in subdirs {
+            tree_entries.push(TreeEntry {
+                name: dir.to_string(),
+                hash: self.store_dir(files)?,
+                is_dir: true,
+                mode: file_mode::REGULAR,
+            });
+        }
+
+        tree_entries.sort_by(|a, b| a.name.cmp(&b.name));
+        self.store.store_tree(tree_entries)
+    }
+
+    /// Rename similarity threshold from `diff.renameThreshold` (e.g. `60%`)
+    pub fn rename_threshold(&self) -> Result<f32> {
+        match self.get_config("diff.renameThreshold")? {
+            Some(value) => diff::parse_rename_threshold(&value).ok_or_else(|| {
+                Error::Custom(format!("Invalid diff.renameThreshold: {}", value))
+            }),
+            None => Ok(diff::DEFAULT_RENAME_THRESHOLD),
+        }
+    }
+
+    /// Compare two snapshots, reporting delete/add pairs as renames
+    pub fn diff_snapshots(
+        &self,
+        old: &HashMap<String, String>,
+        new: &HashMap<String, String>,
+    ) -> Result<Vec<Diff>> {
+        let threshold = self.rename_threshold()?;
+        Ok(diff::detect_renames(
+            diff::diff_snapshots(old, new),
+            threshold,
+            |hash| self.store.get_blob(hash).ok().map(|blob| blob.content),
+        ))
+    }
+
+    /// Fill in the lines added and removed by each diff from the stored blobs
+    pub fn count_diff_lines(&self, diffs: &mut [Diff]) -> Result<()> {
+        let content = |hash: &str| -> Result<Vec<u8>> {
+            if hash.is_empty() {
+                Ok(Vec::new())
+            } else {
+                Ok(self.store.get_blob(hash)?.content)
             }
-        } else {
-            None
         };
+        for diff in diffs {
+            if diff.old_hash == diff.new_hash {
+                continue;
+            }
+            let (old, new) = (content(&diff.old_hash)?, content(&diff.new_hash)?);
+            // Binaries don't count towards insertions and deletions
+            diff.binary = diff::is_binary(&old) || diff::is_binary(&new);
+            if !diff.binary {
+                (diff.lines_added, diff.lines_removed) = diff::count_lines(&old, &new);
+            }
+        }
+        Ok(())
+    }
 
-        // Create commit
-        let commit_log = CommitLog::new(self.db.clone());
-        let commit_id = commit_log.create_commit(tree_hash, author, message, parent_commit_id)?;
+    /// Move the working directory from one snapshot to another, writing
+    /// changed files and removing files that are no longer tracked. Files
+    /// in `modes` are written with that mode, such as symlinks.
+    ///
+    /// Refuses, before writing anything, when a file it would change has
+    /// staged or unstaged local changes.
+    pub fn update_working_tree(
+        &self,
+        from: &HashMap<String, String>,
+        to: &HashMap<String, String>,
+        modes: &FileModes,
+    ) -> Result<()> {
+        let conflicts = self.checkout_conflicts(from, to)?;
+        if !conflicts.is_empty() {
+            return Err(Error::Custom(format!(
+                "Your local changes would be overwritten:\n  {}\nCommit or stash them first.",
+                conflicts.join("\n  ")
+            )));
+        }
+        self.overwrite_working_tree(from, to, modes)
+    }
 
-        // Update branch reference
-        if let Some(branch_name) = current_branch {
-            branch_manager.update_branch(&branch_name, commit_id.clone())?;
+    /// [`Repository::update_working_tree`] without the local changes check,
+    /// for operations that discard them on purpose such as `reset --hard`
+    pub fn overwrite_working_tree(
+        &self,
+        from: &HashMap<String, String>,
+        to: &HashMap<String, String>,
+        modes: &FileModes,
+    ) -> Result<()> {
+        // Trees may come from a remote, so check every path before touching
+        // the disk
+        for path in from.keys().chain(to.keys()) {
+            check_tree_path(path)?;
+        }
+        // A file cannot also be the directory of another file, as with a
+        // symlink `a` and a file `a/b` that would be written through it
+        for path in to.keys() {
+            let mut parent = path.as_str();
+            while let Some((dir, _)) = parent.rsplit_once('/') {
+                if to.contains_key(dir) {
+                    return Err(Error::Custom(format!(
+                        "'{}' would be written inside the file '{}'",
+                        path, dir
+                    )));
+                }
+                parent = dir;
+            }
         }
 
-        // Clear staging area
-        let mut index = Index::new(self.db.clone())?;
-        index.clear()?;
+        for path in from.keys() {
+            if !to.contains_key(path) {
+                file_mode::check_no_symlink_parents(&self.root, path)?;
+                let file_path = self.root.join(path);
+                if file_mode::exists(&file_path) {
+                    fs::remove_file(&file_path)?;
+                }
+            }
+        }
 
-        self.db.flush()?;
+        let materialized = self.materialized()?;
+        let mut large_files = None;
+        for (path, hash) in to {
+            file_mode::check_no_symlink_parents(&self.root, path)?;
+            let file_path = self.root.join(path);
+            let mode = modes.get(path).copied().unwrap_or(file_mode::REGULAR);
+            if from.get(path) == Some(hash) && file_mode::exists(&file_path) {
+                // Same content, but the executable bit may have changed
+                file_mode::set_mode(&file_path, mode)?;
+                continue;
+            }
+            // Paths outside a sparse checkout stay tracked but off disk
+            if !materialized(path) {
+                if file_mode::exists(&file_path) {
+                    fs::remove_file(&file_path)?;
+                }
+                continue;
+            }
+            file_mode::write(&file_path, &self.file_content(hash, &mut large_files)?, mode)?;
+        }
 
-        Ok(commit_id)
+        Ok(())
+    }
+
+    /// Whether a path belongs in the working tree, as limited by the
+    /// sparse checkout patterns if any are set
+    pub fn materialized(&self) -> Result<impl Fn(&str) -> bool> {
+        let sparse = SparseConfig::load(self)?;
+        Ok(move |path: &str| sparse.as_ref().is_none_or(|sparse| sparse.matches(path)))
+    }
+
+    /// A tracked file's content, fetching it from the large file store if
+    /// the object store only has a pointer. `large_files` holds the store
+    /// manager once one has been needed.
+    pub fn file_content(
+        &self,
+        hash: &str,
+        large_files: &mut Option<StoreManager>,
+    ) -> Result<Vec<u8>> {
+        let blob = self.store.get_blob(hash)?;
+        match LargeFilePointer::parse(&blob.content) {
+            Some(pointer) if pointer.hash == hash => large_files
+                .get_or_insert_with(|| self.store_manager())
+                .fetch_large(hash),
+            _ => Ok(blob.content),
+        }
+    }
+
+    /// Get commit history from HEAD, newest first
+    pub fn history(&self) -> Result<Vec<CommitMetadata>> {
+        let head = self.head_commit_id()?.ok_or(Error::NoCommits)?;
+        CommitLog::new(self.db.clone()).history(head)
     }
 
     /// Get commit log
     pub fn log(&self) -> Result<Vec<String>> {
-        let branch_manager = BranchManager::new(self.db.clone());
-        let commit_log = CommitLog::new(self.db.clone());
+        let history = self.history()?;
+        Ok(history.iter().map(format_log_entry).collect())
+    }
+
+    /// Commit history from HEAD as structured entries, newest first
+    pub fn log_structured(&self) -> Result<Vec<CommitInfo>> {
+        let branch = self.current_branch()?;
+        Ok(self
+            .history()?
+            .into_iter()
+            .enumerate()
+            .map(|(i, commit)| CommitInfo {
+                parents: commit.parents(),
+                hash: commit.id,
+                author: commit.author,
+                date: commit.timestamp.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                message: commit.message,
+                is_head: i == 0,
+                branch: if i == 0 { branch.clone() } else { None },
+                signature: None,
+            })
+            .collect())
+    }
+
+    /// Resolve a revision such as `HEAD`, `HEAD~2`, `HEAD^`, `main~1`, a tag
+    /// or a commit id prefix to a full commit id
+    pub fn resolve_rev(&self, spec: &str) -> Result<String> {
+        let split = spec.find(['~', '^']).unwrap_or(spec.len());
+        let (base, mut suffix) = spec.split_at(split);
+
+        let branch_name = base.strip_prefix("refs/heads/").unwrap_or(base);
+        let tag_name = base.strip_prefix("refs/tags/").unwrap_or(base);
+        let reflog_entry = base.strip_suffix('}').and_then(|b| b.split_once("@{"));
+        let mut commit_id = match reflog_entry {
+            Some((name, index)) => self.resolve_reflog_entry(name, index)?,
+            None => match BranchManager::new(self.db.clone()).get_branch(branch_name)? {
+                Some(branch) if base != "HEAD" && !branch.commit_id.is_empty() => branch.commit_id,
+                _ => match TagManager::new(self.db.clone()).get(tag_name)? {
+                    Some(tag) if base != "HEAD" => self.resolve_commit(&tag.commit_id)?,
+                    _ => match self.remote_tracking_ref(base)? {
+                        Some(id) => id,
+                        None => self.resolve_commit(base)?,
+                    },
+                },
+            },
+        };
 
-        let head = branch_manager.get_head()?;
-        if head.is_none() {
-            return Err(Error::NoCommits);
+        let commit_log = CommitLog::new(self.db.clone());
+        while let Some(op) = suffix.chars().next() {
+            suffix = &suffix[1..];
+            let digits = suffix.len() - suffix.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            let count = match (op, &suffix[..digits]) {
+                (_, "") => 1,
+                ('~', n) => n
+                    .parse::<usize>()
+                    .map_err(|_| Error::Custom(format!("Invalid revision: {}", spec)))?,
+                _ => return Err(Error::Custom(format!("Invalid revision: {}", spec))),
+            };
+            suffix = &suffix[digits..];
+
+            for _ in 0..count {
+                commit_id = commit_log
+                    .get_commit(&commit_id)?
+                    .parent
+                    .ok_or_else(|| Error::CommitNotFound(spec.to_string()))?;
+            }
         }
 
-        let branch_name = head.unwrap();
-        let branch = branch_manager.get_branch(&branch_name)?;
+        Ok(commit_id)
+    }
 
-        if branch.is_none() || branch.as_ref().unwrap().commit_id.is_empty() {
-            return Err(Error::NoCommits);
+    /// Commit a ref pointed at `index` moves ago, as in `HEAD@{2}` or
+    /// `main@{1}`; an empty name means HEAD
+    fn resolve_reflog_entry(&self, name: &str, index: &str) -> Result<String> {
+        let ref_name = match name.strip_prefix("refs/heads/").unwrap_or(name) {
+            "" => "HEAD",
+            name => name,
+        };
+        let index: usize = index
+            .parse()
+            .map_err(|_| Error::Custom(format!("Invalid reflog index: {}@{{{}}}", ref_name, index)))?;
+
+        let entries = Reflog::new(self.db.clone()).entries(ref_name)?;
+        match entries.get(index) {
+            Some(entry) if !entry.new_hash.is_empty() => Ok(entry.new_hash.clone()),
+            Some(_) => Err(Error::Custom(format!("{}@{{{}}} is a deleted ref", ref_name, index))),
+            None => Err(Error::Custom(format!(
+                "Log for '{}' only has {} entries",
+                ref_name,
+                entries.len()
+            ))),
         }
+    }
+
+    /// Commit of a remote-tracking branch named `<remote>/<branch>` or
+    /// `refs/remotes/<remote>/<branch>`
+    fn remote_tracking_ref(&self, name: &str) -> Result<Option<String>> {
+        let key = if name.starts_with("refs/remotes/") {
+            name.to_string()
+        } else if name.contains('/') {
+            format!("refs/remotes/{}", name)
+        } else {
+            return Ok(None);
+        };
+        Ok(self
+            .db
+            .get("refs", key.as_bytes())?
+            .map(|id| String::from_utf8_lossy(&id).to_string()))
+    }
 
-        let history = commit_log.history(branch.unwrap().commit_id)?;
+    /// Resolve `HEAD`, a full commit id or a unique id prefix to a full commit id
+    pub fn resolve_commit(&self, prefix: &str) -> Result<String> {
+        if prefix == "HEAD" {
+            return self.head_commit_id()?.ok_or(Error::NoCommits);
+        }
+        if prefix.is_empty() {
+            return Err(Error::CommitNotFound(prefix.to_string()));
+        }
 
-        Ok(history
+        let matches: Vec<String> = self
+            .db
+            .scan("COMMITS", prefix)?
             .into_iter()
-            .map(|c| {
-                format!(
-                    "commit {}\nAuthor: {}\nDate: {}\n\n    {}\n",
-                    hash::short_hash(&c.id),
-                    c.author,
-                    c.timestamp,
-                    c.message
-                )
-            })
-            .collect())
+            .map(|(key, _)| String::from_utf8_lossy(&key).to_string())
+            .collect();
+
+        match matches.len() {
+            0 => Err(Error::CommitNotFound(prefix.to_string())),
+            1 => Ok(matches.into_iter().next().unwrap()),
+            n => Err(Error::Custom(format!(
+                "Ambiguous commit prefix '{}' matches {} commits",
+                prefix, n
+            ))),
+        }
     }
 
-    /// Create a new branch
+    /// Create a new branch at HEAD
     pub fn create_branch(&self, name: String) -> Result<()> {
         let branch_manager = BranchManager::new(self.db.clone());
-        let head = branch_manager.get_head()?;
+        if branch_manager.get_branch(&name)?.is_some() {
+            return Err(Error::Custom(format!("Branch '{}' already exists", name)));
+        }
+
+        // An unborn HEAD yields an empty branch, like the initial `main`
+        let head = self.head_commit_id()?.unwrap_or_default();
+        branch_manager.create_branch(name, head)?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    /// Delete a branch. Refuses the checked-out branch, and unless `force`
+    /// is set, a branch whose commits are not all reachable from HEAD.
+    pub fn delete_branch(&self, name: &str, force: bool) -> Result<()> {
+        let branch_manager = BranchManager::new(self.db.clone());
+        let branch = branch_manager
+            .get_branch(name)?
+            .ok_or_else(|| Error::BranchNotFound(name.to_string()))?;
+
+        if self.current_branch()?.as_deref() == Some(name) {
+            return Err(Error::Custom(format!(
+                "Cannot delete branch '{}': it is checked out",
+                name
+            )));
+        }
+        if let Some(path) = crate::core::worktree::checked_out_elsewhere(self, name)? {
+            return Err(Error::Custom(format!(
+                "Cannot delete branch '{}': it is checked out at {}",
+                name,
+                path.display()
+            )));
+        }
 
-        if let Some(branch_name) = head {
-            if let Some(branch) = branch_manager.get_branch(&branch_name)? {
-                branch_manager.create_branch(name, branch.commit_id)?;
-                self.db.flush()?;
-                return Ok(());
+        if !force && !branch.commit_id.is_empty() {
+            let merged = match self.head_commit_id()? {
+                Some(head) => CommitLog::new(self.db.clone()).is_ancestor(&branch.commit_id, &head)?,
+                None => false,
+            };
+            if !merged {
+                return Err(Error::Custom(format!(
+                    "Branch '{}' is not fully merged; use -D to delete it anyway",
+                    name
+                )));
             }
         }
 
-        Err(Error::NoCommits)
+        branch_manager.delete_branch(name)?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    /// Create a branch at HEAD and switch to it
+    pub fn switch_create(&self, name: String) -> Result<()> {
+        self.create_branch(name.clone())?;
+        BranchManager::new(self.db.clone()).set_head(name)?;
+        self.db.flush()?;
+        Ok(())
     }
 
-    /// Switch to a branch
-    pub fn checkout(&self, branch_name: String) -> Result<()> {
+    /// Switch to a branch, or detach HEAD at any other revision (commit id,
+    /// tag, `HEAD~n`), updating the working directory to match.
+    ///
+    /// Refuses to switch when local changes to a file would be overwritten.
+    pub fn checkout(&self, target: String) -> Result<()> {
         let branch_manager = BranchManager::new(self.db.clone());
 
-        if branch_manager.get_branch(&branch_name)?.is_none() {
-            return Err(Error::BranchNotFound(branch_name));
+        let (commit_id, branch) = match branch_manager.get_branch(&target)? {
+            Some(branch) => {
+                if let Some(path) = crate::core::worktree::checked_out_elsewhere(self, &target)? {
+                    return Err(Error::Custom(format!(
+                        "Branch '{}' is already checked out at {}",
+                        target,
+                        path.display()
+                    )));
+                }
+                (branch.commit_id, Some(target))
+            }
+            None => {
+                let commit_id = self
+                    .resolve_rev(&target)
+                    .map_err(|_| Error::BranchNotFound(target.clone()))?;
+                (commit_id, None)
+            }
+        };
+
+        let current = match self.head_commit_id()? {
+            Some(id) => self.snapshot(&id)?,
+            None => HashMap::new(),
+        };
+        let (next, modes) = if commit_id.is_empty() {
+            (HashMap::new(), FileModes::new())
+        } else {
+            self.snapshot_with_modes(&commit_id)?
+        };
+
+        let conflicts = self.checkout_conflicts(&current, &next)?;
+        if !conflicts.is_empty() {
+            return Err(Error::Custom(format!(
+                "Your local changes would be overwritten by checkout:\n  {}\nCommit or stash them before switching.",
+                conflicts.join("\n  ")
+            )));
         }
+        self.overwrite_working_tree(&current, &next, &modes)?;
 
-        branch_manager.set_head(branch_name)?;
+        match branch {
+            Some(name) => branch_manager.set_head(name)?,
+            None => branch_manager.detach_head(commit_id)?,
+        }
         self.db.flush()?;
         Ok(())
     }
 
+    /// Paths that differ between two snapshots and also have staged or
+    /// unstaged local changes, which switching would lose
+    pub fn checkout_conflicts(
+        &self,
+        from: &HashMap<String, String>,
+        to: &HashMap<String, String>,
+    ) -> Result<Vec<String>> {
+        let staged: HashMap<String, String> = Index::new(self.db.clone())?
+            .entries()
+            .into_iter()
+            .map(|e| (e.path, e.hash))
+            .collect();
+
+        let materialized = self.materialized()?;
+        let mut conflicts = Vec::new();
+        for path in from.keys().chain(to.keys().filter(|p| !from.contains_key(*p))) {
+            if from.get(path) == to.get(path) {
+                continue;
+            }
+            let file_path = self.root.join(path);
+            // Left off disk by a sparse checkout, so nothing to lose
+            if !materialized(path) && !file_path.exists() {
+                continue;
+            }
+            let on_disk = if file_mode::exists(&file_path) && !file_path.is_dir() {
+                Some(self.hash_algorithm().hash_file(&file_path)?)
+            } else {
+                None
+            };
+            let staged_change = staged.get(path).is_some_and(|h| from.get(path) != Some(h));
+            // A file already matching the target is safe to keep
+            let modified = on_disk.as_ref() != from.get(path) && on_disk.as_ref() != to.get(path);
+            if staged_change || modified {
+                conflicts.push(path.clone());
+            }
+        }
+        conflicts.sort();
+        Ok(conflicts)
+    }
+
     /// List all branches
     pub fn branches(&self) -> Result<Vec<String>> {
         let branch_manager = BranchManager::new(self.db.clone());
@@ -306,10 +1256,12 @@ impl Repository {
         Ok(branches.into_iter().map(|b| b.name).collect())
     }
 
-    /// Get the current branch
+    /// Get the current branch, or `None` when HEAD is detached
     pub fn current_branch(&self) -> Result<Option<String>> {
         let branch_manager = BranchManager::new(self.db.clone());
-        branch_manager.get_head()
+        Ok(branch_manager
+            .get_head()?
+            .filter(|head| !head.starts_with("detached:")))
     }
 
     /// Get database reference for advanced operations
@@ -322,9 +1274,27 @@ impl Repository {
         &self.store
     }
 
+    /// Working tree root of the repository, the directory holding `.mug`
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
     /// Get root path of repository
     pub fn root_path(&self) -> &Path {
-        &self.root
+        self.root()
+    }
+
+    /// Root of the main working tree, which holds the shared objects,
+    /// database, hooks and large file cache; the same as `root` outside a
+    /// linked worktree
+    pub fn common_root(&self) -> &Path {
+        &self.common_root
+    }
+
+    /// Name of the linked worktree this handle works in, or `None` in the
+    /// main working tree
+    pub fn worktree_name(&self) -> Option<&str> {
+        self.db.worktree()
     }
 
     /// Load or create workspace configuration
@@ -343,68 +1313,1393 @@ impl Repository {
         }
     }
 
-    /// Set configuration value
+    /// Set configuration value, which must be a known key holding a value
+    /// of its type
     pub fn set_config(&self, key: &str, value: &str) -> Result<()> {
+        let value = config::validate(key, value, false)?;
+        self.db.set("config", key.as_bytes(), value.as_bytes())?;
+        Ok(())
+    }
+
+    /// Set configuration value, allowing keys mug does not know about
+    pub fn add_config(&self, key: &str, value: &str) -> Result<()> {
+        let value = config::validate(key, value, true)?;
         self.db.set("config", key.as_bytes(), value.as_bytes())?;
         Ok(())
     }
 
-    /// Get configuration value
+    /// Get configuration value, from the repository or else the global config
     pub fn get_config(&self, key: &str) -> Result<Option<String>> {
+        match self.repo_config(key)? {
+            Some(value) => Ok(Some(value)),
+            None => Ok(GlobalConfig::load()?.get(key).map(str::to_string)),
+        }
+    }
+
+    /// Boolean configuration value; fails if the stored value is not one
+    pub fn get_config_bool(&self, key: &str) -> Result<Option<bool>> {
+        self.get_config(key)?
+            .map(|value| {
+                config::parse_bool(&value)
+                    .ok_or_else(|| Error::Custom(format!("Invalid {}: '{}' is not a boolean", key, value)))
+            })
+            .transpose()
+    }
+
+    /// Integer configuration value; fails if the stored value is not one
+    pub fn get_config_int(&self, key: &str) -> Result<Option<u64>> {
+        self.get_config(key)?
+            .map(|value| {
+                config::parse_int(&value)
+                    .ok_or_else(|| Error::Custom(format!("Invalid {}: '{}' is not an integer", key, value)))
+            })
+            .transpose()
+    }
+
+    /// Configuration value set in this repository itself
+    fn repo_config(&self, key: &str) -> Result<Option<String>> {
         match self.db.get("config", key.as_bytes())? {
             Some(bytes) => Ok(Some(String::from_utf8_lossy(&bytes).to_string())),
             None => Ok(None),
         }
     }
 
-    /// List all configuration
+    /// The remote and remote branch that `branch` tracks, read from
+    /// `branch.<name>.remote` and `branch.<name>.merge`
+    pub fn upstream(&self, branch: &str) -> Result<Option<(String, String)>> {
+        let remote = self.get_config(&format!("branch.{}.remote", branch))?;
+        let merge = self.get_config(&format!("branch.{}.merge", branch))?;
+        Ok(remote.zip(merge))
+    }
+
+    /// Make `branch` track `merge` on `remote`
+    pub fn set_upstream(&self, branch: &str, remote: &str, merge: &str) -> Result<()> {
+        self.set_config(&format!("branch.{}.remote", branch), remote)?;
+        self.set_config(&format!("branch.{}.merge", branch), merge)
+    }
+
+    /// Resolve the remote and branch for `mug push`/`mug pull`.
+    ///
+    /// Explicit arguments win; a missing branch defaults to the current
+    /// branch, and with no arguments the current branch's upstream is used.
+    pub fn sync_target(
+        &self,
+        command: &str,
+        remote: Option<&str>,
+        branch: Option<&str>,
+    ) -> Result<(String, String)> {
+        if let (Some(remote), Some(branch)) = (remote, branch) {
+            return Ok((remote.to_string(), branch.to_string()));
+        }
+
+        let current = self.current_branch()?.ok_or_else(|| {
+            Error::Custom(format!(
+                "You are not currently on a branch.\nSpecify the remote and branch: mug {} <remote> <branch>",
+                command
+            ))
+        })?;
+        if let Some(remote) = remote {
+            return Ok((remote.to_string(), current));
+        }
+
+        self.upstream(&current)?.ok_or_else(|| {
+            Error::Custom(format!(
+                "The current branch '{0}' has no upstream branch.\nTo push it and set the remote as upstream, use\n\n    mug push -u origin {0}",
+                current
+            ))
+        })
+    }
+
+    /// The configured user name and email, checking `user.name`/`user.email`,
+    /// then `.mug/config.json`, then the global config, and falling back to
+    /// the defaults
+    pub fn user_identity(&self) -> Result<(String, String)> {
+        // The global config is merged into `Config` after config.json
+        let config = crate::core::config::Config::load(&self.common_root)?;
+        let name = match self.repo_config("user.name")? {
+            Some(name) => name,
+            None => config.get_user_name(),
+        };
+        let email = match self.repo_config("user.email")? {
+            Some(email) => email,
+            None => config.get_user_email(),
+        };
+        Ok((name, email))
+    }
+
+    /// Effective configuration, global settings overridden by the
+    /// repository's, sorted by key
     pub fn list_config(&self) -> Result<Vec<(String, String)>> {
-        // Placeholder - would need database iteration
-        Ok(vec![])
+        Ok(self
+            .config_entries()?
+            .into_iter()
+            .filter(|entry| !entry.shadowed)
+            .map(|entry| (entry.key, entry.value))
+            .collect())
+    }
+
+    /// Every configuration value from both layers with where it was set,
+    /// sorted by key with global values before the repository values that
+    /// shadow them. Remote tokens are masked.
+    pub fn config_entries(&self) -> Result<Vec<ConfigEntry>> {
+        self.config_entries_over(&GlobalConfig::load()?)
+    }
+
+    fn config_entries_over(&self, global: &GlobalConfig) -> Result<Vec<ConfigEntry>> {
+        let mut repo_values = BTreeMap::new();
+        for (key, value) in self.db.scan("config", "")? {
+            let key = String::from_utf8_lossy(&key).to_string();
+            let value = if key.starts_with("remote.") && key.ends_with(".token") {
+                "********".to_string()
+            } else {
+                String::from_utf8_lossy(&value).to_string()
+            };
+            repo_values.insert(key, value);
+        }
+
+        let mut entries = global.config_entries();
+        for entry in &mut entries {
+            entry.shadowed = repo_values.contains_key(&entry.key);
+        }
+        let db_path = self.root.join(Self::DB_DIR);
+        entries.extend(repo_values.into_iter().map(|(key, value)| ConfigEntry {
+            key,
+            value,
+            origin: ConfigOrigin::Repository(db_path.clone()),
+            shadowed: false,
+        }));
+        // Stable, so each key's global entry stays ahead of the repository's
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(entries)
     }
 
     /// Update reference
+    ///
+    /// `reference` may be `HEAD`, `refs/heads/<branch>`, or any other ref name.
+    /// `value` may be a commit id or a symbolic ref and must resolve to an
+    /// existing commit.
     pub fn update_ref(&self, reference: &str, value: &str) -> Result<()> {
-        self.db.set("refs", reference.as_bytes(), value.as_bytes())?;
+        let target = self.resolve_symbolic_ref(value)?;
+        if CommitLog::new(self.db.clone()).get_commit(&target).is_err() {
+            return Err(Error::Custom(format!(
+                "Cannot update {}: '{}' is not a known commit",
+                reference, value
+            )));
+        }
+
+        let branch_manager = BranchManager::new(self.db.clone());
+        if reference == "HEAD" {
+            match branch_manager.get_head()? {
+                Some(head) if head.starts_with("detached:") => {
+                    branch_manager.detach_head(target)?;
+                }
+                Some(branch_name) => {
+                    branch_manager.update_branch_with_reason(&branch_name, target, "update-ref")?;
+                }
+                None => return Err(Error::Custom("HEAD is not set".to_string())),
+            }
+        } else if let Some(branch_name) = reference.strip_prefix("refs/heads/") {
+            branch_manager.update_branch_with_reason(branch_name, target, "update-ref")?;
+        } else {
+            let old_value = self
+                .db
+                .get("refs", reference.as_bytes())?
+                .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+                .unwrap_or_default();
+
+            self.db.set("refs", reference.as_bytes(), target.as_bytes())?;
+            Reflog::new(self.db.clone()).record(reference, &old_value, &target, "update-ref")?;
+        }
+
+        self.db.flush()?;
+        Ok(())
+    }
+
+    /// Resolve `HEAD`, `refs/heads/<branch>`, or a branch name to its commit id.
+    /// Anything else is returned unchanged.
+    fn resolve_symbolic_ref(&self, value: &str) -> Result<String> {
+        let branch_manager = BranchManager::new(self.db.clone());
+
+        let branch_name = if value == "HEAD" {
+            match branch_manager.get_head()? {
+                Some(head) => match head.strip_prefix("detached:") {
+                    Some(commit_id) => return Ok(commit_id.to_string()),
+                    None => head,
+                },
+                None => return Err(Error::NoCommits),
+            }
+        } else {
+            value.strip_prefix("refs/heads/").unwrap_or(value).to_string()
+        };
+
+        match branch_manager.get_branch(&branch_name)? {
+            Some(branch) if !branch.commit_id.is_empty() => Ok(branch.commit_id),
+            Some(_) => Err(Error::NoCommits),
+            None => Ok(value.to_string()),
+        }
+    }
+}
+
+/// Format a commit the way `log` prints it
+/// Refuse a tracked path that would leave the working tree or reach into
+/// `.mug`
+pub fn check_tree_path(path: &str) -> Result<()> {
+    if path.split('/').all(TreeEntry::valid_name) {
         Ok(())
+    } else {
+        Err(Error::Custom(format!("Refusing unsafe path in tree: {:?}", path)))
     }
 }
 
+pub fn format_log_entry(commit: &CommitMetadata) -> String {
+    format!(
+        "commit {}\nAuthor: {}\nDate: {}\n\n    {}\n",
+        hash::short_hash(&commit.id),
+        commit.author,
+        commit.timestamp,
+        commit.message
+    )
+}
+
 /// Repository statistics for garbage collection
 pub struct GarbageCollectStats {
     pub cleaned_bytes: u64,
     pub objects_remaining: u64,
+    /// Loose objects moved into packs
+    pub objects_packed: u64,
+    pub packs: usize,
+}
+
+/// Verify repository integrity, returning one message per issue found.
+///
+/// Every branch, tag and ref must point at a stored commit, and every commit
+/// at an existing tree and parents. With `deep`, each loose object is also
+/// re-hashed against its id and every tree reachable from a commit is walked
+/// to check that its entries exist and hash correctly.
+pub fn verify_repository(repo: &Repository, deep: bool) -> Result<Vec<String>> {
+    let mut issues = Vec::new();
+    let store = repo.get_store();
+
+    let mut commits = HashMap::new();
+    for (key, value) in repo.db.scan("COMMITS", "")? {
+        match serde_json::from_slice::<CommitMetadata>(&value) {
+            Ok(commit) => {
+                commits.insert(commit.id.clone(), commit);
+            }
+            Err(_) => issues.push(format!("corrupt commit: {}", String::from_utf8_lossy(&key))),
+        }
+    }
+
+    // Refs
+    let mut check_ref = |kind: &str, name: &str, target: &str| {
+        if !target.is_empty() && !commits.contains_key(target) {
+            issues.push(format!(
+                "dangling ref: {} '{}' points to missing commit {}",
+                kind, name, target
+            ));
+        }
+    };
+    for branch in BranchManager::new(repo.db.clone()).list_branches()? {
+        check_ref("branch", &branch.name, &branch.commit_id);
+    }
+    for tag in TagManager::new(repo.db.clone()).list()? {
+        check_ref("tag", &tag.name, &tag.commit_id);
+    }
+    for (key, value) in repo.db.scan("refs", "")? {
+        check_ref(
+            "ref",
+            &String::from_utf8_lossy(&key),
+            &String::from_utf8_lossy(&value),
+        );
+    }
+    match BranchManager::new(repo.db.clone()).get_head()? {
+        Some(head) => match head.strip_prefix("detached:") {
+            Some(id) => check_ref("ref", "HEAD", id),
+            None => {
+                if BranchManager::new(repo.db.clone())
+                    .get_branch(&head)?
+                    .is_none()
+                    && !commits.is_empty()
+                {
+                    issues.push(format!(
+                        "dangling ref: HEAD points to missing branch '{}'",
+                        head
+                    ));
+                }
+            }
+        },
+        None => issues.push("dangling ref: HEAD is not set".to_string()),
+    }
+
+    // Commits
+    let mut ids: Vec<&String> = commits.keys().collect();
+    ids.sort();
+    for id in &ids {
+        let commit = &commits[*id];
+        for parent in commit.parents() {
+            if !commits.contains_key(&parent) {
+                issues.push(format!(
+                    "missing object: commit {} has missing parent {}",
+                    id, parent
+                ));
+            }
+        }
+        if !store.has_object(&commit.tree_hash) {
+            issues.push(format!(
+                "missing object: tree {} of commit {}",
+                commit.tree_hash, id
+            ));
+        }
+    }
+
+    if !deep {
+        return Ok(issues);
+    }
+
+    // Loose objects must hash to their file name
+    for entry in fs::read_dir(store.objects_dir())? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        let data = fs::read(entry.path())?;
+        let algorithm = store.algorithm();
+        if !blob_matches(algorithm, &name, &data) && !tree_hash_matches(algorithm, &data, &name) {
+            issues.push(format!("hash mismatch: object {}", name));
+        }
+    }
+
+    // Every tree reachable from a commit must be intact
+    let mut seen = std::collections::HashSet::new();
+    for id in &ids {
+        let tree_hash = &commits[*id].tree_hash;
+        if store.has_object(tree_hash) {
+            verify_tree(store, tree_hash, "", &mut seen, &mut issues);
+        }
+    }
+
+    Ok(issues)
+}
+
+/// Whether `data` is a stored tree whose entries hash to `hash`
+fn tree_hash_matches(algorithm: HashAlgorithm, data: &[u8], hash: &str) -> bool {
+    serde_json::from_slice::<crate::core::store::Tree>(data)
+        .ok()
+        .and_then(|tree| serde_json::to_string(&tree.entries).ok())
+        .is_some_and(|json| algorithm.hash_str(&json) == hash)
+}
+
+/// Check a tree and everything below it, recording issues under `prefix`
+fn verify_tree(
+    store: &ObjectStore,
+    tree_hash: &str,
+    prefix: &str,
+    seen: &mut std::collections::HashSet<String>,
+    issues: &mut Vec<String>,
+) {
+    if !seen.insert(tree_hash.to_string()) {
+        return;
+    }
+    let tree = match store.get_tree(tree_hash) {
+        Ok(tree) => tree,
+        Err(_) => {
+            issues.push(format!("corrupt tree: {}", tree_hash));
+            return;
+        }
+    };
+    match serde_json::to_string(&tree.entries) {
+        Ok(json) if store.algorithm().hash_str(&json) == tree_hash => {}
+        _ => issues.push(format!("hash mismatch: tree {}", tree_hash)),
+    }
+
+    for entry in &tree.entries {
+        let path = format!("{}{}", prefix, entry.name);
+        if !store.has_object(&entry.hash) {
+            issues.push(format!("missing object: {} for {}", entry.hash, path));
+        } else if entry.is_dir {
+            verify_tree(store, &entry.hash, &format!("{}/", path), seen, issues);
+        } else if seen.insert(entry.hash.clone()) {
+            match store.get_blob(&entry.hash) {
+                Ok(blob) if blob_matches(store.algorithm(), &entry.hash, &blob.content) => {}
+                _ => issues.push(format!("hash mismatch: blob {} for {}", entry.hash, path)),
+            }
+        }
+    }
+}
+
+/// Kind of a stored object
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ObjectKind {
+    Commit,
+    Tree,
+    Blob,
+}
+
+impl std::fmt::Display for ObjectKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ObjectKind::Commit => write!(f, "commit"),
+            ObjectKind::Tree => write!(f, "tree"),
+            ObjectKind::Blob => write!(f, "blob"),
+        }
+    }
 }
 
-/// Verify repository integrity
-pub fn verify_repository(_repo: &Repository) -> Result<Vec<String>> {
-    // Placeholder for integrity checks
-    Ok(vec![])
+/// Find dangling objects: objects no ref reaches and no other unreachable
+/// object refers to, so each is the top of some lost work (a commit left
+/// behind by a reset, a tree or blob that was staged and then replaced).
+///
+/// Branches, tags, remote-tracking refs, HEAD, the index, stashes and a
+/// halted merge, rebase or cherry-pick all count as reachable. Results are
+/// sorted by kind, then hash.
+pub fn dangling_objects(repo: &Repository) -> Result<Vec<(ObjectKind, String)>> {
+    use std::collections::HashSet;
+
+    let store = repo.get_store();
+    let commits: HashMap<String, CommitMetadata> = repo
+        .db
+        .scan("COMMITS", "")?
+        .into_iter()
+        .filter_map(|(_, value)| serde_json::from_slice::<CommitMetadata>(&value).ok())
+        .map(|commit| (commit.id.clone(), commit))
+        .collect();
+
+    let mut objects: HashSet<String> = HashSet::new();
+    for entry in fs::read_dir(store.objects_dir())? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            objects.insert(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    if let Some(reader) = store.pack_reader()? {
+        objects.extend(reader.manifest().objects.keys().cloned());
+    }
+
+    // Roots
+    let mut commit_roots = Vec::new();
+    let mut object_roots = Vec::new();
+    for branch in BranchManager::new(repo.db.clone()).list_branches()? {
+        commit_roots.push(branch.commit_id);
+    }
+    for tag in TagManager::new(repo.db.clone()).list()? {
+        commit_roots.push(tag.commit_id);
+    }
+    for (_, value) in repo.db.scan("refs", "")? {
+        commit_roots.push(String::from_utf8_lossy(&value).to_string());
+    }
+    for stash in crate::core::stash::StashManager::new(repo.db.clone()).list()? {
+        object_roots.extend(stash.files.into_iter().map(|file| file.hash));
+    }
+    // Every worktree has its own HEAD, index and operation in progress
+    for view in crate::core::worktree::views(repo)? {
+        if let Some(head) = BranchManager::new(view.db.clone()).get_head()? {
+            if let Some(id) = head.strip_prefix("detached:") {
+                commit_roots.push(id.to_string());
+            }
+        }
+        for entry in Index::new(view.db.clone())?.entries() {
+            object_roots.push(entry.hash);
+        }
+        if let Some(state) = crate::core::sequencer::OperationState::load(&view)? {
+            commit_roots.push(state.orig_head);
+            commit_roots.push(state.target);
+            object_roots.extend(state.files.into_values());
+        }
+    }
+
+    // Everything reachable from the roots
+    let mut reachable_commits = HashSet::new();
+    let mut reachable = HashSet::new();
+    let mut pending = commit_roots;
+    while let Some(id) = pending.pop() {
+        let Some(commit) = commits.get(&id) else { continue };
+        if reachable_commits.insert(id) {
+            object_roots.push(commit.tree_hash.clone());
+            pending.extend(commit.parents());
+        }
+    }
+    while let Some(hash) = object_roots.pop() {
+        if reachable.insert(hash.clone()) {
+            if let Ok(tree) = store.get_tree(&hash) {
+                object_roots.extend(tree.entries.into_iter().map(|entry| entry.hash));
+            }
+        }
+    }
+
+    // Unreachable objects referred to by other unreachable ones are not
+    // dangling; recovering the referrer brings them back too
+    let mut referenced = HashSet::new();
+    let mut unreachable = Vec::new();
+    for (id, commit) in &commits {
+        if !reachable_commits.contains(id) {
+            referenced.extend(commit.parents());
+            referenced.insert(commit.tree_hash.clone());
+            unreachable.push((ObjectKind::Commit, id.clone()));
+        }
+    }
+    for hash in objects.into_iter().filter(|hash| !reachable.contains(hash)) {
+        match store.get_tree(&hash) {
+            Ok(tree) => {
+                referenced.extend(tree.entries.into_iter().map(|entry| entry.hash));
+                unreachable.push((ObjectKind::Tree, hash));
+            }
+            Err(_) => unreachable.push((ObjectKind::Blob, hash)),
+        }
+    }
+
+    let mut dangling: Vec<_> = unreachable
+        .into_iter()
+        .filter(|(_, hash)| !referenced.contains(hash))
+        .collect();
+    dangling.sort();
+    Ok(dangling)
+}
+
+/// Perform garbage collection: pack every loose object and delete the
+/// loose copies.
+///
+/// New objects go into a fresh pack next to the existing ones. With `full`,
+/// everything is repacked into a new pack set, written to a staging
+/// directory and only then swapped in.
+pub fn garbage_collect(repo: &Repository, full: bool) -> Result<GarbageCollectStats> {
+    let store = repo.get_store();
+    let existing = store.pack_reader()?;
+    let old_pack_bytes = existing.as_ref().map_or(0, |r| r.manifest().total_size());
+
+    let mut loose = Vec::new();
+    let mut loose_bytes = 0;
+    for entry in fs::read_dir(store.objects_dir())? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            loose_bytes += entry.metadata()?.len();
+            loose.push(entry.path());
+        }
+    }
+    if loose.is_empty() && !(full && existing.is_some()) {
+        let manifest = existing.as_ref().map(|r| r.manifest());
+        return Ok(GarbageCollectStats {
+            cleaned_bytes: 0,
+            objects_remaining: manifest.map_or(0, |m| m.object_count as u64),
+            objects_packed: 0,
+            packs: manifest.map_or(0, |m| m.packs.len()),
+        });
+    }
+
+    let packs_dir = store.packs_dir();
+    let builder = PackBuilder::new(&repo.common_root, DEFAULT_PACK_SIZE)?;
+    if let (false, Some(reader)) = (full, existing.as_deref()) {
+        let manifest = builder.build_incremental(&packs_dir, reader.manifest())?;
+        manifest.save(&packs_dir.join(PACK_MANIFEST))?;
+        drop(existing);
+        store.clear_pack_cache();
+        return finish_gc(&loose, loose_bytes, old_pack_bytes, &manifest);
+    }
+
+    let staging = packs_dir.with_file_name("packs.tmp");
+    let retired = packs_dir.with_file_name("packs.old");
+    for dir in [&staging, &retired] {
+        if dir.exists() {
+            fs::remove_dir_all(dir)?;
+        }
+    }
+
+    let manifest = builder.build_packs_including(&staging, existing.as_deref())?;
+    manifest.save(&staging.join(PACK_MANIFEST))?;
+    drop(existing);
+    store.clear_pack_cache();
+
+    if packs_dir.exists() {
+        fs::rename(&packs_dir, &retired)?;
+    }
+    fs::rename(&staging, &packs_dir)?;
+    if retired.exists() {
+        fs::remove_dir_all(&retired)?;
+    }
+
+    finish_gc(&loose, loose_bytes, old_pack_bytes, &manifest)
 }
 
-/// Perform garbage collection
-pub fn garbage_collect(_repo: &Repository) -> Result<GarbageCollectStats> {
-    // Placeholder for GC implementation
+/// Delete the loose copies that made it into the packs and tally the result
+fn finish_gc(
+    loose: &[PathBuf],
+    loose_bytes: u64,
+    old_pack_bytes: u64,
+    manifest: &crate::pack::PackManifest,
+) -> Result<GarbageCollectStats> {
+    let mut objects_packed = 0;
+    for path in loose {
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        if manifest.objects.contains_key(&name) {
+            fs::remove_file(path)?;
+            objects_packed += 1;
+        }
+    }
+
     Ok(GarbageCollectStats {
-        cleaned_bytes: 0,
-        objects_remaining: 0,
+        cleaned_bytes: (loose_bytes + old_pack_bytes).saturating_sub(manifest.total_size()),
+        objects_remaining: manifest.object_count as u64,
+        objects_packed,
+        packs: manifest.packs.len(),
     })
 }
 
-/// Get reference log
-pub fn get_reflog(_repo: &Repository, _reference: Option<&str>) -> Result<Vec<String>> {
-    // Placeholder for reflog implementation
-    Ok(vec![])
+/// Get reference log, newest first (defaults to HEAD)
+pub fn get_reflog(repo: &Repository, reference: Option<&str>) -> Result<Vec<String>> {
+    let ref_name = reference.unwrap_or("HEAD");
+    let entries = Reflog::new(repo.get_db().clone()).entries(ref_name)?;
+
+    Ok(entries
+        .into_iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            format!(
+                "{} {}@{{{}}}: {} ({}, {})",
+                hash::short_hash(&entry.new_hash),
+                ref_name,
+                i,
+                entry.operation,
+                entry.author,
+                entry.timestamp
+            )
+        })
+        .collect())
 }
 
-// Helper function to clone the database (since Sled Db doesn't impl Clone)
-impl Clone for Repository {
-    fn clone(&self) -> Self {
-        Repository {
-            root: self.root.clone(),
-            mug_dir: self.mug_dir.clone(),
-            db: MugDb::new(self.mug_dir.join("db")).expect("Failed to clone database"),
-            store: ObjectStore::new(self.mug_dir.join("objects")).expect("Failed to clone store"),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn repo_with_commit() -> (TempDir, Repository, String) {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        fs::write(dir.path().join("file.txt"), "hello").unwrap();
+        repo.add("file.txt").unwrap();
+        let commit_id = repo
+            .commit("Tester".to_string(), "Initial".to_string())
+            .unwrap();
+        (dir, repo, commit_id)
+    }
+
+    #[test]
+    fn test_add_reports_case_collisions() {
+        let (dir, repo, _) = repo_with_commit();
+        fs::write(dir.path().join("File.txt"), "other").unwrap();
+        assert_eq!(
+            repo.case_collisions(&["File.txt", "new.txt", "NEW.txt"]).unwrap(),
+            vec![
+                ("File.txt".to_string(), "file.txt".to_string()),
+                ("NEW.txt".to_string(), "new.txt".to_string()),
+            ]
+        );
+
+        repo.set_config("core.ignorecase", "true").unwrap();
+        let err = repo.add("File.txt").unwrap_err();
+        assert!(err.to_string().contains("File.txt collides with file.txt"));
+        assert!(repo.add_all().is_err());
+        assert!(!Index::new(repo.get_db().clone()).unwrap().contains("File.txt"));
+
+        // Off, the collision is only a warning
+        repo.set_config("core.ignorecase", "false").unwrap();
+        repo.add("File.txt").unwrap();
+        assert!(Index::new(repo.get_db().clone()).unwrap().contains("File.txt"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_are_committed_and_checked_out_as_links() {
+        let (dir, repo, _) = repo_with_commit();
+        std::os::unix::fs::symlink("file.txt", dir.path().join("link")).unwrap();
+        std::os::unix::fs::symlink("missing", dir.path().join("dangling")).unwrap();
+        repo.add_all().unwrap();
+        let id = repo.commit("Tester".to_string(), "Links".to_string()).unwrap();
+
+        let (files, modes) = repo.snapshot_with_modes(&id).unwrap();
+        assert_eq!(modes.get("link"), Some(&file_mode::SYMLINK));
+        assert_eq!(repo.get_store().get_blob(&files["link"]).unwrap().content, b"file.txt");
+        assert!(!modes.contains_key("file.txt"));
+        assert!(repo.status().unwrap().porcelain().is_empty());
+
+        repo.create_branch("links".to_string()).unwrap();
+        repo.checkout("HEAD~1".to_string()).unwrap();
+        assert!(!file_mode::exists(&dir.path().join("link")));
+        repo.checkout("links".to_string()).unwrap();
+        assert_eq!(fs::read_link(dir.path().join("link")).unwrap(), Path::new("file.txt"));
+        assert_eq!(fs::read_link(dir.path().join("dangling")).unwrap(), Path::new("missing"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_files_are_never_written_beyond_a_symlink() {
+        let (dir, repo, _) = repo_with_commit();
+        let outside = TempDir::new().unwrap();
+        let target = outside.path().to_string_lossy().into_owned();
+        let link = repo.get_store().store_blob(target.as_bytes()).unwrap();
+        let payload = repo.get_store().store_blob(b"payload").unwrap();
+        let modes: FileModes = [("a".to_string(), file_mode::SYMLINK)].into_iter().collect();
+
+        // A symlink and a file below it in the same tree
+        let to: HashMap<String, String> =
+            [("a".to_string(), link), ("a/evil".to_string(), payload.clone())].into_iter().collect();
+        assert!(repo.overwrite_working_tree(&HashMap::new(), &to, &modes).is_err());
+        assert!(!file_mode::exists(&dir.path().join("a")));
+
+        // A symlink already on disk
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("b")).unwrap();
+        let to: HashMap<String, String> = [("b/evil".to_string(), payload)].into_iter().collect();
+        let err = repo.overwrite_working_tree(&HashMap::new(), &to, &FileModes::new()).unwrap_err();
+        assert!(err.to_string().contains("beyond a symbolic link"));
+        fs::write(outside.path().join("kept"), "outside").unwrap();
+        let from: HashMap<String, String> = [("b/kept".to_string(), String::new())].into_iter().collect();
+        assert!(repo.overwrite_working_tree(&from, &HashMap::new(), &FileModes::new()).is_err());
+
+        assert!(!outside.path().join("evil").exists());
+        assert!(outside.path().join("kept").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_executable_bit_is_staged_and_restored() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (dir, repo, _) = repo_with_commit();
+        let script = dir.path().join("run.sh");
+        fs::write(&script, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        repo.add("run.sh").unwrap();
+        let entry = Index::new(repo.get_db().clone()).unwrap().get("run.sh").cloned().unwrap();
+        assert_eq!(entry.mode, file_mode::EXECUTABLE);
+        let id = repo.commit("Tester".to_string(), "Script".to_string()).unwrap();
+        assert_eq!(repo.file_modes(&id).unwrap().get("run.sh"), Some(&file_mode::EXECUTABLE));
+
+        repo.create_branch("script".to_string()).unwrap();
+        repo.checkout("HEAD~1".to_string()).unwrap();
+        assert!(!script.exists());
+        repo.checkout("script".to_string()).unwrap();
+        assert_eq!(fs::metadata(&script).unwrap().permissions().mode() & 0o111, 0o111);
+
+        // Dropping the bit is a change of mode alone
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o644)).unwrap();
+        repo.add("run.sh").unwrap();
+        let id = repo.commit("Tester".to_string(), "Not executable".to_string()).unwrap();
+        assert!(repo.file_modes(&id).unwrap().is_empty());
+        repo.checkout("HEAD~1".to_string()).unwrap();
+        assert_eq!(fs::metadata(&script).unwrap().permissions().mode() & 0o111, 0o111);
+    }
+
+    #[test]
+    fn test_update_ref_rejects_unknown_commit() {
+        let (_dir, repo, _) = repo_with_commit();
+        assert!(repo.update_ref("refs/tags/bogus", "does-not-exist").is_err());
+        assert!(repo.get_db().get("refs", "refs/tags/bogus").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_update_ref_resolves_symbolic_refs() {
+        let (_dir, repo, commit_id) = repo_with_commit();
+
+        repo.update_ref("refs/heads/dev", "HEAD").unwrap();
+        let branch_manager = BranchManager::new(repo.get_db().clone());
+        let dev = branch_manager.get_branch("dev").unwrap().unwrap();
+        assert_eq!(dev.commit_id, commit_id);
+
+        repo.update_ref("refs/custom", "refs/heads/dev").unwrap();
+        let stored = repo.get_db().get("refs", "refs/custom").unwrap().unwrap();
+        assert_eq!(String::from_utf8(stored).unwrap(), commit_id);
+
+        let reflog = get_reflog(&repo, Some("refs/custom")).unwrap();
+        assert_eq!(reflog.len(), 1);
+    }
+
+    #[test]
+    fn test_resolve_commit_by_prefix() {
+        let (_dir, repo, commit_id) = repo_with_commit();
+
+        assert_eq!(repo.resolve_commit(&commit_id[..7]).unwrap(), commit_id);
+        assert_eq!(repo.resolve_commit(&commit_id).unwrap(), commit_id);
+        assert_eq!(repo.resolve_commit("HEAD").unwrap(), commit_id);
+        assert!(matches!(
+            repo.resolve_commit("zzzz"),
+            Err(Error::CommitNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_resolve_commit_ambiguous_prefix() {
+        let (_dir, repo, _) = repo_with_commit();
+        repo.get_db().set("COMMITS", "abc1-one", b"{}").unwrap();
+        repo.get_db().set("COMMITS", "abc2-two", b"{}").unwrap();
+
+        let err = repo.resolve_commit("abc").unwrap_err();
+        assert!(err.to_string().contains("Ambiguous"));
+        assert_eq!(repo.resolve_commit("abc1").unwrap(), "abc1-one");
+    }
+
+    #[test]
+    fn test_resolve_rev_relative_to_head_and_branch() {
+        let (dir, repo, first) = repo_with_commit();
+        fs::write(dir.path().join("file.txt"), "second").unwrap();
+        repo.add("file.txt").unwrap();
+        let second = repo.commit("Tester".to_string(), "Second".to_string()).unwrap();
+        fs::write(dir.path().join("file.txt"), "third").unwrap();
+        repo.add("file.txt").unwrap();
+        let third = repo.commit("Tester".to_string(), "Third".to_string()).unwrap();
+
+        assert_eq!(repo.resolve_rev("HEAD").unwrap(), third);
+        assert_eq!(repo.resolve_rev("HEAD^").unwrap(), second);
+        assert_eq!(repo.resolve_rev("HEAD~2").unwrap(), first);
+        assert_eq!(repo.resolve_rev("HEAD^^").unwrap(), first);
+        assert_eq!(repo.resolve_rev("main~1").unwrap(), second);
+        assert_eq!(repo.resolve_rev(&format!("{}~1", &third[..8])).unwrap(), second);
+        assert!(matches!(repo.resolve_rev("HEAD~3"), Err(Error::CommitNotFound(_))));
+        assert!(repo.resolve_rev("HEAD^2").is_err());
+    }
+
+    #[test]
+    fn test_clone_shares_database() {
+        let (dir, repo, first) = repo_with_commit();
+        let other = repo.clone();
+
+        fs::write(dir.path().join("second.txt"), "second").unwrap();
+        other.add("second.txt").unwrap();
+        let second = other.commit("Tester".to_string(), "Second".to_string()).unwrap();
+        assert_eq!(repo.resolve_rev("HEAD").unwrap(), second);
+
+        repo.create_branch("feature".to_string()).unwrap();
+        assert!(other.branches().unwrap().contains(&"feature".to_string()));
+        assert_eq!(other.resolve_rev("HEAD^").unwrap(), first);
+    }
+
+    #[test]
+    fn test_log_structured_keeps_full_message() {
+        let (dir, repo, first) = repo_with_commit();
+        fs::write(dir.path().join("file.txt"), "second").unwrap();
+        repo.add("file.txt").unwrap();
+        let message = "Subject line\n\nBody paragraph\nwith two lines".to_string();
+        let second = repo.commit("Tester".to_string(), message.clone()).unwrap();
+
+        let log = repo.log_structured().unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].hash, second);
+        assert_eq!(log[0].message, message);
+        assert_eq!(log[0].parents, vec![first.clone()]);
+        assert!(log[0].is_head);
+        assert_eq!(log[0].branch.as_deref(), Some("main"));
+        assert_eq!(log[1].hash, first);
+        assert!(log[1].parents.is_empty());
+    }
+
+    #[test]
+    fn test_checkout_commit_detaches_head() {
+        let (dir, repo, first) = repo_with_commit();
+        fs::write(dir.path().join("file.txt"), "changed").unwrap();
+        fs::write(dir.path().join("new.txt"), "new").unwrap();
+        repo.add("file.txt").unwrap();
+        repo.add("new.txt").unwrap();
+        repo.commit("Tester".to_string(), "Second".to_string()).unwrap();
+
+        repo.checkout(first[..8].to_string()).unwrap();
+        assert_eq!(repo.current_branch().unwrap(), None);
+        assert_eq!(repo.head_commit_id().unwrap(), Some(first.clone()));
+        assert_eq!(fs::read_to_string(dir.path().join("file.txt")).unwrap(), "hello");
+        assert!(!dir.path().join("new.txt").exists());
+        assert_eq!(repo.branches().unwrap(), vec!["main".to_string()]);
+
+        // Commits on a detached HEAD advance HEAD, not a branch
+        fs::write(dir.path().join("other.txt"), "other").unwrap();
+        repo.add("other.txt").unwrap();
+        let detached = repo.commit("Tester".to_string(), "Detached".to_string()).unwrap();
+        assert_eq!(repo.head_commit_id().unwrap(), Some(detached.clone()));
+        assert_eq!(repo.history().unwrap()[1].id, first);
+        let main = BranchManager::new(repo.get_db().clone()).get_branch("main").unwrap().unwrap();
+        assert_ne!(main.commit_id, detached);
+
+        repo.create_branch("rescue".to_string()).unwrap();
+        repo.checkout("rescue".to_string()).unwrap();
+        assert_eq!(repo.current_branch().unwrap(), Some("rescue".to_string()));
+        assert!(matches!(
+            repo.checkout("nope".to_string()),
+            Err(Error::BranchNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_checkout_updates_working_directory() {
+        let (dir, repo, _) = repo_with_commit();
+        repo.create_branch("feature".to_string()).unwrap();
+        repo.checkout("feature".to_string()).unwrap();
+        fs::write(dir.path().join("file.txt"), "feature").unwrap();
+        fs::write(dir.path().join("extra.txt"), "extra").unwrap();
+        repo.add("file.txt").unwrap();
+        repo.add("extra.txt").unwrap();
+        repo.commit("Tester".to_string(), "Feature".to_string()).unwrap();
+
+        repo.checkout("main".to_string()).unwrap();
+        assert_eq!(fs::read_to_string(dir.path().join("file.txt")).unwrap(), "hello");
+        assert!(!dir.path().join("extra.txt").exists());
+
+        repo.checkout("feature".to_string()).unwrap();
+        assert_eq!(fs::read_to_string(dir.path().join("file.txt")).unwrap(), "feature");
+        assert!(dir.path().join("extra.txt").exists());
+    }
+
+    #[test]
+    fn test_checkout_refuses_to_overwrite_local_changes() {
+        let (dir, repo, _) = repo_with_commit();
+        repo.create_branch("feature".to_string()).unwrap();
+        repo.checkout("feature".to_string()).unwrap();
+        fs::write(dir.path().join("file.txt"), "feature").unwrap();
+        repo.add("file.txt").unwrap();
+        repo.commit("Tester".to_string(), "Feature".to_string()).unwrap();
+
+        fs::write(dir.path().join("file.txt"), "local edit").unwrap();
+        let err = repo.checkout("main".to_string()).unwrap_err();
+        assert!(err.to_string().contains("file.txt"));
+        assert_eq!(repo.current_branch().unwrap(), Some("feature".to_string()));
+        assert_eq!(fs::read_to_string(dir.path().join("file.txt")).unwrap(), "local edit");
+
+        // Once the edit is reverted, untracked files are left in place
+        fs::write(dir.path().join("file.txt"), "feature").unwrap();
+        fs::write(dir.path().join("notes.txt"), "untracked").unwrap();
+        repo.checkout("main".to_string()).unwrap();
+        assert!(dir.path().join("notes.txt").exists());
+    }
+
+    #[test]
+    fn test_working_tree_update_refuses_unsafe_paths() {
+        let (dir, repo, _) = repo_with_commit();
+        let hash = repo.get_store().store_blob(b"#!/bin/sh\n").unwrap();
+        for path in ["../escaped", "/tmp/absolute", ".MUG/hooks/pre-commit", "a//b", "a/./b", "a\\b"] {
+            let to: HashMap<String, String> = [
+                ("safe.txt".to_string(), hash.clone()),
+                (path.to_string(), hash.clone()),
+            ]
+            .into_iter()
+            .collect();
+            let err = repo.overwrite_working_tree(&HashMap::new(), &to, &FileModes::new());
+            assert!(err.unwrap_err().to_string().contains("unsafe path"), "{}", path);
+            assert!(!dir.path().join("safe.txt").exists());
         }
+        assert!(!dir.path().parent().unwrap().join("escaped").exists());
+        assert!(!dir.path().join(".mug/hooks/pre-commit").exists());
+    }
+
+    #[test]
+    fn test_add_all_honors_mugignore() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        fs::write(dir.path().join(".mugignore"), "build/\n*.log\n!keep.log\n").unwrap();
+        fs::create_dir_all(dir.path().join("build/deep")).unwrap();
+        fs::write(dir.path().join("build/deep/out.o"), "bin").unwrap();
+        fs::write(dir.path().join("debug.log"), "noise").unwrap();
+        fs::write(dir.path().join("keep.log"), "kept").unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+
+        repo.add_all().unwrap();
+        let mut staged: Vec<String> = Index::new(repo.get_db().clone())
+            .unwrap()
+            .entries()
+            .into_iter()
+            .map(|e| e.path)
+            .collect();
+        staged.sort();
+        assert_eq!(staged, vec!["keep.log".to_string(), "main.rs".to_string()]);
+    }
+
+    #[test]
+    fn test_commit_stores_nested_trees() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        fs::create_dir_all(dir.path().join("src/bin")).unwrap();
+        fs::write(dir.path().join("README"), "readme").unwrap();
+        fs::write(dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(dir.path().join("src/bin/tool.rs"), "tool").unwrap();
+        repo.add("README").unwrap();
+        repo.add("src/main.rs").unwrap();
+        repo.add("src/bin/tool.rs").unwrap();
+        let commit_id = repo.commit("Tester".to_string(), "Nested".to_string()).unwrap();
+
+        let commit = CommitLog::new(repo.get_db().clone()).get_commit(&commit_id).unwrap();
+        let root = repo.get_store().get_tree(&commit.tree_hash).unwrap();
+        let names: Vec<(&str, bool)> = root.entries.iter().map(|e| (e.name.as_str(), e.is_dir)).collect();
+        assert_eq!(names, vec![("README", false), ("src", true)]);
+
+        let snapshot = repo.snapshot(&commit_id).unwrap();
+        assert_eq!(snapshot.len(), 3);
+        assert_eq!(snapshot["src/bin/tool.rs"], hash::hash_bytes(b"tool"));
+
+        // The same files always produce the same tree hash
+        assert_eq!(repo.store_snapshot(&snapshot).unwrap(), commit.tree_hash);
+
+        fs::remove_dir_all(dir.path().join("src")).unwrap();
+        repo.checkout(commit_id).unwrap();
+        assert_eq!(fs::read_to_string(dir.path().join("src/bin/tool.rs")).unwrap(), "tool");
+    }
+
+    #[test]
+    fn test_amend_replaces_head_commit() {
+        let (dir, repo, first) = repo_with_commit();
+        fs::write(dir.path().join("file.txt"), "second").unwrap();
+        repo.add("file.txt").unwrap();
+        let second = repo.commit("Tester".to_string(), "Second".to_string()).unwrap();
+
+        fs::write(dir.path().join("extra.txt"), "forgotten").unwrap();
+        repo.add("extra.txt").unwrap();
+        let amended = repo.amend("Tester".to_string(), None).unwrap();
+
+        let history = repo.history().unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].id, amended);
+        assert_eq!(history[0].message, "Second");
+        assert_eq!(history[0].parent.as_deref(), Some(first.as_str()));
+        let files = repo.snapshot(&amended).unwrap();
+        assert!(files.contains_key("extra.txt") && files.contains_key("file.txt"));
+
+        // Message-only amend with nothing staged
+        let reworded = repo.amend("Tester".to_string(), Some("Reworded".to_string())).unwrap();
+        assert_eq!(repo.history().unwrap()[0].message, "Reworded");
+        assert_eq!(repo.snapshot(&reworded).unwrap(), files);
+
+        let reflog = get_reflog(&repo, Some("main")).unwrap();
+        assert!(reflog.iter().any(|entry| entry.contains("commit (amend): Reworded")));
+        assert_ne!(second, amended);
+    }
+
+    #[test]
+    fn test_amend_without_commits_fails() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        assert!(repo.amend("Tester".to_string(), Some("x".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_switch_create() {
+        let (dir, repo, commit_id) = repo_with_commit();
+        fs::write(dir.path().join("file.txt"), "local edit").unwrap();
+
+        repo.switch_create("topic".to_string()).unwrap();
+        assert_eq!(repo.current_branch().unwrap(), Some("topic".to_string()));
+        assert_eq!(repo.head_commit_id().unwrap(), Some(commit_id));
+        // Uncommitted work comes along to the new branch
+        assert_eq!(fs::read_to_string(dir.path().join("file.txt")).unwrap(), "local edit");
+
+        let err = repo.switch_create("main".to_string()).unwrap_err();
+        assert!(err.to_string().contains("already exists"));
+        assert_eq!(repo.current_branch().unwrap(), Some("topic".to_string()));
+    }
+
+    #[test]
+    fn test_delete_branch_refuses_current() {
+        let (_dir, repo, _) = repo_with_commit();
+        let err = repo.delete_branch("main", true).unwrap_err();
+        assert!(err.to_string().contains("checked out"));
+        assert!(repo.branches().unwrap().contains(&"main".to_string()));
+    }
+
+    #[test]
+    fn test_delete_unmerged_branch_needs_force() {
+        let (dir, repo, _) = repo_with_commit();
+        repo.switch_create("topic".to_string()).unwrap();
+        fs::write(dir.path().join("topic.txt"), "topic").unwrap();
+        repo.add("topic.txt").unwrap();
+        let topic = repo.commit("Tester".to_string(), "Topic".to_string()).unwrap();
+        repo.checkout("main".to_string()).unwrap();
+        repo.create_branch("merged".to_string()).unwrap();
+
+        let err = repo.delete_branch("topic", false).unwrap_err();
+        assert!(err.to_string().contains("not fully merged"));
+        repo.delete_branch("merged", false).unwrap();
+
+        repo.delete_branch("topic", true).unwrap();
+        assert_eq!(repo.branches().unwrap(), vec!["main".to_string()]);
+        let entries = Reflog::new(repo.get_db().clone()).entries("topic").unwrap();
+        let deleted = entries.iter().find(|e| e.operation == "branch: deleted").unwrap();
+        assert_eq!(deleted.old_hash, topic);
+    }
+
+    #[test]
+    fn test_sync_target_uses_upstream() {
+        let (_dir, repo, _) = repo_with_commit();
+        let err = repo.sync_target("pull", None, None).unwrap_err();
+        assert!(err.to_string().contains("mug push -u origin main"));
+
+        assert_eq!(
+            repo.sync_target("push", Some("backup"), None).unwrap(),
+            ("backup".to_string(), "main".to_string())
+        );
+
+        repo.set_upstream("main", "origin", "trunk").unwrap();
+        assert_eq!(
+            repo.sync_target("pull", None, None).unwrap(),
+            ("origin".to_string(), "trunk".to_string())
+        );
+        assert_eq!(
+            repo.sync_target("pull", Some("other"), Some("dev")).unwrap(),
+            ("other".to_string(), "dev".to_string())
+        );
+    }
+
+    #[test]
+    fn test_resolve_rev_remote_tracking_branch() {
+        let (_dir, repo, first) = repo_with_commit();
+        let mut branches = std::collections::HashMap::new();
+        branches.insert("main".to_string(), first.clone());
+        crate::remote::RemoteManager::new(repo.get_db().clone())
+            .update_tracking_refs("origin", &branches, false)
+            .unwrap();
+
+        assert_eq!(repo.resolve_rev("origin/main").unwrap(), first);
+        assert_eq!(repo.resolve_rev("refs/remotes/origin/main").unwrap(), first);
+        assert!(repo.resolve_rev("origin/dev").is_err());
+    }
+
+    #[test]
+    fn test_garbage_collect_packs_loose_objects() {
+        let (dir, repo, first) = repo_with_commit();
+        let stats = garbage_collect(&repo, false).unwrap();
+        assert_eq!(stats.objects_packed, 2);
+        assert_eq!(stats.packs, 1);
+        assert_eq!(fs::read_dir(dir.path().join(".mug/objects")).unwrap().count(), 0);
+        assert_eq!(repo.snapshot(&first).unwrap().len(), 1);
+
+        // A second run keeps the earlier packed objects alongside new ones
+        fs::write(dir.path().join("file.txt"), "changed").unwrap();
+        repo.add("file.txt").unwrap();
+        let second = repo.commit("Tester".to_string(), "Second".to_string()).unwrap();
+        let stats = garbage_collect(&repo, false).unwrap();
+        assert_eq!(stats.objects_packed, 2);
+        assert_eq!(stats.objects_remaining, 4);
+
+        let old = repo.snapshot(&first).unwrap();
+        let new = repo.snapshot(&second).unwrap();
+        assert_eq!(repo.get_store().get_blob(&old["file.txt"]).unwrap().content, b"hello");
+        assert_eq!(repo.get_store().get_blob(&new["file.txt"]).unwrap().content, b"changed");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_commit_runs_hooks() {
+        let (dir, repo, first) = repo_with_commit();
+        let hooks = HookManager::new(dir.path()).unwrap();
+        let log = dir.path().join(".mug").join("hook.log");
+        hooks
+            .install(
+                "log",
+                HookType::PreCommit,
+                &format!("#!/bin/sh\necho \"pre $(cat \"$1\") $2\" >> {}", log.display()),
+            )
+            .unwrap();
+        hooks
+            .install(
+                "log",
+                HookType::PostCommit,
+                &format!("#!/bin/sh\necho \"post $1\" >> {}", log.display()),
+            )
+            .unwrap();
+
+        fs::write(dir.path().join("file.txt"), "changed").unwrap();
+        repo.add("file.txt").unwrap();
+        let second = repo.commit("Tester".to_string(), "Second".to_string()).unwrap();
+        assert_eq!(
+            fs::read_to_string(&log).unwrap(),
+            format!("pre Second file.txt\npost {}\n", second)
+        );
+
+        // A failing pre-commit hook aborts and keeps the staged changes
+        hooks
+            .install("reject", HookType::PreCommit, "#!/bin/sh\necho nope >&2\nexit 1")
+            .unwrap();
+        fs::write(dir.path().join("file.txt"), "again").unwrap();
+        repo.add("file.txt").unwrap();
+        let err = repo
+            .commit("Tester".to_string(), "Third".to_string())
+            .unwrap_err();
+        assert!(err.to_string().contains("nope"));
+        assert_eq!(repo.head_commit_id().unwrap(), Some(second.clone()));
+        assert!(Index::new(repo.get_db().clone()).unwrap().contains("file.txt"));
+        assert_ne!(first, second);
+
+        // Unless hooks are skipped
+        let logged = fs::read_to_string(&log).unwrap();
+        let repo = repo.with_hooks(false);
+        let third = repo.commit("Tester".to_string(), "Third".to_string()).unwrap();
+        assert_eq!(repo.head_commit_id().unwrap(), Some(third));
+        assert_eq!(fs::read_to_string(&log).unwrap(), logged);
+    }
+
+    #[test]
+    fn test_verify_repository_reports_issues() {
+        let (dir, repo, first) = repo_with_commit();
+        assert!(verify_repository(&repo, true).unwrap().is_empty());
+
+        // Corrupt the blob on disk
+        let blob = repo.snapshot(&first).unwrap()["file.txt"].clone();
+        fs::write(dir.path().join(".mug/objects").join(&blob), "tampered").unwrap();
+        assert!(verify_repository(&repo, false).unwrap().is_empty());
+        let issues = verify_repository(&repo, true).unwrap();
+        assert!(issues
+            .iter()
+            .any(|i| i == &format!("hash mismatch: object {}", blob)));
+        assert!(issues
+            .iter()
+            .any(|i| i.starts_with("hash mismatch: blob") && i.ends_with("file.txt")));
+
+        // Remove it and point a tag at a commit that doesn't exist
+        fs::remove_file(dir.path().join(".mug/objects").join(&blob)).unwrap();
+        TagManager::new(repo.get_db().clone())
+            .create("bogus".to_string(), "missing-commit".to_string())
+            .unwrap();
+        let issues = verify_repository(&repo, true).unwrap();
+        assert!(issues
+            .iter()
+            .any(|i| i.starts_with("missing object:") && i.ends_with("file.txt")));
+        assert!(issues.iter().any(|i| i.contains("tag 'bogus'")));
+    }
+
+    #[test]
+    fn test_dangling_objects_after_reset() {
+        let (dir, repo, first) = repo_with_commit();
+        assert!(dangling_objects(&repo).unwrap().is_empty());
+
+        fs::write(dir.path().join("file.txt"), "second").unwrap();
+        repo.add("file.txt").unwrap();
+        let second = repo.commit("Tester".to_string(), "Second".to_string()).unwrap();
+        crate::core::reset::reset(&repo, crate::core::reset::ResetMode::Hard, Some(&first)).unwrap();
+        // Staged, then replaced before committing
+        fs::write(dir.path().join("file.txt"), "draft").unwrap();
+        repo.add("file.txt").unwrap();
+        let draft = Index::new(repo.get_db().clone()).unwrap().entries()[0].hash.clone();
+        fs::write(dir.path().join("file.txt"), "final").unwrap();
+        repo.add("file.txt").unwrap();
+
+        // The second commit's tree and blob hang off it, so only the commit is listed
+        assert_eq!(
+            dangling_objects(&repo).unwrap(),
+            vec![(ObjectKind::Commit, second), (ObjectKind::Blob, draft)]
+        );
+    }
+
+    #[test]
+    fn test_config_entries_mark_shadowed_globals() {
+        let (dir, repo, _) = repo_with_commit();
+        let global_path = dir.path().join("global-config");
+        let mut global = GlobalConfig::open(&global_path).unwrap();
+        global.set("user.name", "Global").unwrap();
+        global.set("user.email", "global@example.com").unwrap();
+        repo.set_config("user.name", "Local").unwrap();
+        repo.set_config("remote.origin.token", "secret").unwrap();
+
+        let entries: Vec<_> = repo
+            .config_entries_over(&global)
+            .unwrap()
+            .into_iter()
+            .filter(|e| e.key.starts_with("user.") || e.key.starts_with("remote."))
+            .map(|e| (e.key, e.value, e.origin, e.shadowed))
+            .collect();
+        let db = dir.path().join(".mug/db");
+        assert_eq!(
+            entries,
+            vec![
+                ("remote.origin.token".to_string(), "********".to_string(), ConfigOrigin::Repository(db.clone()), false),
+                ("user.email".to_string(), "global@example.com".to_string(), ConfigOrigin::Global(global_path.clone()), false),
+                ("user.name".to_string(), "Global".to_string(), ConfigOrigin::Global(global_path), true),
+                ("user.name".to_string(), "Local".to_string(), ConfigOrigin::Repository(db), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_discover_searches_parent_directories() {
+        let dir = TempDir::new().unwrap();
+        drop(Repository::init(dir.path()).unwrap());
+        let nested = dir.path().join("src/deep");
+        fs::create_dir_all(&nested).unwrap();
+
+        let repo = Repository::discover(&nested).unwrap();
+        assert_eq!(repo.root(), dir.path().canonicalize().unwrap());
+        drop(repo);
+
+        let outside = TempDir::new().unwrap();
+        assert!(matches!(
+            Repository::discover(outside.path()),
+            Err(Error::NotARepository)
+        ));
+    }
+
+    #[test]
+    fn test_hash_algorithm_is_fixed_per_repo() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init_with_hash(dir.path(), HashAlgorithm::Sha1).unwrap();
+        fs::write(dir.path().join("hello.txt"), "hello\n").unwrap();
+        repo.add("hello.txt").unwrap();
+        let commit_id = repo.commit("Tester".to_string(), "Initial".to_string()).unwrap();
+        drop(repo);
+
+        let repo = Repository::open(dir.path()).unwrap();
+        assert_eq!(repo.hash_algorithm(), HashAlgorithm::Sha1);
+        // Same blob id git gives the file
+        assert_eq!(
+            repo.snapshot(&commit_id).unwrap()["hello.txt"],
+            "ce013625030ba8dba906f756967f9e9ca394464a"
+        );
+        assert!(verify_repository(&repo, true).unwrap().is_empty());
+
+        assert!(repo.set_config(HashAlgorithm::CONFIG_KEY, "sha256").is_err());
+        assert!(repo.adopt_hash_algorithm(HashAlgorithm::Sha256).is_err());
+    }
+
+    #[test]
+    fn test_large_files_are_stored_as_pointers() {
+        let dir = TempDir::new().unwrap();
+        let config = StoreConfig {
+            large_file_threshold_bytes: 16,
+            // Nothing listens here, so uploads and downloads fail
+            central_server: Some("http://127.0.0.1:9/repo/none".to_string()),
+            ..StoreConfig::default()
+        };
+        let repo = Repository::init(dir.path()).unwrap().with_store_config(config);
+
+        let content = b"large file content, over the threshold";
+        fs::write(dir.path().join("big.bin"), content).unwrap();
+        assert!(repo.add("big.bin").is_err());
+
+        // As if the upload had gone through: a cached copy and a pointer
+        let hash = repo.hash_algorithm().hash_blob(content);
+        fs::create_dir_all(dir.path().join(".mug/cache")).unwrap();
+        fs::write(dir.path().join(".mug/cache").join(&hash), content).unwrap();
+        repo.get_store()
+            .store_pointer(&LargeFilePointer { hash: hash.clone(), size: content.len() as u64 })
+            .unwrap();
+        Index::new(repo.get_db().clone()).unwrap().add("big.bin".to_string(), hash.clone()).unwrap();
+        let commit_id = repo.commit("Tester".to_string(), "Add big file".to_string()).unwrap();
+
+        let stored = repo.get_store().get_blob(&hash).unwrap().content;
+        assert!(LargeFilePointer::parse(&stored).is_some());
+        assert!(verify_repository(&repo, true).unwrap().is_empty());
+
+        fs::remove_file(dir.path().join("big.bin")).unwrap();
+        let snapshot = repo.snapshot(&commit_id).unwrap();
+        repo.update_working_tree(&HashMap::new(), &snapshot, &FileModes::new()).unwrap();
+        assert_eq!(fs::read(dir.path().join("big.bin")).unwrap(), content);
+        assert_eq!(repo.store_manager().cache_stats().hits, 1);
+    }
+
+    #[test]
+    fn test_store_settings_are_saved_in_config() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        assert!(repo.set_config(StoreConfig::CACHE_LIMIT_KEY, "lots").is_err());
+        assert!(repo.set_config(StoreConfig::THRESHOLD_KEY, "-1").is_err());
+        repo.set_config(StoreConfig::CACHE_LIMIT_KEY, "5242880").unwrap();
+        repo.set_config(StoreConfig::THRESHOLD_KEY, "1024").unwrap();
+        repo.set_config(StoreConfig::SERVER_KEY, "http://store.example.com/repo").unwrap();
+        drop(repo);
+
+        let repo = Repository::open(dir.path()).unwrap();
+        let manager = repo.store_manager();
+        assert_eq!(manager.cache_limit(), 5 * 1024 * 1024);
+        assert_eq!(manager.large_file_threshold(), 1024);
+        assert_eq!(manager.central_server(), Some("http://store.example.com/repo"));
     }
 }
@@ -1,9 +1,11 @@
-use std::fs;
-use std::path::Path;
+use std::collections::HashMap;
 
+use crate::core::branch::BranchManager;
 use crate::core::error::Result;
 use crate::core::index::Index;
+use crate::core::reflog::Reflog;
 use crate::core::repo::Repository;
+use crate::core::sequencer::OperationState;
 
 /// Reset mode determines what gets reset
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -30,60 +32,64 @@ impl ResetMode {
     }
 }
 
-/// Reset repository to a previous commit
+/// Reset the current branch (or detached HEAD) to a revision.
+///
+/// Soft only moves HEAD; mixed also clears the index; hard also discards
+/// working tree changes to tracked files and abandons any conflicted merge,
+/// rebase, or cherry-pick. HEAD's move is recorded in the reflog, so a
+/// reset can be undone with `reset --hard HEAD@{1}`.
 pub fn reset(repo: &Repository, mode: ResetMode, commit_id: Option<&str>) -> Result<()> {
-    let target_commit = commit_id.unwrap_or("HEAD");
+    let spec = commit_id.unwrap_or("HEAD");
+    let target_commit = repo.resolve_rev(spec)?;
+    let old_head = repo.head_commit_id()?;
 
-    // Get the target commit's state
-    let commits = repo.log()?;
-    let mut target_found = false;
+    let staged: Vec<String> = Index::new(repo.get_db().clone())?.paths();
+    let operation = format!("reset: moving to {}", spec);
+    let current_branch = repo.current_branch()?;
+    let branch_manager = BranchManager::new(repo.get_db().clone());
 
-    for commit_entry in commits {
-        if commit_entry.contains(target_commit) {
-            target_found = true;
-            break;
+    // Move HEAD, clear the staging area and abandon any conflicted
+    // operation together so an interrupted reset leaves nothing half-done
+    repo.get_db().transaction(|tx| {
+        match current_branch.as_deref() {
+            Some(branch) => {
+                branch_manager.update_branch_in(tx, branch, target_commit.clone(), &operation)?
+            }
+            None => {
+                tx.set("HEAD", "HEAD", format!("detached:{}", target_commit))?;
+                Reflog::new(repo.get_db().clone()).record_in(
+                    tx,
+                    "HEAD",
+                    old_head.as_deref().unwrap_or_default(),
+                    &target_commit,
+                    &operation,
+                )?;
+            }
         }
-    }
-
-    if !target_found {
-        return Err(crate::core::error::Error::CommitNotFound(
-            target_commit.to_string(),
-        ));
-    }
-
-    match mode {
-        ResetMode::Soft => {
-            // Soft reset: only change HEAD, keep index and working directory
-            // This would move the HEAD pointer (not fully implemented in this simplified version)
-            eprintln!("Soft reset to {} (HEAD moved)", target_commit);
+        if mode != ResetMode::Soft {
+            for path in &staged {
+                tx.delete("INDEX", path)?;
+            }
         }
-        ResetMode::Mixed => {
-            // Mixed reset: change HEAD and index, keep working directory
-            // Clear the index to match HEAD state
-            let mut index = Index::new(repo.get_db().clone())?;
-            index.clear()?;
-            eprintln!("Mixed reset to {} (index cleared)", target_commit);
+        if mode == ResetMode::Hard {
+            OperationState::clear_in(tx)?;
         }
-        ResetMode::Hard => {
-            // Hard reset: change HEAD, index, and working directory
-            let mut index = Index::new(repo.get_db().clone())?;
-            index.clear()?;
-
-            // Remove tracked files from working directory
-            for entry in index.entries() {
-                if Path::new(&entry.path).exists() {
-                    let _ = fs::remove_file(&entry.path);
-                }
-            }
+        Ok(())
+    })?;
 
-            eprintln!(
-                "Hard reset to {} (working directory cleaned)",
-                target_commit
-            );
+    if mode == ResetMode::Hard {
+        let (target, modes) = repo.snapshot_with_modes(&target_commit)?;
+        // Blank hashes make every tracked or staged file differ from the
+        // target, so local edits are overwritten and files it lacks removed
+        let mut from: HashMap<String, String> =
+            staged.into_iter().map(|path| (path, String::new())).collect();
+        if let Some(head) = &old_head {
+            from.extend(repo.snapshot(head)?.into_keys().map(|path| (path, String::new())));
         }
+        repo.overwrite_working_tree(&from, &target, &modes)?;
     }
 
-    Ok(())
+    repo.get_db().flush()
 }
 
 #[cfg(test)]
@@ -97,4 +103,75 @@ mod tests {
         assert_eq!(ResetMode::from_str("hard").unwrap(), ResetMode::Hard);
         assert!(ResetMode::from_str("invalid").is_err());
     }
+
+    #[test]
+    fn test_hard_reset_is_recoverable_from_reflog() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let file = dir.path().join("file.txt");
+        std::fs::write(&file, "one\n").unwrap();
+        repo.add("file.txt").unwrap();
+        let first = repo.commit("Tester".to_string(), "First".to_string()).unwrap();
+        std::fs::write(&file, "two\n").unwrap();
+        repo.add("file.txt").unwrap();
+        let second = repo.commit("Tester".to_string(), "Second".to_string()).unwrap();
+
+        std::fs::write(&file, "local edit\n").unwrap();
+        reset(&repo, ResetMode::Hard, Some("HEAD~1")).unwrap();
+        assert_eq!(repo.head_commit_id().unwrap(), Some(first.clone()));
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "one\n");
+
+        assert_eq!(repo.resolve_rev("HEAD@{0}").unwrap(), first);
+        assert_eq!(repo.resolve_rev("HEAD@{1}").unwrap(), second);
+        reset(&repo, ResetMode::Hard, Some("HEAD@{1}")).unwrap();
+        assert_eq!(repo.head_commit_id().unwrap(), Some(second.clone()));
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "two\n");
+        assert_eq!(repo.resolve_rev("main@{2}~1").unwrap(), first);
+        assert!(repo.resolve_rev("HEAD@{99}").is_err());
+    }
+
+    #[test]
+    fn test_soft_reset_keeps_index_and_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let file = dir.path().join("file.txt");
+        std::fs::write(&file, "one\n").unwrap();
+        repo.add("file.txt").unwrap();
+        let first = repo.commit("Tester".to_string(), "First".to_string()).unwrap();
+        std::fs::write(&file, "two\n").unwrap();
+        repo.add("file.txt").unwrap();
+        repo.commit("Tester".to_string(), "Second".to_string()).unwrap();
+
+        std::fs::write(&file, "three\n").unwrap();
+        repo.add("file.txt").unwrap();
+        reset(&repo, ResetMode::Soft, Some("HEAD~1")).unwrap();
+        assert_eq!(repo.head_commit_id().unwrap(), Some(first));
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "three\n");
+        assert!(!Index::new(repo.get_db().clone()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_mixed_reset_on_detached_head_clears_index() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let file = dir.path().join("file.txt");
+        std::fs::write(&file, "one\n").unwrap();
+        repo.add("file.txt").unwrap();
+        let first = repo.commit("Tester".to_string(), "First".to_string()).unwrap();
+        std::fs::write(&file, "two\n").unwrap();
+        repo.add("file.txt").unwrap();
+        let second = repo.commit("Tester".to_string(), "Second".to_string()).unwrap();
+        BranchManager::new(repo.get_db().clone()).detach_head(second.clone()).unwrap();
+
+        std::fs::write(&file, "three\n").unwrap();
+        repo.add("file.txt").unwrap();
+        reset(&repo, ResetMode::Mixed, Some(&first)).unwrap();
+        assert_eq!(repo.head_commit_id().unwrap(), Some(first.clone()));
+        assert_eq!(repo.current_branch().unwrap(), None);
+        assert_eq!(repo.resolve_rev("HEAD@{1}").unwrap(), second);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "three\n");
+        assert!(Index::new(repo.get_db().clone()).unwrap().is_empty());
+        let main = BranchManager::new(repo.get_db().clone()).get_branch("main").unwrap();
+        assert_eq!(main.map(|b| b.commit_id), Some(second));
+    }
 }
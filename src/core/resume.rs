@@ -184,6 +184,24 @@ impl OperationManager {
         }
     }
 
+    /// Look up an operation by its full ID or a unique prefix of it, such
+    /// as the shortened IDs shown by `mug resume list`
+    pub fn find(&self, id: &str) -> Result<Option<Operation>> {
+        if let Some(op) = self.get(id)? {
+            return Ok(Some(op));
+        }
+        let mut matches = self.list(None)?;
+        matches.retain(|op| op.id.starts_with(id));
+        match matches.len() {
+            0 => Ok(None),
+            1 => Ok(matches.pop()),
+            n => Err(crate::core::error::Error::Custom(format!(
+                "Operation ID '{}' is ambiguous ({} matches)",
+                id, n
+            ))),
+        }
+    }
+
     /// Update operation status
     pub fn update_status(&self, op_id: &str, status: OperationStatus) -> Result<()> {
         if let Some(mut op) = self.get(op_id)? {
@@ -337,6 +355,7 @@ impl OperationManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tempfile::TempDir;
 
     #[test]
     fn test_operation_progress_percentage() {
@@ -374,4 +393,22 @@ mod tests {
         assert_eq!(OperationStatus::Completed.as_str(), "completed");
         assert_eq!(OperationStatus::Failed.as_str(), "failed");
     }
+
+    #[test]
+    fn test_find_by_prefix() {
+        let dir = TempDir::new().unwrap();
+        let manager = OperationManager::new(MugDb::new(dir.path().join("db")).unwrap());
+        let op = manager
+            .create(OperationType::Fetch, String::new(), Default::default())
+            .unwrap();
+
+        assert_eq!(manager.find(&op.id).unwrap().unwrap().id, op.id);
+        assert_eq!(manager.find(&op.id[..16]).unwrap().unwrap().id, op.id);
+        assert!(manager.find("op-missing").unwrap().is_none());
+
+        manager
+            .create(OperationType::Push, String::new(), Default::default())
+            .unwrap();
+        assert!(manager.find("op-").is_err());
+    }
 }
@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::branch::BranchManager;
+use crate::core::commit::CommitLog;
+use crate::core::database::Transaction;
+use crate::core::error::{Error, Result};
+use crate::core::file_mode;
+use crate::core::index::Index;
+use crate::core::merge::conflict_hunks;
+use crate::core::rebase_tui::RebaseAction;
+use crate::core::repo::Repository;
+
+const OPERATION_TREE: &str = "OPERATION";
+const OPERATION_KEY: &str = "current";
+
+/// An operation that can stop part way through on conflicts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OperationKind {
+    Merge,
+    Rebase,
+    CherryPick,
+}
+
+impl fmt::Display for OperationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OperationKind::Merge => write!(f, "merge"),
+            OperationKind::Rebase => write!(f, "rebase"),
+            OperationKind::CherryPick => write!(f, "cherry-pick"),
+        }
+    }
+}
+
+/// A merge, rebase, or cherry-pick halted by conflicts, kept until it is
+/// continued or aborted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationState {
+    pub kind: OperationKind,
+    pub branch: String,
+    /// Branch tip before the operation started
+    pub orig_head: String,
+    /// Branch merged, branch rebased onto, or commit picked
+    pub target: String,
+    /// Files written to the working tree, with conflict markers in the
+    /// conflicted paths
+    pub files: HashMap<String, String>,
+    pub conflicts: Vec<String>,
+    /// Author, message, and parents of the commit that concludes the
+    /// conflicted step
+    pub author: String,
+    pub message: String,
+    pub parent: Option<String>,
+    pub merge_head: Option<String>,
+    /// Open the editor on `message` before committing (rebase reword)
+    #[serde(default)]
+    pub edit_message: bool,
+    /// Commits still to apply once the conflicted step is committed
+    #[serde(default)]
+    pub remaining: Vec<(String, RebaseAction)>,
+    /// Commits applied so far, including the conflicted one
+    #[serde(default)]
+    pub applied: usize,
+}
+
+impl OperationState {
+    /// The operation in progress, if any
+    pub fn load(repo: &Repository) -> Result<Option<Self>> {
+        match repo.get_db().get(OPERATION_TREE, OPERATION_KEY)? {
+            Some(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The operation in progress, which must be of the given kind
+    pub fn load_kind(repo: &Repository, kind: OperationKind) -> Result<Self> {
+        match Self::load(repo)? {
+            Some(state) if state.kind == kind => Ok(state),
+            Some(state) => Err(Error::Custom(format!(
+                "A {} is in progress, not a {}",
+                state.kind, kind
+            ))),
+            None => Err(Error::Custom(format!("No {} in progress", kind))),
+        }
+    }
+
+    pub fn save(&self, repo: &Repository) -> Result<()> {
+        repo.get_db()
+            .set(OPERATION_TREE, OPERATION_KEY, serde_json::to_vec(self)?)?;
+        repo.get_db().flush()
+    }
+
+    pub fn clear(repo: &Repository) -> Result<()> {
+        repo.get_db().delete(OPERATION_TREE, OPERATION_KEY)?;
+        repo.get_db().flush()
+    }
+
+    /// Clear the operation in progress as part of a transaction
+    pub fn clear_in(tx: &Transaction) -> Result<()> {
+        tx.delete(OPERATION_TREE, OPERATION_KEY)
+    }
+}
+
+/// Fail if a conflicted operation is waiting to be continued or aborted
+pub fn ensure_no_operation(repo: &Repository) -> Result<()> {
+    match OperationState::load(repo)? {
+        Some(state) => Err(Error::Custom(format!(
+            "A {kind} is in progress; run 'mug {kind} --continue' or 'mug {kind} --abort'",
+            kind = state.kind
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// The resolved files of a halted operation: the recorded result with the
+/// conflicted paths read back from the working tree and anything staged
+/// since applied on top. Fails while conflict markers remain.
+pub fn resolved_files(repo: &Repository, state: &OperationState) -> Result<HashMap<String, String>> {
+    let mut unresolved = Vec::new();
+    for path in &state.conflicts {
+        file_mode::check_no_symlink_parents(repo.root_path(), path)?;
+        let file_path = repo.root_path().join(path);
+        if !file_path.exists() {
+            Index::new(repo.get_db().clone())?.stage_removal(path)?;
+            continue;
+        }
+        let content = std::fs::read(&file_path)?;
+        if let Ok(text) = std::str::from_utf8(&content) {
+            if !conflict_hunks(path, text).is_empty() {
+                unresolved.push(path.clone());
+                continue;
+            }
+        }
+        repo.add(path)?;
+    }
+
+    if !unresolved.is_empty() {
+        return Err(Error::Custom(format!(
+            "Conflict markers remain in: {}",
+            unresolved.join(", ")
+        )));
+    }
+
+    let mut files = state.files.clone();
+    for entry in Index::new(repo.get_db().clone())?.entries() {
+        if entry.is_removal() {
+            files.remove(&entry.path);
+        } else {
+            files.insert(entry.path, entry.hash);
+        }
+    }
+    Ok(files)
+}
+
+/// Clear the index and the recorded operation once it has concluded
+pub fn finish(repo: &Repository) -> Result<()> {
+    let mut index = Index::new(repo.get_db().clone())?;
+    index.clear()?;
+    index.flush()?;
+    OperationState::clear(repo)
+}
+
+/// Abandon the operation in progress, restoring the branch, working tree,
+/// and index to how they were before it started
+pub fn abort(repo: &Repository, kind: OperationKind) -> Result<OperationState> {
+    let state = OperationState::load_kind(repo, kind)?;
+
+    let (orig_files, modes) = repo.snapshot_with_modes(&state.orig_head)?;
+    repo.overwrite_working_tree(&state.files, &orig_files, &modes)?;
+    // Conflicted paths may have been edited since, so always rewrite them
+    let mut large_files = None;
+    for path in &state.conflicts {
+        file_mode::check_no_symlink_parents(repo.root_path(), path)?;
+        let file_path = repo.root_path().join(path);
+        match orig_files.get(path) {
+            Some(hash) => {
+                let mode = modes.get(path).copied().unwrap_or(file_mode::REGULAR);
+                file_mode::write(&file_path, &repo.file_content(hash, &mut large_files)?, mode)?
+            }
+            None if file_path.exists() => std::fs::remove_file(&file_path)?,
+            None => {}
+        }
+    }
+
+    let head = BranchManager::new(repo.get_db().clone())
+        .get_branch(&state.branch)?
+        .map(|branch| branch.commit_id);
+    if head.as_deref() != Some(state.orig_head.as_str()) {
+        BranchManager::new(repo.get_db().clone()).update_branch_with_reason(
+            &state.branch,
+            state.orig_head.clone(),
+            &format!("{} --abort", state.kind),
+        )?;
+    }
+
+    finish(repo)?;
+    Ok(state)
+}
+
+/// Store the resolved files and create the commit that concludes the
+/// conflicted step. Unless `move_branch` is false, the branch is moved to it.
+pub fn commit_resolution(
+    repo: &Repository,
+    state: &OperationState,
+    files: &HashMap<String, String>,
+    message: String,
+    move_branch: bool,
+) -> Result<String> {
+    // Files keep the mode of the side their content came from
+    let target = repo.resolve_rev(&state.target).ok();
+    let sources: Vec<&str> = [state.parent.as_deref(), state.merge_head.as_deref(), target.as_deref()]
+        .into_iter()
+        .flatten()
+        .collect();
+    let modes = repo.carry_modes(files, &sources)?;
+    let tree_hash = repo.store_snapshot_with_modes(files, &modes)?;
+    let signing_key = repo.default_signing_key()?;
+    let commit = CommitLog::prepare_commit(
+        tree_hash,
+        state.author.clone(),
+        message,
+        state.parent.clone(),
+        state.merge_head.clone(),
+        signing_key.as_ref(),
+    )?;
+    let serialized = serde_json::to_vec(&commit)?;
+
+    let summary = commit.message.lines().next().unwrap_or_default().to_string();
+    let branch_manager = BranchManager::new(repo.get_db().clone());
+    repo.get_db().transaction(|tx| {
+        tx.set("COMMITS", &commit.id, &serialized)?;
+        if move_branch {
+            branch_manager.update_branch_in(
+                tx,
+                &state.branch,
+                commit.id.clone(),
+                &format!("{} (continue): {}", state.kind, summary),
+            )?;
+        }
+        Ok(())
+    })?;
+    repo.get_db().flush()?;
+    Ok(commit.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::cherry_pick::cherry_pick;
+
+    #[test]
+    fn test_abort_restores_branch_and_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let file = dir.path().join("file.txt");
+        let commit = |content: &str, message: &str| {
+            std::fs::write(&file, content).unwrap();
+            repo.add("file.txt").unwrap();
+            repo.commit("Tester".to_string(), message.to_string()).unwrap()
+        };
+
+        commit("base\n", "Base");
+        repo.create_branch("feature".to_string()).unwrap();
+        let head = commit("main\n", "Main");
+        repo.checkout("feature".to_string()).unwrap();
+        let picked = commit("feature\n", "Feature");
+        repo.checkout("main".to_string()).unwrap();
+
+        assert!(!cherry_pick(&repo, &picked).unwrap().success);
+        assert!(abort(&repo, OperationKind::Merge).is_err());
+        std::fs::write(&file, "half resolved\n").unwrap();
+
+        let state = abort(&repo, OperationKind::CherryPick).unwrap();
+        assert_eq!(state.target, picked);
+        assert_eq!(repo.head_commit_id().unwrap(), Some(head));
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "main\n");
+        assert!(Index::new(repo.get_db().clone()).unwrap().is_empty());
+        assert!(OperationState::load(&repo).unwrap().is_none());
+        ensure_no_operation(&repo).unwrap();
+    }
+}
@@ -2,6 +2,7 @@
 /// Allows cloning/checking out only specific directories
 
 use crate::core::error::{Error, Result};
+use crate::core::file_mode;
 use crate::core::repo::Repository;
 use serde::{Deserialize, Serialize};
 use std::fs;
@@ -38,6 +39,31 @@ impl SparseConfig {
         }
     }
 
+    /// A config materializing only paths matching `patterns`. A bare
+    /// directory such as `services/api` includes everything under it.
+    pub fn from_patterns(patterns: &[String]) -> Self {
+        Self {
+            includes: patterns
+                .iter()
+                .map(|p| p.trim_start_matches("./").trim_end_matches('/').to_string())
+                .filter(|p| !p.is_empty())
+                .collect(),
+            excludes: vec![],
+            cone_mode: true,
+        }
+    }
+
+    /// Whether the repository-relative `path` belongs in the working tree.
+    /// In cone mode files at the top level, such as .mugignore, always do.
+    pub fn matches(&self, path: &str) -> bool {
+        if self.cone_mode && !path.contains('/') {
+            return true;
+        }
+        // Excludes take precedence
+        !self.excludes.iter().any(|p| matches_pattern(path, p))
+            && self.includes.iter().any(|p| matches_pattern(path, p))
+    }
+
     /// Add include pattern
     pub fn add_include(&mut self, pattern: String) {
         self.includes.push(pattern);
@@ -57,6 +83,15 @@ impl SparseConfig {
         Ok(())
     }
 
+    /// Remove .mug/sparse-checkout, so every path is materialized again
+    pub fn clear(repo: &Repository) -> Result<()> {
+        let sparse_file = repo.root_path().join(".mug/sparse-checkout");
+        if sparse_file.exists() {
+            fs::remove_file(sparse_file)?;
+        }
+        Ok(())
+    }
+
     /// Load from .mug/sparse-checkout
     pub fn load(repo: &Repository) -> Result<Option<Self>> {
         let sparse_file = repo.root_path().join(".mug/sparse-checkout");
@@ -70,6 +105,40 @@ impl SparseConfig {
     }
 }
 
+/// Simple glob pattern matching: `*` matches everything, `dir/**` and a
+/// bare `dir` match everything under the directory, and other patterns may
+/// use `*` wildcards
+fn matches_pattern(path: &str, pattern: &str) -> bool {
+    if pattern == "*" {
+        return true;
+    }
+
+    if let Some(dir) = pattern.strip_suffix("/**") {
+        return path == dir || path.starts_with(&format!("{}/", dir));
+    }
+
+    if pattern.contains('*') {
+        let pattern = regex::escape(pattern).replace("\\*", ".*");
+        if let Ok(re) = regex::Regex::new(&format!("^{}$", pattern)) {
+            return re.is_match(path);
+        }
+    }
+
+    path == pattern || path.starts_with(&format!("{}/", pattern))
+}
+
+/// Remove the directories above a deleted file that are left empty, up to
+/// the repository root
+fn remove_empty_parents(root: &Path, file_path: &Path) {
+    let mut dir = file_path.parent();
+    while let Some(current) = dir {
+        if current == root || fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+}
+
 /// Sparse checkout manager
 pub struct SparseCheckout {
     config: SparseConfig,
@@ -82,86 +151,44 @@ impl SparseCheckout {
         Self { config, repo }
     }
 
-    /// Check if path should be included in checkout
+    /// Check if the repository-relative path should be included in checkout
     pub fn should_include(&self, path: &Path) -> bool {
-        let path_str = path.to_string_lossy();
-
-        // Check excludes first (they take precedence)
-        for exclude in &self.config.excludes {
-            if self.matches_pattern(&path_str, exclude) {
-                return false;
-            }
-        }
-
-        // Check includes
-        for include in &self.config.includes {
-            if self.matches_pattern(&path_str, include) {
-                return true;
-            }
-        }
-
-        false
-    }
-
-    /// Simple glob pattern matching
-    fn matches_pattern(&self, path: &str, pattern: &str) -> bool {
-        // Handle common patterns
-        if pattern == "*" {
-            return true;
-        }
-
-        if pattern.ends_with("/**") {
-            // Match directory and all contents
-            let dir = pattern.trim_end_matches("/**");
-            return path.starts_with(dir);
-        }
-
-        if pattern.contains('*') {
-            // Simple wildcard matching
-            let pattern = pattern.replace("*", ".*");
-            if let Ok(re) = regex::Regex::new(&format!("^{}$", pattern)) {
-                return re.is_match(path);
-            }
-        }
-
-        path == pattern
+        self.config.matches(&path.to_string_lossy())
     }
 
-    /// Apply sparse checkout - removes files not in sparse config
-    pub fn apply(&self) -> Result<()> {
-        let mut to_remove = Vec::new();
-
-        // Find files to remove
-        for entry in walkdir::WalkDir::new(self.repo.root_path())
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
-            let path = entry.path();
-
-            // Skip .mug directory
-            if path.components().any(|c| c.as_os_str() == ".mug") {
-                continue;
-            }
-
-            // Skip directories
-            if path.is_dir() {
-                continue;
-            }
-
-            // Check if should be removed
-            if !self.should_include(path) {
-                to_remove.push(path.to_path_buf());
+    /// Bring the working tree in line with the config: write matching HEAD
+    /// files that are missing and delete the rest. Files outside the config
+    /// with local changes are kept and returned.
+    pub fn apply(&self) -> Result<Vec<String>> {
+        let (head, modes) = match self.repo.head_commit_id()? {
+            Some(id) => self.repo.snapshot_with_modes(&id)?,
+            None => Default::default(),
+        };
+        let root = self.repo.root_path();
+
+        let mut kept = Vec::new();
+        let mut large_files = None;
+        let mut paths: Vec<_> = head.iter().collect();
+        paths.sort();
+        for (path, hash) in paths {
+            file_mode::check_no_symlink_parents(root, path)?;
+            let file_path = root.join(path);
+            if self.should_include(Path::new(path)) {
+                if !file_mode::exists(&file_path) {
+                    let mode = modes.get(path).copied().unwrap_or(file_mode::REGULAR);
+                    let content = self.repo.file_content(hash, &mut large_files)?;
+                    file_mode::write(&file_path, &content, mode)?;
+                }
+            } else if file_mode::exists(&file_path) && !file_path.is_dir() {
+                if &self.repo.hash_algorithm().hash_file(&file_path)? != hash {
+                    kept.push(path.clone());
+                    continue;
+                }
+                fs::remove_file(&file_path)?;
+                remove_empty_parents(root, &file_path);
             }
         }
-
-        // Remove files not in sparse config
-        for path in to_remove {
-            if let Err(e) = fs::remove_file(&path) {
-                eprintln!("Warning: Failed to remove {}: {}", path.display(), e);
-            }
-        }
-
-        Ok(())
+        Ok(kept)
     }
 
     /// Get current sparse config
@@ -198,16 +225,42 @@ mod tests {
 
     #[test]
     fn test_pattern_matching() {
-        let repo = Repository::open(".").unwrap_or_else(|_| {
-            Repository::init(".mug_test").expect("Failed to create test repo")
-        });
-        let checkout = SparseCheckout::new(
-            repo,
-            SparseConfig::for_monorepo(&["src"]),
-        );
-
-        assert!(checkout.matches_pattern("src/main.rs", "src/**"));
-        assert!(checkout.matches_pattern("src/lib/mod.rs", "src/**"));
-        assert!(!checkout.matches_pattern("docs/readme.md", "src/**"));
+        assert!(matches_pattern("src/main.rs", "src/**"));
+        assert!(matches_pattern("src/lib/mod.rs", "src/**"));
+        assert!(!matches_pattern("docs/readme.md", "src/**"));
+        assert!(matches_pattern("src/lib/mod.rs", "src/lib"));
+        assert!(!matches_pattern("src/library.rs", "src/lib"));
+        assert!(matches_pattern("docs/readme.md", "docs/*.md"));
+    }
+
+    #[test]
+    fn test_sparse_checkout_leaves_other_paths_off_disk() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        for path in ["services/api/main.rs", "libs/util.rs", "README.md"] {
+            let file = dir.path().join(path);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(&file, path).unwrap();
+        }
+        repo.add_all().unwrap();
+        repo.commit("Tester".to_string(), "Base".to_string()).unwrap();
+        repo.create_branch("feature".to_string()).unwrap();
+
+        let config = SparseConfig::from_patterns(&["services/api/".to_string()]);
+        assert!(SparseCheckout::new(repo.clone(), config.clone()).apply().unwrap().is_empty());
+        config.save(&repo).unwrap();
+        assert!(dir.path().join("services/api/main.rs").exists());
+        assert!(dir.path().join("README.md").exists());
+        assert!(!dir.path().join("libs").exists());
+        assert!(repo.status().unwrap().porcelain().is_empty());
+
+        // Switching branches writes only matching paths
+        repo.checkout("feature".to_string()).unwrap();
+        fs::write(dir.path().join("README.md"), "edited").unwrap();
+        repo.add("README.md").unwrap();
+        let id = repo.commit("Tester".to_string(), "Edit".to_string()).unwrap();
+        assert!(repo.snapshot(&id).unwrap().contains_key("libs/util.rs"));
+        repo.checkout("main".to_string()).unwrap();
+        assert!(!dir.path().join("libs").exists());
     }
 }
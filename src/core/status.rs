@@ -1,10 +1,12 @@
 use std::collections::HashMap;
 use std::path::Path;
 
+use serde::Serialize;
 use walkdir::WalkDir;
 
+use crate::core::diff::{self, Diff};
 use crate::core::error::Result;
-use crate::core::hash;
+use crate::core::hash::HashAlgorithm;
 use crate::core::ignore::IgnoreRules;
 use crate::core::index::Index;
 
@@ -35,11 +37,44 @@ pub struct FileStatusInfo {
     pub status: FileStatus,
 }
 
+/// Status summary for machine-readable output
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StatusReport {
+    pub branch: String,
+    /// Paths whose staged content differs from HEAD
+    pub staged: Vec<String>,
+    /// Tracked paths changed or deleted in the working tree
+    pub modified: Vec<String>,
+    pub untracked: Vec<String>,
+}
+
+/// A changed path with git-style porcelain codes: `index` compares the
+/// staging area to HEAD and `worktree` compares the working tree to the
+/// staging area. Untracked paths use `??`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PorcelainEntry {
+    pub index: char,
+    pub worktree: char,
+    pub path: String,
+    /// Original path of a renamed file (`R`)
+    pub orig_path: Option<String>,
+}
+
+impl std::fmt::Display for PorcelainEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.orig_path {
+            Some(orig) => write!(f, "{}{} {} -> {}", self.index, self.worktree, orig, self.path),
+            None => write!(f, "{}{} {}", self.index, self.worktree, self.path),
+        }
+    }
+}
+
 pub struct Status {
     staged: HashMap<String, String>,   // path -> hash (ready to commit)
     working: HashMap<String, String>,  // path -> hash (current state)
     previous: HashMap<String, String>, // path -> hash (last commit)
     ignore_rules: IgnoreRules,         // patterns to exclude from tracking
+    renames: HashMap<String, String>,  // new path -> old path
 }
 
 impl Status {
@@ -49,17 +84,36 @@ impl Status {
             working: HashMap::new(),
             previous: HashMap::new(),
             ignore_rules: IgnoreRules::new(),
+            renames: HashMap::new(),
         }
     }
 
     /// Build status from index and working directory
     pub fn from_index_and_wd(index: &Index, repo_path: &Path) -> Result<Self> {
         let ignore_rules = IgnoreRules::load_from_repo(repo_path).unwrap_or_default();
+        Self::from_head_index_and_wd(
+            HashMap::new(),
+            index,
+            repo_path,
+            ignore_rules,
+            HashAlgorithm::default(),
+        )
+    }
+
+    /// Build status comparing the HEAD snapshot, index and working directory
+    pub fn from_head_index_and_wd(
+        previous: HashMap<String, String>,
+        index: &Index,
+        repo_path: &Path,
+        ignore_rules: IgnoreRules,
+        algorithm: HashAlgorithm,
+    ) -> Result<Self> {
         let mut status = Status {
             staged: HashMap::new(),
             working: HashMap::new(),
-            previous: HashMap::new(),
+            previous,
             ignore_rules,
+            renames: HashMap::new(),
         };
 
         // Load staged changes from index
@@ -68,11 +122,18 @@ impl Status {
         }
 
         // Scan working directory
-        for entry in WalkDir::new(repo_path)
+        let ignore_rules = &status.ignore_rules;
+        let entries: Vec<_> = WalkDir::new(repo_path)
             .into_iter()
+            .filter_entry(|e| {
+                let rel_path = e.path().strip_prefix(repo_path).unwrap_or(e.path());
+                !e.file_type().is_dir() || !ignore_rules.should_ignore_dir(&rel_path.to_string_lossy())
+            })
             .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_file())
-        {
+            .filter(|e| e.file_type().is_file() || e.file_type().is_symlink())
+            .collect();
+
+        for entry in entries {
             let path = entry.path();
 
             // Skip .mug directory
@@ -88,7 +149,7 @@ impl Status {
                     continue;
                 }
 
-                if let Ok(hash) = hash::hash_file(path) {
+                if let Ok(hash) = algorithm.hash_file(path) {
                     status.working.insert(path_str, hash);
                 }
             }
@@ -108,7 +169,7 @@ impl Status {
             let working_hash = self.working.get(path);
             let prev_hash = self.previous.get(path);
 
-            let status = if working_hash.is_none() {
+            let status = if staged_hash.is_empty() || working_hash.is_none() {
                 FileStatus::Deleted
             } else if prev_hash.is_none() {
                 FileStatus::Added
@@ -137,6 +198,149 @@ impl Status {
         results
     }
 
+    /// Pair committed files that are deleted (staged or on disk) with newly
+    /// staged files of the same or similar content, reporting them as
+    /// renames. `load` returns a blob's content by hash.
+    pub fn detect_renames<F>(&mut self, threshold: f32, load: F)
+    where
+        F: FnMut(&str) -> Option<Vec<u8>>,
+    {
+        let deleted = self
+            .previous
+            .iter()
+            .filter(|(path, _)| match self.staged.get(*path) {
+                Some(staged) => staged.is_empty(),
+                None => !self.working.contains_key(*path),
+            })
+            .map(|(path, hash)| (path.clone(), hash.clone()));
+        let added = self
+            .staged
+            .iter()
+            .filter(|(path, hash)| !hash.is_empty() && !self.previous.contains_key(*path))
+            .map(|(path, hash)| (path.clone(), hash.clone()));
+
+        let diffs = deleted
+            .map(|(path, old_hash)| Diff {
+                path,
+                old_hash,
+                new_hash: String::new(),
+                lines_added: 0,
+                lines_removed: 0,
+                binary: false,
+                renamed_from: None,
+            })
+            .chain(added.map(|(path, new_hash)| Diff {
+                path,
+                old_hash: String::new(),
+                new_hash,
+                lines_added: 0,
+                lines_removed: 0,
+                binary: false,
+                renamed_from: None,
+            }))
+            .collect();
+
+        self.renames = diff::detect_renames(diffs, threshold, load)
+            .into_iter()
+            .filter_map(|d| d.renamed_from.map(|old| (d.path, old)))
+            .collect();
+    }
+
+    /// Every changed path as porcelain codes, sorted by path
+    pub fn porcelain(&self) -> Vec<PorcelainEntry> {
+        let renamed_from: std::collections::HashSet<&String> = self.renames.values().collect();
+        let mut paths: Vec<&String> = self
+            .previous
+            .keys()
+            .chain(self.staged.keys())
+            .chain(self.working.keys())
+            .filter(|path| !renamed_from.contains(path))
+            .collect();
+        paths.sort();
+        paths.dedup();
+
+        let mut entries = Vec::new();
+        for path in paths {
+            let head = self.previous.get(path);
+            let staged = self.staged.get(path);
+            let working = self.working.get(path);
+            let orig_path = self.renames.get(path).cloned();
+
+            if head.is_none() && staged.is_none() {
+                entries.push(PorcelainEntry {
+                    index: '?',
+                    worktree: '?',
+                    path: path.clone(),
+                    orig_path: None,
+                });
+                continue;
+            }
+
+            // A staged removal whose file is still on disk is also untracked
+            if staged.is_some_and(|h| h.is_empty()) {
+                entries.push(PorcelainEntry {
+                    index: 'D',
+                    worktree: ' ',
+                    path: path.clone(),
+                    orig_path: None,
+                });
+                if working.is_some() {
+                    entries.push(PorcelainEntry {
+                        index: '?',
+                        worktree: '?',
+                        path: path.clone(),
+                        orig_path: None,
+                    });
+                }
+                continue;
+            }
+
+            let index = match (head, staged) {
+                (None, Some(_)) if orig_path.is_some() => 'R',
+                (None, Some(_)) => 'A',
+                (Some(h), Some(s)) if h != s => 'M',
+                _ => ' ',
+            };
+            let worktree = match (staged.or(head), working) {
+                (Some(_), None) => 'D',
+                (Some(expected), Some(actual)) if expected != actual => 'M',
+                _ => ' ',
+            };
+
+            if index != ' ' || worktree != ' ' {
+                entries.push(PorcelainEntry {
+                    index,
+                    worktree,
+                    path: path.clone(),
+                    orig_path,
+                });
+            }
+        }
+
+        entries
+    }
+
+    /// Summarize the porcelain entries by category
+    pub fn report(&self, branch: &str) -> StatusReport {
+        let mut report = StatusReport {
+            branch: branch.to_string(),
+            ..StatusReport::default()
+        };
+        for entry in self.porcelain() {
+            if entry.index == '?' {
+                report.untracked.push(entry.path);
+                continue;
+            }
+            if entry.index != ' ' {
+                report.staged.push(entry.path.clone());
+            }
+            if entry.worktree != ' ' {
+                report.modified.push(entry.path);
+            }
+        }
+        report
+    }
+
     /// Get only staged changes
     pub fn staged(&self) -> Vec<FileStatusInfo> {
         self.get_status()
@@ -170,6 +374,9 @@ impl Status {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::hash;
+    use crate::core::database::MugDb;
+    use tempfile::TempDir;
 
     #[test]
     fn test_file_status() {
@@ -177,4 +384,81 @@ mod tests {
         let file_statuses = status.get_status();
         assert!(file_statuses.is_empty());
     }
+
+    #[test]
+    fn test_porcelain_codes() {
+        let map = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs.iter().map(|(p, h)| (p.to_string(), h.to_string())).collect()
+        };
+        let status = Status {
+            previous: map(&[("kept", "1"), ("edited", "1"), ("removed", "1"), ("restaged", "1")]),
+            staged: map(&[("new", "2"), ("restaged", "2")]),
+            working: map(&[
+                ("kept", "1"),
+                ("edited", "2"),
+                ("new", "3"),
+                ("restaged", "2"),
+                ("stray", "4"),
+            ]),
+            ignore_rules: IgnoreRules::new(),
+            renames: HashMap::new(),
+        };
+
+        let lines: Vec<String> = status.porcelain().iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            lines,
+            vec![" M edited", "AM new", " D removed", "M  restaged", "?? stray"]
+        );
+
+        let report = status.report("main");
+        assert_eq!(report.staged, vec!["new", "restaged"]);
+        assert_eq!(report.modified, vec!["edited", "new", "removed"]);
+        assert_eq!(report.untracked, vec!["stray"]);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["branch"], "main");
+    }
+
+    #[test]
+    fn test_porcelain_reports_renames() {
+        let map = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs.iter().map(|(p, h)| (p.to_string(), h.to_string())).collect()
+        };
+        let mut status = Status {
+            previous: map(&[("old.txt", "1"), ("gone.txt", "2")]),
+            staged: map(&[("new.txt", "1"), ("fresh.txt", "3")]),
+            working: map(&[("new.txt", "1"), ("fresh.txt", "3")]),
+            ignore_rules: IgnoreRules::new(),
+            renames: HashMap::new(),
+        };
+        status.detect_renames(0.5, |hash| Some(format!("content {}\n", hash).into_bytes()));
+
+        let lines: Vec<String> = status.porcelain().iter().map(|e| e.to_string()).collect();
+        assert_eq!(lines, vec!["A  fresh.txt", " D gone.txt", "R  old.txt -> new.txt"]);
+    }
+
+    #[test]
+    fn test_staged_file_deleted_from_disk() {
+        let dir = TempDir::new().unwrap();
+        let db = MugDb::new(dir.path().join(".mug").join("db")).unwrap();
+        std::fs::write(dir.path().join("gone.txt"), "bye").unwrap();
+        std::fs::write(dir.path().join("stray.txt"), "new").unwrap();
+        let mut index = Index::new(db).unwrap();
+        index
+            .add("gone.txt".to_string(), hash::hash_bytes(b"bye"))
+            .unwrap();
+        std::fs::remove_file(dir.path().join("gone.txt")).unwrap();
+
+        let status = Status::from_index_and_wd(&index, dir.path()).unwrap();
+        let mut results = status.get_status();
+        results.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].path, "gone.txt");
+        assert_eq!(results[0].status, FileStatus::Deleted);
+        assert_eq!(results[1].path, "stray.txt");
+        assert_eq!(results[1].status, FileStatus::Untracked);
+
+        let lines: Vec<String> = status.porcelain().iter().map(|e| e.to_string()).collect();
+        assert_eq!(lines, vec!["AD gone.txt", "?? stray.txt"]);
+        assert_eq!(status.report("main").modified, vec!["gone.txt"]);
+    }
 }
@@ -1,8 +1,14 @@
-use crate::core::error::Result;
-use crate::core::hash;
+use crate::core::error::{Error, Result};
+use crate::core::file_mode;
+use crate::core::hash::HashAlgorithm;
+use crate::core::store_manager::LargeFilePointer;
+use crate::pack::pack_builder::PACK_MANIFEST;
+use crate::pack::PackReader;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
 /// A single file snapshot in the content-addressable store
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,22 +30,84 @@ pub struct TreeEntry {
     pub name: String,
     pub hash: String,
     pub is_dir: bool,
+    /// File mode. Left out for regular files and directories, so their
+    /// trees hash the same as before modes were recorded.
+    #[serde(default = "regular_mode", skip_serializing_if = "is_regular_mode")]
+    pub mode: u32,
+}
+
+impl TreeEntry {
+    /// Whether `name` is safe as a single path component in the working
+    /// tree: not empty, `.` or `..`, free of separators, and not `.mug`
+    pub fn valid_name(name: &str) -> bool {
+        let mut components = Path::new(name).components();
+        matches!(components.next(), Some(std::path::Component::Normal(_)))
+            && components.next().is_none()
+            && !name.contains(['/', '\\'])
+            && !name.eq_ignore_ascii_case(".mug")
+    }
+}
+
+/// Refuse a tree with an unsafe entry name, or with two entries of the same
+/// name such as a file and a directory
+pub fn check_tree_entries(entries: &[TreeEntry]) -> Result<()> {
+    let mut names = std::collections::HashSet::new();
+    for entry in entries {
+        if !TreeEntry::valid_name(&entry.name) {
+            return Err(Error::Custom(format!("Unsafe name in tree: {:?}", entry.name)));
+        }
+        if !names.insert(entry.name.as_str()) {
+            return Err(Error::Custom(format!("Duplicate name in tree: {:?}", entry.name)));
+        }
+    }
+    Ok(())
+}
+
+fn regular_mode() -> u32 {
+    file_mode::REGULAR
+}
+
+fn is_regular_mode(mode: &u32) -> bool {
+    *mode == file_mode::REGULAR
 }
 
 /// The content-addressable object store
+#[derive(Clone)]
 pub struct ObjectStore {
     objects_dir: PathBuf,
+    algorithm: HashAlgorithm,
+    /// Pack reader shared by clones, reloaded when the manifest changes
+    packs: Arc<Mutex<Option<CachedPacks>>>,
+}
+
+struct CachedPacks {
+    modified: SystemTime,
+    reader: Arc<PackReader>,
 }
 
 impl ObjectStore {
     pub fn new(objects_dir: PathBuf) -> Result<Self> {
         fs::create_dir_all(&objects_dir)?;
-        Ok(ObjectStore { objects_dir })
+        Ok(ObjectStore {
+            objects_dir,
+            algorithm: HashAlgorithm::default(),
+            packs: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// Address new objects with `algorithm` instead of SHA-256
+    pub fn with_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
     }
 
     /// Store a blob and return its hash
     pub fn store_blob(&self, content: &[u8]) -> Result<String> {
-        let hash = hash::hash_bytes(content);
+        let hash = self.algorithm.hash_blob(content);
         let path = self.object_path(&hash);
 
         // Skip if already exists
@@ -51,6 +119,32 @@ impl ObjectStore {
         Ok(hash)
     }
 
+    /// Store a pointer in place of a large file's content, under the
+    /// content's hash
+    pub fn store_pointer(&self, pointer: &LargeFilePointer) -> Result<()> {
+        let path = self.object_path(&pointer.hash);
+        if !path.exists() {
+            fs::write(&path, pointer.to_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Store a blob received from elsewhere under `hash`, which may be a
+    /// large file pointer
+    pub fn store_received(&self, hash: &str, content: &[u8]) -> Result<()> {
+        if !self.algorithm.is_object_id(hash) {
+            return Err(Error::Custom(format!("Invalid blob id received: {:?}", hash)));
+        }
+        if !blob_matches(self.algorithm, hash, content) {
+            return Err(Error::Custom(format!("Corrupt blob received: {}", hash)));
+        }
+        let path = self.object_path(hash);
+        if !path.exists() {
+            fs::write(&path, content)?;
+        }
+        Ok(())
+    }
+
     /// Store a file and return its blob hash
     pub fn store_file<P: AsRef<Path>>(&self, path: P) -> Result<String> {
         let content = fs::read(&path)?;
@@ -59,8 +153,7 @@ impl ObjectStore {
 
     /// Retrieve a blob by hash
     pub fn get_blob(&self, hash: &str) -> Result<Blob> {
-        let path = self.object_path(hash);
-        let content = fs::read(&path)?;
+        let content = self.read_object(hash)?;
         Ok(Blob {
             hash: hash.to_string(),
             size: content.len() as u64,
@@ -71,7 +164,7 @@ impl ObjectStore {
     /// Store a tree and return its hash
     pub fn store_tree(&self, entries: Vec<TreeEntry>) -> Result<String> {
         let tree_json = serde_json::to_string(&entries)?;
-        let hash = hash::hash_str(&tree_json);
+        let hash = self.algorithm.hash_str(&tree_json);
         let path = self.object_path(&hash);
 
         if !path.exists() {
@@ -88,15 +181,69 @@ impl ObjectStore {
 
     /// Retrieve a tree by hash
     pub fn get_tree(&self, hash: &str) -> Result<Tree> {
-        let path = self.object_path(hash);
-        let data = fs::read(&path)?;
+        let data = self.read_object(hash)?;
         let tree = serde_json::from_slice(&data)?;
         Ok(tree)
     }
 
-    /// Check if an object exists
+    /// Check if an object exists, loose or packed
     pub fn has_object(&self, hash: &str) -> bool {
         self.object_path(hash).exists()
+            || matches!(self.pack_reader(), Ok(Some(reader)) if reader.has_object(hash))
+    }
+
+    /// Directory holding loose objects
+    pub fn objects_dir(&self) -> &Path {
+        &self.objects_dir
+    }
+
+    /// Directory holding pack files and their manifest, next to `objects`
+    pub fn packs_dir(&self) -> PathBuf {
+        self.objects_dir.with_file_name("packs")
+    }
+
+    /// Reader over the repository's packs, if it has any.
+    ///
+    /// The reader and its open pack files are cached until the manifest is
+    /// rewritten.
+    pub fn pack_reader(&self) -> Result<Option<Arc<PackReader>>> {
+        let manifest = self.packs_dir().join(PACK_MANIFEST);
+        let mut cache = self.packs.lock().unwrap();
+        let modified = match fs::metadata(&manifest) {
+            Ok(metadata) => metadata.modified()?,
+            Err(_) => {
+                *cache = None;
+                return Ok(None);
+            }
+        };
+
+        match cache.as_ref() {
+            Some(cached) if cached.modified == modified => Ok(Some(cached.reader.clone())),
+            _ => {
+                let reader = Arc::new(PackReader::new(&manifest)?);
+                *cache = Some(CachedPacks {
+                    modified,
+                    reader: reader.clone(),
+                });
+                Ok(Some(reader))
+            }
+        }
+    }
+
+    /// Drop the cached pack reader, closing its pack files
+    pub fn clear_pack_cache(&self) {
+        *self.packs.lock().unwrap() = None;
+    }
+
+    /// Read an object's bytes from its loose file, falling back to the packs
+    fn read_object(&self, hash: &str) -> Result<Vec<u8>> {
+        match fs::read(self.object_path(hash)) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => match self.pack_reader()? {
+                Some(reader) if reader.has_object(hash) => Ok(reader.get_object(hash)?),
+                _ => Err(e.into()),
+            },
+            result => Ok(result?),
+        }
     }
 
     fn object_path(&self, hash: &str) -> PathBuf {
@@ -104,6 +251,14 @@ impl ObjectStore {
     }
 }
 
+/// Whether `content` is valid for the blob `hash`: either the content
+/// itself or a pointer to a large file with that hash
+pub fn blob_matches(algorithm: HashAlgorithm, hash: &str, content: &[u8]) -> bool {
+    algorithm.hash_blob(content) == hash
+        || (algorithm.is_object_id(hash)
+            && LargeFilePointer::parse(content).is_some_and(|pointer| pointer.hash == hash))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -130,10 +285,48 @@ mod tests {
             name: "file.txt".to_string(),
             hash: "abc123".to_string(),
             is_dir: false,
+            mode: file_mode::REGULAR,
         }];
 
         let hash = store.store_tree(entries).unwrap();
         let tree = store.get_tree(&hash).unwrap();
         assert_eq!(tree.entries.len(), 1);
     }
+
+    #[test]
+    fn test_read_objects_from_packs() {
+        use crate::pack::PackBuilder;
+
+        let dir = TempDir::new().unwrap();
+        let store = ObjectStore::new(dir.path().join(".mug/objects")).unwrap();
+        let blob = store.store_blob(b"packed content").unwrap();
+        let tree = store
+            .store_tree(vec![TreeEntry {
+                name: "file.txt".to_string(),
+                hash: blob.clone(),
+                is_dir: false,
+                mode: file_mode::REGULAR,
+            }])
+            .unwrap();
+
+        let manifest = PackBuilder::new(dir.path(), 1_000_000)
+            .unwrap()
+            .build_packs(&store.packs_dir())
+            .unwrap();
+        manifest.save(&store.packs_dir().join(PACK_MANIFEST)).unwrap();
+        for entry in fs::read_dir(store.objects_dir()).unwrap() {
+            fs::remove_file(entry.unwrap().path()).unwrap();
+        }
+
+        let resolved = store.get_tree(&tree).unwrap();
+        assert_eq!(resolved.entries[0].hash, blob);
+        assert_eq!(store.get_blob(&blob).unwrap().content, b"packed content");
+        assert!(store.has_object(&tree));
+        assert!(store.get_blob("missing").is_err());
+
+        // Lookups share one reader until the manifest changes
+        let first = store.pack_reader().unwrap().unwrap();
+        let again = store.clone().pack_reader().unwrap().unwrap();
+        assert!(Arc::ptr_eq(&first, &again));
+    }
 }
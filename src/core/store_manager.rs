@@ -1,7 +1,14 @@
 /// Hybrid store management - local files + centralized large file server
-use crate::core::error::Result;
+use crate::core::error::{Error, Result};
+use crate::core::hash::HashAlgorithm;
 use serde::{Deserialize, Serialize};
+use std::fs;
+use std::future::Future;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Cache counters, kept next to the cache so every command adds to them
+const STATS_FILE: &str = "cache-stats.json";
 
 /// Configuration for object storage
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,6 +23,9 @@ pub struct StoreConfig {
     pub cache_size_bytes: usize,
     /// Cache policy: LRU, FIFO, or TTL
     pub cache_policy: CachePolicy,
+    /// Token sent to the central server (MUG_TOKEN overrides it)
+    #[serde(default)]
+    pub auth_token: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -54,12 +64,50 @@ pub struct ObjectMetadata {
     pub compression: Option<String>,
 }
 
+/// Stand-in kept in the object store, under the file's own hash, for a
+/// large file whose content lives on the central server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LargeFilePointer {
+    pub hash: String,
+    pub size: u64,
+}
+
+impl LargeFilePointer {
+    const HEADER: &'static str = "mug-large-file v1";
+    /// Pointer records are tiny; anything bigger is ordinary content
+    const MAX_LEN: usize = 256;
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        format!("{}\noid {}\nsize {}\n", Self::HEADER, self.hash, self.size).into_bytes()
+    }
+
+    /// Parse a pointer record, or `None` if `data` is ordinary content
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() > Self::MAX_LEN {
+            return None;
+        }
+        let mut lines = std::str::from_utf8(data).ok()?.lines();
+        if lines.next()? != Self::HEADER {
+            return None;
+        }
+        let hash = lines.next()?.strip_prefix("oid ")?.to_string();
+        // The id names files in the object store and cache
+        if ![HashAlgorithm::Sha256, HashAlgorithm::Sha1].iter().any(|a| a.is_object_id(&hash)) {
+            return None;
+        }
+        let size = lines.next()?.strip_prefix("size ")?.parse().ok()?;
+        Some(LargeFilePointer { hash, size })
+    }
+}
+
 pub struct StoreManager {
     config: StoreConfig,
     cache_stats: CacheStats,
+    /// Where the counters persist, for managers opened on a repository
+    stats_path: Option<PathBuf>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct CacheStats {
     pub hits: usize,
     pub misses: usize,
@@ -67,6 +115,15 @@ pub struct CacheStats {
     pub size_bytes: usize,
 }
 
+impl StoreConfig {
+    /// Repository config key holding the central server URL
+    pub const SERVER_KEY: &'static str = "store.server";
+    /// Repository config key holding the large file threshold in bytes
+    pub const THRESHOLD_KEY: &'static str = "store.threshold";
+    /// Repository config key holding the cache limit in bytes
+    pub const CACHE_LIMIT_KEY: &'static str = "store.cacheLimit";
+}
+
 impl Default for StoreConfig {
     fn default() -> Self {
         StoreConfig {
@@ -75,6 +132,7 @@ impl Default for StoreConfig {
             cache_dir: PathBuf::from(".mug/cache"),
             cache_size_bytes: 1024 * 1024 * 1024, // 1GB default
             cache_policy: CachePolicy::LRU,
+            auth_token: None,
         }
     }
 }
@@ -84,7 +142,121 @@ impl StoreManager {
         StoreManager {
             config,
             cache_stats: CacheStats::default(),
+            stats_path: None,
+        }
+    }
+
+    /// Manager for the repository at `root`. A relative cache directory is
+    /// taken from the root, and cache counters persist across commands.
+    pub fn for_repo(root: &Path, mut config: StoreConfig) -> Self {
+        if config.cache_dir.is_relative() {
+            config.cache_dir = root.join(&config.cache_dir);
+        }
+        let stats_path = root.join(".mug").join(STATS_FILE);
+        let cache_stats = fs::read(&stats_path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        StoreManager {
+            config,
+            cache_stats,
+            stats_path: Some(stats_path),
+        }
+    }
+
+    /// Whether a file of this size goes to the central server
+    pub fn is_large(&self, size_bytes: usize) -> bool {
+        self.determine_source(size_bytes) == ObjectSource::Central
+    }
+
+    /// Upload a large file to the central server, keeping a copy in the cache
+    pub fn store_large(&mut self, hash: &str, content: &[u8]) -> Result<()> {
+        let url = self.object_url(hash)?;
+        let request = self.authorize(reqwest::Client::new().put(&url).body(content.to_vec()));
+        let response = block_on(request.send())?
+            .map_err(|e| Error::Custom(format!("Failed to upload large file {}: {}", hash, e)))?;
+        if !response.status().is_success() {
+            return Err(Error::Custom(format!(
+                "Failed to upload large file {}: HTTP {}",
+                hash,
+                response.status()
+            )));
+        }
+        self.write_cache(hash, content)
+    }
+
+    /// A large file's content, from the cache when possible and otherwise
+    /// from the central server
+    pub fn fetch_large(&mut self, hash: &str) -> Result<Vec<u8>> {
+        let path = self.cache_path(hash);
+        if let Ok(content) = fs::read(&path) {
+            self.cache_stats.hits += 1;
+            self.save_stats()?;
+            // Reading counts as a use for LRU eviction
+            fs::File::options().append(true).open(&path)?.set_modified(SystemTime::now())?;
+            return Ok(content);
+        }
+
+        self.cache_stats.misses += 1;
+        self.save_stats()?;
+        let url = self.object_url(hash)?;
+        let request = self.authorize(reqwest::Client::new().get(&url));
+        let content = block_on(async move {
+            let response = request.send().await?.error_for_status()?;
+            response.bytes().await
+        })?
+        .map_err(|e| Error::Custom(format!("Failed to download large file {}: {}", hash, e)))?;
+        self.write_cache(hash, &content)?;
+        Ok(content.to_vec())
+    }
+
+    /// `<server>/repo/<name>/store/<hash>` for the configured central server
+    fn object_url(&self, hash: &str) -> Result<String> {
+        let server = self.central_server().ok_or_else(|| {
+            Error::Custom("No central server configured (mug store set-server)".to_string())
+        })?;
+        Ok(format!("{}/store/{}", crate::remote::client::repo_url(server), hash))
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let token = std::env::var("MUG_TOKEN")
+            .ok()
+            .filter(|t| !t.is_empty())
+            .or_else(|| self.config.auth_token.clone());
+        match token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// Add an entry to the cache, evicting older ones if it grows too big
+    fn write_cache(&mut self, hash: &str, content: &[u8]) -> Result<()> {
+        fs::create_dir_all(&self.config.cache_dir)?;
+        fs::write(self.cache_path(hash), content)?;
+        // Never evict the entry just written
+        self.evict_to_limit(Some(hash))?;
+        self.save_stats()
+    }
+
+    fn save_stats(&self) -> Result<()> {
+        if let Some(path) = &self.stats_path {
+            fs::write(path, serde_json::to_vec(&self.cache_stats)?)?;
+        }
+        Ok(())
+    }
+
+    /// Cache entries with their last-used times, least recently used first
+    fn cache_entries(&self) -> Result<Vec<(PathBuf, SystemTime)>> {
+        let mut entries = Vec::new();
+        if self.config.cache_dir.exists() {
+            for entry in fs::read_dir(&self.config.cache_dir)? {
+                let entry = entry?;
+                let modified = entry.metadata()?.modified()?;
+                entries.push((entry.path(), modified));
+            }
         }
+        entries.sort_by_key(|(_, modified)| *modified);
+        Ok(entries)
     }
 
     /// Determine where an object should be stored
@@ -129,9 +301,41 @@ impl StoreManager {
 
     /// Evict oldest entry from cache (LRU policy)
     pub fn evict_lru(&mut self) -> Result<Option<String>> {
-        // Would scan cache_dir, find oldest file by mtime, delete it
-        // Return hash of evicted file
-        Ok(None)
+        let evicted = self.evict_oldest(None)?;
+        self.save_stats()?;
+        Ok(evicted)
+    }
+
+    /// Evict least recently used entries until the cache is within its
+    /// limit, returning how many went
+    pub fn enforce_cache_limit(&mut self) -> Result<usize> {
+        let evicted = self.evict_to_limit(None)?;
+        self.save_stats()?;
+        Ok(evicted)
+    }
+
+    fn evict_to_limit(&mut self, keep: Option<&str>) -> Result<usize> {
+        let mut evicted = 0;
+        while self.cache_size()? > self.config.cache_size_bytes
+            && self.evict_oldest(keep)?.is_some()
+        {
+            evicted += 1;
+        }
+        Ok(evicted)
+    }
+
+    /// Remove the least recently used entry other than `keep`
+    fn evict_oldest(&mut self, keep: Option<&str>) -> Result<Option<String>> {
+        let oldest = self
+            .cache_entries()?
+            .into_iter()
+            .find(|(path, _)| keep.is_none_or(|keep| path.file_name() != Some(keep.as_ref())));
+        let Some((path, _)) = oldest else {
+            return Ok(None);
+        };
+        fs::remove_file(&path)?;
+        self.cache_stats.evictions += 1;
+        Ok(path.file_name().map(|name| name.to_string_lossy().into_owned()))
     }
 
     /// Clear entire cache
@@ -174,6 +378,16 @@ impl StoreManager {
         self.config.central_server = Some(url);
     }
 
+    /// Get maximum cache size in bytes
+    pub fn cache_limit(&self) -> usize {
+        self.config.cache_size_bytes
+    }
+
+    /// Set maximum cache size in bytes
+    pub fn set_cache_limit(&mut self, bytes: usize) {
+        self.config.cache_size_bytes = bytes;
+    }
+
     /// Get large file threshold
     pub fn large_file_threshold(&self) -> usize {
         self.config.large_file_threshold_bytes
@@ -185,6 +399,25 @@ impl StoreManager {
     }
 }
 
+/// Run a request to completion from synchronous repository code, which may
+/// itself be called from inside the server's or client's runtime
+fn block_on<F: Future + Send>(future: F) -> Result<F::Output>
+where
+    F::Output: Send,
+{
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()?;
+                Ok(runtime.block_on(future))
+            })
+            .join()
+            .map_err(|_| Error::Custom("Large file transfer panicked".to_string()))?
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -224,4 +457,55 @@ mod tests {
         let path = manager.cache_path(hash);
         assert!(path.to_string_lossy().contains("abc123def456"));
     }
+
+    #[test]
+    fn test_pointer_round_trip() {
+        let pointer = LargeFilePointer {
+            hash: "ab".repeat(32),
+            size: 12_000_000,
+        };
+        assert_eq!(LargeFilePointer::parse(&pointer.to_bytes()), Some(pointer));
+        assert_eq!(LargeFilePointer::parse(b"just a file\n"), None);
+        let escaping = LargeFilePointer { hash: "../../x".to_string(), size: 1 };
+        assert_eq!(LargeFilePointer::parse(&escaping.to_bytes()), None);
+    }
+
+    #[test]
+    fn test_fetch_large_counts_cache_hits_and_evicts_lru() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join(".mug")).unwrap();
+        let config = StoreConfig {
+            cache_size_bytes: 10,
+            ..StoreConfig::default()
+        };
+
+        let mut manager = StoreManager::for_repo(dir.path(), config.clone());
+        manager.write_cache("old", b"123456").unwrap();
+        manager.write_cache("new", b"abcdef").unwrap();
+        // Both don't fit, so the older entry went
+        assert!(!manager.exists_cache("old").unwrap());
+        assert_eq!(manager.fetch_large("new").unwrap(), b"abcdef");
+        // A miss with no server configured fails, but is still counted
+        assert!(manager.fetch_large("old").is_err());
+
+        let reopened = StoreManager::for_repo(dir.path(), config);
+        assert_eq!(reopened.cache_stats().hits, 1);
+        assert_eq!(reopened.cache_stats().misses, 1);
+        assert_eq!(reopened.cache_stats().evictions, 1);
+    }
+
+    #[test]
+    fn test_enforce_cache_limit() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join(".mug")).unwrap();
+        let mut manager = StoreManager::for_repo(dir.path(), StoreConfig::default());
+        manager.write_cache("a", b"12345").unwrap();
+        manager.write_cache("b", b"12345").unwrap();
+        assert_eq!(manager.enforce_cache_limit().unwrap(), 0);
+
+        manager.set_cache_limit(5);
+        assert_eq!(manager.cache_limit(), 5);
+        assert_eq!(manager.enforce_cache_limit().unwrap(), 1);
+        assert_eq!(manager.cache_size().unwrap(), 5);
+    }
 }
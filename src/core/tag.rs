@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 
+use crate::core::crypto::{CryptoKey, KeyManager, SignatureStatus};
 use crate::core::database::MugDb;
 use crate::core::error::Result;
 
@@ -11,6 +12,12 @@ pub struct Tag {
     pub message: Option<String>,
     pub author: Option<String>,
     pub timestamp: Option<String>,
+    /// Base64-encoded Ed25519 signature over the tag content
+    #[serde(default)]
+    pub signature: Option<String>,
+    /// Base64-encoded public key of the signer
+    #[serde(default)]
+    pub signer_key: Option<String>,
 }
 
 impl Tag {
@@ -21,6 +28,8 @@ impl Tag {
             message: None,
             author: None,
             timestamp: None,
+            signature: None,
+            signer_key: None,
         }
     }
 
@@ -38,6 +47,30 @@ impl Tag {
         self.timestamp = Some(timestamp);
         self
     }
+
+    /// Sign the tag content with the given key
+    pub fn signed_with(mut self, key: &CryptoKey) -> Result<Self> {
+        self.signature = Some(key.sign(&self.signing_payload())?);
+        self.signer_key = Some(key.public_key.clone());
+        Ok(self)
+    }
+
+    /// The bytes covered by the tag signature
+    pub fn signing_payload(&self) -> Vec<u8> {
+        format!(
+            "tag {}\ncommit {}\nauthor {}\ntimestamp {}\n\n{}",
+            self.name,
+            self.commit_id,
+            self.author.as_deref().unwrap_or(""),
+            self.timestamp.as_deref().unwrap_or(""),
+            self.message.as_deref().unwrap_or("")
+        )
+        .into_bytes()
+    }
+
+    pub fn is_signed(&self) -> bool {
+        self.signature.is_some()
+    }
 }
 
 /// Tag manager for creating and listing tags
@@ -59,25 +92,30 @@ impl TagManager {
             )));
         }
 
-        let tag = Tag::new(name.clone(), commit_id);
+        let tag = Tag::new(name.clone(), commit_id)
+            .with_timestamp(chrono::Local::now().to_rfc3339());
         let serialized = serde_json::to_vec(&tag)?;
         self.db.set("tags", &name, serialized)?;
 
         Ok(())
     }
 
-    /// Create an annotated tag with message
+    /// Create an annotated tag with message, optionally signed
     pub fn create_annotated(
         &self,
         name: String,
         commit_id: String,
         message: String,
         author: String,
+        signing_key: Option<&CryptoKey>,
     ) -> Result<()> {
-        let tag = Tag::new(name.clone(), commit_id)
+        let mut tag = Tag::new(name.clone(), commit_id)
             .with_message(message)
             .with_author(author)
             .with_timestamp(chrono::Local::now().to_rfc3339());
+        if let Some(key) = signing_key {
+            tag = tag.signed_with(key)?;
+        }
 
         let serialized = serde_json::to_vec(&tag)?;
         self.db.set("tags", &name, serialized)?;
@@ -128,6 +166,19 @@ impl TagManager {
         }
     }
 
+    /// Check the signature of a tag against the local keyring
+    pub fn verify_signature(&self, name: &str) -> Result<SignatureStatus> {
+        let tag = self.get(name)?.ok_or_else(|| {
+            crate::core::error::Error::Custom(format!("Tag '{}' not found", name))
+        })?;
+
+        KeyManager::new(self.db.clone()).check(
+            &tag.signing_payload(),
+            tag.signature.as_deref(),
+            tag.signer_key.as_deref(),
+        )
+    }
+
     /// Get tag by commit ID
     pub fn find_by_commit(&self, commit_id: &str) -> Result<Option<Tag>> {
         let tags = self.list()?;
@@ -168,9 +219,9 @@ mod tests {
             .create("v1.0.0".to_string(), "commit1".to_string())
             .unwrap();
 
-        let tag = manager.get("v1.0.0").unwrap();
-        assert!(tag.is_some());
-        assert_eq!(tag.unwrap().commit_id, "commit1");
+        let tag = manager.get("v1.0.0").unwrap().unwrap();
+        assert_eq!(tag.commit_id, "commit1");
+        assert!(tag.timestamp.is_some());
     }
 
     #[test]
@@ -209,4 +260,34 @@ mod tests {
         assert_eq!(tags[1].name, "v1.1.0");
         assert_eq!(tags[2].name, "v1.2.0");
     }
+
+    #[test]
+    fn test_signed_tag_verification() {
+        let dir = TempDir::new().unwrap();
+        let db = MugDb::new(dir.path().join("db")).unwrap();
+        let (key, _) = CryptoKey::generate().unwrap();
+        KeyManager::new(db.clone()).add(&key).unwrap();
+        let manager = TagManager::new(db.clone());
+
+        manager
+            .create_annotated(
+                "v1.0.0".to_string(),
+                "commit1".to_string(),
+                "Release".to_string(),
+                "Tester".to_string(),
+                Some(&key),
+            )
+            .unwrap();
+        assert!(manager.verify_signature("v1.0.0").unwrap().is_good());
+
+        // Tampering with the stored tag invalidates the signature
+        let mut tag = manager.get("v1.0.0").unwrap().unwrap();
+        tag.commit_id = "commit2".to_string();
+        db.set("tags", "v1.0.0", serde_json::to_vec(&tag).unwrap())
+            .unwrap();
+        assert!(matches!(
+            manager.verify_signature("v1.0.0").unwrap(),
+            SignatureStatus::Bad(_)
+        ));
+    }
 }
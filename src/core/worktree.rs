@@ -0,0 +1,265 @@
+/// Linked worktrees: extra working directories sharing one repository's
+/// objects, branches and config, each with its own HEAD and index
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::branch::BranchManager;
+use crate::core::error::{Error, Result};
+use crate::core::repo::Repository;
+
+/// File in a linked worktree's `.mug` pointing back at the main repository
+const LINK_FILE: &str = "worktree.json";
+/// Directory under the main `.mug` recording each linked worktree
+const REGISTRY_DIR: &str = "worktrees";
+
+/// Contents of a linked worktree's `.mug/worktree.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorktreeLink {
+    /// Root of the main working tree
+    pub common_root: PathBuf,
+    pub name: String,
+}
+
+/// A linked worktree's entry in the main `.mug/worktrees`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RegistryEntry {
+    name: String,
+    path: PathBuf,
+}
+
+/// A working tree of the repository, as listed by `mug worktree list`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Worktree {
+    /// `None` for the main working tree
+    pub name: Option<String>,
+    pub path: PathBuf,
+    /// Branch checked out, or `detached:<id>`
+    pub head: Option<String>,
+    /// Whether the directory has gone missing
+    pub missing: bool,
+}
+
+/// The link in `mug_dir` if it belongs to a linked worktree
+pub fn read_link(mug_dir: &Path) -> Result<Option<WorktreeLink>> {
+    let path = mug_dir.join(LINK_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+}
+
+fn registry_dir(repo: &Repository) -> PathBuf {
+    repo.common_root().join(".mug").join(REGISTRY_DIR)
+}
+
+/// Linked worktrees as (name, path), sorted by name
+fn registered(repo: &Repository) -> Result<Vec<(String, PathBuf)>> {
+    let dir = registry_dir(repo);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut worktrees = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            let entry: RegistryEntry = serde_json::from_str(&fs::read_to_string(&path)?)?;
+            worktrees.push((entry.name, entry.path));
+        }
+    }
+    worktrees.sort();
+    Ok(worktrees)
+}
+
+/// A handle on every working tree of the repository, the main one first
+pub fn views(repo: &Repository) -> Result<Vec<Repository>> {
+    let mut views = vec![repo.linked(repo.common_root(), None)];
+    for (name, path) in registered(repo)? {
+        views.push(repo.linked(&path, Some(&name)));
+    }
+    Ok(views)
+}
+
+/// Every working tree of the repository with what it has checked out
+pub fn list(repo: &Repository) -> Result<Vec<Worktree>> {
+    views(repo)?
+        .into_iter()
+        .map(|view| {
+            Ok(Worktree {
+                name: view.worktree_name().map(str::to_string),
+                path: view.root().to_path_buf(),
+                head: BranchManager::new(view.get_db().clone()).get_head()?,
+                missing: !view.root().exists(),
+            })
+        })
+        .collect()
+}
+
+/// The path of another working tree that has `branch` checked out
+pub fn checked_out_elsewhere(repo: &Repository, branch: &str) -> Result<Option<PathBuf>> {
+    Ok(list(repo)?
+        .into_iter()
+        .find(|worktree| {
+            worktree.name.as_deref() != repo.worktree_name() && worktree.head.as_deref() == Some(branch)
+        })
+        .map(|worktree| worktree.path))
+}
+
+/// Check out `branch` into a new linked worktree at `path`, which must be
+/// empty or not exist yet. Returns the worktree's name.
+pub fn add(repo: &Repository, path: &Path, branch: &str) -> Result<String> {
+    let commit_id = BranchManager::new(repo.get_db().clone())
+        .get_branch(branch)?
+        .ok_or_else(|| Error::BranchNotFound(branch.to_string()))?
+        .commit_id;
+    if let Some(existing) = list(repo)?.iter().find(|w| w.head.as_deref() == Some(branch)) {
+        return Err(Error::Custom(format!(
+            "Branch '{}' is already checked out at {}",
+            branch,
+            existing.path.display()
+        )));
+    }
+
+    if path.exists() && fs::read_dir(path)?.next().is_some() {
+        return Err(Error::Custom(format!("{} already exists and is not empty", path.display())));
+    }
+    fs::create_dir_all(path)?;
+    let path = fs::canonicalize(path)?;
+    let common_root = fs::canonicalize(repo.common_root())?;
+    if path.starts_with(&common_root) {
+        return Err(Error::Custom(
+            "A worktree cannot be placed inside the main working tree".to_string(),
+        ));
+    }
+
+    // Named after the directory, made unique among existing worktrees
+    let base = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "worktree".to_string());
+    let taken: Vec<String> = registered(repo)?.into_iter().map(|(name, _)| name).collect();
+    let name = (1..)
+        .map(|n| if n == 1 { base.clone() } else { format!("{}-{}", base, n) })
+        .find(|name| !taken.contains(name))
+        .unwrap();
+
+    fs::create_dir_all(path.join(".mug"))?;
+    let link = WorktreeLink {
+        common_root: common_root.clone(),
+        name: name.clone(),
+    };
+    fs::write(path.join(".mug").join(LINK_FILE), serde_json::to_string_pretty(&link)?)?;
+    let entry = RegistryEntry {
+        name: name.clone(),
+        path: path.clone(),
+    };
+    fs::create_dir_all(registry_dir(repo))?;
+    fs::write(
+        registry_dir(repo).join(format!("{}.json", name)),
+        serde_json::to_string_pretty(&entry)?,
+    )?;
+
+    let linked = repo.linked(&path, Some(&name));
+    BranchManager::new(linked.get_db().clone()).set_head(branch.to_string())?;
+    if !commit_id.is_empty() {
+        let (files, modes) = linked.snapshot_with_modes(&commit_id)?;
+        linked.update_working_tree(&Default::default(), &files, &modes)?;
+    }
+    repo.get_db().flush()?;
+    Ok(name)
+}
+
+/// Delete a linked worktree, found by name or path. Unless `force` is set,
+/// refuses one with uncommitted or untracked changes.
+pub fn remove(repo: &Repository, worktree: &str, force: bool) -> Result<Worktree> {
+    let wanted = fs::canonicalize(worktree).ok();
+    let target = list(repo)?
+        .into_iter()
+        .find(|w| w.name.as_deref() == Some(worktree) || Some(&w.path) == wanted.as_ref())
+        .ok_or_else(|| Error::Custom(format!("No worktree named '{}'", worktree)))?;
+    let Some(name) = target.name.clone() else {
+        return Err(Error::Custom("The main working tree cannot be removed".to_string()));
+    };
+    if repo.worktree_name() == Some(name.as_str()) {
+        return Err(Error::Custom("Cannot remove the worktree you are in".to_string()));
+    }
+
+    if !target.missing {
+        let view = repo.linked(&target.path, Some(&name));
+        if !force && !view.status()?.porcelain().is_empty() {
+            return Err(Error::Custom(format!(
+                "Worktree '{}' has local changes; use --force to remove it anyway",
+                name
+            )));
+        }
+        fs::remove_dir_all(&target.path)?;
+    }
+    fs::remove_file(registry_dir(repo).join(format!("{}.json", name)))?;
+    repo.get_db().drop_worktree(&name)?;
+    repo.get_db().flush()?;
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn repo_with_feature() -> (TempDir, Repository) {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("main");
+        let repo = Repository::init(&root).unwrap();
+        fs::write(root.join("file.txt"), "base\n").unwrap();
+        repo.add("file.txt").unwrap();
+        repo.commit("Tester".to_string(), "Base".to_string()).unwrap();
+        repo.create_branch("feature".to_string()).unwrap();
+        (dir, repo)
+    }
+
+    #[test]
+    fn test_add_checks_out_branch_with_own_head() {
+        let (dir, repo) = repo_with_feature();
+        let path = dir.path().join("feature-wt");
+        assert_eq!(add(&repo, &path, "feature").unwrap(), "feature-wt");
+        assert_eq!(fs::read_to_string(path.join("file.txt")).unwrap(), "base\n");
+
+        let linked = repo.linked(&fs::canonicalize(&path).unwrap(), Some("feature-wt"));
+        fs::write(path.join("file.txt"), "feature\n").unwrap();
+        linked.add("file.txt").unwrap();
+        let id = linked.commit("Tester".to_string(), "Feature".to_string()).unwrap();
+
+        // The commit moved the shared branch but not the main HEAD
+        assert_eq!(repo.current_branch().unwrap().as_deref(), Some("main"));
+        assert_eq!(repo.resolve_rev("feature").unwrap(), id);
+        assert_eq!(fs::read_to_string(repo.root().join("file.txt")).unwrap(), "base\n");
+
+        let worktrees = list(&repo).unwrap();
+        assert_eq!(worktrees.len(), 2);
+        assert_eq!(worktrees[1].head.as_deref(), Some("feature"));
+    }
+
+    #[test]
+    fn test_branch_in_use_is_refused() {
+        let (dir, repo) = repo_with_feature();
+        assert!(add(&repo, &dir.path().join("wt"), "main").is_err());
+        add(&repo, &dir.path().join("wt"), "feature").unwrap();
+        assert!(add(&repo, &dir.path().join("other"), "feature").is_err());
+        assert!(repo.checkout("feature".to_string()).is_err());
+        assert!(repo.delete_branch("feature", true).is_err());
+    }
+
+    #[test]
+    fn test_remove_refuses_local_changes_without_force() {
+        let (dir, repo) = repo_with_feature();
+        let path = dir.path().join("wt");
+        add(&repo, &path, "feature").unwrap();
+        fs::write(path.join("file.txt"), "edited\n").unwrap();
+
+        assert!(remove(&repo, "wt", false).is_err());
+        remove(&repo, "wt", true).unwrap();
+        assert!(!path.exists());
+        assert_eq!(list(&repo).unwrap().len(), 1);
+        repo.checkout("feature".to_string()).unwrap();
+    }
+}
@@ -1,13 +1,22 @@
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
 use std::path::PathBuf;
 
 use mug::core::error::Result;
+use mug::core::hash::HashAlgorithm;
 use mug::core::repo::Repository;
 
 #[derive(Parser)]
 #[command(name = "mug")]
 #[command(about = "A fast, Rust-powered version control system", long_about = None)]
 struct Cli {
+    /// Emit JSON instead of formatted output (log, shortlog, status)
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Print long output (log) directly instead of through `$PAGER`
+    #[arg(long, global = true)]
+    no_pager: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -19,6 +28,10 @@ enum Commands {
         /// Directory to initialize (default: current directory)
         #[arg(default_value = ".")]
         path: PathBuf,
+
+        /// Object hash algorithm (sha256 or sha1); fixed after init
+        #[arg(long, default_value = "sha256")]
+        hash: HashAlgorithm,
     },
 
     /// Stage files for commit
@@ -35,17 +48,41 @@ enum Commands {
     },
 
     /// Show repository status
-    Status,
+    Status {
+        /// Print one `XY path` line per change
+        #[arg(short, long)]
+        short: bool,
+
+        /// Machine-readable output; same format as --short
+        #[arg(long)]
+        porcelain: bool,
+    },
 
     /// Commit staged changes
     Commit {
-        /// Commit message
+        /// Commit message (opens the editor when omitted)
         #[arg(short, long)]
-        message: String,
+        message: Option<String>,
 
         /// Author name (overrides config user.name)
         #[arg(short, long)]
         author: Option<String>,
+
+        /// Sign the commit with the current signing key
+        #[arg(short = 'S', long)]
+        sign: bool,
+
+        /// Add a Signed-off-by trailer for the configured user
+        #[arg(short, long)]
+        signoff: bool,
+
+        /// Replace the last commit instead of adding a new one
+        #[arg(long, conflicts_with = "sign")]
+        amend: bool,
+
+        /// Bypass the pre-commit and post-commit hooks, e.g. when a hook is broken
+        #[arg(short = 'n', long)]
+        no_verify: bool,
     },
 
     /// Show commit history
@@ -53,12 +90,77 @@ enum Commands {
         /// Abbreviated view
         #[arg(short, long)]
         oneline: bool,
+
+        /// Verify and show commit signatures
+        #[arg(long)]
+        show_signature: bool,
+
+        /// Browse commits in a terminal UI (terminals only)
+        #[arg(short, long, conflicts_with_all = ["oneline", "show_signature"])]
+        interactive: bool,
+    },
+
+    /// Summarize commits by author, most commits first
+    Shortlog {
+        /// Only commits made on or after this date (YYYY-MM-DD or RFC 3339)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Only commits made on or before this date (YYYY-MM-DD or RFC 3339)
+        #[arg(long)]
+        until: Option<String>,
+
+        /// Only print each author's commit count
+        #[arg(short, long)]
+        summary: bool,
     },
 
     /// Show commit details
+    #[command(visible_alias = "show")]
     Inspect {
-        /// Commit ID to show
+        /// Commit to show (id prefix, HEAD, HEAD~N, <branch>~N, <tag>)
         commit: String,
+
+        /// Summarize lines added and removed per file
+        #[arg(long)]
+        stat: bool,
+    },
+
+    /// Print a stored object (blob, tree or commit) by hash
+    CatFile {
+        /// Object hash, or any revision for a commit
+        hash: String,
+        /// Object type (blob, tree, commit); detected when omitted
+        kind: Option<String>,
+        /// Print only the object type
+        #[arg(short = 't', conflicts_with = "size")]
+        show_type: bool,
+        /// Print only the object size in bytes
+        #[arg(short = 's')]
+        size: bool,
+    },
+
+    /// List the files recorded in a commit's tree
+    LsTree {
+        /// Revision whose tree to list
+        #[arg(default_value = "HEAD")]
+        rev: String,
+        /// Recurse into subdirectories
+        #[arg(short, long)]
+        recursive: bool,
+    },
+
+    /// Export the files of a revision as a tar or zip archive
+    Archive {
+        /// Revision to export
+        #[arg(default_value = "HEAD")]
+        rev: String,
+        /// Archive format: tar or zip
+        #[arg(long, default_value = "tar")]
+        format: String,
+        /// File to write the archive to
+        #[arg(short, long)]
+        output: String,
     },
 
     /// Search files for pattern (parallel grep)
@@ -67,33 +169,66 @@ enum Commands {
         pattern: String,
     },
 
-    /// Create a new bookmark (branch)
+    /// Create or delete a bookmark (branch)
+    #[command(visible_alias = "branch")]
     Bookmark {
         /// Bookmark name
         name: String,
+        /// Delete the bookmark (must be merged into HEAD)
+        #[arg(short, long)]
+        delete: bool,
+        /// Delete the bookmark even if it is not merged
+        #[arg(short = 'D', conflicts_with = "delete")]
+        force_delete: bool,
     },
 
     /// List bookmarks (branches)
-    Bookmarks,
+    #[command(visible_alias = "branches")]
+    Bookmarks {
+        /// List remote-tracking branches only
+        #[arg(short, long, conflicts_with = "all")]
+        remotes: bool,
+        /// List local and remote-tracking branches
+        #[arg(short, long)]
+        all: bool,
+        /// Pick a branch to switch to after listing (terminals only)
+        #[arg(short, long, conflicts_with = "remotes")]
+        interactive: bool,
+    },
 
     /// Switch branches
     Checkout {
-        /// Branch name to switch to
+        /// Branch to switch to, or a commit/tag to detach HEAD at
         branch: String,
     },
 
+    /// Switch branches, optionally creating the branch at HEAD first
+    Switch {
+        /// Branch to switch to
+        name: String,
+        /// Create the branch before switching
+        #[arg(short, long)]
+        create: bool,
+    },
+
     /// Remove files from repository
     Rm {
         /// Files to remove
         paths: Vec<String>,
+        /// Stop tracking the files but keep them on disk
+        #[arg(long)]
+        cached: bool,
     },
 
     /// Move or rename files
     Mv {
-        /// Source file
+        /// Source file or directory
         from: String,
-        /// Destination file
+        /// Destination path, or a directory to move into
         to: String,
+        /// Overwrite an existing destination file
+        #[arg(short, long)]
+        force: bool,
     },
 
     /// Restore working tree files
@@ -104,13 +239,25 @@ enum Commands {
 
     /// Show diff between commits
     Diff {
-        /// From commit
+        /// Revision range `<from>..<to>`; either side defaults to HEAD
+        #[arg(conflicts_with_all = ["from", "to"])]
+        range: Option<String>,
+
+        /// From revision (default: HEAD)
         #[arg(long)]
         from: Option<String>,
 
-        /// To commit
+        /// To revision (default: HEAD)
         #[arg(long)]
         to: Option<String>,
+
+        /// Summarize lines added and removed per file instead
+        #[arg(long, conflicts_with = "word_diff")]
+        stat: bool,
+
+        /// Show file contents, highlighting the changed words within lines
+        #[arg(long)]
+        word_diff: bool,
     },
 
     /// Reset to a commit
@@ -119,18 +266,33 @@ enum Commands {
         #[arg(default_value = "mixed")]
         mode: String,
 
-        /// Commit to reset to (default: HEAD)
+        /// Revision to reset to, e.g. HEAD~1 (default: HEAD)
         commit: Option<String>,
     },
 
-    /// Create a tag
+    /// Create, list, or verify tags
     Tag {
         /// Tag name
-        name: String,
+        name: Option<String>,
+
+        /// Revision to tag (defaults to HEAD)
+        commit: Option<String>,
 
         /// Optional tag message
         #[arg(short, long)]
         message: Option<String>,
+
+        /// Sign the annotated tag with the current signing key
+        #[arg(short, long)]
+        sign: bool,
+
+        /// List tags
+        #[arg(short, long)]
+        list: bool,
+
+        /// Verify tag signatures
+        #[arg(long)]
+        verify: bool,
     },
 
     /// List tags
@@ -145,23 +307,59 @@ enum Commands {
     /// Merge a branch
     Merge {
         /// Branch to merge
-        branch: String,
+        #[arg(required_unless_present_any = ["continue", "abort"])]
+        branch: Option<String>,
+
+        /// Merge strategy: simple, recursive, ours, theirs
+        #[arg(short, long, default_value = "simple")]
+        strategy: String,
+
+        /// Leave conflict markers instead of opening the resolver
+        #[arg(long)]
+        no_tui: bool,
+
+        /// Commit the merge once conflicts are resolved
+        #[arg(long, conflicts_with = "abort")]
+        r#continue: bool,
+
+        /// Abandon the merge and restore the branch
+        #[arg(long)]
+        abort: bool,
     },
 
     /// Rebase current branch onto another branch
     Rebase {
         /// Target branch to rebase onto
-        target: String,
+        #[arg(required_unless_present_any = ["continue", "abort"])]
+        target: Option<String>,
 
         /// Use interactive rebase
         #[arg(short, long)]
         interactive: bool,
+
+        /// Resume the rebase once conflicts are resolved
+        #[arg(long, conflicts_with = "abort")]
+        r#continue: bool,
+
+        /// Abandon the rebase and restore the branch
+        #[arg(long)]
+        abort: bool,
     },
 
     /// Cherry-pick a commit
     CherryPick {
         /// Commit ID to cherry-pick
-        commit: String,
+        #[arg(required_unless_present_any = ["continue", "abort"])]
+        commit: Option<String>,
+
+        /// Commit the pick once conflicts are resolved and pick any
+        /// remaining commits of the range
+        #[arg(long, conflicts_with = "abort")]
+        r#continue: bool,
+
+        /// Abandon the cherry-pick and restore the branch
+        #[arg(long)]
+        abort: bool,
     },
 
     /// Cherry-pick a range of commits
@@ -205,26 +403,55 @@ enum Commands {
         action: RemoteAction,
     },
 
+    /// Limit which paths are checked out into the working tree
+    SparseCheckout {
+        #[command(subcommand)]
+        action: SparseAction,
+    },
+
+    /// Manage linked worktrees sharing this repository
+    Worktree {
+        #[command(subcommand)]
+        action: WorktreeAction,
+    },
+
     /// Push commits to remote
     Push {
-        /// Remote name
-        #[arg(default_value = "origin")]
-        remote: String,
+        /// Remote name (defaults to the current branch's upstream)
+        remote: Option<String>,
 
-        /// Branch to push
-        #[arg(default_value = "main")]
-        branch: String,
+        /// Branch to push (defaults to the current branch)
+        branch: Option<String>,
+
+        /// Record the remote and branch as the current branch's upstream
+        #[arg(short = 'u', long = "set-upstream")]
+        set_upstream: bool,
+
+        /// Show what would be pushed without sending anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Bypass the pre-push and post-push hooks, e.g. when a hook is broken
+        #[arg(long)]
+        no_verify: bool,
+
+        /// Replace the remote branch even if it has commits this one lacks
+        /// (needs admin access on the server)
+        #[arg(short, long)]
+        force: bool,
     },
 
     /// Pull commits from remote
     Pull {
-        /// Remote name
-        #[arg(default_value = "origin")]
-        remote: String,
+        /// Remote name (defaults to the current branch's upstream)
+        remote: Option<String>,
 
-        /// Branch to pull
-        #[arg(default_value = "main")]
-        branch: String,
+        /// Branch to pull (defaults to the current branch)
+        branch: Option<String>,
+
+        /// Show what would be pulled without applying anything
+        #[arg(long)]
+        dry_run: bool,
     },
 
     /// Fetch commits from remote
@@ -232,24 +459,66 @@ enum Commands {
         /// Remote name
         #[arg(default_value = "origin")]
         remote: String,
+
+        /// Show what would be fetched without storing anything
+        #[arg(long)]
+        dry_run: bool,
     },
 
     /// Clone a remote repository
     Clone {
-        /// Remote URL
+        /// Remote URL, or a bundle file
         url: String,
 
         /// Destination directory
         destination: Option<String>,
+
+        /// Number of parallel download workers (default: CPU count)
+        #[arg(short, long)]
+        jobs: Option<usize>,
+
+        /// Check out this branch instead of the remote's default
+        #[arg(short, long)]
+        branch: Option<String>,
+
+        /// Only fetch the checked-out branch and its history
+        #[arg(long)]
+        single_branch: bool,
+    },
+
+    /// Write history to a single file for offline transfer
+    Bundle {
+        /// File to write the bundle to
+        output: String,
+
+        /// `<base>..<tip>` for only the commits after base, a revision for
+        /// all of its history (default: every branch)
+        rev_range: Option<String>,
+    },
+
+    /// Import the history in a bundle file
+    Unbundle {
+        /// Bundle file written by `mug bundle`
+        bundle: String,
     },
 
     /// Migrate a Git repository to MUG
+    #[command(visible_alias = "import")]
     Migrate {
         /// Path to Git repository
         git_path: PathBuf,
 
         /// Path to create MUG repository
         mug_path: PathBuf,
+
+        /// Object hash algorithm; sha1 keeps git's blob ids
+        #[arg(long, default_value = "sha256")]
+        hash: HashAlgorithm,
+
+        /// Update a MUG repository migrated earlier with only what git has
+        /// gained since
+        #[arg(long)]
+        incremental: bool,
     },
 
     /// Manage cryptographic signing keys
@@ -277,16 +546,28 @@ enum Commands {
     },
 
     /// Configure repository settings
+    #[command(visible_alias = "config")]
     Conf {
         #[command(subcommand)]
         action: ConfigAction,
     },
 
     /// Verify repository integrity
-    Verify,
+    Verify {
+        /// Re-hash every object and walk every tree
+        #[arg(long)]
+        deep: bool,
+    },
+
+    /// List dangling commits, trees and blobs that no ref reaches
+    Fsck,
 
     /// Garbage collection - optimize repository
-    Gc,
+    Gc {
+        /// Repack every object instead of adding a pack for new ones
+        #[arg(long)]
+        full: bool,
+    },
 
     /// Show reference history
     History {
@@ -294,6 +575,12 @@ enum Commands {
         reference: Option<String>,
     },
 
+    /// Show or expire the reflog (defaults to showing HEAD's)
+    Reflog {
+        #[command(subcommand)]
+        action: Option<ReflogAction>,
+    },
+
     /// Update reference (advanced)
     UpdateRef {
         /// Reference name
@@ -316,6 +603,35 @@ enum Commands {
         /// Base directory for repositories
         #[arg(long, default_value = ".")]
         repos: PathBuf,
+
+        /// Provision an access token for this name and exit
+        #[arg(long, value_name = "NAME", requires = "repo")]
+        add_token: Option<String>,
+
+        /// Register a public key (from `mug keys generate`) that signs in by
+        /// answering a challenge, and exit
+        #[arg(long, value_name = "PUBKEY", requires = "repo", conflicts_with = "add_token")]
+        add_key: Option<String>,
+
+        /// Repository the new token or key grants access to
+        #[arg(long)]
+        repo: Option<String>,
+
+        /// Access level of the new token or key: read, write or admin
+        #[arg(long, default_value = "read")]
+        access: String,
+
+        /// Maximum request body size in megabytes
+        #[arg(long, default_value = "64")]
+        max_body_mb: usize,
+
+        /// Maximum size of one large file upload in megabytes
+        #[arg(long, default_value = "1024")]
+        max_store_mb: u64,
+
+        /// Requests allowed per client each minute (0 disables the limit)
+        #[arg(long, default_value = "120")]
+        rate_limit: u32,
     },
 
     /// Manage resumable operations
@@ -323,6 +639,20 @@ enum Commands {
         #[command(subcommand)]
         action: Option<ResumeAction>,
     },
+
+    /// Print a shell completion script (bash, zsh or fish)
+    Completions {
+        /// Shell to complete for
+        shell: String,
+    },
+
+    /// Print completion candidates for a partial command line
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        /// Words after `mug`, the last being the one to complete
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        words: Vec<String>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -378,6 +708,40 @@ enum ResumeAction {
     },
 }
 
+#[derive(Subcommand)]
+enum SparseAction {
+    /// Check out only paths matching these patterns (directories or globs)
+    Set {
+        #[arg(required = true)]
+        patterns: Vec<String>,
+    },
+    /// Show the patterns in effect
+    List,
+    /// Check out every path again
+    Disable,
+}
+
+#[derive(Subcommand)]
+enum WorktreeAction {
+    /// Check out a branch into a new working directory
+    Add {
+        /// Directory to create the worktree in
+        path: String,
+        /// Branch to check out
+        branch: String,
+    },
+    /// List the main working tree and linked worktrees
+    List,
+    /// Delete a linked worktree
+    Remove {
+        /// Worktree name or path
+        worktree: String,
+        /// Remove even with uncommitted or untracked changes
+        #[arg(long)]
+        force: bool,
+    },
+}
+
 #[derive(Subcommand)]
 enum RemoteAction {
     /// Add a remote
@@ -406,6 +770,21 @@ enum RemoteAction {
         /// New URL
         url: String,
     },
+    /// Store the access token sent to a remote (MUG_TOKEN overrides it)
+    SetToken {
+        /// Remote name
+        name: String,
+        /// Bearer token
+        token: String,
+    },
+    /// Sign in to a remote with a key instead of a stored token
+    SetKey {
+        /// Remote name
+        name: String,
+        /// Public key registered on the server (defaults to the current
+        /// signing key)
+        public_key: Option<String>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -416,14 +795,30 @@ enum ConfigAction {
         key: String,
         /// Config value
         value: String,
+        /// Set it for every repository in the user's global config
+        #[arg(long)]
+        global: bool,
+        /// Allow a key mug does not know about
+        #[arg(long)]
+        add: bool,
     },
     /// Get configuration value
     Get {
         /// Config key
         key: String,
+        /// Read only the global config
+        #[arg(long)]
+        global: bool,
     },
     /// List all configuration
-    List,
+    List {
+        /// List only the global config
+        #[arg(long)]
+        global: bool,
+        /// Show the file each value comes from, including shadowed values
+        #[arg(long)]
+        show_origin: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -466,6 +861,23 @@ enum TemporalAction {
     },
 }
 
+#[derive(Subcommand)]
+enum ReflogAction {
+    /// Show a ref's movements, newest first; `<ref>@{n}` names entry n
+    Show {
+        /// Ref to show (default: HEAD)
+        reference: Option<String>,
+    },
+    /// Delete reflog entries older than a number of days
+    Expire {
+        /// Age in days past which entries are removed
+        #[arg(long, value_name = "DAYS")]
+        older_than: u32,
+        /// Only expire this ref's entries (default: every ref)
+        reference: Option<String>,
+    },
+}
+
 #[derive(Subcommand)]
 enum StoreAction {
     /// Set central server for large files
@@ -481,6 +893,11 @@ enum StoreAction {
         #[arg(default_value = "10")]
         megabytes: usize,
     },
+    /// Set the maximum local cache size in MB
+    SetCacheLimit {
+        /// Size in megabytes
+        megabytes: usize,
+    },
     /// Show cache statistics
     CacheStats,
     /// Clear cache
@@ -494,6 +911,15 @@ enum PackAction {
         /// Output directory for pack files
         #[arg(default_value = ".")]
         output: String,
+        /// Compression algorithm: zstd or gzip
+        #[arg(long, default_value = "zstd")]
+        algorithm: String,
+        /// Compression level (zstd 1..22, gzip 0..9); higher is smaller but slower
+        #[arg(long)]
+        level: Option<u8>,
+        /// Rewrite every pack instead of only packing objects not yet packed
+        #[arg(long)]
+        full: bool,
     },
     /// Show pack file statistics
     Stats {
@@ -509,19 +935,36 @@ enum PackAction {
     },
 }
 
+/// Command line arguments with any `alias.<name>` command expanded.
+/// Aliases come from the repository's config when run inside one.
+fn expand_aliases(args: Vec<String>) -> Result<Vec<String>> {
+    let cli = Cli::command();
+    mug::core::alias::expand(
+        args,
+        |name| cli.find_subcommand(name).is_some() || name == "help",
+        |name| {
+            let key = mug::core::alias::config_key(name);
+            match Repository::discover(".") {
+                Ok(repo) => repo.get_config(&key),
+                Err(_) => Ok(mug::core::config::GlobalConfig::load()?.get(&key).map(str::to_string)),
+            }
+        },
+    )
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let cli = Cli::parse_from(expand_aliases(std::env::args().collect())?);
 
     match cli.command {
-        Commands::Init { path } => {
-            let _repo = Repository::init(&path)?;
+        Commands::Init { path, hash } => {
+            let _repo = Repository::init_with_hash(&path, hash)?;
             println!("Initialized empty MUG repository in {:?}", path);
             println!("Happy Mugging!");
         }
 
         Commands::Add { path } => {
-            let repo = Repository::open(".")?;
+            let repo = Repository::discover(".")?;
             if path == "." {
                 let count = repo.add_all()?;
                 if count == 0 {
@@ -530,121 +973,175 @@ async fn main() -> Result<()> {
                     println!("Staged {} file{}", count, if count == 1 { "" } else { "s" });
                 }
             } else {
-                repo.add(&path)?;
+                repo.add(&repo.relative_path(&path)?)?;
                 println!("Staged {}", path);
             }
             println!("Happy Mugging!");
         }
 
         Commands::Remove { path } => {
-            let repo = Repository::open(".")?;
-            repo.remove(&path)?;
+            let repo = Repository::discover(".")?;
+            repo.remove(&repo.relative_path(&path)?)?;
             println!("Removed {} from staging", path);
             println!("Happy Mugging!");
         }
 
-        Commands::Status => {
+        Commands::Status { short, porcelain } => {
             use mug::ui::UnicodeFormatter;
             
-            let repo = Repository::open(".")?;
-            let _status = repo.status()?;
-            
-            let branch = repo.current_branch()?.unwrap_or("main".to_string());
-            let changes = vec![]; // TODO: Parse actual changes from status
-            
-            let formatter = UnicodeFormatter::new(true, true);
-            println!("{}", formatter.format_status(&branch, &changes));
+            let repo = Repository::discover(".")?;
+            let status = repo.status()?;
+            let entries = status.porcelain();
+
+            if cli.json {
+                let branch = repo.current_branch()?.unwrap_or("HEAD (detached)".to_string());
+                println!("{}", serde_json::to_string_pretty(&status.report(&branch))?);
+            } else if short || porcelain {
+                for entry in &entries {
+                    println!("{}", entry);
+                }
+            } else {
+                let branch = repo.current_branch()?.unwrap_or("HEAD (detached)".to_string());
+                let changes: Vec<(String, char)> = entries
+                    .into_iter()
+                    .map(|e| {
+                        // A file gone from disk reads as deleted even if staged
+                        let kind = match (e.index, e.worktree) {
+                            (_, 'D') => 'D',
+                            (' ', worktree) => worktree,
+                            (index, _) => index,
+                        };
+                        (e.path, kind)
+                    })
+                    .collect();
+
+                let formatter = UnicodeFormatter::new(true, true);
+                println!("{}", formatter.format_status(&branch, &changes));
+            }
         }
 
-        Commands::Commit { message, author } => {
+        Commands::Commit { message, author, sign, signoff, amend, no_verify } => {
             use mug::ui::UnicodeFormatter;
             use mug::ui::formatter::{CommitStats, FileChange, FileMode};
+            use mug::core::commit_editor::{add_signoff, validate_message};
             
-            let repo = Repository::open(".")?;
+            let repo = Repository::discover(".")?.with_hooks(!no_verify);
+            let (user_name, user_email) = repo.user_identity()?;
             
             // Use provided author or fallback to config
-            let author_name = if let Some(a) = author {
-                a
-            } else {
-                let config = mug::core::config::Config::load(std::path::Path::new("."))?;
-                config.get_user_name()
-            };
+            let author_name = author.unwrap_or_else(|| user_name.clone());
             
             // Get current branch name and parent commit BEFORE committing
-            let branch_manager = mug::core::branch::BranchManager::new(repo.get_db().clone());
-            let branch_name = branch_manager.get_head()?.unwrap_or("main".to_string());
-            
-            // Get parent tree hash BEFORE committing
-            let parent_tree_hash = if let Some(branch) = branch_manager.get_branch(&branch_name)? {
-                if !branch.commit_id.is_empty() {
-                    let commit_log = mug::core::commit::CommitLog::new(repo.get_db().clone());
-                    if let Ok(commit) = commit_log.get_commit(&branch.commit_id) {
-                        Some(commit.tree_hash)
-                    } else {
-                        None
-                    }
-                } else {
-                    None
+            let branch_name = match repo.current_branch()? {
+                Some(name) => name,
+                None => {
+                    let formatter = UnicodeFormatter::new(true, true);
+                    eprintln!("{}", formatter.format_warning(
+                        "Committing on a detached HEAD; create a branch to keep this commit"
+                    ));
+                    "HEAD (detached)".to_string()
                 }
+            };
+
+            // The commit being replaced by --amend
+            let amended = if amend {
+                let head = repo.head_commit_id()?.ok_or_else(|| {
+                    mug::core::error::Error::Custom("Cannot amend: there are no commits yet".to_string())
+                })?;
+                Some(mug::core::commit::CommitLog::new(repo.get_db().clone()).get_commit(&head)?)
             } else {
                 None
             };
-            
-            // Get index to count files
-            let index = mug::core::index::Index::new(repo.get_db().clone())?;
-            let file_count = index.len();
-            
-            let commit_id = repo.commit(author_name, message.clone())?;
-            let short_hash = mug::core::hash::short_hash(&commit_id);
 
-            let files: Vec<FileChange> = if let Some(parent_hash) = parent_tree_hash {
-                // Compare with parent tree
-                if let Ok(parent_tree) = repo.get_store().get_tree(&parent_hash) {
-                    let parent_hashes: std::collections::HashSet<String> = 
-                        parent_tree.entries.iter().map(|e| e.name.clone()).collect();
-                    
-                    index.entries()
-                        .into_iter()
-                        .map(|entry| {
-                            let mode = if parent_hashes.contains(&entry.path) {
-                                FileMode::Modified
-                            } else {
-                                FileMode::Created
-                            };
-                            FileChange {
-                                path: entry.path,
-                                mode,
-                            }
-                        })
-                        .collect()
-                } else {
-                    // Fallback: treat all as created
-                    index.entries()
+            let message = match message {
+                Some(message) => message,
+                None => {
+                    use mug::core::commit_editor::{commit_template, run_commit_editor_with_diff};
+
+                    let staged: Vec<String> = mug::core::index::Index::new(repo.get_db().clone())?
+                        .entries()
                         .into_iter()
-                        .map(|entry| FileChange {
-                            path: entry.path,
-                            mode: FileMode::Created,
-                        })
-                        .collect()
+                        .map(|entry| entry.path)
+                        .collect();
+                    if staged.is_empty() && !amend {
+                        return Err(mug::core::error::Error::Custom(
+                            "Nothing to commit. Stage files with 'mug add'.".to_string(),
+                        ));
+                    }
+
+                    // Pre-fill with the amended message or the commit.template file
+                    let mut template = commit_template(&branch_name, &staged);
+                    if let Some(commit) = &amended {
+                        template = format!("{}\n{}", commit.message.trim_end(), template);
+                    } else if let Some(path) = repo.get_config("commit.template")? {
+                        let contents = std::fs::read_to_string(repo.root_path().join(&path))
+                            .map_err(|e| mug::core::error::Error::Custom(format!(
+                                "Failed to read commit template '{}': {}",
+                                path, e
+                            )))?;
+                        template = format!("{}\n{}", contents.trim_end(), template);
+                    }
+
+                    run_commit_editor_with_diff(Some(template), mug::commands::staged_diff(&repo)?)?
+                        .ok_or_else(|| {
+                            mug::core::error::Error::Custom(
+                                "Aborting commit due to empty commit message".to_string(),
+                            )
+                        })?
                 }
+            };
+            validate_message(&message)?;
+            let message = if signoff {
+                add_signoff(&message, &user_name, &user_email)
             } else {
-                // First commit: all files are created
-                index.entries()
-                    .into_iter()
-                    .map(|entry| FileChange {
-                        path: entry.path,
-                        mode: FileMode::Created,
-                    })
-                    .collect()
+                message
             };
             
+            // Get the parent's files BEFORE committing
+            let parent_id = match &amended {
+                Some(commit) => commit.parent.clone(),
+                None => repo.head_commit_id()?,
+            };
+            let parent_files = match parent_id {
+                Some(parent_id) => repo.snapshot(&parent_id)?,
+                None => std::collections::HashMap::new(),
+            };
+            
+            let commit_id = if amend {
+                repo.amend(author_name, Some(message.clone()))?
+            } else if sign {
+                repo.commit_with_signing(author_name, message.clone(), true)?
+            } else {
+                repo.commit(author_name, message.clone())?
+            };
+            let short_hash = mug::core::hash::short_hash(&commit_id);
+
+            let mut changes = repo.diff_snapshots(&parent_files, &repo.snapshot(&commit_id)?)?;
+            repo.count_diff_lines(&mut changes)?;
+            let line_stats = mug::core::diff::diff_stats(&changes);
+            let files: Vec<FileChange> = changes
+                .into_iter()
+                .map(|change| {
+                    let mode = match change.renamed_from {
+                        Some(old_path) => FileMode::Renamed(old_path),
+                        None if change.old_hash.is_empty() => FileMode::Created,
+                        None if change.new_hash.is_empty() => FileMode::Deleted,
+                        None => FileMode::Modified,
+                    };
+                    FileChange {
+                        path: change.path,
+                        mode,
+                    }
+                })
+                .collect();
+            
             let stats = CommitStats {
                 branch: branch_name,
                 commit_hash: short_hash,
                 message,
-                files_changed: file_count,
-                insertions: 0,  // TODO: Calculate from diff
-                deletions: 0,   // TODO: Calculate from diff
+                files_changed: files.len(),
+                insertions: line_stats.lines_added,
+                deletions: line_stats.lines_removed,
                 files,
             };
             
@@ -652,73 +1149,130 @@ async fn main() -> Result<()> {
             println!("{}", formatter.format_commit_summary(&stats));
         }
 
-        Commands::Log { oneline } => {
-            use mug::ui::formatter::{UnicodeFormatter, CommitInfo};
-            
-            let repo = Repository::open(".")?;
-            let commits = repo.log()?;
+        Commands::Log { oneline, show_signature, interactive } => {
+            use mug::ui::formatter::UnicodeFormatter;
             
-            if oneline {
-                // Simple oneline output
-                for commit in commits {
-                    println!("{}", commit.lines().next().unwrap_or(""));
+            let repo = Repository::discover(".")?;
+
+            if interactive {
+                use mug::core::log_tui::{run_log_browser, LogAction};
+                use std::io::IsTerminal;
+
+                let formatter = UnicodeFormatter::new(true, true);
+                if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+                    return Err(mug::core::error::Error::Custom(
+                        "log --interactive needs a terminal".to_string(),
+                    ));
                 }
+                match run_log_browser(&repo)? {
+                    Some(LogAction::Checkout(target)) => {
+                        repo.checkout(target.clone())?;
+                        match repo.current_branch()? {
+                            Some(name) => println!("{}", formatter.format_success(&format!("Switched to branch: {}", name))),
+                            None => println!("{}", formatter.format_warning(&format!(
+                                "HEAD is now detached at {}",
+                                mug::core::hash::short_hash(&target)
+                            ))),
+                        }
+                    }
+                    Some(LogAction::Show(hash)) => {
+                        println!("{}", mug::commands::show_commit(&repo, &hash, false)?);
+                    }
+                    None => {}
+                }
+                return Ok(());
+            }
+            let mut infos = repo.log_structured()?;
+
+            if show_signature {
+                let commit_log = mug::core::commit::CommitLog::new(repo.get_db().clone());
+                for (info, commit) in infos.iter_mut().zip(repo.history()?) {
+                    info.signature = Some(commit_log.verify_signature(&commit)?.describe());
+                }
+            }
+
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&infos)?);
+            } else if oneline {
+                // One commit per line: abbreviated hash and subject
+                let mut output = String::new();
+                for info in &infos {
+                    match &info.signature {
+                        Some(sig) => output.push_str(&format!("{} ({})\n", info.oneline(), sig)),
+                        None => output.push_str(&format!("{}\n", info.oneline())),
+                    }
+                }
+                mug::ui::pager::page(&output, !cli.no_pager)?;
             } else {
                 // Beautiful Unicode output
                 let formatter = UnicodeFormatter::new(true, true);
-                let mut commit_infos = Vec::new();
-                
-                for (i, commit) in commits.iter().enumerate() {
-                    let lines: Vec<&str> = commit.lines().collect();
-                    
-                    // Parse commit format: "commit <hash>\nAuthor: <author>\nDate: <date>\n\n<message>"
-                    let hash = if let Some(first) = lines.first() {
-                        first.replace("commit ", "").to_string()
-                    } else {
-                        "unknown".to_string()
-                    };
-                    
-                    let author = lines.iter()
-                        .find(|l| l.starts_with("Author:"))
-                        .map(|l| l.replace("Author: ", "").trim().to_string())
-                        .unwrap_or("Unknown".to_string());
-                    
-                    let date = lines.iter()
-                        .find(|l| l.starts_with("Date:"))
-                        .map(|l| l.replace("Date: ", "").trim().to_string())
-                        .unwrap_or("Unknown".to_string());
-                    
-                    let message = lines.iter()
-                        .skip_while(|l| !l.is_empty())
-                        .skip(1)
-                        .next()
-                        .unwrap_or(&"")
-                        .trim()
-                        .to_string();
-                    
-                    let is_head = i == 0;
-                    
-                    commit_infos.push(CommitInfo {
-                        hash,
-                        author,
-                        date,
-                        message,
-                        is_head,
-                        branch: None,
-                    });
-                }
-                
-                let output = formatter.format_log(&commit_infos);
-                println!("{}", output);
+                mug::ui::pager::page(&format!("{}\n", formatter.format_log(&infos)), !cli.no_pager)?;
+            }
+        }
+
+        Commands::Shortlog { since, until, summary } => {
+            let repo = Repository::discover(".")?;
+            let authors = mug::commands::shortlog(&repo, since.as_deref(), until.as_deref())?;
+
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&authors)?);
+            } else {
+                let mut output = String::new();
+                for author in &authors {
+                    if summary {
+                        output.push_str(&format!("{:>6}\t{}\n", author.subjects.len(), author.author));
+                        continue;
+                    }
+                    output.push_str(&format!("{} ({}):\n", author.author, author.subjects.len()));
+                    for subject in &author.subjects {
+                        output.push_str(&format!("      {}\n", subject));
+                    }
+                    output.push('\n');
+                }
+                mug::ui::pager::page(&output, !cli.no_pager)?;
             }
         }
 
-        Commands::Inspect { commit } => {
-            let repo = Repository::open(".")?;
-            let info = mug::commands::show_commit(&repo, &commit)?;
+        Commands::Inspect { commit, stat } => {
+            let repo = Repository::discover(".")?;
+            let info = mug::commands::show_commit(&repo, &commit, stat)?;
             println!("{}", info);
         }
 
+        Commands::CatFile { hash, kind, show_type, size } => {
+            use std::io::Write;
+
+            let repo = Repository::discover(".")?;
+            let object = mug::commands::cat_object(&repo, &hash, kind.as_deref())?;
+            if show_type {
+                println!("{}", object.kind);
+            } else if size {
+                println!("{}", object.size);
+            } else {
+                std::io::stdout().write_all(&object.content)?;
+            }
+        }
+
+        Commands::LsTree { rev, recursive } => {
+            let repo = Repository::discover(".")?;
+            for line in mug::commands::ls_tree(&repo, &rev, recursive)? {
+                println!("{}", line);
+            }
+        }
+
+        Commands::Archive { rev, format, output } => {
+            let repo = Repository::discover(".")?;
+            let format = format.parse::<mug::core::archive::ArchiveFormat>()?;
+            let count = mug::core::archive::write_archive(
+                &repo,
+                &rev,
+                format,
+                std::path::Path::new(&output),
+            )?;
+            println!("✓ Wrote {} files from {} to {}", count, rev, output);
+            println!("Happy Mugging!");
+        }
+
         Commands::Grep { pattern } => {
             let results = mug::commands::grep(std::path::Path::new("."), &pattern)?;
             if results.is_empty() {
@@ -731,55 +1285,125 @@ async fn main() -> Result<()> {
             println!("Happy Mugging!");
         }
 
-        Commands::Bookmark { name } => {
+        Commands::Bookmark { name, delete, force_delete } => {
             use mug::ui::UnicodeFormatter;
             
-            let repo = Repository::open(".")?;
-            repo.create_branch(name.clone())?;
-            
+            let repo = Repository::discover(".")?;
             let formatter = UnicodeFormatter::new(true, true);
-            println!("{}", formatter.format_success(&format!("Created branch: {}", name)));
+            if delete || force_delete {
+                repo.delete_branch(&name, force_delete)?;
+                println!("{}", formatter.format_success(&format!("Deleted branch: {}", name)));
+            } else {
+                repo.create_branch(name.clone())?;
+                println!("{}", formatter.format_success(&format!("Created branch: {}", name)));
+            }
         }
 
-        Commands::Bookmarks => {
+        Commands::Bookmarks { remotes, all, interactive } => {
             use mug::ui::UnicodeFormatter;
             
-            let repo = Repository::open(".")?;
+            let repo = Repository::discover(".")?;
             let current = repo.current_branch()?;
-            let branches = repo.branches()?;
+            let mut branches = if remotes { Vec::new() } else { repo.branches()? };
+            if remotes || all {
+                let tracking = mug::remote::RemoteManager::new(repo.get_db().clone()).tracking_branches()?;
+                branches.extend(tracking.into_iter().map(|(name, _)| {
+                    if all { format!("remotes/{}", name) } else { name }
+                }));
+            }
             
-            let current_str = current.unwrap_or("main".to_string());
+            let current_str = current.unwrap_or("HEAD (detached)".to_string());
             
             let formatter = UnicodeFormatter::new(true, true);
             println!("{}", formatter.format_branch_list(&current_str, &branches));
+
+            if interactive {
+                use std::io::IsTerminal;
+
+                if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+                    eprintln!("{}", formatter.format_warning("Not a terminal; skipping branch selection"));
+                } else if let Some(branch) = mug::ui::select_branch_interactive(repo.branches()?, current_str.clone()) {
+                    if branch != current_str {
+                        repo.checkout(branch.clone())?;
+                        println!("{}", formatter.format_success(&format!("Switched to branch: {}", branch)));
+                    }
+                }
+            }
         }
 
         Commands::Checkout { branch } => {
             use mug::ui::UnicodeFormatter;
             
-            let repo = Repository::open(".")?;
+            let repo = Repository::discover(".")?;
             repo.checkout(branch.clone())?;
             
             let formatter = UnicodeFormatter::new(true, true);
-            println!("{}", formatter.format_success(&format!("Switched to branch: {}", branch)));
+            match repo.current_branch()? {
+                Some(name) => println!("{}", formatter.format_success(&format!("Switched to branch: {}", name))),
+                None => {
+                    let head = repo.head_commit_id()?.unwrap_or_default();
+                    println!("{}", formatter.format_warning(&format!(
+                        "HEAD is now detached at {}",
+                        mug::core::hash::short_hash(&head)
+                    )));
+                }
+            }
+        }
+
+        Commands::Switch { name, create } => {
+            use mug::ui::UnicodeFormatter;
+            
+            let repo = Repository::discover(".")?;
+            if create {
+                repo.switch_create(name.clone())?;
+            } else if mug::core::branch::BranchManager::new(repo.get_db().clone())
+                .get_branch(&name)?
+                .is_some()
+            {
+                repo.checkout(name.clone())?;
+            } else {
+                return Err(mug::core::error::Error::BranchNotFound(name));
+            }
+            
+            let formatter = UnicodeFormatter::new(true, true);
+            let message = if create {
+                format!("Switched to a new branch: {}", name)
+            } else {
+                format!("Switched to branch: {}", name)
+            };
+            println!("{}", formatter.format_success(&message));
         }
 
-        Commands::Rm { paths } => {
+        Commands::Rm { paths, cached } => {
             use mug::ui::UnicodeFormatter;
             
-            let repo = Repository::open(".")?;
+            let repo = Repository::discover(".")?;
+            let paths = paths
+                .iter()
+                .map(|p| repo.relative_path(p))
+                .collect::<Result<Vec<_>>>()?;
             let path_refs: Vec<&str> = paths.iter().map(|s| s.as_str()).collect();
-            mug::commands::remove_files(&repo, &path_refs)?;
+            mug::commands::remove_files(&repo, &path_refs, cached)?;
             
             let formatter = UnicodeFormatter::new(true, true);
-            println!("{}", formatter.format_success(&format!("Removed {} files", paths.len())));
+            let summary = if cached {
+                format!("Removed {} files from tracking (kept on disk)", paths.len())
+            } else {
+                format!("Deleted {} files", paths.len())
+            };
+            println!("{}", formatter.format_success(&summary));
         }
 
-        Commands::Mv { from, to } => {
+        Commands::Mv { from, to, force } => {
             use mug::ui::UnicodeFormatter;
             
-            let repo = Repository::open(".")?;
-            mug::commands::mv_file(&repo, &from, &to)?;
+            let repo = Repository::discover(".")?;
+            mug::commands::mv_file(
+                &repo,
+                &repo.relative_path(&from)?,
+                &repo.relative_path(&to)?,
+                force,
+            )?;
             
             let formatter = UnicodeFormatter::new(true, true);
             println!("{}", formatter.format_success(&format!("Moved {} to {}", from, to)));
@@ -788,7 +1412,11 @@ async fn main() -> Result<()> {
         Commands::Restore { paths } => {
             use mug::ui::UnicodeFormatter;
             
-            let repo = Repository::open(".")?;
+            let repo = Repository::discover(".")?;
+            let paths = paths
+                .iter()
+                .map(|p| repo.relative_path(p))
+                .collect::<Result<Vec<_>>>()?;
             let path_refs: Vec<&str> = paths.iter().map(|s| s.as_str()).collect();
             mug::commands::restore_files(&repo, &path_refs)?;
             
@@ -796,9 +1424,26 @@ async fn main() -> Result<()> {
             println!("{}", formatter.format_success(&format!("Restored {} files", paths.len())));
         }
 
-        Commands::Diff { from, to } => {
-            let repo = Repository::open(".")?;
-            let diffs = mug::commands::diff_commits(&repo, from.as_deref(), to.as_deref())?;
+        Commands::Diff { range, from, to, stat, word_diff } => {
+            let repo = Repository::discover(".")?;
+            let (from, to) = match range.as_deref().map(|r| r.split_once("..").unwrap_or((r, ""))) {
+                Some((a, b)) => (
+                    Some(a.to_string()).filter(|a| !a.is_empty()),
+                    Some(b.to_string()).filter(|b| !b.is_empty()),
+                ),
+                None => (from, to),
+            };
+            if word_diff {
+                let hunks = mug::commands::word_diff(&repo, from.as_deref(), to.as_deref())?;
+                let formatter = mug::ui::UnicodeFormatter::new(true, true);
+                print!("{}", formatter.format_diff(&hunks));
+                return Ok(());
+            }
+            let diffs = if stat {
+                mug::commands::diff_stat(&repo, from.as_deref(), to.as_deref())?
+            } else {
+                mug::commands::diff_commits(&repo, from.as_deref(), to.as_deref())?
+            };
             for diff in diffs {
                 println!("{}", diff);
             }
@@ -806,47 +1451,93 @@ async fn main() -> Result<()> {
         }
 
         Commands::Reset { mode, commit } => {
-            let repo = Repository::open(".")?;
+            let repo = Repository::discover(".")?;
             let reset_mode = mug::core::reset::ResetMode::from_str(&mode)?;
             mug::core::reset::reset(&repo, reset_mode, commit.as_deref())?;
+            let head = repo.history()?.remove(0);
             println!(
-                "Reset {} to {:?}",
-                mode,
-                commit.unwrap_or("HEAD".to_string())
+                "HEAD is now at {} {}",
+                mug::core::hash::short_hash(&head.id),
+                head.message.lines().next().unwrap_or_default()
             );
             println!("Happy Mugging!");
         }
 
-        Commands::Tag { name, message } => {
+        Commands::Tag { name, commit, message, sign, list, verify } => {
             use mug::ui::UnicodeFormatter;
             
-            let repo = Repository::open(".")?;
+            let repo = Repository::discover(".")?;
             let tag_manager = mug::core::tag::TagManager::new(repo.get_db().clone());
+            let formatter = UnicodeFormatter::new(true, true);
 
-            // Get current HEAD commit
-            let commits = repo.log()?;
-            let head_commit = commits
-                .first()
-                .map(|c| c.lines().next().unwrap_or(""))
-                .unwrap_or("");
-
-            if let Some(msg) = message {
-                tag_manager.create_annotated(
-                    name.clone(),
-                    head_commit.to_string(),
-                    msg,
-                    "MUG User".to_string(),
-                )?;
-            } else {
-                tag_manager.create(name.clone(), head_commit.to_string())?;
-            }
+            match name {
+                Some(name) if verify && !list => {
+                    let status = tag_manager.verify_signature(&name)?;
+                    if status.is_good() {
+                        println!("{}", formatter.format_success(&status.describe()));
+                    } else {
+                        println!("{}", formatter.format_error(&format!("{}: {}", name, status.describe())));
+                        std::process::exit(1);
+                    }
+                }
+                Some(name) if !list => {
+                    let target = repo.resolve_rev(commit.as_deref().unwrap_or("HEAD"))?;
+
+                    if let Some(msg) = message {
+                        let signing_key = if sign {
+                            let keys = mug::core::crypto::KeyManager::new(repo.get_db().clone());
+                            Some(keys.current()?.ok_or_else(|| {
+                                mug::core::error::Error::Custom(
+                                    "No signing key configured. Run 'mug keys generate' first.".to_string(),
+                                )
+                            })?)
+                        } else {
+                            None
+                        };
+
+                        let (user_name, user_email) = repo.user_identity()?;
+                        tag_manager.create_annotated(
+                            name.clone(),
+                            target,
+                            msg,
+                            format!("{} <{}>", user_name, user_email),
+                            signing_key.as_ref(),
+                        )?;
+                    } else if sign {
+                        return Err(mug::core::error::Error::Custom(
+                            "Signed tags must be annotated; pass a message with -m".to_string(),
+                        ));
+                    } else {
+                        tag_manager.create(name.clone(), target)?;
+                    }
 
-            let formatter = UnicodeFormatter::new(true, true);
-            println!("{}", formatter.format_success(&format!("Created tag: {}", name)));
+                    println!("{}", formatter.format_success(&format!("Created tag: {}", name)));
+                }
+                _ => {
+                    let tags = tag_manager.list()?;
+                    if tags.is_empty() {
+                        println!("No tags found");
+                    }
+                    for tag in tags {
+                        let status = tag_manager.verify_signature(&tag.name)?;
+                        let mut line = tag.name.clone();
+                        if tag.is_signed() {
+                            line = format!("{} {}", status.indicator(), line);
+                        }
+                        if let Some(msg) = &tag.message {
+                            line = format!("{} - {}", line, msg);
+                        }
+                        if verify && tag.is_signed() {
+                            line = format!("{} ({})", line, status.describe());
+                        }
+                        println!("{}", line);
+                    }
+                }
+            }
         }
 
         Commands::Tags => {
-            let repo = Repository::open(".")?;
+            let repo = Repository::discover(".")?;
             let tag_manager = mug::core::tag::TagManager::new(repo.get_db().clone());
             let tags = tag_manager.list()?;
 
@@ -854,10 +1545,15 @@ async fn main() -> Result<()> {
                 println!("No tags found");
             } else {
                 for tag in tags {
+                    let marker = if tag.is_signed() {
+                        format!("{} ", tag_manager.verify_signature(&tag.name)?.indicator())
+                    } else {
+                        String::new()
+                    };
                     if let Some(msg) = tag.message {
-                        println!("{} - {}", tag.name, msg);
+                        println!("{}{} - {}", marker, tag.name, msg);
                     } else {
-                        println!("{}", tag.name);
+                        println!("{}{}", marker, tag.name);
                     }
                 }
             }
@@ -867,7 +1563,7 @@ async fn main() -> Result<()> {
         Commands::DeleteTag { name } => {
             use mug::ui::UnicodeFormatter;
             
-            let repo = Repository::open(".")?;
+            let repo = Repository::discover(".")?;
             let tag_manager = mug::core::tag::TagManager::new(repo.get_db().clone());
             tag_manager.delete(&name)?;
             
@@ -875,38 +1571,73 @@ async fn main() -> Result<()> {
             println!("{}", formatter.format_success(&format!("Deleted tag: {}", name)));
         }
 
-        Commands::Merge { branch } => {
+        Commands::Merge { branch, strategy, no_tui, r#continue, abort } => {
             use mug::ui::UnicodeFormatter;
+            use std::io::IsTerminal;
             
-            let repo = Repository::open(".")?;
-            let result = mug::core::merge::merge(&repo, &branch, mug::core::merge::MergeStrategy::Simple)?;
+            let repo = Repository::discover(".")?;
+            if abort {
+                let state = mug::core::sequencer::abort(&repo, mug::core::sequencer::OperationKind::Merge)?;
+                let formatter = UnicodeFormatter::new(true, true);
+                println!("{}", formatter.format_success(&format!("Merge of {} aborted", state.target)));
+                return Ok(());
+            }
+            let result = match branch {
+                Some(branch) if !r#continue => {
+                    let strategy: mug::core::merge::MergeStrategy = strategy.parse()?;
+                    mug::core::merge::merge(&repo, &branch, strategy)?
+                }
+                _ => mug::core::merge::continue_merge(&repo)?,
+            };
 
             let formatter = UnicodeFormatter::new(true, true);
             if result.merged {
                 println!("{}", formatter.format_success(&result.message));
             } else {
-                println!("{}", formatter.format_error(&format!("Merge failed: {}", result.message)));
-                for conflict in result.conflicts {
-                    println!("  {}", formatter.format_warning(&format!("Conflict: {}", conflict)));
+                let remaining = if !no_tui && std::io::stdin().is_terminal() {
+                    mug::core::merge::resolve_conflicts_interactively(&repo, &result.conflicts)?
+                } else {
+                    result.conflicts.clone()
+                };
+
+                if remaining.is_empty() {
+                    println!("{}", formatter.format_success("All conflicts resolved and staged"));
+                    println!("Run 'mug merge --continue' to conclude the merge");
+                } else {
+                    println!("{}", formatter.format_error(&format!("Merge failed: {}", result.message)));
+                    for conflict in remaining {
+                        println!("  {}", formatter.format_warning(&format!("Conflict: {}", conflict)));
+                    }
                 }
             }
         }
 
-        Commands::Rebase { target, interactive } => {
+        Commands::Rebase { target, interactive, r#continue, abort } => {
             use mug::ui::UnicodeFormatter;
             
-            let repo = Repository::open(".")?;
+            let repo = Repository::discover(".")?;
+            if abort {
+                let state = mug::core::sequencer::abort(&repo, mug::core::sequencer::OperationKind::Rebase)?;
+                let formatter = UnicodeFormatter::new(true, true);
+                println!("{}", formatter.format_success(&format!("Rebase aborted; {} was left unchanged", state.branch)));
+                return Ok(());
+            }
             let strategy = if interactive {
                 mug::core::rebase::RebaseStrategy::Interactive
             } else {
                 mug::core::rebase::RebaseStrategy::Rebase
             };
-            let result = mug::core::rebase::rebase(&repo, &target, strategy)?;
+            let result = match target {
+                Some(target) if !r#continue => mug::core::rebase::rebase(&repo, &target, strategy)?,
+                _ => mug::core::rebase::continue_rebase(&repo)?,
+            };
 
             let formatter = UnicodeFormatter::new(true, true);
             if result.success {
                 println!("{}", formatter.format_success(&result.message));
                 println!("{}", formatter.format_success(&format!("Applied {} commits", result.applied)));
+            } else if result.conflicts.is_empty() {
+                println!("{}", formatter.format_warning(&result.message));
             } else {
                 println!("{}", formatter.format_error("Rebase encountered conflicts:"));
                 for conflict in result.conflicts {
@@ -916,40 +1647,64 @@ async fn main() -> Result<()> {
             }
         }
 
-        Commands::CherryPick { commit } => {
+        Commands::CherryPick { commit, r#continue, abort } => {
             use mug::ui::UnicodeFormatter;
             
-            let repo = Repository::open(".")?;
+            let repo = Repository::discover(".")?;
+            let formatter = UnicodeFormatter::new(true, true);
+            if abort {
+                let state = mug::core::sequencer::abort(&repo, mug::core::sequencer::OperationKind::CherryPick)?;
+                println!("{}", formatter.format_success(&format!("Cherry-pick aborted; {} was restored", state.branch)));
+                return Ok(());
+            }
+            let commit = match commit {
+                Some(commit) if !r#continue => commit,
+                _ => {
+                    let result = mug::core::cherry_pick::continue_cherry_pick(&repo)?;
+                    for picked in &result.picked_commits {
+                        println!("{}", formatter.format_success(&picked.message));
+                    }
+                    if let Some((commit, error)) = result.failed_commits.first() {
+                        println!("{}", formatter.format_error(&format!("Stopped at {}: {}", mug::core::hash::short_hash(commit), error)));
+                        for conflict in &result.conflicts {
+                            println!("  {}", formatter.format_warning(&format!("Conflict: {}", conflict)));
+                        }
+                    }
+                    return Ok(());
+                }
+            };
             let result = mug::core::cherry_pick::cherry_pick(&repo, &commit)?;
 
-            let formatter = UnicodeFormatter::new(true, true);
             if result.success {
                 println!("{}", formatter.format_success(&result.message));
                 println!("{}", formatter.format_success(&format!("New commit: {}", result.new_commit)));
             } else {
                 println!("{}", formatter.format_error(&format!("Cherry-pick failed: {}", result.message)));
+                for conflict in &result.conflicts {
+                    println!("  {}", formatter.format_warning(&format!("Conflict: {}", conflict)));
+                }
             }
         }
 
         Commands::CherryPickRange { start, end } => {
-            let repo = Repository::open(".")?;
+            let repo = Repository::discover(".")?;
             let result = mug::core::cherry_pick::cherry_pick_range(&repo, &start, &end)?;
 
             println!(
                 "Cherry-picked {} of {} commits",
                 result.successful, result.total
             );
-            if result.failed > 0 {
-                println!("Failed to cherry-pick {} commits:", result.failed);
-                for (commit, error) in result.failed_commits {
-                    println!("  {}: {}", commit, error);
+            if let Some((commit, error)) = result.failed_commits.first() {
+                println!("Stopped at {}: {}", mug::core::hash::short_hash(commit), error);
+                for conflict in &result.conflicts {
+                    println!("  Conflict: {}", conflict);
                 }
             }
             println!("Happy Mugging!");
         }
 
         Commands::BisectStart { bad, good } => {
-            let repo = Repository::open(".")?;
+            let repo = Repository::discover(".")?;
             let session = mug::core::bisect::start(&repo, &bad, &good)?;
             println!("Started bisect session");
             println!("Testing commit: {}", session.current_commit);
@@ -958,21 +1713,21 @@ async fn main() -> Result<()> {
         }
 
         Commands::BisectGood => {
-            let _repo = Repository::open(".")?;
+            let _repo = Repository::discover(".")?;
             // In a real implementation, would load persisted session
             println!("Mark current commit as good");
         }
 
         Commands::BisectBad => {
-            let _repo = Repository::open(".")?;
+            let _repo = Repository::discover(".")?;
             // In a real implementation, would load persisted session
             println!("Mark current commit as bad");
         }
 
         Commands::Stash { message } => {
-            let repo = Repository::open(".")?;
+            let repo = Repository::discover(".")?;
             let stash_manager = mug::core::stash::StashManager::new(repo.get_db().clone());
-            let current_branch = repo.current_branch()?.unwrap_or("main".to_string());
+            let current_branch = repo.current_branch()?.unwrap_or("HEAD (detached)".to_string());
             let msg = message.unwrap_or("WIP: stashed changes".to_string());
 
             let index = mug::core::index::Index::new(repo.get_db().clone())?;
@@ -984,7 +1739,7 @@ async fn main() -> Result<()> {
         }
 
         Commands::StashPop => {
-            let repo = Repository::open(".")?;
+            let repo = Repository::discover(".")?;
             let stash_manager = mug::core::stash::StashManager::new(repo.get_db().clone());
 
             match stash_manager.latest()? {
@@ -999,7 +1754,7 @@ async fn main() -> Result<()> {
         }
 
         Commands::StashList => {
-            let repo = Repository::open(".")?;
+            let repo = Repository::discover(".")?;
             let stash_manager = mug::core::stash::StashManager::new(repo.get_db().clone());
             let stashes = stash_manager.list()?;
 
@@ -1012,8 +1767,66 @@ async fn main() -> Result<()> {
             }
         }
 
+        Commands::SparseCheckout { action } => {
+            use mug::core::sparse::{SparseCheckout, SparseConfig};
+
+            let repo = Repository::discover(".")?;
+            let config = match &action {
+                SparseAction::Set { patterns } => SparseConfig::from_patterns(patterns),
+                SparseAction::List => {
+                    match SparseConfig::load(&repo)? {
+                        Some(config) => config.includes.iter().for_each(|p| println!("{}", p)),
+                        None => println!("Sparse checkout is not enabled"),
+                    }
+                    return Ok(());
+                }
+                SparseAction::Disable => SparseConfig::default(),
+            };
+
+            let kept = SparseCheckout::new(repo.clone(), config.clone()).apply()?;
+            match action {
+                SparseAction::Disable => SparseConfig::clear(&repo)?,
+                _ => config.save(&repo)?,
+            }
+            for path in &kept {
+                eprintln!("warning: {} has local changes and was left in place", path);
+            }
+            match SparseConfig::load(&repo)? {
+                Some(config) => println!("Sparse checkout set to: {}", config.includes.join(" ")),
+                None => println!("Sparse checkout disabled; all files checked out"),
+            }
+        }
+
+        Commands::Worktree { action } => {
+            let repo = Repository::discover(".")?;
+
+            match action {
+                WorktreeAction::Add { path, branch } => {
+                    let name = mug::core::worktree::add(&repo, std::path::Path::new(&path), &branch)?;
+                    println!("Created worktree '{}' at {} on branch {}", name, path, branch);
+                }
+                WorktreeAction::List => {
+                    for worktree in mug::core::worktree::list(&repo)? {
+                        let head = match worktree.head.as_deref() {
+                            Some(head) => match head.strip_prefix("detached:") {
+                                Some(id) => format!("(detached at {})", mug::core::hash::short_hash(id)),
+                                None => format!("[{}]", head),
+                            },
+                            None => "(no HEAD)".to_string(),
+                        };
+                        let missing = if worktree.missing { " (missing)" } else { "" };
+                        println!("{}  {}{}", worktree.path.display(), head, missing);
+                    }
+                }
+                WorktreeAction::Remove { worktree, force } => {
+                    let removed = mug::core::worktree::remove(&repo, &worktree, force)?;
+                    println!("Removed worktree at {}", removed.path.display());
+                }
+            }
+        }
+
         Commands::Remote { action } => {
-            let repo = Repository::open(".")?;
+            let repo = Repository::discover(".")?;
             let remote_manager = mug::remote::RemoteManager::new(repo.get_db().clone());
 
             match action {
@@ -1043,25 +1856,61 @@ async fn main() -> Result<()> {
                     remote_manager.update_url(&name, &url)?;
                     println!("Updated remote '{}' URL to {}", name, url);
                 }
+                RemoteAction::SetToken { name, token } => {
+                    remote_manager.set_token(&name, &token)?;
+                    println!("Stored token for remote '{}'", name);
+                }
+                RemoteAction::SetKey { name, public_key } => {
+                    let keys = mug::core::crypto::KeyManager::new(repo.get_db().clone());
+                    let public_key = match public_key {
+                        Some(public_key) => public_key,
+                        None => keys
+                            .current()?
+                            .map(|key| key.public_key)
+                            .ok_or_else(|| mug::core::error::Error::Custom(
+                                "No signing key configured; run 'mug keys generate'".to_string(),
+                            ))?,
+                    };
+                    if keys.get(&public_key)?.is_none_or(|key| key.seed.is_none()) {
+                        return Err(mug::core::error::Error::Custom(format!(
+                            "No private key for {} in this repository (see 'mug keys import')",
+                            public_key
+                        )));
+                    }
+                    remote_manager.set_key(&name, &public_key)?;
+                    println!("Remote '{}' will sign in with key {}", name, public_key);
+                }
             }
         }
 
-        Commands::Push { remote, branch } => {
-            let repo = Repository::open(".")?;
-            let sync_manager = mug::remote::sync::SyncManager::new(repo);
-            let result = sync_manager.push(&remote, &branch).await?;
+        Commands::Push { remote, branch, set_upstream, dry_run, no_verify, force } => {
+            let repo = Repository::discover(".")?.with_hooks(!no_verify);
+            let (remote, branch) = repo.sync_target("push", remote.as_deref(), branch.as_deref())?;
+            let current = repo.current_branch()?;
+            let sync_manager = mug::remote::sync::SyncManager::new(repo.clone()).with_progress(true);
+            let result = sync_manager.push(&remote, &branch, dry_run, force).await?;
 
             if result.success {
                 println!("{}", result.message);
+                if set_upstream && !dry_run {
+                    match current {
+                        Some(current) => {
+                            repo.set_upstream(&current, &remote, &branch)?;
+                            println!("Branch '{}' set up to track '{}/{}'", current, remote, branch);
+                        }
+                        None => eprintln!("warning: HEAD is detached; no upstream was set"),
+                    }
+                }
             } else {
                 eprintln!("Push failed: {}", result.message);
             }
         }
 
-        Commands::Pull { remote, branch } => {
-            let repo = Repository::open(".")?;
-            let sync_manager = mug::remote::sync::SyncManager::new(repo);
-            let result = sync_manager.pull(&remote, &branch).await?;
+        Commands::Pull { remote, branch, dry_run } => {
+            let repo = Repository::discover(".")?;
+            let (remote, branch) = repo.sync_target("pull", remote.as_deref(), branch.as_deref())?;
+            let sync_manager = mug::remote::sync::SyncManager::new(repo).with_progress(true);
+            let result = sync_manager.pull(&remote, &branch, dry_run).await?;
 
             if result.success {
                 println!("{}", result.message);
@@ -1070,10 +1919,10 @@ async fn main() -> Result<()> {
             }
         }
 
-        Commands::Fetch { remote } => {
-            let repo = Repository::open(".")?;
-            let sync_manager = mug::remote::sync::SyncManager::new(repo);
-            let result = sync_manager.fetch(&remote).await?;
+        Commands::Fetch { remote, dry_run } => {
+            let repo = Repository::discover(".")?;
+            let sync_manager = mug::remote::sync::SyncManager::new(repo).with_progress(true);
+            let result = sync_manager.fetch(&remote, dry_run).await?;
 
             if result.success {
                 println!("{}", result.message);
@@ -1082,11 +1931,62 @@ async fn main() -> Result<()> {
             }
         }
 
-        Commands::Clone { url, destination } => {
-            mug::remote::sync::SyncManager::clone(&url, destination.as_deref())?;
+        Commands::Clone { url, destination, .. } if std::path::Path::new(&url).is_file() => {
+            let bundle = std::path::Path::new(&url);
+            let destination = destination.map(PathBuf::from).unwrap_or_else(|| {
+                PathBuf::from(bundle.file_stem().unwrap_or(bundle.as_os_str()))
+            });
+            let result = mug::remote::bundle::clone_bundle(bundle, &destination)?;
+            println!(
+                "Cloned {} into {} ({} commits)",
+                url,
+                destination.display(),
+                result.commits
+            );
+        }
+
+        Commands::Clone { url, destination, jobs, branch, single_branch } => {
+            let result = mug::remote::sync::SyncManager::clone(
+                &url,
+                destination.as_deref(),
+                jobs,
+                branch.as_deref(),
+                single_branch,
+                true,
+            )
+            .await?;
+            println!("{}", result.message);
+        }
+
+        Commands::Bundle { output, rev_range } => {
+            let repo = Repository::discover(".")?;
+            let header =
+                mug::remote::bundle::create_bundle(&repo, rev_range.as_deref(), std::path::Path::new(&output))?;
+            println!(
+                "✓ Wrote {} ({} objects)",
+                output,
+                header.manifest.object_count
+            );
+            for (name, id) in &header.refs {
+                println!("  {} {}", mug::core::hash::short_hash(id), name);
+            }
+            for id in &header.prerequisites {
+                println!("  requires {}", mug::core::hash::short_hash(id));
+            }
+            println!("Happy Mugging!");
+        }
+
+        Commands::Unbundle { bundle } => {
+            let repo = Repository::discover(".")?;
+            let result = mug::remote::bundle::unbundle(&repo, std::path::Path::new(&bundle))?;
+            println!("✓ Imported {} commits from {}", result.commits, bundle);
+            for line in &result.refs {
+                println!("  {}", line);
+            }
+            println!("Happy Mugging!");
         }
 
-        Commands::Migrate { git_path, mug_path } => {
+        Commands::Migrate { git_path, mug_path, hash, incremental } => {
             let git_str = git_path.to_str().ok_or(
                 mug::core::error::Error::Custom("Invalid Git path".to_string())
             )?;
@@ -1094,33 +1994,92 @@ async fn main() -> Result<()> {
                 mug::core::error::Error::Custom("Invalid MUG path".to_string())
             )?;
             
-            let message = mug::remote::git_compat::migrate_git_to_mug(git_str, mug_str)?;
-            println!("✓ Migration complete");
-            println!("{}", message);
+            if incremental {
+                let stats = mug::remote::git_compat::import_git_incremental(git_str, mug_str)?;
+                println!(
+                    "✓ Imported {} new commits and {} new objects",
+                    stats.commits, stats.objects
+                );
+            } else {
+                let message = mug::remote::git_compat::migrate_git_to_mug(git_str, mug_str, hash)?;
+                println!("✓ Migration complete");
+                println!("{}", message);
+            }
+        }
+
+        Commands::Completions { shell } => {
+            print!("{}", mug::ui::completion::script(&shell)?);
+        }
+
+        Commands::Complete { mut words } => {
+            // Aliases in the finished words complete like what they stand for
+            let partial = words.pop().unwrap_or_default();
+            let before: Vec<String> = std::iter::once("mug".to_string()).chain(words).collect();
+            let mut words = expand_aliases(before.clone()).unwrap_or(before);
+            words.push(partial);
+            let repo = Repository::discover(".").ok();
+            let candidates =
+                mug::ui::completion::candidates(&Cli::command(), repo.as_ref(), &words[1..]).unwrap_or_default();
+            for candidate in candidates {
+                println!("{}", candidate);
+            }
         }
 
         Commands::Conf { action } => {
-            let repo = Repository::open(".")?;
-            
+            // Global settings don't need a repository
+            let global = matches!(
+                action,
+                ConfigAction::Set { global: true, .. }
+                    | ConfigAction::Get { global: true, .. }
+                    | ConfigAction::List { global: true, .. }
+            );
+            let repo = if global { None } else { Some(Repository::discover(".")?) };
+
             match action {
-                ConfigAction::Set { key, value } => {
-                    repo.set_config(&key, &value)?;
+                ConfigAction::Set { key, value, add, .. } => {
+                    // Booleans are stored as true/false however they were written
+                    let value = mug::core::config::validate(&key, &value, add)?;
+                    match &repo {
+                        Some(repo) if add => repo.add_config(&key, &value)?,
+                        Some(repo) => repo.set_config(&key, &value)?,
+                        None => mug::core::config::GlobalConfig::load()?.set(&key, &value)?,
+                    }
                     println!("Set {} = {}", key, value);
                     println!("Happy Mugging!");
                 }
-                ConfigAction::Get { key } => {
-                    match repo.get_config(&key)? {
+                ConfigAction::Get { key, .. } => {
+                    let value = match &repo {
+                        Some(repo) => repo.get_config(&key)?,
+                        None => mug::core::config::GlobalConfig::load()?.get(&key).map(str::to_string),
+                    };
+                    match value {
                         Some(value) => println!("{}", value),
                         None => println!("Config key not found: {}", key),
                     }
                 }
-                ConfigAction::List => {
-                    let configs = repo.list_config()?;
-                    if configs.is_empty() {
+                ConfigAction::List { show_origin, .. } => {
+                    let entries = match &repo {
+                        Some(repo) => repo.config_entries()?,
+                        None => mug::core::config::GlobalConfig::load()?.config_entries(),
+                    };
+                    if entries.is_empty() {
                         println!("No configuration found");
+                    } else if show_origin {
+                        let width = entries.iter().map(|e| e.origin.to_string().len()).max().unwrap_or(0);
+                        for entry in entries {
+                            let shadowed = if entry.shadowed { " (shadowed)" } else { "" };
+                            println!(
+                                "{:<width$}  {} = {}{}",
+                                entry.origin.to_string(),
+                                entry.key,
+                                entry.value,
+                                shadowed,
+                                width = width
+                            );
+                        }
                     } else {
-                        for (key, value) in configs {
-                            println!("{} = {}", key, value);
+                        for entry in entries.into_iter().filter(|e| !e.shadowed) {
+                            println!("{} = {}", entry.key, entry.value);
                         }
                     }
                     println!("Happy Mugging!");
@@ -1128,9 +2087,9 @@ async fn main() -> Result<()> {
             }
         }
 
-        Commands::Verify => {
-            let repo = Repository::open(".")?;
-            let issues = mug::core::repo::verify_repository(&repo)?;
+        Commands::Verify { deep } => {
+            let repo = Repository::discover(".")?;
+            let issues = mug::core::repo::verify_repository(&repo, deep)?;
             
             if issues.is_empty() {
                 println!("✓ Repository integrity verified");
@@ -1143,17 +2102,41 @@ async fn main() -> Result<()> {
             println!("Happy Mugging!");
         }
 
-        Commands::Gc => {
-            let repo = Repository::open(".")?;
-            let stats = mug::core::repo::garbage_collect(&repo)?;
+        Commands::Fsck => {
+            let repo = Repository::discover(".")?;
+            let dangling = mug::core::repo::dangling_objects(&repo)?;
+
+            if dangling.is_empty() {
+                println!("✓ No dangling objects");
+            } else {
+                let commit_log = mug::core::commit::CommitLog::new(repo.get_db().clone());
+                for (kind, hash) in &dangling {
+                    let summary = match kind {
+                        mug::core::repo::ObjectKind::Commit => commit_log
+                            .get_commit(hash)
+                            .map(|c| format!(" {}", c.message.lines().next().unwrap_or_default()))
+                            .unwrap_or_default(),
+                        _ => String::new(),
+                    };
+                    println!("dangling {} {}{}", kind, mug::core::hash::short_hash(hash), summary);
+                }
+                println!("Recover a commit with 'mug checkout <hash>' before it is lost");
+            }
+            println!("Happy Mugging!");
+        }
+
+        Commands::Gc { full } => {
+            let repo = Repository::discover(".")?;
+            let stats = mug::core::repo::garbage_collect(&repo, full)?;
             println!("Garbage collection complete");
-            println!("  Cleaned: {} bytes", stats.cleaned_bytes);
+            println!("  Packed: {} loose objects into {} packs", stats.objects_packed, stats.packs);
+            println!("  Saved: {} bytes", stats.cleaned_bytes);
             println!("  Objects: {} remaining", stats.objects_remaining);
             println!("Happy Mugging!");
         }
 
         Commands::History { reference } => {
-            let repo = Repository::open(".")?;
+            let repo = Repository::discover(".")?;
             let history = mug::core::repo::get_reflog(&repo, reference.as_deref())?;
             
             if history.is_empty() {
@@ -1166,41 +2149,123 @@ async fn main() -> Result<()> {
             println!("Happy Mugging!");
         }
 
+        Commands::Reflog { action } => {
+            let repo = Repository::discover(".")?;
+            match action.unwrap_or(ReflogAction::Show { reference: None }) {
+                ReflogAction::Show { reference } => {
+                    let history = mug::core::repo::get_reflog(&repo, reference.as_deref())?;
+                    if history.is_empty() {
+                        println!("No reflog history found");
+                    }
+                    for entry in history {
+                        println!("{}", entry);
+                    }
+                }
+                ReflogAction::Expire { older_than, reference } => {
+                    let cutoff = chrono::Local::now() - chrono::Duration::days(older_than.into());
+                    let removed = mug::core::reflog::Reflog::new(repo.get_db().clone())
+                        .expire(reference.as_deref(), cutoff)?;
+                    repo.get_db().flush()?;
+                    println!("Expired {} reflog entries older than {} days", removed, older_than);
+                }
+            }
+        }
+
         Commands::UpdateRef { reference, value } => {
-            let repo = Repository::open(".")?;
+            let repo = Repository::discover(".")?;
             repo.update_ref(&reference, &value)?;
             println!("Updated {} to {}", reference, mug::core::hash::short_hash(&value));
             println!("Happy Mugging!");
         }
 
-        Commands::Serve { host, port, repos } => {
+        Commands::Serve { host, port, repos, add_token, add_key, repo, access, max_body_mb, max_store_mb, rate_limit } => {
+            if let Some(public_key) = add_key {
+                use mug::core::auth::{Permission, ServerAuth};
+
+                let repo = repo.unwrap_or_default();
+                let permission = Permission::for_access(&access, &repo)?;
+                let mut auth = ServerAuth::open(mug::remote::server::tokens_path(&repos))?;
+                let name = format!("key {}", &public_key[..public_key.len().min(12)]);
+                auth.add_key(&public_key, name, vec![permission])?;
+                auth.save()?;
+
+                println!("Added {} key on repo '{}': {}", access, repo, public_key);
+                return Ok(());
+            }
+            if let Some(name) = add_token {
+                use mug::core::auth::{AuthManager, Permission, ServerAuth};
+
+                let repo = repo.unwrap_or_default();
+                let permission = Permission::for_access(&access, &repo)?;
+                let mut auth = ServerAuth::open(mug::remote::server::tokens_path(&repos))?;
+                let token = AuthManager::generate_token();
+                auth.add_token(token.clone(), name.clone(), vec![permission]);
+                auth.save()?;
+
+                println!("Added {} token for '{}' on repo '{}':", access, name, repo);
+                println!("{}", token);
+                return Ok(());
+            }
+
             println!("Starting MUG server on {}:{}", host, port);
             println!("Base repository directory: {}", repos.display());
             
-            mug::remote::server::run_server(repos, &host, port).await?;
+            let limits = mug::remote::server::ServerLimits {
+                max_body_bytes: max_body_mb * 1024 * 1024,
+                max_store_bytes: max_store_mb * 1024 * 1024,
+                requests_per_minute: rate_limit,
+            };
+            mug::remote::server::run_server(repos, &host, port, limits).await?;
         }
 
         Commands::Keys { action } => {
+            use mug::core::crypto::{CryptoKey, KeyManager};
+
+            let keys = Repository::discover(".")
+                .ok()
+                .map(|repo| KeyManager::new(repo.get_db().clone()));
+
             match action {
                 KeyAction::Generate => {
-                    let (key, public) = mug::core::crypto::CryptoKey::generate()?;
+                    let (key, public) = CryptoKey::generate()?;
                     if let Some(seed) = &key.seed {
                         println!("✓ Signing key generated");
                         println!("Public Key: {}", public);
                         println!("Seed (save securely): {}", seed);
                         println!("⚠️  Never share your seed");
                     }
+                    if let Some(keys) = &keys {
+                        keys.add(&key)?;
+                        println!("✓ Set as current signing key");
+                    }
                 }
                 KeyAction::List => {
-                    println!("TODO: List signing keys from repo");
+                    let keys = keys.ok_or(mug::core::error::Error::NotARepository)?;
+                    let current = keys.current()?.map(|k| k.public_key);
+                    let all = keys.list()?;
+                    if all.is_empty() {
+                        println!("No signing keys");
+                    }
+                    for key in all {
+                        let marker = if current.as_deref() == Some(key.public_key.as_str()) { "*" } else { " " };
+                        println!("{} {}", marker, key.public_key);
+                    }
                 }
                 KeyAction::Import { seed } => {
-                    let key = mug::core::crypto::CryptoKey::from_seed(&seed)?;
+                    let key = CryptoKey::from_seed(&seed)?;
                     println!("✓ Key imported");
                     println!("Public Key: {}", key.public_key);
+                    if let Some(keys) = &keys {
+                        keys.add(&key)?;
+                        println!("✓ Set as current signing key");
+                    }
                 }
                 KeyAction::Current => {
-                    println!("TODO: Show current signing key");
+                    let keys = keys.ok_or(mug::core::error::Error::NotARepository)?;
+                    match keys.current()? {
+                        Some(key) => println!("Current signing key: {}", key.public_key),
+                        None => println!("No signing key configured"),
+                    }
                 }
             }
             println!("Happy Mugging!");
@@ -1209,7 +2274,7 @@ async fn main() -> Result<()> {
         Commands::Temporal { action } => {
             use mug::core::temporal::TemporalBranchManager;
             
-            let repo = Repository::open(".")?;
+            let repo = Repository::discover(".")?;
             let temporal = TemporalBranchManager::new(repo.get_db().clone());
             
             match action {
@@ -1241,17 +2306,21 @@ async fn main() -> Result<()> {
         }
 
         Commands::Store { action } => {
-            use mug::core::store_manager::{StoreManager, StoreConfig};
-            
-            let config = StoreConfig::default();
-            let mut manager = StoreManager::new(config);
+            use mug::core::store_manager::StoreConfig;
+
+            let repo = Repository::discover(".")?;
+            let mut manager = repo.store_manager();
+            let megabytes = |bytes: usize| bytes as f64 / (1024.0 * 1024.0);
             
             match action {
                 StoreAction::SetServer { url } => {
+                    repo.set_config(StoreConfig::SERVER_KEY, &url)?;
                     println!("✓ Central server configured: {}", url);
-                    println!("Large files (>10MB) will be stored centrally");
-                    println!("Local cache: .mug/cache/ (1GB max)");
-                    manager.set_central_server(url);
+                    println!(
+                        "Large files (>={}MB) will be stored centrally",
+                        manager.large_file_threshold() / (1024 * 1024)
+                    );
+                    println!("Local cache: .mug/cache/ ({:.0}MB max)", megabytes(manager.cache_limit()));
                 }
                 StoreAction::Config => {
                     println!("Store Configuration:");
@@ -1262,14 +2331,25 @@ async fn main() -> Result<()> {
                         println!("  Central server: (not configured)");
                     }
                     println!("  Cache directory: .mug/cache/");
+                    println!("  Cache limit: {:.0}MB", megabytes(manager.cache_limit()));
                     println!("  Cache policy: LRU");
                 }
                 StoreAction::SetThreshold { megabytes } => {
                     let bytes = megabytes * 1024 * 1024;
-                    manager.set_large_file_threshold(bytes);
+                    repo.set_config(StoreConfig::THRESHOLD_KEY, &bytes.to_string())?;
                     println!("✓ Threshold set to {}MB", megabytes);
                     println!("Files >= {}MB will use central storage", megabytes);
                 }
+                StoreAction::SetCacheLimit { megabytes: limit } => {
+                    let bytes = limit * 1024 * 1024;
+                    repo.set_config(StoreConfig::CACHE_LIMIT_KEY, &bytes.to_string())?;
+                    manager.set_cache_limit(bytes);
+                    let evicted = manager.enforce_cache_limit()?;
+                    println!("✓ Cache limit set to {}MB", limit);
+                    if evicted > 0 {
+                        println!("Evicted {} cached file(s) to fit", evicted);
+                    }
+                }
                 StoreAction::CacheStats => {
                     let stats = manager.cache_stats();
                     let size = manager.cache_size()?;
@@ -1277,8 +2357,8 @@ async fn main() -> Result<()> {
                     println!("  Hits: {}", stats.hits);
                     println!("  Misses: {}", stats.misses);
                     println!("  Evictions: {}", stats.evictions);
-                    println!("  Current size: {:.2}MB", size as f64 / (1024.0 * 1024.0));
-                    println!("  Max size: 1.0GB");
+                    println!("  Current size: {:.2}MB", megabytes(size));
+                    println!("  Max size: {:.2}MB", megabytes(manager.cache_limit()));
                 }
                 StoreAction::ClearCache => {
                     manager.clear_cache()?;
@@ -1292,27 +2372,39 @@ async fn main() -> Result<()> {
             use mug::pack::{RepositoryPacker, PackBuilder, PackReader};
             
             match action {
-                PackAction::Create { output } => {
+                PackAction::Create { output, algorithm, level, full } => {
+                    let compression = mug::pack::compression::CompressionSettings::new(
+                        mug::pack::compression::Algorithm::parse(&algorithm)?,
+                        level,
+                    )?;
                     println!("✓ Creating pack files from repository objects...");
                     println!("  Output directory: {}", output);
-                    println!("  Compression: zstd (10x faster than zlib)");
+                    println!("  Compression: {}", compression);
                     println!("  Deduplication: content-addressed blocks (rolling hash)");
                     println!("");
                     
                     let builder = PackBuilder::new(
                         std::path::Path::new("."),
-                        2_000_000_000  // 2GB target pack size
+                        mug::pack::pack_builder::DEFAULT_PACK_SIZE
                     ).unwrap_or_else(|_| {
                         eprintln!("Error: Could not initialize pack builder");
                         std::process::exit(1);
-                    });
+                    }).with_compression(compression);
                     
-                    match builder.build_packs(std::path::Path::new(&output)) {
+                    let output_dir = std::path::Path::new(&output);
+                    let manifest_path = output_dir.join(mug::pack::pack_builder::PACK_MANIFEST);
+                    let built = match mug::pack::PackManifest::load(&manifest_path) {
+                        Ok(existing) if !full => {
+                            println!("  Incremental: adding to {} existing packs", existing.packs.len());
+                            builder.build_incremental(output_dir, &existing)
+                        }
+                        _ => builder.build_packs(output_dir),
+                    };
+                    match built {
                         Ok(manifest) => {
                             manifest.display();
                             
                             // Save manifest
-                            let manifest_path = std::path::Path::new(&output).join("manifest.json");
                             if let Err(e) = manifest.save(&manifest_path) {
                                 eprintln!("Warning: Could not save manifest: {}", e);
                             } else {
@@ -1376,7 +2468,7 @@ async fn main() -> Result<()> {
         Commands::Resume { action } => {
             use mug::core::resume::{OperationManager, OperationStatus};
 
-            let repo = Repository::open(".")?;
+            let repo = Repository::discover(".")?;
             let manager = OperationManager::new(repo.get_db().clone());
 
             match action {
@@ -1436,7 +2528,7 @@ async fn main() -> Result<()> {
                 }
 
                 Some(ResumeAction::Show { operation_id }) => {
-                    match manager.get(&operation_id)? {
+                    match manager.find(&operation_id)? {
                         Some(op) => {
                             println!("Operation Details:");
                             println!("  ID: {}", op.id);
@@ -1481,29 +2573,41 @@ async fn main() -> Result<()> {
                 }
 
                 Some(ResumeAction::Continue { operation_id }) => {
-                    match manager.get(&operation_id)? {
+                    match manager.find(&operation_id)? {
                         Some(op) => {
-                            println!("Resuming operation: {} ({})", &operation_id[..16], op.op_type.as_str());
+                            println!("Resuming operation: {} ({})", &op.id[..16], op.op_type.as_str());
                             println!("Previous checkpoint: {}", op.state.current_step);
                             println!("Progress: {}/{} items", 
                                 op.progress.processed,
                                 op.progress.total.map(|t| t.to_string()).unwrap_or_else(|| "unknown".to_string())
                             );
                             println!();
-                            println!("⚠️  Resume functionality is operation-specific");
-                            println!("Run the original command with --resume {} to continue", &operation_id[..16]);
+
+                            let sync_manager = mug::remote::sync::SyncManager::new(repo.clone()).with_progress(true);
+                            let result = sync_manager.resume(&op).await?;
+                            if result.success {
+                                println!("{}", result.message);
+                            } else {
+                                eprintln!("{} failed: {}", op.op_type.as_str(), result.message);
+                            }
                         }
                         None => println!("Operation {} not found", operation_id),
                     }
                 }
 
                 Some(ResumeAction::Pause { operation_id }) => {
-                    manager.update_status(&operation_id, OperationStatus::Paused)?;
+                    let op = manager.find(&operation_id)?.ok_or_else(|| {
+                        mug::core::error::Error::Custom(format!("Operation {} not found", operation_id))
+                    })?;
+                    manager.update_status(&op.id, OperationStatus::Paused)?;
                     println!("✓ Operation paused");
                 }
 
                 Some(ResumeAction::Delete { operation_id }) => {
-                    manager.delete(&operation_id)?;
+                    let op = manager.find(&operation_id)?.ok_or_else(|| {
+                        mug::core::error::Error::Custom(format!("Operation {} not found", operation_id))
+                    })?;
+                    manager.delete(&op.id)?;
                     println!("✓ Operation deleted");
                 }
 
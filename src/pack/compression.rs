@@ -44,11 +44,25 @@ impl Compressor for ZstdCompressor {
 }
 
 /// Flate2/zlib compression (backwards compatible with Git)
-pub struct FlateCompressor;
+pub struct FlateCompressor {
+    level: u32,
+}
+
+impl FlateCompressor {
+    pub fn new(level: u32) -> Self {
+        FlateCompressor { level }
+    }
+}
+
+impl Default for FlateCompressor {
+    fn default() -> Self {
+        FlateCompressor { level: 6 }
+    }
+}
 
 impl Compressor for FlateCompressor {
     fn compress(&self, data: &[u8]) -> std::io::Result<Vec<u8>> {
-        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::new(self.level));
         encoder.write_all(data)?;
         encoder.finish()
     }
@@ -61,6 +75,112 @@ impl Compressor for FlateCompressor {
     }
 }
 
+/// Compression algorithm, identified by a byte in pack headers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Zstd,
+    Gzip,
+}
+
+impl Algorithm {
+    pub fn parse(name: &str) -> std::io::Result<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "zstd" => Ok(Algorithm::Zstd),
+            "gzip" | "zlib" => Ok(Algorithm::Gzip),
+            other => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Unknown compression algorithm '{}' (expected zstd or gzip)", other),
+            )),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Zstd => "zstd",
+            Algorithm::Gzip => "gzip",
+        }
+    }
+
+    pub fn id(self) -> u8 {
+        match self {
+            Algorithm::Zstd => 1,
+            Algorithm::Gzip => 2,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(Algorithm::Zstd),
+            2 => Some(Algorithm::Gzip),
+            _ => None,
+        }
+    }
+
+    /// Levels the algorithm accepts
+    pub fn levels(self) -> std::ops::RangeInclusive<u8> {
+        match self {
+            Algorithm::Zstd => 1..=22,
+            Algorithm::Gzip => 0..=9,
+        }
+    }
+
+    pub fn default_level(self) -> u8 {
+        match self {
+            Algorithm::Zstd => 3,
+            Algorithm::Gzip => 6,
+        }
+    }
+}
+
+/// Algorithm and level used to compress a pack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionSettings {
+    pub algorithm: Algorithm,
+    pub level: u8,
+}
+
+impl CompressionSettings {
+    /// Settings for `algorithm`, using its default level when `level` is `None`
+    pub fn new(algorithm: Algorithm, level: Option<u8>) -> std::io::Result<Self> {
+        let level = level.unwrap_or_else(|| algorithm.default_level());
+        if !algorithm.levels().contains(&level) {
+            let levels = algorithm.levels();
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "Invalid {} level {} (expected {}..{})",
+                    algorithm.name(),
+                    level,
+                    levels.start(),
+                    levels.end()
+                ),
+            ));
+        }
+        Ok(CompressionSettings { algorithm, level })
+    }
+
+    /// Zstd at its fast default level
+    pub fn fast() -> Self {
+        CompressionSettings {
+            algorithm: Algorithm::Zstd,
+            level: Algorithm::Zstd.default_level(),
+        }
+    }
+
+    pub fn compressor(&self) -> Box<dyn Compressor> {
+        match self.algorithm {
+            Algorithm::Zstd => Box::new(ZstdCompressor::new(self.level as i32)),
+            Algorithm::Gzip => Box::new(FlateCompressor::new(self.level as u32)),
+        }
+    }
+}
+
+impl std::fmt::Display for CompressionSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (level {})", self.algorithm.name(), self.level)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -88,4 +208,18 @@ mod tests {
         // Highly repetitive data should compress < 1%
         assert!(ratio < 0.01);
     }
+
+    #[test]
+    fn test_compression_settings() {
+        let gzip = CompressionSettings::new(Algorithm::parse("gzip").unwrap(), Some(9)).unwrap();
+        let data = b"hello world".repeat(100);
+        let compressor = gzip.compressor();
+        assert_eq!(compressor.decompress(&compressor.compress(&data).unwrap()).unwrap(), data);
+
+        assert_eq!(CompressionSettings::new(Algorithm::Zstd, None).unwrap(), CompressionSettings::fast());
+        assert!(CompressionSettings::new(Algorithm::Zstd, Some(23)).is_err());
+        assert!(CompressionSettings::new(Algorithm::Gzip, Some(10)).is_err());
+        assert!(Algorithm::parse("lz4").is_err());
+        assert_eq!(Algorithm::from_id(Algorithm::Gzip.id()), Some(Algorithm::Gzip));
+    }
 }
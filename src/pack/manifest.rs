@@ -125,6 +125,15 @@ impl ChunkPackManifest {
         self.chunks.iter().find(|c| c.hash == hash)
     }
 
+    /// Keep only the chunks `keep` accepts, updating the totals to match
+    pub fn retain_chunks(&mut self, keep: impl Fn(&ChunkMetadata) -> bool) {
+        self.chunks.retain(|chunk| keep(chunk));
+        let hashes: std::collections::HashSet<_> = self.chunks.iter().map(|c| c.hash.clone()).collect();
+        self.checksums.retain(|hash, _| hashes.contains(hash));
+        self.chunk_count = self.chunks.len();
+        self.total_size = self.get_download_size();
+    }
+
     pub fn get_download_size(&self) -> u64 {
         self.chunks
             .iter()
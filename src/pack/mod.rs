@@ -1,5 +1,6 @@
 pub mod chunk;
 pub mod compression;
+pub mod manifest;
 pub mod pack_file;
 pub mod chunker;
 pub mod packer;
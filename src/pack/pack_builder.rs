@@ -1,15 +1,34 @@
 use super::chunker::Chunker;
-use super::compression::{ZstdCompressor, Compressor};
+use super::pack_reader::PackReader;
+use super::compression::{Compressor, CompressionSettings};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::io::Write;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use sha2::{Sha256, Digest};
 
+/// Target size of each pack file
+pub const DEFAULT_PACK_SIZE: u64 = 2_000_000_000;
+
+/// File name of the manifest written next to a set of packs
+pub const PACK_MANIFEST: &str = "manifest.json";
+
+/// Magic bytes opening every pack file
+pub const PACK_MAGIC: &[u8; 4] = b"MUG1";
+/// Version 2 records the compression algorithm and level after the version;
+/// version 3 stores chunk hashes as raw digests in fixed-size index entries
+pub const PACK_VERSION: u8 = 3;
+/// Header layout: magic, version, algorithm, level, u32 chunk count
+pub const HEADER_SIZE: usize = 11;
+/// Index entry layout: 32-byte SHA-256 digest, u32 original size,
+/// u32 compressed size, u64 record offset
+pub const ENTRY_SIZE: usize = 48;
+
 /// Builds pack files from repository objects with chunking and compression
 pub struct PackBuilder {
     chunker: Chunker,
-    compressor: ZstdCompressor,
+    compression: CompressionSettings,
+    compressor: Box<dyn Compressor>,
     target_pack_size: u64,
     objects_dir: PathBuf,
 }
@@ -20,137 +39,216 @@ impl PackBuilder {
         
         Ok(PackBuilder {
             chunker: Chunker::new(),
-            compressor: ZstdCompressor::fast(),
+            compression: CompressionSettings::fast(),
+            compressor: CompressionSettings::fast().compressor(),
             target_pack_size,
             objects_dir,
         })
     }
 
+    /// Compress chunks with `settings` instead of zstd's fast level
+    pub fn with_compression(mut self, settings: CompressionSettings) -> Self {
+        self.compressor = settings.compressor();
+        self.compression = settings;
+        self
+    }
+
     /// Build all packs and return manifest
     pub fn build_packs(&self, output_dir: &Path) -> std::io::Result<PackManifest> {
-        fs::create_dir_all(output_dir)?;
-
-        let mut manifest = PackManifest::new();
-        let mut current_pack = PackBuffer::new(0);
-        let mut chunk_registry: HashMap<String, ChunkLocation> = HashMap::new();
+        self.build_packs_including(output_dir, None)
+    }
 
-        // Walk all objects
-        if !self.objects_dir.exists() {
+    /// Build packs from the loose objects plus every object `existing`
+    /// already holds, so the new packs can replace the old ones
+    pub fn build_packs_including(
+        &self,
+        output_dir: &Path,
+        existing: Option<&PackReader>,
+    ) -> std::io::Result<PackManifest> {
+        if !self.objects_dir.exists() && existing.is_none() {
+            fs::create_dir_all(output_dir)?;
             eprintln!("No objects directory found");
-            return Ok(manifest);
+            return Ok(PackManifest::new());
         }
+        self.build(output_dir, PackManifest::new(), existing)
+    }
 
-        let mut object_count = 0;
-        for entry in walkdir::WalkDir::new(&self.objects_dir)
+    /// Add the loose objects `existing` doesn't cover to a fresh pack next to
+    /// its packs, leaving those untouched. Chunks already stored are reused.
+    pub fn build_incremental(&self, output_dir: &Path, existing: &PackManifest) -> std::io::Result<PackManifest> {
+        self.build(output_dir, existing.clone(), None)
+    }
+
+    fn build(
+        &self,
+        output_dir: &Path,
+        mut manifest: PackManifest,
+        existing: Option<&PackReader>,
+    ) -> std::io::Result<PackManifest> {
+        fs::create_dir_all(output_dir)?;
+        let mut current_pack = PackBuffer::new(manifest.packs.len() as u32);
+
+        // Walk all objects
+        let loose = walkdir::WalkDir::new(&self.objects_dir)
             .into_iter()
             .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_file())
-        {
-            let path = entry.path();
-            let object_name = path.file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("unknown");
-
-            if let Ok(data) = fs::read(path) {
-                object_count += 1;
-
-                // Chunk the object
-                let chunks = self.chunker.split(&data);
-
-                for (chunk_data, chunk_hash) in chunks {
-                    // Compress chunk
-                    let compressed = self.compressor.compress(&chunk_data)
-                        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-
-                    // Check if starting new pack
-                    if current_pack.size + compressed.len() as u64 > self.target_pack_size {
-                        // Finalize current pack
-                        let pack_info = self.write_pack(&current_pack, output_dir, manifest.packs.len())?;
-                        manifest.packs.push(pack_info);
-
-                        current_pack = PackBuffer::new(manifest.packs.len() as u32);
-                    }
-
-                    // Add to current pack
-                    let offset = current_pack.size;
-                    current_pack.chunks.push(ChunkEntry {
-                        hash: chunk_hash.clone(),
-                        offset,
-                        size: compressed.len() as u32,
-                        original_size: chunk_data.len() as u32,
-                    });
-                    current_pack.data.write_all(&compressed)?;
-                    current_pack.size += compressed.len() as u64;
-
-                    // Register chunk location
-                    chunk_registry.insert(chunk_hash, ChunkLocation {
-                        pack_id: current_pack.pack_id,
-                        offset,
-                    });
+            .filter(|e| e.file_type().is_file());
+        for entry in loose {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if manifest.objects.contains_key(&name) {
+                continue;
+            }
+            if let Ok(data) = fs::read(entry.path()) {
+                self.add_object(name, &data, &mut current_pack, output_dir, &mut manifest)?;
+            }
+        }
+
+        if let Some(existing) = existing {
+            for name in existing.object_names() {
+                if !manifest.objects.contains_key(name) {
+                    let data = existing.get_object(name)?;
+                    self.add_object(name.clone(), &data, &mut current_pack, output_dir, &mut manifest)?;
                 }
             }
         }
 
         // Finalize last pack
         if !current_pack.chunks.is_empty() {
-            let pack_info = self.write_pack(&current_pack, output_dir, manifest.packs.len())?;
-            manifest.packs.push(pack_info);
+            self.finish_pack(&current_pack, output_dir, &mut manifest)?;
         }
 
-        manifest.object_count = object_count;
-        manifest.chunk_registry = chunk_registry;
+        manifest.object_count = manifest.objects.len();
         manifest.created_at = chrono::Utc::now().to_rfc3339();
 
         Ok(manifest)
     }
 
-    /// Write a single pack file with index
-    fn write_pack(&self, buffer: &PackBuffer, output_dir: &Path, pack_num: usize) -> std::io::Result<PackInfo> {
+    /// Chunk, compress and buffer one object, starting a new pack when the
+    /// current one would grow past the target size
+    fn add_object(
+        &self,
+        name: String,
+        data: &[u8],
+        current_pack: &mut PackBuffer,
+        output_dir: &Path,
+        manifest: &mut PackManifest,
+    ) -> std::io::Result<()> {
+        let mut chunk_hashes = Vec::new();
+        for (chunk_data, chunk_hash) in self.chunker.split(data) {
+            // Identical chunks are stored once and shared between objects
+            if manifest.chunk_registry.contains_key(&chunk_hash) || current_pack.hashes.contains(&chunk_hash) {
+                manifest.duplicate_chunks += 1;
+                manifest.deduplicated_bytes += chunk_data.len() as u64;
+                chunk_hashes.push(chunk_hash);
+                continue;
+            }
+
+            // Compress chunk
+            let compressed = self.compressor.compress(&chunk_data)?;
+
+            // Check if starting new pack
+            if current_pack.size + compressed.len() as u64 > self.target_pack_size {
+                // Finalize current pack
+                self.finish_pack(current_pack, output_dir, manifest)?;
+                *current_pack = PackBuffer::new(manifest.packs.len() as u32);
+            }
+
+            // Add to current pack as a [u32 length][compressed bytes] record
+            let offset = current_pack.size;
+            current_pack.chunks.push(ChunkEntry {
+                hash: chunk_hash.clone(),
+                offset,
+                size: compressed.len() as u32,
+                original_size: chunk_data.len() as u32,
+            });
+            current_pack.data.write_all(&(compressed.len() as u32).to_le_bytes())?;
+            current_pack.data.write_all(&compressed)?;
+            current_pack.size += 4 + compressed.len() as u64;
+            current_pack.hashes.insert(chunk_hash.clone());
+            manifest.uncompressed_size += chunk_data.len() as u64;
+            manifest.compressed_size += compressed.len() as u64;
+            chunk_hashes.push(chunk_hash);
+        }
+        manifest.objects.insert(name, chunk_hashes);
+        Ok(())
+    }
+
+    /// Write a pack and register its chunks at their offsets in the file
+    fn finish_pack(&self, buffer: &PackBuffer, output_dir: &Path, manifest: &mut PackManifest) -> std::io::Result<()> {
+        let (pack_info, data_start) = self.write_pack(buffer, output_dir, manifest.packs.len())?;
+        for chunk in &buffer.chunks {
+            manifest.chunk_registry.insert(chunk.hash.clone(), ChunkLocation {
+                pack_id: buffer.pack_id,
+                offset: data_start + chunk.offset,
+            });
+        }
+        manifest.packs.push(pack_info);
+        Ok(())
+    }
+
+    /// Write a single pack file with index, returning where its data starts.
+    ///
+    /// The pack ends with the SHA-256 of every preceding byte.
+    fn write_pack(&self, buffer: &PackBuffer, output_dir: &Path, pack_num: usize) -> std::io::Result<(PackInfo, u64)> {
         let pack_name = format!("pack-{:04}.mug", pack_num);
         let pack_path = output_dir.join(&pack_name);
 
-        let mut file = fs::File::create(&pack_path)?;
+        let mut pack = Vec::with_capacity(buffer.data.len() + 64);
 
         // Write magic header
-        file.write_all(b"MUG1")?;
+        pack.write_all(PACK_MAGIC)?;
 
-        // Write pack version
-        file.write_all(&[1u8])?;
+        // Write pack version and the compression readers must use
+        pack.write_all(&[PACK_VERSION, self.compression.algorithm.id(), self.compression.level])?;
 
         // Write number of chunks
-        file.write_all(&(buffer.chunks.len() as u32).to_le_bytes())?;
+        pack.write_all(&(buffer.chunks.len() as u32).to_le_bytes())?;
 
-        // Write chunk entries and data
-        let mut data_offset = 0u64;
-        for chunk in &buffer.chunks {
-            // Write entry header
-            file.write_all(chunk.hash.as_bytes())?;
-            file.write_all(&chunk.original_size.to_le_bytes())?;
-            file.write_all(&chunk.size.to_le_bytes())?;
-            file.write_all(&data_offset.to_le_bytes())?;
+        // Data follows the header and one index entry per chunk
+        let data_start = (HEADER_SIZE + buffer.chunks.len() * ENTRY_SIZE) as u64;
 
-            data_offset += chunk.size as u64;
+        // Write chunk entries
+        for chunk in &buffer.chunks {
+            pack.write_all(&encode_hash(&chunk.hash)?)?;
+            pack.write_all(&chunk.original_size.to_le_bytes())?;
+            pack.write_all(&chunk.size.to_le_bytes())?;
+            pack.write_all(&(data_start + chunk.offset).to_le_bytes())?;
         }
 
-        // Write all compressed data
-        file.write_all(&buffer.data)?;
+        // Write all compressed data, then the checksum
+        pack.write_all(&buffer.data)?;
+        let checksum = Sha256::digest(&pack);
+        pack.write_all(&checksum)?;
+        fs::write(&pack_path, &pack)?;
 
         let pack_info = PackInfo {
             id: buffer.pack_id,
             name: pack_name,
-            size: pack_path.metadata()?.len(),
+            size: pack.len() as u64,
             chunk_count: buffer.chunks.len(),
             created_at: chrono::Utc::now().to_rfc3339(),
         };
 
-        Ok(pack_info)
+        Ok((pack_info, data_start))
     }
 }
 
+/// Raw digest bytes of a hex SHA-256 chunk hash
+fn encode_hash(hash: &str) -> std::io::Result<[u8; 32]> {
+    hex::decode(hash)
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .ok_or_else(|| std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Chunk hash is not a SHA-256 digest: {}", hash),
+        ))
+}
+
 /// In-memory pack buffer
 struct PackBuffer {
     pack_id: u32,
     chunks: Vec<ChunkEntry>,
+    hashes: HashSet<String>,
     data: Vec<u8>,
     size: u64,
 }
@@ -160,6 +258,7 @@ impl PackBuffer {
         PackBuffer {
             pack_id,
             chunks: Vec::new(),
+            hashes: HashSet::new(),
             data: Vec::new(),
             size: 0,
         }
@@ -186,6 +285,21 @@ pub struct PackManifest {
     pub packs: Vec<PackInfo>,
     pub object_count: usize,
     pub chunk_registry: HashMap<String, ChunkLocation>,
+    /// Chunk hashes making up each packed object, in order
+    #[serde(default)]
+    pub objects: HashMap<String, Vec<String>>,
+    /// Summed original size of the stored chunks
+    #[serde(default)]
+    pub uncompressed_size: u64,
+    /// Summed compressed size of the stored chunks
+    #[serde(default)]
+    pub compressed_size: u64,
+    /// Chunk references satisfied by an already stored identical chunk
+    #[serde(default)]
+    pub duplicate_chunks: usize,
+    /// Original bytes those duplicate references would have taken
+    #[serde(default)]
+    pub deduplicated_bytes: u64,
     pub created_at: String,
 }
 
@@ -195,14 +309,21 @@ impl PackManifest {
             packs: Vec::new(),
             object_count: 0,
             chunk_registry: HashMap::new(),
+            objects: HashMap::new(),
+            uncompressed_size: 0,
+            compressed_size: 0,
+            duplicate_chunks: 0,
+            deduplicated_bytes: 0,
             created_at: String::new(),
         }
     }
 
+    /// Write the manifest, replacing any previous one in a single rename
     pub fn save(&self, path: &Path) -> std::io::Result<()> {
         let json = serde_json::to_string_pretty(self)?;
-        fs::write(path, json)?;
-        Ok(())
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, json)?;
+        fs::rename(tmp, path)
     }
 
     pub fn load(path: &Path) -> std::io::Result<Self> {
@@ -215,19 +336,12 @@ impl PackManifest {
         self.packs.iter().map(|p| p.size).sum()
     }
 
+    /// Compressed size of the stored chunks relative to their original size
     pub fn compression_ratio(&self) -> f64 {
-        if self.packs.is_empty() {
+        if self.uncompressed_size == 0 {
             0.0
         } else {
-            let total_compressed: u64 = self.packs.iter().map(|p| p.size).sum();
-            let avg_chunk_size = 65536u64; // ~65KB average
-            let total_uncompressed = self.chunk_registry.len() as u64 * avg_chunk_size;
-            
-            if total_uncompressed == 0 {
-                0.0
-            } else {
-                total_compressed as f64 / total_uncompressed as f64
-            }
+            self.compressed_size as f64 / self.uncompressed_size as f64
         }
     }
 
@@ -237,7 +351,12 @@ impl PackManifest {
         println!("  Total size: {:.2}MB", self.total_size() as f64 / (1024.0 * 1024.0));
         println!("  Objects: {}", self.object_count);
         println!("  Chunks: {}", self.chunk_registry.len());
+        println!("  Uncompressed: {:.2}MB", self.uncompressed_size as f64 / (1024.0 * 1024.0));
         println!("  Compression ratio: {:.1}%", self.compression_ratio() * 100.0);
+        println!("  Deduplicated: {} chunks ({:.2}MB)",
+            self.duplicate_chunks,
+            self.deduplicated_bytes as f64 / (1024.0 * 1024.0)
+        );
         println!("  Created: {}", self.created_at);
         
         for pack in &self.packs {
@@ -278,4 +397,59 @@ mod tests {
         assert_eq!(manifest.packs.len(), 0);
         assert_eq!(manifest.object_count, 0);
     }
+
+    #[test]
+    fn test_manifest_records_real_sizes_and_dedup() {
+        let dir = TempDir::new().unwrap();
+        let objects = dir.path().join(".mug/objects");
+        fs::create_dir_all(&objects).unwrap();
+        let content = b"same bytes in both objects".repeat(100);
+        fs::write(objects.join("a"), &content).unwrap();
+        fs::write(objects.join("b"), &content).unwrap();
+
+        let manifest = PackBuilder::new(dir.path(), 1_000_000)
+            .unwrap()
+            .build_packs(&dir.path().join("packs"))
+            .unwrap();
+        assert_eq!(manifest.object_count, 2);
+        assert_eq!(manifest.chunk_registry.len(), 1);
+        assert_eq!(manifest.uncompressed_size, content.len() as u64);
+        assert_eq!(manifest.duplicate_chunks, 1);
+        assert_eq!(manifest.deduplicated_bytes, content.len() as u64);
+        let ratio = manifest.compression_ratio();
+        assert!(ratio > 0.0 && ratio < 1.0);
+        assert_eq!(manifest.objects["a"], manifest.objects["b"]);
+    }
+
+    #[test]
+    fn test_incremental_build_only_packs_new_objects() {
+        let dir = TempDir::new().unwrap();
+        let objects = dir.path().join(".mug/objects");
+        fs::create_dir_all(&objects).unwrap();
+        fs::write(objects.join("a"), b"first object").unwrap();
+
+        let out = dir.path().join("packs");
+        let builder = PackBuilder::new(dir.path(), 1_000_000).unwrap();
+        let first = builder.build_packs(&out).unwrap();
+        let first_pack = fs::read(out.join("pack-0000.mug")).unwrap();
+
+        // Nothing new: no pack is written
+        let same = builder.build_incremental(&out, &first).unwrap();
+        assert_eq!(same.packs.len(), 1);
+
+        fs::write(objects.join("b"), b"second object").unwrap();
+        fs::write(objects.join("c"), b"first object").unwrap();
+        let next = builder.build_incremental(&out, &first).unwrap();
+        assert_eq!(next.packs.len(), 2);
+        assert_eq!(next.packs[1].chunk_count, 1);
+        assert_eq!(next.object_count, 3);
+        assert_eq!(next.duplicate_chunks, 1);
+        assert_eq!(fs::read(out.join("pack-0000.mug")).unwrap(), first_pack);
+
+        next.save(&out.join(PACK_MANIFEST)).unwrap();
+        let reader = PackReader::new(&out.join(PACK_MANIFEST)).unwrap();
+        for (name, content) in [("a", "first object"), ("b", "second object"), ("c", "first object")] {
+            assert_eq!(reader.get_object(name).unwrap(), content.as_bytes());
+        }
+    }
 }
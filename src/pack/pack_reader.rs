@@ -1,14 +1,22 @@
-use super::compression::{ZstdCompressor, Compressor};
-use super::pack_builder::PackManifest;
+use super::compression::{Algorithm, CompressionSettings};
+use super::pack_builder::{PackManifest, ENTRY_SIZE, HEADER_SIZE, PACK_MAGIC};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::io::{Read, Seek};
+use std::sync::Mutex;
+use sha2::{Digest, Sha256};
+use crate::core::hash::hash_bytes;
 
 /// Reads and reconstructs objects from pack files
 pub struct PackReader {
     manifest: PackManifest,
     pack_dir: PathBuf,
-    compressor: ZstdCompressor,
+    /// Pack files opened so far, kept open for later lookups
+    handles: Mutex<HashMap<u32, fs::File>>,
+    /// Compression read from each pack's header
+    compression: Mutex<HashMap<u32, CompressionSettings>>,
 }
 
 impl PackReader {
@@ -21,34 +29,105 @@ impl PackReader {
         Ok(PackReader {
             manifest,
             pack_dir,
-            compressor: ZstdCompressor::default(),
+            handles: Mutex::new(HashMap::new()),
+            compression: Mutex::new(HashMap::new()),
         })
     }
 
     /// Retrieve a single chunk by hash
     pub fn get_chunk(&self, chunk_hash: &str) -> std::io::Result<Vec<u8>> {
+        let compressed = self.get_compressed_chunk(chunk_hash)?;
+        let pack_id = self.manifest.chunk_registry[chunk_hash].pack_id;
+        self.pack_compression(pack_id)?
+            .compressor()
+            .decompress(&compressed)
+    }
+
+    /// Compression recorded in a pack's header.
+    ///
+    /// Packs without one (version 1, or headerless serve packs) use zstd.
+    pub fn pack_compression(&self, pack_id: u32) -> std::io::Result<CompressionSettings> {
+        if let Some(settings) = self.compression.lock().unwrap().get(&pack_id) {
+            return Ok(*settings);
+        }
+
+        let mut header = [0u8; 7];
+        let read = self.with_pack(pack_id, |file| {
+            file.seek(std::io::SeekFrom::Start(0))?;
+            file.read(&mut header)
+        })?;
+        let settings = if read < header.len() || &header[..4] != PACK_MAGIC || header[4] < 2 {
+            CompressionSettings::fast()
+        } else {
+            let algorithm = Algorithm::from_id(header[5]).ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Unknown compression algorithm id {} in pack {}", header[5], pack_id),
+                )
+            })?;
+            CompressionSettings::new(algorithm, Some(header[6]))?
+        };
+        self.compression.lock().unwrap().insert(pack_id, settings);
+        Ok(settings)
+    }
+
+    /// Run `f` on a pack's file handle, opening it on first use
+    fn with_pack<T>(
+        &self,
+        pack_id: u32,
+        f: impl FnOnce(&mut fs::File) -> std::io::Result<T>,
+    ) -> std::io::Result<T> {
+        let mut handles = self.handles.lock().unwrap();
+        let file = match handles.entry(pack_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                entry.insert(fs::File::open(self.pack_dir.join(format!("pack-{:04}.mug", pack_id)))?)
+            }
+        };
+        f(file)
+    }
+
+    /// Whether `name` is a packed object
+    pub fn has_object(&self, name: &str) -> bool {
+        self.manifest.objects.contains_key(name)
+    }
+
+    /// Names of every packed object
+    pub fn object_names(&self) -> impl Iterator<Item = &String> {
+        self.manifest.objects.keys()
+    }
+
+    /// Reassemble a packed object from its chunks
+    pub fn get_object(&self, name: &str) -> std::io::Result<Vec<u8>> {
+        let chunks = self.manifest.objects.get(name)
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Object not found"))?;
+        let mut data = Vec::new();
+        for chunk_hash in chunks {
+            data.extend(self.get_chunk(chunk_hash)?);
+        }
+        Ok(data)
+    }
+
+    /// Retrieve a chunk's bytes as stored in the pack, without decompressing
+    pub fn get_compressed_chunk(&self, chunk_hash: &str) -> std::io::Result<Vec<u8>> {
         let location = self.manifest.chunk_registry.get(chunk_hash)
             .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Chunk not found"))?;
 
-        let pack_name = format!("pack-{:04}.mug", location.pack_id);
-        let pack_path = self.pack_dir.join(&pack_name);
+        self.with_pack(location.pack_id, |file| {
+            // Seek to chunk location
+            file.seek(std::io::SeekFrom::Start(location.offset))?;
 
-        let mut file = fs::File::open(pack_path)?;
-        
-        // Seek to chunk location
-        file.seek(std::io::SeekFrom::Start(location.offset))?;
+            // Read compressed chunk size header
+            let mut size_buf = [0u8; 4];
+            file.read_exact(&mut size_buf)?;
+            let compressed_size = u32::from_le_bytes(size_buf) as usize;
 
-        // Read compressed chunk size header (assuming format)
-        let mut size_buf = [0u8; 4];
-        file.read_exact(&mut size_buf)?;
-        let compressed_size = u32::from_le_bytes(size_buf) as usize;
+            // Read compressed data
+            let mut compressed = vec![0u8; compressed_size];
+            file.read_exact(&mut compressed)?;
 
-        // Read compressed data
-        let mut compressed = vec![0u8; compressed_size];
-        file.read_exact(&mut compressed)?;
-
-        // Decompress
-        self.compressor.decompress(&compressed)
+            Ok(compressed)
+        })
     }
 
     /// Extract all objects to output directory with progress
@@ -85,49 +164,119 @@ impl PackReader {
         Ok(stats)
     }
 
-    /// Verify pack integrity (check manifest + pack files exist)
+    /// Read the chunk index stored at the start of a pack file
+    pub fn pack_entries(&self, pack_id: u32) -> std::io::Result<Vec<PackEntry>> {
+        let mut file = fs::File::open(self.pack_dir.join(format!("pack-{:04}.mug", pack_id)))?;
+        let mut header = [0u8; HEADER_SIZE];
+        file.read_exact(&mut header)?;
+        if &header[..4] != PACK_MAGIC || header[4] < 3 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("pack-{:04}.mug has no readable chunk index", pack_id),
+            ));
+        }
+
+        let count = u32::from_le_bytes(header[7..11].try_into().unwrap()) as usize;
+        let mut index = vec![0u8; count * ENTRY_SIZE];
+        file.read_exact(&mut index)?;
+        Ok(index
+            .chunks_exact(ENTRY_SIZE)
+            .map(|entry| PackEntry {
+                hash: hex::encode(&entry[..32]),
+                original_size: u32::from_le_bytes(entry[32..36].try_into().unwrap()),
+                size: u32::from_le_bytes(entry[36..40].try_into().unwrap()),
+                offset: u64::from_le_bytes(entry[40..48].try_into().unwrap()),
+            })
+            .collect())
+    }
+
+    /// Verify pack integrity: each pack's trailing checksum, then every
+    /// chunk, decompressed and re-hashed against its registered hash
     pub fn verify(&self, show_progress: bool) -> std::io::Result<VerifyStats> {
         let mut stats = VerifyStats::default();
         let total_packs = self.manifest.packs.len();
-        let total_chunks = self.manifest.chunk_registry.len();
 
-        // Verify pack files exist
         for pack in &self.manifest.packs {
-            if show_progress {
-                stats.checked += 1;
-                if stats.checked % 10 == 0 {
-                    eprintln!("[{}/{}] Verifying packs...", stats.checked, total_packs);
-                }
+            stats.checked += 1;
+            if show_progress && stats.checked % 10 == 0 {
+                eprintln!("[{}/{}] Verifying packs...", stats.checked, total_packs);
             }
 
             let pack_path = self.pack_dir.join(&pack.name);
-            if pack_path.exists() && pack_path.metadata().ok().map(|m| m.len()) == Some(pack.size) {
-                stats.valid += 1;
-            } else {
-                stats.invalid += 1;
-                stats.invalid_hashes.push(pack.name.clone());
+            match self.checksum_matches(&pack_path) {
+                Ok(true) => stats.valid += 1,
+                Ok(false) => {
+                    stats.invalid += 1;
+                    stats.invalid_hashes.push(format!("{} (checksum mismatch)", pack.name));
+                }
+                Err(e) => {
+                    stats.invalid += 1;
+                    stats.invalid_hashes.push(format!("{} ({})", pack.name, e));
+                }
             }
         }
 
-        // Verify manifest integrity
-        if self.manifest.chunk_registry.len() == total_chunks {
-            stats.valid += 1;
-        } else {
-            stats.invalid += 1;
+        let mut chunks: Vec<_> = self.manifest.chunk_registry.iter().collect();
+        chunks.sort_by(|a, b| a.0.cmp(b.0));
+        let total_chunks = chunks.len();
+        for (index, (hash, location)) in chunks.into_iter().enumerate() {
+            stats.checked += 1;
+            if show_progress && (index + 1) % 100 == 0 {
+                eprintln!("[{}/{}] Verifying chunks...", index + 1, total_chunks);
+            }
+
+            match self.get_chunk(hash) {
+                Ok(data) if &hash_bytes(&data) == hash => stats.valid += 1,
+                _ => {
+                    stats.invalid += 1;
+                    stats.invalid_hashes.push(format!(
+                        "{} at pack-{:04}.mug offset {}",
+                        hash, location.pack_id, location.offset
+                    ));
+                }
+            }
         }
 
         if show_progress {
-            eprintln!("[{}/{}] Verification complete!", total_packs, total_packs);
+            eprintln!("[{}/{}] Verification complete!", total_chunks, total_chunks);
         }
 
         Ok(stats)
     }
 
+    /// Compare a pack's trailing SHA-256 with its contents. Packs written
+    /// before checksums were added only need to exist.
+    fn checksum_matches(&self, pack_path: &Path) -> std::io::Result<bool> {
+        let mut file = fs::File::open(pack_path)?;
+        let len = file.metadata()?.len();
+        let mut header = [0u8; 5];
+        if len < 37 || file.read_exact(&mut header).is_err() || &header[..4] != PACK_MAGIC || header[4] < 2 {
+            return Ok(true);
+        }
+
+        file.seek(std::io::SeekFrom::Start(0))?;
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut (&mut file).take(len - 32), &mut hasher)?;
+        let mut stored = [0u8; 32];
+        file.read_exact(&mut stored)?;
+        Ok(hasher.finalize().as_slice() == stored)
+    }
+
     pub fn manifest(&self) -> &PackManifest {
         &self.manifest
     }
 }
 
+/// One chunk in a pack file's index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackEntry {
+    pub hash: String,
+    pub original_size: u32,
+    pub size: u32,
+    /// Offset of the chunk's `[u32 length][compressed bytes]` record
+    pub offset: u64,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct ExtractStats {
     pub chunks_extracted: usize,
@@ -163,7 +312,7 @@ impl VerifyStats {
         if !self.invalid_hashes.is_empty() {
             println!("\nInvalid chunks:");
             for hash in &self.invalid_hashes {
-                println!("  {}", &hash[..16]);
+                println!("  {}", hash);
             }
         }
     }
@@ -189,4 +338,95 @@ mod tests {
 
         assert!(stats.is_valid());
     }
+
+    #[test]
+    fn test_pack_header_records_compression() {
+        use crate::pack::compression::{Algorithm, CompressionSettings};
+        use crate::pack::PackBuilder;
+
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join(".mug/objects")).unwrap();
+        fs::write(dir.path().join(".mug/objects/blob"), b"packed".repeat(50)).unwrap();
+
+        let settings = CompressionSettings::new(Algorithm::Gzip, Some(9)).unwrap();
+        let out = dir.path().join("packs");
+        let manifest = PackBuilder::new(dir.path(), 1_000_000)
+            .unwrap()
+            .with_compression(settings)
+            .build_packs(&out)
+            .unwrap();
+        manifest.save(&out.join("manifest.json")).unwrap();
+
+        let reader = PackReader::new(&out.join("manifest.json")).unwrap();
+        assert_eq!(reader.pack_compression(0).unwrap(), settings);
+    }
+
+    #[test]
+    fn test_verify_detects_corruption() {
+        use crate::pack::PackBuilder;
+
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join(".mug/objects")).unwrap();
+        fs::write(dir.path().join(".mug/objects/a"), b"first object".repeat(20)).unwrap();
+        fs::write(dir.path().join(".mug/objects/b"), b"second object".repeat(20)).unwrap();
+
+        let out = dir.path().join("packs");
+        let manifest = PackBuilder::new(dir.path(), 1_000_000).unwrap().build_packs(&out).unwrap();
+        manifest.save(&out.join("manifest.json")).unwrap();
+
+        let reader = PackReader::new(&out.join("manifest.json")).unwrap();
+        let stats = reader.verify(false).unwrap();
+        assert!(stats.is_valid());
+        assert_eq!(stats.valid, 3);
+
+        // Flip a byte in the last chunk's compressed data
+        let pack_path = out.join("pack-0000.mug");
+        let mut bytes = fs::read(&pack_path).unwrap();
+        let target = bytes.len() - 40;
+        bytes[target] ^= 0xff;
+        fs::write(&pack_path, bytes).unwrap();
+
+        let stats = reader.verify(false).unwrap();
+        assert!(!stats.is_valid());
+        assert!(stats.invalid_hashes.iter().any(|h| h.contains("checksum mismatch")));
+        assert!(stats.invalid_hashes.iter().any(|h| h.contains("offset")));
+    }
+
+    #[test]
+    fn test_pack_round_trip() {
+        use crate::pack::PackBuilder;
+
+        let dir = TempDir::new().unwrap();
+        let objects = dir.path().join(".mug/objects");
+        fs::create_dir_all(&objects).unwrap();
+        let contents: Vec<Vec<u8>> = (0..5u8)
+            .map(|i| (0..20_000u32).map(|n| (n % 251) as u8 ^ i).collect())
+            .collect();
+        for (i, content) in contents.iter().enumerate() {
+            fs::write(objects.join(format!("object-{}", i)), content).unwrap();
+        }
+
+        let out = dir.path().join("packs");
+        let manifest = PackBuilder::new(dir.path(), 1_000_000).unwrap().build_packs(&out).unwrap();
+        manifest.save(&out.join("manifest.json")).unwrap();
+        let reader = PackReader::new(&out.join("manifest.json")).unwrap();
+
+        let entries = reader.pack_entries(0).unwrap();
+        assert_eq!(entries.len(), manifest.chunk_registry.len());
+        for entry in &entries {
+            assert_eq!(manifest.chunk_registry[&entry.hash].offset, entry.offset);
+            let chunk = reader.get_chunk(&entry.hash).unwrap();
+            assert_eq!(chunk.len() as u32, entry.original_size);
+            assert_eq!(hash_bytes(&chunk), entry.hash);
+        }
+
+        let mut all: Vec<u8> = entries
+            .iter()
+            .flat_map(|e| reader.get_chunk(&e.hash).unwrap())
+            .collect();
+        let mut expected: Vec<u8> = contents.concat();
+        all.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(all, expected);
+    }
 }
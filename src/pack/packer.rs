@@ -28,7 +28,8 @@ impl RepositoryPacker {
     /// Pack repository objects into pack files
     pub fn pack_all(&self) -> std::io::Result<PackingStats> {
         let mut stats = PackingStats::default();
-        let mut chunk_dedup: HashMap<String, usize> = HashMap::new();
+        // Chunk hash -> (references, chunk size)
+        let mut chunk_dedup: HashMap<String, (usize, u64)> = HashMap::new();
 
         // Walk all objects
         if !self.objects_dir.exists() {
@@ -53,23 +54,20 @@ impl RepositoryPacker {
                     stats.chunk_size_total += chunk_data.len() as u64;
 
                     // Track duplicates
-                    *chunk_dedup.entry(chunk_hash).or_insert(0) += 1;
+                    chunk_dedup.entry(chunk_hash).or_insert((0, chunk_data.len() as u64)).0 += 1;
                 }
             }
         }
 
         // Calculate deduplication stats
         stats.unique_chunks = chunk_dedup.len();
-        let duplicate_refs: usize = chunk_dedup.values().map(|&c| c.saturating_sub(1)).sum();
-        stats.duplicate_refs = duplicate_refs;
+        stats.duplicate_refs = chunk_dedup.values().map(|&(refs, _)| refs - 1).sum();
 
-        // Calculate savings
-        let avg_chunk_size = if stats.chunk_count > 0 {
-            stats.chunk_size_total / stats.chunk_count as u64
-        } else {
-            0
-        };
-        stats.dedup_savings = (duplicate_refs as u64) * avg_chunk_size;
+        // Every extra reference to a chunk saves that chunk's size
+        stats.dedup_savings = chunk_dedup
+            .values()
+            .map(|&(refs, size)| (refs as u64 - 1) * size)
+            .sum();
 
         Ok(stats)
     }
@@ -117,7 +115,7 @@ impl PackingStats {
         println!("  Unique chunks: {}", self.unique_chunks);
         println!("  Deduplication ratio: {:.1}%", self.dedup_ratio() * 100.0);
         println!("  Potential savings: {:.2}MB", self.dedup_savings as f64 / (1024.0 * 1024.0));
-        println!("  Compression ratio: {:.1}%", (1.0 - self.compression_ratio()) * 100.0);
+        println!("  Space saved by deduplication: {:.1}%", (1.0 - self.compression_ratio()) * 100.0);
     }
 }
 
@@ -145,4 +143,20 @@ mod tests {
 
         assert_eq!(stats.dedup_ratio(), 10.0 / 50.0);
     }
+
+    #[test]
+    fn test_dedup_savings_are_measured() {
+        let dir = TempDir::new().unwrap();
+        let objects = dir.path().join(".mug/objects");
+        fs::create_dir_all(&objects).unwrap();
+        fs::write(objects.join("a"), b"duplicate").unwrap();
+        fs::write(objects.join("b"), b"duplicate").unwrap();
+        fs::write(objects.join("c"), b"unique").unwrap();
+
+        let stats = RepositoryPacker::new(dir.path()).unwrap().pack_all().unwrap();
+        assert_eq!(stats.chunk_count, 3);
+        assert_eq!(stats.unique_chunks, 2);
+        assert_eq!(stats.duplicate_refs, 1);
+        assert_eq!(stats.dedup_savings, b"duplicate".len() as u64);
+    }
 }
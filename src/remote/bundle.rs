@@ -0,0 +1,399 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::branch::BranchManager;
+use crate::core::commit::CommitLog;
+use crate::core::error::{Error, Result};
+use crate::core::hash::HashAlgorithm;
+use crate::core::repo::Repository;
+use crate::core::store::Blob;
+use crate::pack::pack_builder::{PackManifest, DEFAULT_PACK_SIZE, PACK_MANIFEST};
+use crate::pack::{PackBuilder, PackReader};
+use crate::remote::objects::ObjectSet;
+
+/// First line of every bundle file
+const BUNDLE_MAGIC: &[u8] = b"# mug bundle v1\n";
+
+/// Largest header a bundle may claim before we refuse to allocate it
+const MAX_HEADER_LEN: usize = 64 * 1024 * 1024;
+
+/// Refs, requirements and pack index stored at the start of a bundle.
+///
+/// Layout: the magic line, a u32 header length and the header as JSON, then
+/// each pack in `manifest.packs` as a u64 length and the pack file's bytes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleHeader {
+    pub hash_algorithm: HashAlgorithm,
+    /// Branch name -> commit id
+    pub refs: BTreeMap<String, String>,
+    /// Branch checked out where the bundle was made
+    pub head: Option<String>,
+    /// Commits the receiving repository must already have
+    pub prerequisites: Vec<String>,
+    /// Packed objects, named `commit-<id>`, `tree-<hash>` or `blob-<hash>`
+    pub manifest: PackManifest,
+}
+
+/// What importing a bundle changed
+#[derive(Debug, Clone)]
+pub struct UnbundleResult {
+    pub header: BundleHeader,
+    pub commits: usize,
+    /// One line per ref: updated, unchanged or skipped
+    pub refs: Vec<String>,
+}
+
+/// Write the history in `rev_range` to a single bundle file.
+///
+/// `A..B` bundles the commits reachable from `B` but not from `A`, which the
+/// receiver must already have; a single revision bundles all of its
+/// history; no range bundles every branch.
+pub fn create_bundle(repo: &Repository, rev_range: Option<&str>, output: &Path) -> Result<BundleHeader> {
+    let commit_log = CommitLog::new(repo.get_db().clone());
+    let head = repo.current_branch()?;
+    let mut refs = BTreeMap::new();
+    let mut prerequisites = Vec::new();
+    let mut known = HashSet::new();
+
+    match rev_range {
+        None => {
+            for branch in BranchManager::new(repo.get_db().clone()).list_branches()? {
+                if !branch.commit_id.is_empty() {
+                    refs.insert(branch.name, branch.commit_id);
+                }
+            }
+        }
+        Some(range) => {
+            let (base, tip) = match range.split_once("..") {
+                Some((base, tip)) => (Some(base), tip),
+                None => (None, range),
+            };
+            if let Some(base) = base {
+                let base = repo.resolve_rev(if base.is_empty() { "HEAD" } else { base })?;
+                known = commit_log.ancestors(&base)?;
+                prerequisites.push(base);
+            }
+            let tip = if tip.is_empty() { "HEAD" } else { tip };
+            refs.insert(tip_ref_name(repo, tip)?, repo.resolve_rev(tip)?);
+        }
+    }
+    if refs.is_empty() {
+        return Err(Error::NoCommits);
+    }
+
+    let heads: Vec<String> = refs.values().cloned().collect();
+    let objects = ObjectSet::collect(repo, &heads, &known)?;
+
+    // Lay the objects out as loose files and pack them with the usual builder
+    let scratch = tempfile::TempDir::new()?;
+    let objects_dir = scratch.path().join(".mug/objects");
+    fs::create_dir_all(&objects_dir)?;
+    for commit in &objects.commits {
+        fs::write(objects_dir.join(format!("commit-{}", commit.id)), serde_json::to_vec(commit)?)?;
+    }
+    for tree in &objects.trees {
+        fs::write(objects_dir.join(format!("tree-{}", tree.hash)), serde_json::to_vec(tree)?)?;
+    }
+    for blob in &objects.blobs {
+        fs::write(objects_dir.join(format!("blob-{}", blob.hash)), &blob.content)?;
+    }
+    let packs_dir = scratch.path().join("packs");
+    let manifest = PackBuilder::new(scratch.path(), DEFAULT_PACK_SIZE)?.build_packs(&packs_dir)?;
+
+    let header = BundleHeader {
+        hash_algorithm: repo.hash_algorithm(),
+        refs,
+        head,
+        prerequisites,
+        manifest,
+    };
+    let header_json = serde_json::to_vec(&header)?;
+    let mut out = BufWriter::new(File::create(output)?);
+    out.write_all(BUNDLE_MAGIC)?;
+    out.write_all(&(header_json.len() as u32).to_le_bytes())?;
+    out.write_all(&header_json)?;
+    for pack in &header.manifest.packs {
+        let mut file = File::open(packs_dir.join(&pack.name))?;
+        out.write_all(&file.metadata()?.len().to_le_bytes())?;
+        std::io::copy(&mut file, &mut out)?;
+    }
+    out.flush()?;
+
+    Ok(header)
+}
+
+/// Name a bundled tip is recorded under: the branch it names, the current
+/// branch for `HEAD`, or `HEAD` for a bare commit
+fn tip_ref_name(repo: &Repository, spec: &str) -> Result<String> {
+    let name = spec.strip_prefix("refs/heads/").unwrap_or(spec);
+    if name == "HEAD" {
+        return Ok(repo.current_branch()?.unwrap_or_else(|| "HEAD".to_string()));
+    }
+    match BranchManager::new(repo.get_db().clone()).get_branch(name)? {
+        Some(_) => Ok(name.to_string()),
+        None => Ok("HEAD".to_string()),
+    }
+}
+
+/// Whether `name` is a pack file name as written by PackBuilder: `pack-NNNN.mug`
+fn valid_pack_name(name: &str) -> bool {
+    name.strip_prefix("pack-")
+        .and_then(|rest| rest.strip_suffix(".mug"))
+        .is_some_and(|num| !num.is_empty() && num.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Read a bundle's header without importing it
+pub fn read_header(path: &Path) -> Result<BundleHeader> {
+    read_header_from(&mut BufReader::new(File::open(path)?))
+}
+
+fn read_header_from(reader: &mut impl Read) -> Result<BundleHeader> {
+    let mut magic = [0u8; BUNDLE_MAGIC.len()];
+    if reader.read_exact(&mut magic).is_err() || magic != BUNDLE_MAGIC {
+        return Err(Error::Custom("Not a mug bundle".to_string()));
+    }
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_HEADER_LEN {
+        return Err(Error::Custom(format!("Bundle header is too large ({} bytes)", len)));
+    }
+    let mut header = vec![0u8; len];
+    reader.read_exact(&mut header)?;
+    Ok(serde_json::from_slice(&header)?)
+}
+
+/// Import a bundle's objects into `repo`, after checking every pack
+/// checksum and chunk hash, and fast-forward its branches
+pub fn unbundle(repo: &Repository, path: &Path) -> Result<UnbundleResult> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let header = read_header_from(&mut reader)?;
+
+    let commit_log = CommitLog::new(repo.get_db().clone());
+    for id in &header.prerequisites {
+        if commit_log.get_commit(id).is_err() {
+            return Err(Error::Custom(format!("Bundle requires commit {}, which is missing", id)));
+        }
+    }
+    let repo = repo.adopt_hash_algorithm(header.hash_algorithm)?;
+
+    // Pack names come from the bundle, so only accept the names PackBuilder
+    // writes before any of them is joined onto a path
+    if let Some(pack) = header.manifest.packs.iter().find(|p| !valid_pack_name(&p.name)) {
+        return Err(Error::Custom(format!("Bundle has an invalid pack name '{}'", pack.name)));
+    }
+
+    // Unpack the packs next to their manifest so PackReader can check them
+    let scratch = tempfile::TempDir::new()?;
+    for pack in &header.manifest.packs {
+        let mut len = [0u8; 8];
+        reader.read_exact(&mut len)?;
+        let mut file = File::create(scratch.path().join(&pack.name))?;
+        let len = u64::from_le_bytes(len);
+        if std::io::copy(&mut (&mut reader).take(len), &mut file)? != len {
+            return Err(Error::Custom(format!("Bundle is truncated in {}", pack.name)));
+        }
+    }
+    let manifest_path = scratch.path().join(PACK_MANIFEST);
+    header.manifest.save(&manifest_path)?;
+    let packs = PackReader::new(&manifest_path)?;
+    let verify = packs.verify(false)?;
+    if verify.invalid > 0 {
+        return Err(Error::Custom(format!(
+            "Bundle failed verification: {}",
+            verify.invalid_hashes.join(", ")
+        )));
+    }
+
+    let mut objects = ObjectSet::default();
+    for name in packs.object_names() {
+        let data = packs.get_object(name)?;
+        if name.starts_with("commit-") {
+            objects.commits.push(serde_json::from_slice(&data)?);
+        } else if name.starts_with("tree-") {
+            objects.trees.push(serde_json::from_slice(&data)?);
+        } else if let Some(hash) = name.strip_prefix("blob-") {
+            objects.blobs.push(Blob {
+                hash: hash.to_string(),
+                size: data.len() as u64,
+                content: data,
+            });
+        }
+    }
+    objects.store(&repo)?;
+
+    let branch_manager = BranchManager::new(repo.get_db().clone());
+    let operation = format!("unbundle: from {}", path.display());
+    let checked_out = repo.current_branch()?;
+    let mut refs = Vec::new();
+    for (name, id) in &header.refs {
+        if name == "HEAD" {
+            refs.push(format!("HEAD is {} (not a branch, left alone)", id));
+            continue;
+        }
+        let current = branch_manager
+            .get_branch(name)?
+            .map(|b| b.commit_id)
+            .filter(|c| !c.is_empty());
+        match current {
+            Some(current) if current == *id => refs.push(format!("{} unchanged", name)),
+            Some(current) if !commit_log.is_ancestor(&current, id)? => {
+                refs.push(format!("{} skipped: not a fast-forward of {}", name, current))
+            }
+            current => {
+                branch_manager.update_branch_with_reason(name, id.clone(), &operation)?;
+                // Fast-forward the checked-out files along with their branch
+                if checked_out.as_ref() == Some(name) {
+                    let from = match current {
+                        Some(current) => repo.snapshot(&current)?,
+                        None => HashMap::new(),
+                    };
+                    let (files, modes) = repo.snapshot_with_modes(id)?;
+                    repo.update_working_tree(&from, &files, &modes)?;
+                }
+                refs.push(format!("{} -> {}", name, id));
+            }
+        }
+    }
+    repo.get_db().flush()?;
+
+    Ok(UnbundleResult {
+        commits: objects.commits.len(),
+        header,
+        refs,
+    })
+}
+
+/// Create a repository in `destination` from a bundle holding complete
+/// history, and check out its default branch
+pub fn clone_bundle(path: &Path, destination: &Path) -> Result<UnbundleResult> {
+    if fs::read_dir(destination).map(|mut d| d.next().is_some()).unwrap_or(false) {
+        return Err(Error::Custom(format!(
+            "Destination '{}' already exists and is not empty",
+            destination.display()
+        )));
+    }
+    fs::create_dir_all(destination)?;
+    let result = clone_bundle_into(path, destination);
+    if result.is_err() {
+        // Don't leave a half-initialized repository behind
+        let _ = fs::remove_dir_all(destination);
+    }
+    result
+}
+
+fn clone_bundle_into(path: &Path, destination: &Path) -> Result<UnbundleResult> {
+    let header = read_header(path)?;
+    let repo = Repository::init(destination)?;
+
+    // Point HEAD at the default branch first so importing checks it out
+    let branch_manager = BranchManager::new(repo.get_db().clone());
+    let default = header
+        .head
+        .clone()
+        .filter(|head| header.refs.contains_key(head))
+        .or_else(|| header.refs.keys().find(|name| *name != "HEAD").cloned());
+    if let Some(default) = default {
+        branch_manager.set_head(default)?;
+        if !header.refs.contains_key("main") {
+            branch_manager.delete_branch("main")?;
+        }
+    }
+    unbundle(&repo, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn commit_file(repo: &Repository, dir: &Path, name: &str, content: &str) -> String {
+        fs::write(dir.join(name), content).unwrap();
+        repo.add(name).unwrap();
+        repo.commit("Tester".to_string(), format!("Add {}", name)).unwrap()
+    }
+
+    #[test]
+    fn test_bundle_clone_and_incremental_unbundle() {
+        let src_dir = TempDir::new().unwrap();
+        let src = Repository::init(src_dir.path()).unwrap();
+        let first = commit_file(&src, src_dir.path(), "a.txt", "alpha\n");
+        let bundle = src_dir.path().join("full.bundle");
+        let header = create_bundle(&src, None, &bundle).unwrap();
+        assert_eq!(header.refs["main"], first);
+
+        let work = TempDir::new().unwrap();
+        let dest = work.path().join("clone");
+        let result = clone_bundle(&bundle, &dest).unwrap();
+        assert_eq!(result.commits, 1);
+        assert_eq!(fs::read_to_string(dest.join("a.txt")).unwrap(), "alpha\n");
+
+        // Only the new commit travels in a range bundle
+        let second = commit_file(&src, src_dir.path(), "b.txt", "beta\n");
+        let update = src_dir.path().join("update.bundle");
+        let header = create_bundle(&src, Some(&format!("{}..main", first)), &update).unwrap();
+        assert_eq!(header.prerequisites, vec![first]);
+
+        let dest_repo = Repository::open(&dest).unwrap();
+        let result = unbundle(&dest_repo, &update).unwrap();
+        assert_eq!(result.commits, 1);
+        assert_eq!(dest_repo.resolve_rev("main").unwrap(), second);
+        assert_eq!(fs::read_to_string(dest.join("b.txt")).unwrap(), "beta\n");
+
+        // A repository without the prerequisite can't take the update
+        let empty = TempDir::new().unwrap();
+        let empty_repo = Repository::init(empty.path()).unwrap();
+        assert!(unbundle(&empty_repo, &update).is_err());
+    }
+
+    #[test]
+    fn test_corrupt_bundle_is_rejected() {
+        let src_dir = TempDir::new().unwrap();
+        let src = Repository::init(src_dir.path()).unwrap();
+        commit_file(&src, src_dir.path(), "a.txt", &"content\n".repeat(100));
+        let bundle = src_dir.path().join("repo.bundle");
+        create_bundle(&src, None, &bundle).unwrap();
+
+        let mut data = fs::read(&bundle).unwrap();
+        let last = data.len() - 40;
+        data[last] ^= 0xff;
+        fs::write(&bundle, data).unwrap();
+
+        let dest = TempDir::new().unwrap();
+        let repo = Repository::init(dest.path()).unwrap();
+        let err = unbundle(&repo, &bundle).unwrap_err();
+        assert!(err.to_string().contains("verification"), "{}", err);
+    }
+
+    #[test]
+    fn test_bundle_pack_name_cannot_escape_scratch_dir() {
+        let src_dir = TempDir::new().unwrap();
+        let src = Repository::init(src_dir.path()).unwrap();
+        commit_file(&src, src_dir.path(), "a.txt", "alpha\n");
+        let bundle = src_dir.path().join("repo.bundle");
+        let mut header = create_bundle(&src, None, &bundle).unwrap();
+
+        // Rewrite the header with a traversal name, keeping the pack bytes
+        let data = fs::read(&bundle).unwrap();
+        let old_len = u32::from_le_bytes(data[BUNDLE_MAGIC.len()..BUNDLE_MAGIC.len() + 4].try_into().unwrap());
+        let packs = &data[BUNDLE_MAGIC.len() + 4 + old_len as usize..];
+        let target = src_dir.path().join("PWNED");
+        header.manifest.packs[0].name = format!("../../../../../..{}", target.display());
+        let header_json = serde_json::to_vec(&header).unwrap();
+        let mut forged = BUNDLE_MAGIC.to_vec();
+        forged.extend_from_slice(&(header_json.len() as u32).to_le_bytes());
+        forged.extend_from_slice(&header_json);
+        forged.extend_from_slice(packs);
+        fs::write(&bundle, forged).unwrap();
+
+        let dest = TempDir::new().unwrap();
+        let repo = Repository::init(dest.path()).unwrap();
+        let err = unbundle(&repo, &bundle).unwrap_err();
+        assert!(err.to_string().contains("invalid pack name"), "{}", err);
+        assert!(!target.exists());
+    }
+}
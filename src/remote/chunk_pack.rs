@@ -0,0 +1,151 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::core::branch::BranchManager;
+use crate::core::error::{Error, Result};
+use crate::core::repo::Repository;
+use crate::pack::compression::{Compressor, ZstdCompressor};
+use crate::pack::manifest::ChunkPackManifest;
+use crate::pack::pack_builder::{ChunkLocation, PackInfo, PackManifest};
+use crate::pack::PackReader;
+use crate::remote::objects::ObjectSet;
+
+/// Chunk manifest published to parallel clients
+const MANIFEST_FILE: &str = "manifest.json";
+/// Pack index read by `PackReader`
+const PACK_INDEX_FILE: &str = "pack-manifest.json";
+const PACK_FILE: &str = "pack-0000.mug";
+
+/// Serializes pack rebuilds across concurrent requests
+static BUILD_LOCK: Mutex<()> = Mutex::new(());
+
+/// Directory holding the chunk pack served for a repository
+pub fn pack_dir(repo: &Repository) -> PathBuf {
+    repo.root_path().join(".mug").join("serve")
+}
+
+/// Return the chunk manifest for every blob reachable from the repository's
+/// branches, rebuilding the pack when a branch has moved since the last build.
+///
+/// Each chunk is one blob, keyed by the blob hash and stored zstd-compressed.
+pub fn ensure_chunk_pack(repo: &Repository) -> Result<ChunkPackManifest> {
+    let mut heads: Vec<String> = BranchManager::new(repo.get_db().clone())
+        .list_branches()?
+        .into_iter()
+        .map(|b| b.commit_id)
+        .filter(|id| !id.is_empty())
+        .collect();
+    heads.sort();
+    heads.dedup();
+
+    let _guard = BUILD_LOCK.lock().unwrap();
+    let dir = pack_dir(repo);
+    if let Ok(json) = fs::read_to_string(dir.join(MANIFEST_FILE)) {
+        if let Ok(manifest) = ChunkPackManifest::from_json(&json) {
+            if manifest.metadata.commits_included == heads {
+                return Ok(manifest);
+            }
+        }
+    }
+
+    fs::create_dir_all(&dir)?;
+    let objects = ObjectSet::collect(repo, &heads, &HashSet::new())?;
+    let compressor = ZstdCompressor::fast();
+
+    let mut manifest = ChunkPackManifest::new(uuid::Uuid::new_v4().to_string());
+    let mut index = PackManifest::new();
+    let mut pack = fs::File::create(dir.join(PACK_FILE))?;
+    let mut offset = 0u64;
+
+    // Record layout: [u32 compressed length][compressed bytes]
+    for blob in &objects.blobs {
+        let compressed = compressor.compress(&blob.content)?;
+        pack.write_all(&(compressed.len() as u32).to_le_bytes())?;
+        pack.write_all(&compressed)?;
+
+        manifest.add_chunk_compressed(
+            blob.hash.clone(),
+            blob.size,
+            compressed.len() as u64,
+            offset,
+            blob.hash.clone(),
+            "zstd".to_string(),
+        );
+        index
+            .chunk_registry
+            .insert(blob.hash.clone(), ChunkLocation { pack_id: 0, offset });
+        index.uncompressed_size += blob.size;
+        index.compressed_size += compressed.len() as u64;
+        offset += 4 + compressed.len() as u64;
+    }
+    pack.flush()?;
+
+    manifest.set_metadata(None, None, heads, None);
+    index.object_count = objects.blobs.len();
+    index.created_at = chrono::Utc::now().to_rfc3339();
+    index.packs.push(PackInfo {
+        id: 0,
+        name: PACK_FILE.to_string(),
+        size: offset,
+        chunk_count: objects.blobs.len(),
+        created_at: index.created_at.clone(),
+    });
+
+    // Write the published manifest last so a partial build is never served
+    index.save(&dir.join(PACK_INDEX_FILE))?;
+    fs::write(dir.join(MANIFEST_FILE), manifest.to_json()?)?;
+
+    Ok(manifest)
+}
+
+/// Read a chunk's compressed bytes from the repository's chunk pack
+pub fn read_chunk(repo: &Repository, hash: &str) -> Result<Vec<u8>> {
+    let reader = PackReader::new(&pack_dir(repo).join(PACK_INDEX_FILE))?;
+    reader.get_compressed_chunk(hash).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => Error::ObjectNotFound(hash.to_string()),
+        _ => e.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::remote::parallel_fetch::decode_chunk;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_chunk_pack_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        fs::write(dir.path().join("a.txt"), "hello").unwrap();
+        fs::write(dir.path().join("b.txt"), "world".repeat(100)).unwrap();
+        repo.add("a.txt").unwrap();
+        repo.add("b.txt").unwrap();
+        repo.commit("Tester".to_string(), "First".to_string()).unwrap();
+
+        let manifest = ensure_chunk_pack(&repo).unwrap();
+        assert_eq!(manifest.chunk_count, 2);
+
+        for chunk in &manifest.chunks {
+            let data = read_chunk(&repo, &chunk.hash).unwrap();
+            let content = decode_chunk(chunk, &data, Some(repo.hash_algorithm())).unwrap();
+            assert_eq!(content, repo.get_store().get_blob(&chunk.hash).unwrap().content);
+        }
+
+        // Unchanged branches reuse the existing pack
+        let again = ensure_chunk_pack(&repo).unwrap();
+        assert_eq!(again.pack_id, manifest.pack_id);
+
+        fs::write(dir.path().join("c.txt"), "more").unwrap();
+        repo.add("c.txt").unwrap();
+        repo.commit("Tester".to_string(), "Second".to_string()).unwrap();
+        let rebuilt = ensure_chunk_pack(&repo).unwrap();
+        assert_ne!(rebuilt.pack_id, manifest.pack_id);
+        assert_eq!(rebuilt.chunk_count, 3);
+
+        assert!(read_chunk(&repo, "missing").is_err());
+    }
+}
@@ -1,15 +1,39 @@
 use crate::core::error::{Error, Result};
+use crate::core::auth::challenge_message;
+use crate::core::crypto::CryptoKey;
 use crate::remote::protocol::{
-    CloneRequest, CloneResponse, FetchRequest, FetchResponse, PullRequest, PullResponse,
-    PushRequest, PushResponse,
+    branches_etag, ChallengeRequest, ChallengeResponse, CloneRequest, CloneResponse, ErrorResponse, FetchRequest,
+    FetchResponse, PullRequest, PullResponse, PushRequest, PushResponse, SessionRequest,
+    SessionResponse, TransferPreview,
 };
-use crate::remote::{Protocol, Remote};
+use crate::core::resume::OperationProgress;
+use crate::remote::objects::ObjectSet;
+use crate::remote::{Protocol, Remote, RemoteManager};
+use crate::core::branch::BranchManager;
 use crate::core::repo::Repository;
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder};
+use serde::de::DeserializeOwned;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Size of the pieces an upload is streamed in, so progress can be reported
+const UPLOAD_CHUNK: usize = 64 * 1024;
+
+/// Receives the transfer phase ("Writing objects" or "Receiving objects")
+/// and the bytes moved so far
+pub type TransferCallback = Arc<dyn Fn(&str, &OperationProgress) + Send + Sync>;
+
+/// A decoded response and the number of payload bytes sent or received
+#[derive(Debug, Clone)]
+pub struct Transfer<T> {
+    pub response: T,
+    pub bytes: usize,
+}
 
 /// Remote client for push/pull/fetch/clone operations with HTTP transport
 pub struct RemoteClient {
     client: Client,
+    progress: Option<TransferCallback>,
 }
 
 impl RemoteClient {
@@ -17,17 +41,25 @@ impl RemoteClient {
     pub fn new() -> Result<Self> {
         Ok(Self {
             client: Client::new(),
+            progress: None,
         })
     }
 
+    /// Report upload and download progress to `callback`
+    pub fn with_progress(mut self, callback: TransferCallback) -> Self {
+        self.progress = Some(callback);
+        self
+    }
+
     /// Push to remote repository
     pub async fn push(
         &self,
         remote: &Remote,
         repo: &Repository,
         branch: &str,
-        _token: &str,
-    ) -> Result<PushResponse> {
+        token: &str,
+        force: bool,
+    ) -> Result<Transfer<PushResponse>> {
         // Only HTTP(S) supported in this version
         if remote.protocol != Protocol::Http && remote.protocol != Protocol::Https {
             return Err(Error::Custom(
@@ -35,70 +67,64 @@ impl RemoteClient {
             ));
         }
 
-        // Get commits to push
-        let commits_str = repo.log()?;
-        if commits_str.is_empty() {
-            return Ok(PushResponse {
-                success: false,
-                message: "No commits to push".to_string(),
-                head: None,
+        let Some(request) = push_request(remote, repo, branch, force)? else {
+            return Ok(Transfer {
+                response: PushResponse {
+                    success: false,
+                    message: "No commits to push".to_string(),
+                    head: None,
+                },
+                bytes: 0,
             });
-        }
-
-        // Convert string commit IDs to Commit objects (placeholder)
-        let commits = commits_str
-            .into_iter()
-            .map(|id| crate::core::commit::Commit {
-                id: id.clone(),
-                tree_hash: String::new(),
-                parent: None,
-                author: String::new(),
-                message: String::new(),
-                timestamp: String::new(),
-            })
-            .collect();
-
-        // Extract repo name from URL
-        let repo_name = extract_repo_name(&remote.url).unwrap_or_else(|| "repo".to_string());
-
-        // Gather blobs from repository
-        let blobs = gather_repository_blobs(repo).unwrap_or_default();
-
-        // Gather trees from repository
-        let trees = gather_repository_trees(repo).unwrap_or_default();
-
-        // Build request
-        let request = PushRequest {
-            repo: repo_name,
-            branch: branch.to_string(),
-            commits,
-            blobs,
-            trees,
-            head: "HEAD".to_string(),
         };
+        let body = serde_json::to_vec(&request)?;
+        let bytes = body.len();
 
         // Send push request
-        let url = format!("{}/repo/push", remote.url.trim_end_matches('/'));
-        match self.client.post(&url).json(&request).send().await {
-            Ok(response) => match response.json::<PushResponse>().await {
-                Ok(resp) => Ok(resp),
-                Err(e) => Err(Error::Custom(format!(
-                    "Failed to parse push response: {}",
-                    e
-                ))),
-            },
-            Err(e) => Err(Error::Custom(format!("Push failed: {}", e))),
-        }
+        let url = endpoint(&remote.url, "push");
+        let builder = self
+            .client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .header("Content-Length", bytes)
+            .body(upload_body(body, self.progress.clone()));
+        let (response, _) = send(authorize(builder, token), "push", None).await?;
+        Ok(Transfer { response, bytes })
+    }
+
+    /// Summarize what `push` would send, or `None` if the branch has no
+    /// commits. Nothing is sent.
+    pub fn preview_push(
+        &self,
+        remote: &Remote,
+        repo: &Repository,
+        branch: &str,
+    ) -> Result<Option<TransferPreview>> {
+        let Some(request) = push_request(remote, repo, branch, false)? else {
+            return Ok(None);
+        };
+        let bytes = serde_json::to_vec(&request)?.len();
+        let objects = ObjectSet {
+            commits: request.commits,
+            trees: request.trees,
+            blobs: request.blobs,
+        };
+        Ok(Some(TransferPreview {
+            bytes,
+            ..objects.preview()?
+        }))
     }
 
-    /// Pull from remote repository
+    /// Pull from remote repository. With `dry_run` the server only reports
+    /// what it would send.
     pub async fn pull(
         &self,
         remote: &Remote,
-        _repo: &Repository,
+        repo: &Repository,
         branch: &str,
-        _token: &str,
-    ) -> Result<PullResponse> {
+        token: &str,
+        dry_run: bool,
+    ) -> Result<Transfer<PullResponse>> {
         // Only HTTP(S) supported in this version
         if remote.protocol != Protocol::Http && remote.protocol != Protocol::Https {
             return Err(Error::Custom(
@@ -106,8 +132,11 @@ impl RemoteClient {
             ));
         }
 
-        // Get current head (placeholder)
-        let current_head = Some("HEAD".to_string());
+        // History reachable from our copy of the branch is not sent again
+        let current_head = BranchManager::new(repo.get_db().clone())
+            .get_branch(branch)?
+            .map(|b| b.commit_id)
+            .filter(|id| !id.is_empty());
 
         // Extract repo name
         let repo_name = extract_repo_name(&remote.url).unwrap_or_else(|| "repo".to_string());
@@ -117,29 +146,25 @@ impl RemoteClient {
             repo: repo_name,
             branch: branch.to_string(),
             current_head,
+            dry_run,
         };
 
         // Send pull request
-        let url = format!("{}/repo/pull", remote.url.trim_end_matches('/'));
-        match self.client.get(&url).json(&request).send().await {
-            Ok(response) => match response.json::<PullResponse>().await {
-                Ok(resp) => Ok(resp),
-                Err(e) => Err(Error::Custom(format!(
-                    "Failed to parse pull response: {}",
-                    e
-                ))),
-            },
-            Err(e) => Err(Error::Custom(format!("Pull failed: {}", e))),
-        }
+        let url = endpoint(&remote.url, "pull");
+        let (response, bytes) = send(authorize(self.client.post(&url).json(&request), token), "pull", self.progress.as_ref()).await?;
+        Ok(Transfer { response, bytes })
     }
 
-    /// Fetch from remote repository
+    /// Fetch from remote repository. With `dry_run` the server only reports
+    /// what it would send.
     pub async fn fetch(
         &self,
         remote: &Remote,
-        _branch: Option<&str>,
-        _token: &str,
-    ) -> Result<FetchResponse> {
+        repo: &Repository,
+        branch: Option<&str>,
+        token: &str,
+        dry_run: bool,
+    ) -> Result<Transfer<FetchResponse>> {
         // Only HTTP(S) supported in this version
         if remote.protocol != Protocol::Http && remote.protocol != Protocol::Https {
             return Err(Error::Custom(
@@ -150,28 +175,71 @@ impl RemoteClient {
         // Extract repo name
         let repo_name = extract_repo_name(&remote.url).unwrap_or_else(|| "repo".to_string());
 
+        // Tell the server which heads we already have, local and remote-tracking
+        let mut known: Vec<String> = BranchManager::new(repo.get_db().clone())
+            .list_branches()?
+            .into_iter()
+            .map(|b| b.commit_id)
+            .filter(|id| !id.is_empty())
+            .collect();
+        known.extend(
+            RemoteManager::new(repo.get_db().clone())
+                .tracking_branches()?
+                .into_iter()
+                .map(|(_, id)| id),
+        );
+        known.sort();
+        known.dedup();
+
+        // The heads last fetched let the server answer 304 when nothing
+        // moved, or send only the branches that did
+        let mut tracking = RemoteManager::new(repo.get_db().clone()).remote_heads(&remote.name)?;
+        if let Some(branch) = branch {
+            tracking.retain(|name, _| name == branch);
+        }
+        let etag = (!tracking.is_empty()).then(|| branches_etag(&tracking));
+
         // Build request
         let request = FetchRequest {
             repo: repo_name,
-            branch: _branch.map(|s| s.to_string()),
+            branch: branch.map(|s| s.to_string()),
+            known,
+            dry_run,
+            tracking,
         };
 
         // Send fetch request
-        let url = format!("{}/repo/fetch", remote.url.trim_end_matches('/'));
-        match self.client.get(&url).json(&request).send().await {
-            Ok(response) => match response.json::<FetchResponse>().await {
-                Ok(resp) => Ok(resp),
-                Err(e) => Err(Error::Custom(format!(
-                    "Failed to parse fetch response: {}",
-                    e
-                ))),
-            },
-            Err(e) => Err(Error::Custom(format!("Fetch failed: {}", e))),
+        let url = endpoint(&remote.url, "fetch");
+        let mut builder = authorize(self.client.post(&url).json(&request), token);
+        if let Some(etag) = etag {
+            builder = builder.header("If-None-Match", etag);
+        }
+        match send_conditional(builder, "fetch", self.progress.as_ref()).await? {
+            Some((response, bytes)) => Ok(Transfer { response, bytes }),
+            None => Ok(Transfer {
+                response: FetchResponse {
+                    success: true,
+                    branches: Default::default(),
+                    removed: vec![],
+                    commits: vec![],
+                    blobs: vec![],
+                    trees: vec![],
+                    message: "Already up to date".to_string(),
+                    preview: dry_run.then(TransferPreview::default),
+                },
+                bytes: 0,
+            }),
         }
     }
 
-    /// Clone a repository
-    pub async fn clone(&self, remote: &Remote, _dest: &str, _token: &str) -> Result<CloneResponse> {
+    /// Clone a repository, optionally leaving out file contents
+    pub async fn clone(
+        &self,
+        remote: &Remote,
+        token: &str,
+        include_blobs: bool,
+        branch: Option<&str>,
+    ) -> Result<Transfer<CloneResponse>> {
         // Only HTTP(S) supported in this version
         if remote.protocol != Protocol::Http && remote.protocol != Protocol::Https {
             return Err(Error::Custom(
@@ -183,20 +251,36 @@ impl RemoteClient {
         let repo_name = extract_repo_name(&remote.url).unwrap_or_else(|| "repo".to_string());
 
         // Build request
-        let request = CloneRequest { repo: repo_name };
+        let request = CloneRequest {
+            repo: repo_name,
+            include_blobs,
+            branch: branch.map(str::to_string),
+        };
 
         // Send clone request
-        let url = format!("{}/repo/clone", remote.url.trim_end_matches('/'));
-        match self.client.get(&url).json(&request).send().await {
-            Ok(response) => match response.json::<CloneResponse>().await {
-                Ok(resp) => Ok(resp),
-                Err(e) => Err(Error::Custom(format!(
-                    "Failed to parse clone response: {}",
-                    e
-                ))),
-            },
-            Err(e) => Err(Error::Custom(format!("Clone failed: {}", e))),
-        }
+        let url = endpoint(&remote.url, "clone");
+        let (response, bytes) = send(authorize(self.client.post(&url).json(&request), token), "clone", self.progress.as_ref()).await?;
+        Ok(Transfer { response, bytes })
+    }
+
+    /// Sign in by signing a server challenge with `key`, returning a
+    /// short-lived bearer token for the session
+    pub async fn authenticate(&self, remote: &Remote, key: &CryptoKey) -> Result<String> {
+        let base = server_base(&remote.url);
+        let request = ChallengeRequest {
+            public_key: key.public_key.clone(),
+        };
+        let builder = self.client.post(format!("{}/auth/challenge", base)).json(&request);
+        let (challenge, _): (ChallengeResponse, _) = send(builder, "authentication", None).await?;
+
+        let request = SessionRequest {
+            public_key: key.public_key.clone(),
+            signature: key.sign(&challenge_message(&challenge.nonce))?,
+            nonce: challenge.nonce,
+        };
+        let builder = self.client.post(format!("{}/auth/session", base)).json(&request);
+        let (session, _): (SessionResponse, _) = send(builder, "authentication", None).await?;
+        Ok(session.token)
     }
 
     /// Test connection to remote
@@ -207,7 +291,7 @@ impl RemoteClient {
             ));
         }
 
-        let url = format!("{}/health", remote.url.trim_end_matches('/'));
+        let url = format!("{}/health", server_base(&remote.url));
         match self.client.get(&url).send().await {
             Ok(response) => Ok(response.status().is_success()),
             Err(_) => Ok(false),
@@ -215,6 +299,50 @@ impl RemoteClient {
     }
 }
 
+/// The push request for everything reachable from `branch`, or `None` if
+/// the branch has no commits
+fn push_request(remote: &Remote, repo: &Repository, branch: &str, force: bool) -> Result<Option<PushRequest>> {
+    let head = match BranchManager::new(repo.get_db().clone()).get_branch(branch)? {
+        Some(b) if !b.commit_id.is_empty() => b.commit_id,
+        _ => return Ok(None),
+    };
+    let objects = ObjectSet::collect(repo, std::slice::from_ref(&head), &HashSet::new())?;
+
+    Ok(Some(PushRequest {
+        repo: extract_repo_name(&remote.url).unwrap_or_else(|| "repo".to_string()),
+        branch: branch.to_string(),
+        commits: objects.commits,
+        blobs: objects.blobs,
+        trees: objects.trees,
+        head,
+        hash_algorithm: repo.hash_algorithm(),
+        force,
+    }))
+}
+
+/// Bearer token for requests to `remote`: its stored token (or
+/// `MUG_TOKEN`), else a session token from signing in with its key, else
+/// none
+pub async fn remote_token(repo: &Repository, remote: &Remote) -> Result<String> {
+    let remote_manager = RemoteManager::new(repo.get_db().clone());
+    if let Some(token) = remote_manager.token(&remote.name)? {
+        return Ok(token);
+    }
+    let Some(public_key) = remote_manager.key(&remote.name)? else {
+        return Ok(String::new());
+    };
+    let key = crate::core::crypto::KeyManager::new(repo.get_db().clone())
+        .get(&public_key)?
+        .filter(|key| key.seed.is_some())
+        .ok_or_else(|| {
+            Error::Custom(format!(
+                "No private key for {} in this repository (see 'mug keys import')",
+                public_key
+            ))
+        })?;
+    build_remote_client(remote).await?.authenticate(remote, &key).await
+}
+
 /// Build correct client based on protocol
 pub async fn build_remote_client(remote: &Remote) -> Result<RemoteClient> {
     match remote.protocol {
@@ -243,28 +371,118 @@ fn extract_repo_name(url: &str) -> Option<String> {
     url.split('/').last().map(|s| s.to_string())
 }
 
-/// Gather all blobs from repository object store
-fn gather_repository_blobs(_repo: &Repository) -> Result<Vec<crate::core::store::Blob>> {
-    let blobs = Vec::new();
+/// Server address for a remote URL of the form `http(s)://host[:port]/<repo>`
+fn server_base(url: &str) -> &str {
+    let url = url.trim_end_matches('/');
+    let path_start = url.find("://").map(|i| i + 3).unwrap_or(0);
+    match url[path_start..].rfind('/') {
+        Some(i) => &url[..path_start + i],
+        None => url,
+    }
+}
+
+/// Base URL of a repository's endpoints, e.g. `http://host/repo/<name>`
+pub fn repo_url(url: &str) -> String {
+    let repo_name = extract_repo_name(url).unwrap_or_else(|| "repo".to_string());
+    format!("{}/repo/{}", server_base(url), repo_name)
+}
+
+/// Endpoint URL for a repository action, e.g. `http://host/repo/<name>/clone`
+fn endpoint(url: &str, action: &str) -> String {
+    format!("{}/{}", repo_url(url), action)
+}
+
+/// Attach the bearer token to a request, if one is configured
+fn authorize(builder: RequestBuilder, token: &str) -> RequestBuilder {
+    if token.is_empty() {
+        builder
+    } else {
+        builder.bearer_auth(token)
+    }
+}
+
+/// Stream `body` in pieces, reporting each one to `progress` as it is sent
+fn upload_body(body: Vec<u8>, progress: Option<TransferCallback>) -> reqwest::Body {
+    let Some(progress) = progress else {
+        return body.into();
+    };
+    let total = body.len() as u64;
+    let chunks: Vec<Vec<u8>> = body.chunks(UPLOAD_CHUNK).map(|c| c.to_vec()).collect();
+    let mut sent = 0;
+    let stream = futures::stream::iter(chunks.into_iter().map(move |chunk| {
+        sent += chunk.len() as u64;
+        progress("Writing objects", &transfer_progress(sent, Some(total)));
+        Ok::<_, std::io::Error>(chunk)
+    }));
+    reqwest::Body::wrap_stream(stream)
+}
+
+fn transfer_progress(bytes: u64, total_bytes: Option<u64>) -> OperationProgress {
+    OperationProgress {
+        processed: bytes,
+        total: total_bytes,
+        bytes_processed: bytes,
+        total_bytes,
+    }
+}
 
-    // Iterate through all objects in store and collect blobs
-    // For now, return empty vector - full implementation would require
-    // iterating through the .mug/objects directory and deserializing stored blobs
-    // This would require database iteration support or walking the filesystem
-    
-    Ok(blobs)
+/// Send a request and decode its JSON response, returning the body size.
+/// The download is reported to `progress` as it arrives.
+async fn send<T: DeserializeOwned>(
+    builder: RequestBuilder,
+    what: &str,
+    progress: Option<&TransferCallback>,
+) -> Result<(T, usize)> {
+    send_conditional(builder, what, progress).await?.ok_or_else(|| {
+        Error::Custom(format!("{} failed: HTTP 304 to an unconditional request", capitalize(what)))
+    })
 }
 
-/// Gather all trees from repository object store
-fn gather_repository_trees(_repo: &Repository) -> Result<Vec<crate::core::store::Tree>> {
-    let trees = Vec::new();
+/// Like `send`, but `None` when the server answers 304 Not Modified to a
+/// conditional request
+async fn send_conditional<T: DeserializeOwned>(
+    builder: RequestBuilder,
+    what: &str,
+    progress: Option<&TransferCallback>,
+) -> Result<Option<(T, usize)>> {
+    let fail = |e: reqwest::Error| Error::Custom(format!("{} failed: {}", capitalize(what), e));
+    let mut response = builder.send().await.map_err(fail)?;
+    let status = response.status();
+    if status == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+    let total = response.content_length();
+    // Error bodies are short and not worth a progress bar
+    let progress = progress.filter(|_| status.is_success());
+    let mut body = Vec::with_capacity(total.unwrap_or(0) as usize);
+    while let Some(chunk) = response.chunk().await.map_err(fail)? {
+        body.extend_from_slice(&chunk);
+        if let Some(progress) = progress {
+            progress("Receiving objects", &transfer_progress(body.len() as u64, total));
+        }
+    }
+    if !status.is_success() {
+        let reason = serde_json::from_slice::<ErrorResponse>(&body)
+            .map(|e| e.error)
+            .unwrap_or_else(|_| String::from_utf8_lossy(&body).to_string());
+        return Err(Error::Custom(format!(
+            "{} failed: HTTP {}: {}",
+            capitalize(what),
+            status.as_u16(),
+            reason
+        )));
+    }
+    let decoded = serde_json::from_slice(&body)
+        .map_err(|e| Error::Custom(format!("Failed to parse {} response: {}", what, e)))?;
+    Ok(Some((decoded, body.len())))
+}
 
-    // Iterate through all objects in store and collect trees
-    // Trees are stored in the object store, so we'd need to iterate through
-    // the .mug/objects directory and deserialize tree objects
-    // For now, return empty vector - requires database iteration support
-    
-    Ok(trees)
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
 }
 
 #[cfg(test)]
@@ -290,4 +508,17 @@ mod tests {
             Some("myrepo".to_string())
         );
     }
+
+    #[test]
+    fn test_endpoint() {
+        assert_eq!(
+            endpoint("http://localhost:3000/demo", "clone"),
+            "http://localhost:3000/repo/demo/clone"
+        );
+        assert_eq!(
+            endpoint("https://example.com/mug/demo.git/", "push"),
+            "https://example.com/mug/repo/demo/push"
+        );
+        assert_eq!(server_base("http://localhost:3000"), "http://localhost:3000");
+    }
 }
@@ -2,14 +2,34 @@
 /// Allows importing Git repositories into MUG
 
 use crate::core::error::{Error, Result};
+use crate::core::file_mode::{self, FileModes};
+use crate::core::hash::HashAlgorithm;
 use crate::core::repo::Repository;
+use crate::core::store::{check_tree_entries, TreeEntry};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::fs;
 use flate2::read::ZlibDecoder;
 use std::io::Read;
 
+/// Db tree mapping Git tree ids to the MUG trees they were converted to,
+/// so a later import skips trees it has already converted
+const IMPORTED_TREES: &str = "git_trees";
+
+/// What an import added to the MUG repository
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ImportStats {
+    pub commits: usize,
+    /// Blobs newly stored and trees newly converted
+    pub objects: usize,
+}
+
 /// Import a Git repository into MUG
-pub fn import_git_repo<P: AsRef<Path>>(git_path: P, mug_path: P) -> Result<()> {
+pub fn import_git_repo<P: AsRef<Path>>(
+    git_path: P,
+    mug_path: P,
+    algorithm: HashAlgorithm,
+) -> Result<ImportStats> {
     let git_path = git_path.as_ref();
     let mug_path = mug_path.as_ref();
 
@@ -19,48 +39,73 @@ pub fn import_git_repo<P: AsRef<Path>>(git_path: P, mug_path: P) -> Result<()> {
     }
 
     // Initialize MUG repository
-    let mug_repo = Repository::init(mug_path)?;
+    let mug_repo = Repository::init_with_hash(mug_path, algorithm)?;
+    import_into(git_path, &mug_repo, false)
+}
+
+/// Import whatever `mug_repo` lacks from the Git repository at `git_path`.
+/// An incremental import relies on the commit walk alone for objects,
+/// rather than also reading every loose object.
+fn import_into(git_path: &Path, mug_repo: &Repository, incremental: bool) -> Result<ImportStats> {
+    let mut stats = ImportStats::default();
+    let old_head = mug_repo.head_commit_id()?;
 
     // Copy Git objects to MUG store
-    import_git_objects(git_path, &mug_repo)?;
+    if !incremental {
+        import_git_objects(git_path, mug_repo, &mut stats)?;
+    }
 
     // Import commit history
-    import_git_commits(git_path, &mug_repo)?;
+    import_git_commits(git_path, mug_repo, &mut stats)?;
+
+    // Refuse before any branch moves, so HEAD never runs ahead of the
+    // working tree
+    let tips = git_branch_tips(git_path)?;
+    let head_branch = git_head_branch(git_path, &tips)?;
+    let new_head = head_branch
+        .clone()
+        .or(mug_repo.current_branch()?)
+        .and_then(|branch| tips.iter().find(|(name, _)| *name == branch))
+        .map(|(_, commit_id)| commit_id.clone())
+        .or_else(|| old_head.clone());
+    let checkout = plan_checkout(mug_repo, old_head.as_deref(), new_head.as_deref())?;
 
     // Create branches from Git refs
-    import_git_branches(git_path, &mug_repo)?;
+    import_git_branches(mug_repo, &tips, head_branch)?;
 
-    Ok(())
+    if let Some((from, files, modes)) = checkout {
+        mug_repo.overwrite_working_tree(&from, &files, &modes)?;
+        mug_repo.get_db().flush()?;
+    }
+
+    Ok(stats)
 }
 
-/// Import Git objects (blobs and trees) into MUG object store
-fn import_git_objects(git_path: &Path, mug_repo: &Repository) -> Result<()> {
+/// Import loose Git blobs into the MUG object store. Packed objects are
+/// not read here.
+fn import_git_objects(git_path: &Path, mug_repo: &Repository, stats: &mut ImportStats) -> Result<()> {
     let objects_dir = git_path.join(".git/objects");
     
     if !objects_dir.exists() {
         return Ok(()); // No objects to import
     }
 
-    // Walk through Git objects directory (excluding pack files for now)
+    let is_hex = |name: &str, len: usize| name.len() == len && name.chars().all(|c| c.is_ascii_hexdigit());
     for entry in fs::read_dir(&objects_dir)? {
-        let entry = entry?;
-        let path = entry.path();
-        
-        // Skip pack directory
-        if path.file_name().map_or(false, |n| n == "pack") {
+        let path = entry?.path();
+
+        // Loose objects live in 2-char fan-out directories; skip pack/ and info/
+        if !path.is_dir() || !path.file_name().and_then(|n| n.to_str()).is_some_and(|n| is_hex(n, 2)) {
             continue;
         }
-        
-        // Read object files (Git uses 2-char + 38-char SHA1)
-        if path.is_dir() {
-            if let Ok(entries) = fs::read_dir(&path) {
-                for obj_entry in entries.flatten() {
-                    let obj_path = obj_entry.path();
-                    if let Ok(content) = fs::read(&obj_path) {
-                        // Store raw content in MUG object store
-                        let _ = mug_repo.get_store().store_blob(&content);
-                    }
-                }
+        for obj_entry in fs::read_dir(&path)? {
+            let obj_path = obj_entry?.path();
+            if !obj_path.file_name().and_then(|n| n.to_str()).is_some_and(|n| is_hex(n, 38)) {
+                continue;
+            }
+            let object = read_git_object(&obj_path)?;
+            if let Some(content) = git_blob_content(&object) {
+                store_new_blob(mug_repo, content, stats)?;
             }
         }
     }
@@ -68,6 +113,26 @@ fn import_git_objects(git_path: &Path, mug_repo: &Repository) -> Result<()> {
     Ok(())
 }
 
+/// Store a blob unless the store already has it, counting it if new
+fn store_new_blob(mug_repo: &Repository, content: &[u8], stats: &mut ImportStats) -> Result<String> {
+    let store = mug_repo.get_store();
+    let hash = store.algorithm().hash_blob(content);
+    if !store.has_object(&hash) {
+        store.store_blob(content)?;
+        stats.objects += 1;
+    }
+    Ok(hash)
+}
+
+/// File content of a decompressed Git object if it is a blob, without its
+/// `blob <size>\0` header
+fn git_blob_content(object: &[u8]) -> Option<&[u8]> {
+    let nul = object.iter().position(|&b| b == 0)?;
+    let size: usize = std::str::from_utf8(object[..nul].strip_prefix(b"blob ")?).ok()?.parse().ok()?;
+    let content = &object[nul + 1..];
+    (content.len() == size).then_some(content)
+}
+
 /// Parse a Git commit object (decompressed content)
 fn parse_git_commit(content: &[u8]) -> Result<(String, String, Option<String>, String)> {
     let content_str = String::from_utf8_lossy(content);
@@ -116,135 +181,246 @@ fn read_git_object(object_path: &Path) -> Result<Vec<u8>> {
 }
 
 /// Import Git commits into MUG database using gix (gitoxide - better pack file handling)
-fn import_git_commits(git_path: &Path, mug_repo: &Repository) -> Result<()> {
+fn import_git_commits(git_path: &Path, mug_repo: &Repository, stats: &mut ImportStats) -> Result<()> {
     use chrono::Utc;
     use std::collections::HashSet;
     
     // Use gix for better performance and pack file support
     let repo = gix::open(git_path)
-        .map_err(|e| crate::core::error::Error::Custom(format!("Failed to open git repo: {}", e)))?;
+        .map_err(|e| Error::Custom(format!("Failed to open git repo: {}", e)))?;
 
     let mut visited = HashSet::new();
-    let mut count = 0;
+    // Commits to write, in the order they were found, with their parents
+    let mut found: Vec<(String, Option<String>, Vec<u8>)> = Vec::new();
     
-    // Walk from HEAD and all refs - gix handles pack files automatically
-    if let Ok(head) = repo.head() {
-        if let Some(head_id) = head.id() {
-            let head_id_str = head_id.to_hex().to_string();
-            let mut queue = vec![head_id_str];
+    // Walk from HEAD and all local branches - gix handles pack files automatically
+    let mut queue: Vec<String> = Vec::new();
+    if let Some(head_id) = repo.head().ok().and_then(|head| head.id()) {
+        queue.push(head_id.to_hex().to_string());
+    }
+    if let Ok(references) = repo.references() {
+        if let Ok(branches) = references.local_branches() {
+            queue.extend(branches.flatten().filter_map(|r| r.try_id()).map(|id| id.to_hex().to_string()));
+        }
+    }
+    while let Some(oid_str) = queue.pop() {
+        // A commit imported before has its ancestors imported too, as
+        // parents are always written first
+        if !visited.insert(oid_str.clone()) || mug_repo.get_db().get("COMMITS", &oid_str)?.is_some() {
+            continue;
+        }
         
-        while let Some(oid_str) = queue.pop() {
-            if visited.contains(&oid_str) {
-                continue;
-            }
-            visited.insert(oid_str.clone());
-            
-            // Parse OID from hex string
-            if let Ok(oid) = gix::ObjectId::from_hex(oid_str.as_bytes()) {
-                if let Ok(object) = repo.find_object(oid) {
-                    if let Ok(commit) = object.try_into_commit() {
-                        let commit_hash = oid_str.clone();
-                        let tree_hash = commit.tree_id()
-                            .ok()
-                            .map(|id| id.to_hex().to_string())
-                            .unwrap_or_else(|| "0000000000000000000000000000000000000000".to_string());
-
-                        let author_str = commit.author()
-                            .ok()
-                            .and_then(|a| std::str::from_utf8(a.name).ok())
-                            .unwrap_or("Unknown")
-                            .to_string();
-
-                        let message = commit.message_raw()
-                            .ok()
-                            .and_then(|b| std::str::from_utf8(&*b).ok())
-                            .map(|m| m.trim().to_string())
-                            .unwrap_or_else(|| "(no message)".to_string());
-
-                        let mut parent_ids = commit.parent_ids();
-                        let parent_str: Option<String> = parent_ids.next().map(|p| {
-                            let hex = p.to_hex().to_string();
-                            queue.push(hex.clone());
-                            hex
-                        });
-                        let commit_json = if let Some(parent_hash) = parent_str {
-                            serde_json::json!({
-                                "id": commit_hash,
-                                "tree_hash": tree_hash,
-                                "parent": parent_hash,
-                                "author": author_str,
-                                "message": message,
-                                "timestamp": Utc::now().to_rfc3339(),
-                            })
-                        } else {
-                            serde_json::json!({
-                                "id": commit_hash,
-                                "tree_hash": tree_hash,
-                                "parent": serde_json::Value::Null,
-                                "author": author_str,
-                                "message": message,
-                                "timestamp": Utc::now().to_rfc3339(),
-                            })
-                        };
-
-                        if let Ok(serialized) = serde_json::to_vec(&commit_json) {
-                            let _ = mug_repo.get_db().set("COMMITS", commit_hash.as_bytes(), &serialized);
-                            count += 1;
-                        }
-                    }
+        // Parse OID from hex string
+        if let Ok(oid) = gix::ObjectId::from_hex(oid_str.as_bytes()) {
+            if let Ok(object) = repo.find_object(oid) {
+                if let Ok(commit) = object.try_into_commit() {
+                    let commit_hash = oid_str.clone();
+                    let tree_id = commit.tree_id().map_err(git_error)?.detach();
+                    let tree_hash = import_git_tree(&repo, mug_repo, tree_id, stats)?;
+
+                    let author_str = commit.author()
+                        .ok()
+                        .and_then(|a| std::str::from_utf8(a.name).ok())
+                        .unwrap_or("Unknown")
+                        .to_string();
+
+                    let message = commit.message_raw()
+                        .ok()
+                        .and_then(|b| std::str::from_utf8(&*b).ok())
+                        .map(|m| m.trim().to_string())
+                        .unwrap_or_else(|| "(no message)".to_string());
+
+                    let mut parent_ids = commit.parent_ids();
+                    let parent_str: Option<String> = parent_ids.next().map(|p| {
+                        let hex = p.to_hex().to_string();
+                        queue.push(hex.clone());
+                        hex
+                    });
+                    let commit_json = if let Some(parent_hash) = parent_str.clone() {
+                        serde_json::json!({
+                            "id": commit_hash,
+                            "tree_hash": tree_hash,
+                            "parent": parent_hash,
+                            "author": author_str,
+                            "message": message,
+                            "timestamp": Utc::now().to_rfc3339(),
+                        })
+                    } else {
+                        serde_json::json!({
+                            "id": commit_hash,
+                            "tree_hash": tree_hash,
+                            "parent": serde_json::Value::Null,
+                            "author": author_str,
+                            "message": message,
+                            "timestamp": Utc::now().to_rfc3339(),
+                        })
+                    };
+
+                    let serialized = serde_json::to_vec(&commit_json)?;
+                    found.push((commit_hash, parent_str, serialized));
                 }
             }
         }
+    }
+
+    // Write each commit after its parent, so an interrupted import never
+    // leaves a commit whose ancestors are missing
+    let index: HashMap<&str, usize> =
+        found.iter().enumerate().map(|(i, (id, _, _))| (id.as_str(), i)).collect();
+    let mut written = vec![false; found.len()];
+    for start in 0..found.len() {
+        let mut chain = Vec::new();
+        let mut next = Some(start);
+        while let Some(i) = next.filter(|&i| !written[i]) {
+            written[i] = true;
+            chain.push(i);
+            next = found[i].1.as_deref().and_then(|parent| index.get(parent).copied());
+        }
+        for i in chain.into_iter().rev() {
+            let (commit_hash, _, serialized) = &found[i];
+            mug_repo.get_db().set("COMMITS", commit_hash.as_bytes(), serialized)?;
+            stats.commits += 1;
         }
     }
 
-    eprintln!("[INFO] Imported {} commits from git using gix (native pack file support)", count);
     Ok(())
 }
 
-/// Create branches from Git refs
-fn import_git_branches(git_path: &Path, mug_repo: &Repository) -> Result<()> {
-    use crate::core::branch::{BranchManager, BranchRef};
-    
-    let refs_heads = git_path.join(".git/refs/heads");
-    
-    if !refs_heads.exists() {
-        return Ok(()); // No branches to import
+/// Convert a Git tree and everything under it into MUG trees and blobs,
+/// returning the MUG tree hash. Trees converted before, by this import or
+/// an earlier one, are looked up rather than read again.
+fn import_git_tree(
+    repo: &gix::Repository,
+    mug_repo: &Repository,
+    tree_id: gix::ObjectId,
+    stats: &mut ImportStats,
+) -> Result<String> {
+    use gix::object::tree::EntryKind;
+
+    let key = tree_id.to_hex().to_string();
+    if let Some(hash) = mug_repo.get_db().get(IMPORTED_TREES, &key)? {
+        let hash = String::from_utf8_lossy(&hash).to_string();
+        // Unless gc has since removed it
+        if mug_repo.get_store().has_object(&hash) {
+            return Ok(hash);
+        }
+    }
+    let tree = repo.find_object(tree_id).map_err(git_error)?.try_into_tree().map_err(git_error)?;
+    let mut entries = Vec::new();
+    for entry in tree.decode().map_err(git_error)?.entries {
+        let name = entry.filename.to_string();
+        let id = entry.oid.to_owned();
+        let (hash, is_dir, mode) = match entry.mode.kind() {
+            EntryKind::Tree => (import_git_tree(repo, mug_repo, id, stats)?, true, file_mode::REGULAR),
+            // Submodules have no content in this repository
+            EntryKind::Commit => continue,
+            kind => {
+                let blob = repo.find_object(id).map_err(git_error)?;
+                let mode = match kind {
+                    EntryKind::BlobExecutable => file_mode::EXECUTABLE,
+                    EntryKind::Link => file_mode::SYMLINK,
+                    _ => file_mode::REGULAR,
+                };
+                (store_new_blob(mug_repo, &blob.data, stats)?, false, mode)
+            }
+        };
+        entries.push(TreeEntry { name, hash, is_dir, mode });
     }
 
-    let branch_manager = BranchManager::new(mug_repo.get_db().clone());
-    let mut head_branch: Option<String> = None;
+    check_tree_entries(&entries)?;
+    // Sorted as MUG sorts its own trees, so the same files hash the same
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    let hash = mug_repo.get_store().store_tree(entries)?;
+    mug_repo.get_db().set(IMPORTED_TREES, &key, &hash)?;
+    stats.objects += 1;
+    Ok(hash)
+}
 
-    // Check current HEAD
-    if let Ok(head_ref) = fs::read_to_string(git_path.join(".git/HEAD")) {
-        if let Some(branch) = head_ref.strip_prefix("ref: refs/heads/") {
-            head_branch = Some(branch.trim().to_string());
-        }
+fn git_error(e: impl std::fmt::Display) -> Error {
+    Error::Custom(format!("Failed to read git repository: {}", e))
+}
+
+/// The snapshots that move the working tree from `old_head` to `new_head`,
+/// or `None` if HEAD stays put. On a first import, only files not on disk
+/// yet are written, so migrating in place keeps local edits. On a later
+/// import, local edits to files the new HEAD changes are refused.
+fn plan_checkout(
+    mug_repo: &Repository,
+    old_head: Option<&str>,
+    new_head: Option<&str>,
+) -> Result<Option<(HashMap<String, String>, HashMap<String, String>, FileModes)>> {
+    let Some(head) = new_head else {
+        return Ok(None);
+    };
+    if old_head == Some(head) {
+        return Ok(None);
+    }
+    let (files, modes) = mug_repo.snapshot_with_modes(head)?;
+    let from = match old_head {
+        Some(old_head) => mug_repo.snapshot(old_head)?,
+        None => files
+            .iter()
+            .filter(|(path, _)| file_mode::exists(&mug_repo.root().join(path)))
+            .map(|(path, hash)| (path.clone(), hash.clone()))
+            .collect(),
+    };
+    let conflicts = mug_repo.checkout_conflicts(&from, &files)?;
+    if !conflicts.is_empty() {
+        return Err(Error::Custom(format!(
+            "Your local changes would be overwritten by the import:\n  {}\nCommit or stash them first.",
+            conflicts.join("\n  ")
+        )));
     }
+    Ok(Some((from, files, modes)))
+}
 
-    for entry in fs::read_dir(&refs_heads)? {
-        let entry = entry?;
-        if let Some(branch_name) = entry.file_name().to_str() {
-            let branch_name = branch_name.to_string();
-            let commit_hash = fs::read_to_string(entry.path())?
-                .trim()
-                .to_string();
-            
-            if !commit_hash.is_empty() {
-                // Create branch with proper BranchRef struct
-                let _ = branch_manager.create_branch(branch_name.clone(), commit_hash);
-            }
-        }
+/// Create branches from Git refs and check out `head_branch`, the branch
+/// git has checked out
+fn import_git_branches(
+    mug_repo: &Repository,
+    tips: &[(String, String)],
+    head_branch: Option<String>,
+) -> Result<()> {
+    use crate::core::branch::BranchManager;
+
+    let branch_manager = BranchManager::new(mug_repo.get_db().clone());
+    for (name, commit_id) in tips {
+        branch_manager.update_branch_with_reason(name, commit_id.clone(), "migrate: from git")?;
     }
 
-    // Set HEAD to the current branch if available
+    // Drop the empty main branch from init if git has no such branch
     if let Some(branch_name) = head_branch {
-        let _ = branch_manager.set_head(branch_name);
+        branch_manager.set_head(branch_name)?;
+        let empty_main = branch_manager.get_branch("main")?.is_some_and(|main| main.commit_id.is_empty());
+        if empty_main && !tips.iter().any(|(name, _)| name == "main") {
+            branch_manager.delete_branch("main")?;
+        }
     }
 
     Ok(())
 }
 
+/// The branch git has checked out, if it is one of `tips`
+fn git_head_branch(git_path: &Path, tips: &[(String, String)]) -> Result<Option<String>> {
+    let repo = gix::open(git_path).map_err(git_error)?;
+    let head_branch = repo.head_name().map_err(git_error)?.map(|name| name.shorten().to_string());
+    Ok(head_branch.filter(|name| tips.iter().any(|(tip, _)| tip == name)))
+}
+
+/// Local branches of a Git repository and the commits they point at, read
+/// through gix so packed refs are included
+fn git_branch_tips(git_path: &Path) -> Result<Vec<(String, String)>> {
+    let repo = gix::open(git_path).map_err(git_error)?;
+    let references = repo.references().map_err(git_error)?;
+    let mut tips = Vec::new();
+    for reference in references.local_branches().map_err(git_error)? {
+        let mut reference = reference.map_err(git_error)?;
+        let id = reference.peel_to_id_in_place().map_err(git_error)?;
+        tips.push((reference.name().shorten().to_string(), id.to_hex().to_string()));
+    }
+    Ok(tips)
+}
+
 /// Check if a directory is a Git repository
 pub fn is_git_repo<P: AsRef<Path>>(path: P) -> bool {
     path.as_ref().join(".git").exists()
@@ -252,23 +428,7 @@ pub fn is_git_repo<P: AsRef<Path>>(path: P) -> bool {
 
 /// Get list of branches from Git repository
 pub fn get_git_branches<P: AsRef<Path>>(git_path: P) -> Result<Vec<String>> {
-    let git_path = git_path.as_ref();
-    let refs_heads = git_path.join(".git/refs/heads");
-
-    if !refs_heads.exists() {
-        return Ok(Vec::new());
-    }
-
-    let mut branches = Vec::new();
-
-    for entry in fs::read_dir(&refs_heads)? {
-        let entry = entry?;
-        if let Some(name) = entry.file_name().to_str() {
-            branches.push(name.to_string());
-        }
-    }
-
-    Ok(branches)
+    Ok(git_branch_tips(git_path.as_ref())?.into_iter().map(|(name, _)| name).collect())
 }
 
 /// Get Git commit hash (HEAD)
@@ -304,8 +464,13 @@ pub fn get_git_head_commit<P: AsRef<Path>>(git_path: P) -> Result<Option<String>
     }
 }
 
-/// Migrate Git repository to MUG format
-pub fn migrate_git_to_mug(git_path: &str, mug_path: &str) -> Result<String> {
+/// Migrate Git repository to MUG format. With SHA-1 the migrated blobs
+/// keep the ids they had in git.
+pub fn migrate_git_to_mug(
+    git_path: &str,
+    mug_path: &str,
+    algorithm: HashAlgorithm,
+) -> Result<String> {
     let git_path = PathBuf::from(git_path);
     let mug_path = PathBuf::from(mug_path);
 
@@ -317,7 +482,7 @@ pub fn migrate_git_to_mug(git_path: &str, mug_path: &str) -> Result<String> {
     }
 
     // Run full import process
-    import_git_repo(&git_path, &mug_path)?;
+    let stats = import_git_repo(&git_path, &mug_path, algorithm)?;
 
     // Get branches for summary
     let branches = get_git_branches(&git_path)?;
@@ -325,18 +490,201 @@ pub fn migrate_git_to_mug(git_path: &str, mug_path: &str) -> Result<String> {
 
     // Return migration summary
     Ok(format!(
-        "Migration complete. Migrated {} branches, commits, and objects to MUG.",
-        branch_count
+        "Migration complete. Migrated {} branches, {} commits and {} objects to MUG.",
+        branch_count, stats.commits, stats.objects
     ))
 }
 
+/// Bring a MUG repository migrated from `git_path` up to date, importing
+/// only the commits and objects it does not have yet
+pub fn import_git_incremental(git_path: &str, mug_path: &str) -> Result<ImportStats> {
+    if !is_git_repo(git_path) {
+        return Err(Error::Custom(
+            "Source is not a Git repository".to_string(),
+        ));
+    }
+    let mug_repo = Repository::open(mug_path)?;
+    import_into(Path::new(git_path), &mug_repo, true)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use flate2::{write::ZlibEncoder, Compression};
+    use sha1::{Digest, Sha1};
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    /// Write a loose object into `git_dir` and return its id
+    fn write_object(git_dir: &Path, kind: &str, body: &[u8]) -> String {
+        let mut object = format!("{} {}\0", kind, body.len()).into_bytes();
+        object.extend_from_slice(body);
+        let id = hex::encode(Sha1::digest(&object));
+        let dir = git_dir.join("objects").join(&id[..2]);
+        fs::create_dir_all(&dir).unwrap();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&object).unwrap();
+        fs::write(dir.join(&id[2..]), encoder.finish().unwrap()).unwrap();
+        id
+    }
+
+    /// Write a tree object from (mode, name, id) entries, given in git's order
+    fn write_tree(git_dir: &Path, entries: &[(&str, &str, &str)]) -> String {
+        let mut tree = Vec::new();
+        for (mode, name, id) in entries {
+            tree.extend(format!("{} {}\0", mode, name).into_bytes());
+            tree.extend(hex::decode(id).unwrap());
+        }
+        write_object(git_dir, "tree", &tree)
+    }
+
+    fn write_commit(git_dir: &Path, tree: &str, parent: Option<&str>, message: &str) -> String {
+        let parent = parent.map(|p| format!("parent {}\n", p)).unwrap_or_default();
+        let commit = format!(
+            "tree {}\n{}author Tester <t@example.com> 1700000000 +0000\ncommitter Tester <t@example.com> 1700000000 +0000\n\n{}\n",
+            tree, parent, message
+        );
+        write_object(git_dir, "commit", commit.as_bytes())
+    }
+
+    /// A git repository at `root` whose main branch holds `hello.txt` and an
+    /// executable `bin/run.sh`, and whose feature branch changes `hello.txt`.
+    /// Returns the id of main's `hello.txt` blob.
+    fn tiny_git_repo(root: &Path) -> String {
+        let git_dir = root.join(".git");
+        fs::create_dir_all(git_dir.join("refs/heads")).unwrap();
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::write(git_dir.join("config"), "[core]\n\trepositoryformatversion = 0\n\tbare = false\n").unwrap();
+
+        let hello = write_object(&git_dir, "blob", b"hello\n");
+        let script = write_object(&git_dir, "blob", b"#!/bin/sh\n");
+        let bin = write_tree(&git_dir, &[("100755", "run.sh", &script)]);
+        let tree = write_tree(&git_dir, &[("40000", "bin", &bin), ("100644", "hello.txt", &hello)]);
+        let main = write_commit(&git_dir, &tree, None, "Initial");
+        fs::write(git_dir.join("refs/heads/main"), format!("{}\n", main)).unwrap();
+
+        let changed = write_object(&git_dir, "blob", b"hello from feature\n");
+        let tree = write_tree(&git_dir, &[("40000", "bin", &bin), ("100644", "hello.txt", &changed)]);
+        let feature = write_commit(&git_dir, &tree, Some(&main), "Feature");
+        fs::write(git_dir.join("refs/heads/feature"), format!("{}\n", feature)).unwrap();
+        hello
+    }
+
+    #[test]
+    fn test_migrate_stores_blob_content_not_git_objects() {
+        let dir = TempDir::new().unwrap();
+        let git = dir.path().join("git");
+        let blob = tiny_git_repo(&git);
+
+        for algorithm in [HashAlgorithm::Sha1, HashAlgorithm::Sha256] {
+            let mug = dir.path().join(algorithm.as_str());
+            migrate_git_to_mug(git.to_str().unwrap(), mug.to_str().unwrap(), algorithm).unwrap();
+            let repo = Repository::open(&mug).unwrap();
+            let hash = algorithm.hash_blob(b"hello\n");
+            assert_eq!(repo.get_store().get_blob(&hash).unwrap().content, b"hello\n");
+            // With SHA-1 the blob keeps its git id
+            assert_eq!(hash == blob, algorithm == HashAlgorithm::Sha1);
+        }
+    }
 
     #[test]
     fn test_git_detection() {
         // This would need a test Git repo
         assert!(!is_git_repo("/nonexistent"));
     }
+
+    #[test]
+    fn test_migrated_branches_check_out_original_files() {
+        let dir = TempDir::new().unwrap();
+        let git = dir.path().join("git");
+        tiny_git_repo(&git);
+        let mug = dir.path().join("mug");
+        migrate_git_to_mug(git.to_str().unwrap(), mug.to_str().unwrap(), HashAlgorithm::Sha256).unwrap();
+
+        let repo = Repository::open(&mug).unwrap();
+        assert_eq!(fs::read_to_string(mug.join("hello.txt")).unwrap(), "hello\n");
+        assert_eq!(fs::read_to_string(mug.join("bin/run.sh")).unwrap(), "#!/bin/sh\n");
+        let head = repo.head_commit_id().unwrap().unwrap();
+        let modes = repo.file_modes(&head).unwrap();
+        assert_eq!(modes.get("bin/run.sh"), Some(&file_mode::EXECUTABLE));
+        assert!(repo.status().unwrap().porcelain().is_empty());
+
+        repo.checkout("feature".to_string()).unwrap();
+        assert_eq!(fs::read_to_string(mug.join("hello.txt")).unwrap(), "hello from feature\n");
+        assert_eq!(repo.log().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_incremental_import_adds_only_new_commits_and_objects() {
+        let dir = TempDir::new().unwrap();
+        let git = dir.path().join("git");
+        tiny_git_repo(&git);
+        let mug = dir.path().join("mug");
+        let mug_str = mug.to_str().unwrap();
+        migrate_git_to_mug(git.to_str().unwrap(), mug_str, HashAlgorithm::Sha256).unwrap();
+
+        // A new commit on main changing only hello.txt
+        let git_dir = git.join(".git");
+        let main = fs::read_to_string(git_dir.join("refs/heads/main")).unwrap().trim().to_string();
+        let script = write_object(&git_dir, "blob", b"#!/bin/sh\n");
+        let bin = write_tree(&git_dir, &[("100755", "run.sh", &script)]);
+        let hello = write_object(&git_dir, "blob", b"hello again\n");
+        let tree = write_tree(&git_dir, &[("40000", "bin", &bin), ("100644", "hello.txt", &hello)]);
+        let next = write_commit(&git_dir, &tree, Some(&main), "Again");
+        fs::write(git_dir.join("refs/heads/main"), format!("{}\n", next)).unwrap();
+
+        let stats = import_git_incremental(git.to_str().unwrap(), mug_str).unwrap();
+        // The new blob and root tree; bin/ was converted before
+        assert_eq!(stats, ImportStats { commits: 1, objects: 2 });
+        assert_eq!(fs::read_to_string(mug.join("hello.txt")).unwrap(), "hello again\n");
+        let repo = Repository::open(&mug).unwrap();
+        assert_eq!(repo.head_commit_id().unwrap().as_deref(), Some(next.as_str()));
+        drop(repo);
+
+        let stats = import_git_incremental(git.to_str().unwrap(), mug_str).unwrap();
+        assert_eq!(stats, ImportStats::default());
+
+        // Local edits in the mirror stop an import that changes them
+        // before any branch moves
+        let hello = write_object(&git_dir, "blob", b"hello once more\n");
+        let tree = write_tree(&git_dir, &[("40000", "bin", &bin), ("100644", "hello.txt", &hello)]);
+        let last = write_commit(&git_dir, &tree, Some(&next), "Once more");
+        fs::write(git_dir.join("refs/heads/main"), format!("{}\n", last)).unwrap();
+        fs::write(mug.join("hello.txt"), "edited in the mirror\n").unwrap();
+
+        let err = import_git_incremental(git.to_str().unwrap(), mug_str).unwrap_err();
+        assert!(err.to_string().contains("hello.txt"));
+        assert_eq!(fs::read_to_string(mug.join("hello.txt")).unwrap(), "edited in the mirror\n");
+        let repo = Repository::open(&mug).unwrap();
+        assert_eq!(repo.head_commit_id().unwrap().as_deref(), Some(next.as_str()));
+        drop(repo);
+
+        fs::write(mug.join("hello.txt"), "hello again\n").unwrap();
+        import_git_incremental(git.to_str().unwrap(), mug_str).unwrap();
+        assert_eq!(fs::read_to_string(mug.join("hello.txt")).unwrap(), "hello once more\n");
+    }
+
+    #[test]
+    fn test_incremental_import_finishes_an_interrupted_one() {
+        let dir = TempDir::new().unwrap();
+        let git = dir.path().join("git");
+        tiny_git_repo(&git);
+        let mug = dir.path().join("mug");
+        let mug_str = mug.to_str().unwrap();
+        migrate_git_to_mug(git.to_str().unwrap(), mug_str, HashAlgorithm::Sha256).unwrap();
+
+        // Parents are written first, so an import cut short lacks only the
+        // newest commits
+        let feature = fs::read_to_string(git.join(".git/refs/heads/feature")).unwrap().trim().to_string();
+        let repo = Repository::open(&mug).unwrap();
+        repo.get_db().delete("COMMITS", &feature).unwrap();
+        repo.get_db().flush().unwrap();
+        drop(repo);
+
+        let stats = import_git_incremental(git.to_str().unwrap(), mug_str).unwrap();
+        assert_eq!(stats.commits, 1);
+        let repo = Repository::open(&mug).unwrap();
+        let commit_log = crate::core::commit::CommitLog::new(repo.get_db().clone());
+        assert_eq!(commit_log.history(feature).unwrap().len(), 2);
+    }
 }
@@ -1,5 +1,9 @@
+pub mod bundle;
+pub mod chunk_pack;
 pub mod client;
 pub mod git_compat;
+pub mod parallel_fetch;
+pub mod objects;
 pub mod protocol;
 pub mod remote;
 pub mod server;
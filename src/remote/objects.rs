@@ -0,0 +1,191 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::core::commit::{CommitLog, CommitMetadata};
+use crate::core::error::{Error, Result};
+use crate::core::repo::Repository;
+use crate::core::store::{check_tree_entries, Blob, Tree};
+use crate::remote::protocol::TransferPreview;
+
+/// Commits, trees and blobs exchanged with a remote
+#[derive(Debug, Clone, Default)]
+pub struct ObjectSet {
+    pub commits: Vec<CommitMetadata>,
+    pub trees: Vec<Tree>,
+    pub blobs: Vec<Blob>,
+}
+
+impl ObjectSet {
+    /// Collect every object reachable from `heads`, stopping at commits the
+    /// receiver already has
+    pub fn collect(repo: &Repository, heads: &[String], known: &HashSet<String>) -> Result<Self> {
+        let commit_log = CommitLog::new(repo.get_db().clone());
+        let mut set = ObjectSet::default();
+        let mut seen_commits = HashSet::new();
+        let mut seen_objects = HashSet::new();
+        let mut queue: VecDeque<String> = heads.iter().cloned().collect();
+
+        while let Some(id) = queue.pop_front() {
+            if known.contains(&id) || !seen_commits.insert(id.clone()) {
+                continue;
+            }
+            let commit = commit_log.get_commit(&id)?;
+            queue.extend(commit.parents());
+            set.collect_tree(repo, &commit.tree_hash, &mut seen_objects)?;
+            set.commits.push(commit);
+        }
+
+        Ok(set)
+    }
+
+    fn collect_tree(
+        &mut self,
+        repo: &Repository,
+        tree_hash: &str,
+        seen: &mut HashSet<String>,
+    ) -> Result<()> {
+        if !seen.insert(tree_hash.to_string()) {
+            return Ok(());
+        }
+        let tree = repo.get_store().get_tree(tree_hash)?;
+        for entry in &tree.entries {
+            if entry.is_dir {
+                self.collect_tree(repo, &entry.hash, seen)?;
+            } else if seen.insert(entry.hash.clone()) {
+                self.blobs.push(repo.get_store().get_blob(&entry.hash)?);
+            }
+        }
+        self.trees.push(tree);
+        Ok(())
+    }
+
+    /// Total serialized size of the objects in bytes
+    pub fn size(&self) -> Result<usize> {
+        let commits: usize = self
+            .commits
+            .iter()
+            .map(|c| serde_json::to_vec(c).map(|v| v.len()))
+            .sum::<std::result::Result<_, _>>()?;
+        let trees: usize = self
+            .trees
+            .iter()
+            .map(|t| serde_json::to_vec(t).map(|v| v.len()))
+            .sum::<std::result::Result<_, _>>()?;
+        let blobs: usize = self
+            .blobs
+            .iter()
+            .map(|b| serde_json::to_vec(b).map(|v| v.len()))
+            .sum::<std::result::Result<_, _>>()?;
+        Ok(commits + trees + blobs)
+    }
+
+    /// Summarize the objects for a dry run, newest commit first
+    pub fn preview(&self) -> Result<TransferPreview> {
+        let mut commits = self.commits.clone();
+        commits.sort_by_key(|c| std::cmp::Reverse(c.timestamp));
+        Ok(TransferPreview {
+            commits,
+            trees: self.trees.len(),
+            blobs: self.blobs.len(),
+            bytes: self.size()?,
+        })
+    }
+
+    /// Write the objects into a repository, checking content hashes
+    pub fn store(&self, repo: &Repository) -> Result<()> {
+        // Refuse unsafe trees before anything is written
+        for tree in &self.trees {
+            check_tree_entries(&tree.entries)?;
+        }
+        for blob in &self.blobs {
+            repo.get_store().store_received(&blob.hash, &blob.content)?;
+        }
+        for tree in &self.trees {
+            let hash = repo.get_store().store_tree(tree.entries.clone())?;
+            if hash != tree.hash {
+                return Err(Error::Custom(format!("Corrupt tree received: {}", tree.hash)));
+            }
+        }
+        for commit in &self.commits {
+            repo.get_db()
+                .set("COMMITS", &commit.id, serde_json::to_vec(commit)?)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::store::TreeEntry;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_collect_and_store_round_trip() {
+        let src_dir = TempDir::new().unwrap();
+        let src = Repository::init(src_dir.path()).unwrap();
+        std::fs::write(src_dir.path().join("a.txt"), "hello").unwrap();
+        src.add("a.txt").unwrap();
+        let first = src.commit("Tester".to_string(), "First".to_string()).unwrap();
+        std::fs::write(src_dir.path().join("b.txt"), "world").unwrap();
+        src.add("b.txt").unwrap();
+        let second = src.commit("Tester".to_string(), "Second".to_string()).unwrap();
+
+        let set = ObjectSet::collect(&src, std::slice::from_ref(&second), &HashSet::new()).unwrap();
+        assert_eq!(set.commits.len(), 2);
+        assert_eq!(set.blobs.len(), 2);
+        assert!(set.size().unwrap() > 0);
+        let preview = set.preview().unwrap();
+        assert_eq!(preview.commits[0].id, second);
+        assert_eq!((preview.trees, preview.blobs), (2, 2));
+        assert_eq!(preview.bytes, set.size().unwrap());
+
+        let known: HashSet<String> = [first].into_iter().collect();
+        let incremental = ObjectSet::collect(&src, std::slice::from_ref(&second), &known).unwrap();
+        assert_eq!(incremental.commits.len(), 1);
+
+        let dst_dir = TempDir::new().unwrap();
+        let dst = Repository::init(dst_dir.path()).unwrap();
+        set.store(&dst).unwrap();
+        assert_eq!(dst.snapshot(&second).unwrap(), src.snapshot(&second).unwrap());
+    }
+
+    #[test]
+    fn test_store_refuses_unsafe_trees() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let blob = repo.get_store().store_blob(b"payload").unwrap();
+        let entry = |name: &str, is_dir: bool| TreeEntry {
+            name: name.to_string(),
+            hash: blob.clone(),
+            is_dir,
+            mode: crate::core::file_mode::REGULAR,
+        };
+
+        for entries in [
+            vec![entry("..", false)],
+            vec![entry(".mug", true)],
+            vec![entry("a/b", false)],
+            vec![entry("a", false), entry("a", true)],
+        ] {
+            let set = ObjectSet {
+                trees: vec![Tree { hash: String::new(), entries: entries.clone() }],
+                ..Default::default()
+            };
+            assert!(set.store(&repo).is_err());
+
+            // A tree that reached the store some other way never yields paths
+            let tree_hash = repo.get_store().store_tree(entries).unwrap();
+            let commit = crate::core::commit::CommitLog::prepare_commit(
+                tree_hash,
+                "Tester".to_string(),
+                "Unsafe".to_string(),
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+            repo.get_db().set("COMMITS", &commit.id, serde_json::to_vec(&commit).unwrap()).unwrap();
+            assert!(repo.snapshot(&commit.id).is_err());
+        }
+    }
+}
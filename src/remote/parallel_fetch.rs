@@ -1,7 +1,12 @@
-use crate::core::error::Result;
+use crate::core::error::{Error, Result};
+use crate::core::hash::HashAlgorithm;
+use crate::core::store::blob_matches;
+use crate::pack::compression::{Compressor, FlateCompressor, ZstdCompressor};
 use crate::pack::manifest::{ChunkMetadata, ChunkPackManifest};
 use futures::future::join_all;
+use reqwest::Client;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::task;
 
 #[derive(Debug, Clone)]
@@ -10,6 +15,11 @@ pub struct ParallelFetchConfig {
     pub chunk_timeout_secs: u64,
     pub retry_attempts: usize,
     pub verify_checksums: bool,
+    /// How chunk hashes were computed; chunks served by `mug serve` are
+    /// blobs keyed by the repository's object id
+    pub hash_algorithm: HashAlgorithm,
+    /// Bearer token sent with every request, if the server requires one
+    pub auth_token: Option<String>,
 }
 
 impl Default for ParallelFetchConfig {
@@ -19,10 +29,15 @@ impl Default for ParallelFetchConfig {
             chunk_timeout_secs: 300,
             retry_attempts: 3,
             verify_checksums: true,
+            hash_algorithm: HashAlgorithm::default(),
+            auth_token: None,
         }
     }
 }
 
+/// Called with the totals after every chunk finishes downloading
+pub type DownloadCallback = Arc<dyn Fn(&DownloadProgress) + Send + Sync>;
+
 #[derive(Debug, Clone)]
 pub struct DownloadProgress {
     pub downloaded_chunks: usize,
@@ -78,6 +93,8 @@ pub struct ChunkDownloadResult {
 pub struct ParallelChunkDownloader {
     config: ParallelFetchConfig,
     progress: Arc<Mutex<DownloadProgress>>,
+    on_progress: Option<DownloadCallback>,
+    client: Client,
 }
 
 impl ParallelChunkDownloader {
@@ -87,9 +104,17 @@ impl ParallelChunkDownloader {
         ParallelChunkDownloader {
             config,
             progress: Arc::new(Mutex::new(progress)),
+            on_progress: None,
+            client: Client::new(),
         }
     }
 
+    /// Report progress to `callback` as chunks complete
+    pub fn with_progress(mut self, callback: DownloadCallback) -> Self {
+        self.on_progress = Some(callback);
+        self
+    }
+
     pub fn with_default_config(manifest: &ChunkPackManifest) -> Self {
         Self::new(ParallelFetchConfig::default(), manifest)
     }
@@ -99,7 +124,6 @@ impl ParallelChunkDownloader {
         tasks: Vec<ChunkDownloadTask>,
     ) -> Result<Vec<ChunkDownloadResult>> {
         let config = self.config.clone();
-        let progress = Arc::clone(&self.progress);
 
         let mut results = Vec::new();
         let mut current_batch = Vec::new();
@@ -129,9 +153,11 @@ impl ParallelChunkDownloader {
         let futures = tasks.into_iter().map(|task| {
             let config = config.clone();
             let progress = Arc::clone(&progress);
+            let on_progress = self.on_progress.clone();
+            let client = self.client.clone();
 
             task::spawn(async move {
-                Self::download_chunk_with_retry(task, config, progress).await
+                Self::download_chunk_with_retry(task, config, progress, on_progress, client).await
             })
         });
 
@@ -161,16 +187,24 @@ impl ParallelChunkDownloader {
         task: ChunkDownloadTask,
         config: ParallelFetchConfig,
         progress: Arc<Mutex<DownloadProgress>>,
+        on_progress: Option<DownloadCallback>,
+        client: Client,
     ) -> Result<ChunkDownloadResult> {
         let mut last_error = None;
 
         for attempt in 0..config.retry_attempts {
-            match Self::download_chunk_internal(&task).await {
+            if let Ok(mut prog) = progress.lock() {
+                prog.current_chunk = Some(task.chunk.hash.clone());
+            }
+            match Self::download_chunk_internal(&task, &config, &client).await {
                 Ok(bytes) => {
                     if let Ok(mut prog) = progress.lock() {
                         prog.downloaded_chunks += 1;
                         prog.bytes_downloaded += bytes;
                         prog.current_chunk = None;
+                        if let Some(on_progress) = &on_progress {
+                            on_progress(&prog);
+                        }
                     }
 
                     return Ok(ChunkDownloadResult {
@@ -198,12 +232,42 @@ impl ParallelChunkDownloader {
         })
     }
 
-    async fn download_chunk_internal(task: &ChunkDownloadTask) -> Result<u64> {
-        let size = task.chunk.compressed_size.unwrap_or(task.chunk.size);
-        
-        tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+    /// Download one chunk, decode it and write the content to `local_path`,
+    /// returning the number of bytes transferred
+    async fn download_chunk_internal(
+        task: &ChunkDownloadTask,
+        config: &ParallelFetchConfig,
+        client: &Client,
+    ) -> Result<u64> {
+        let url = format!("{}/chunk/{}", task.remote_url.trim_end_matches('/'), task.chunk.hash);
+        let mut request = client
+            .get(&url)
+            .timeout(Duration::from_secs(config.chunk_timeout_secs));
+        if let Some(token) = &config.auth_token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Error::Custom(format!("Chunk {} download failed: {}", task.chunk.hash, e)))?;
+        if !response.status().is_success() {
+            return Err(Error::Custom(format!(
+                "Chunk {} download failed: HTTP {}",
+                task.chunk.hash,
+                response.status().as_u16()
+            )));
+        }
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| Error::Custom(format!("Chunk {} download failed: {}", task.chunk.hash, e)))?;
 
-        Ok(size)
+        let verify = config.verify_checksums.then_some(config.hash_algorithm);
+        let content = decode_chunk(&task.chunk, &body, verify)?;
+        std::fs::write(&task.local_path, &content)?;
+
+        Ok(body.len() as u64)
     }
 
     pub fn get_progress(&self) -> Result<DownloadProgress> {
@@ -211,19 +275,117 @@ impl ParallelChunkDownloader {
     }
 }
 
+/// Decompress a downloaded chunk and, if `verify` is set, check its content
+/// against the chunk hash
+pub fn decode_chunk(
+    chunk: &ChunkMetadata,
+    data: &[u8],
+    verify: Option<HashAlgorithm>,
+) -> Result<Vec<u8>> {
+    let content = match chunk.compression.as_deref() {
+        None | Some("none") => data.to_vec(),
+        Some("zstd") => ZstdCompressor::default().decompress(data)?,
+        Some("gzip") | Some("zlib") => FlateCompressor::default().decompress(data)?,
+        Some(other) => {
+            return Err(Error::Custom(format!(
+                "Chunk {} uses unsupported compression '{}'",
+                chunk.hash, other
+            )))
+        }
+    };
+
+    if let Some(algorithm) = verify {
+        if !blob_matches(algorithm, &chunk.hash, &content) {
+            return Err(Error::Custom(format!(
+                "Checksum mismatch for chunk {}: got {}",
+                chunk.hash,
+                algorithm.hash_blob(&content)
+            )));
+        }
+    }
+
+    Ok(content)
+}
+
+/// Check that a manifest's chunk count and total size agree with its chunks
+fn validate_manifest(manifest: &ChunkPackManifest) -> Result<()> {
+    if manifest.chunk_count != manifest.chunks.len() {
+        return Err(Error::Custom(format!(
+            "Invalid manifest: chunk_count is {} but {} chunks are listed",
+            manifest.chunk_count,
+            manifest.chunks.len()
+        )));
+    }
+    let total: u64 = manifest.get_download_size();
+    if manifest.total_size != total {
+        return Err(Error::Custom(format!(
+            "Invalid manifest: total_size is {} but chunks add up to {}",
+            manifest.total_size, total
+        )));
+    }
+    Ok(())
+}
+
 pub struct PackBatchDownloader {
     config: ParallelFetchConfig,
+    on_progress: Option<DownloadCallback>,
+    client: Client,
 }
 
 impl PackBatchDownloader {
     pub fn new(config: ParallelFetchConfig) -> Self {
-        PackBatchDownloader { config }
+        PackBatchDownloader {
+            config,
+            on_progress: None,
+            client: Client::new(),
+        }
+    }
+
+    /// Report progress to `callback` as chunks complete
+    pub fn with_progress(mut self, callback: DownloadCallback) -> Self {
+        self.on_progress = Some(callback);
+        self
+    }
+
+    /// Verify chunks against ids computed with `algorithm`
+    pub fn with_hash_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.config.hash_algorithm = algorithm;
+        self
     }
 
+    /// Fetch and validate the chunk manifest published at
+    /// `{remote_url}/manifest.json`
     pub async fn fetch_manifest(&self, remote_url: &str) -> Result<ChunkPackManifest> {
-        Err(crate::core::error::Error::Custom(
-            "Fetch manifest not yet implemented".to_string(),
-        ))
+        let url = format!("{}/manifest.json", remote_url.trim_end_matches('/'));
+        let mut request = self
+            .client
+            .get(&url)
+            .timeout(Duration::from_secs(self.config.chunk_timeout_secs));
+        if let Some(token) = &self.config.auth_token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Error::Custom(format!("Failed to fetch manifest from {}: {}", url, e)))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::Custom(format!(
+                "Failed to fetch manifest from {}: HTTP {}",
+                url,
+                status.as_u16()
+            )));
+        }
+        let body = response
+            .text()
+            .await
+            .map_err(|e| Error::Custom(format!("Failed to fetch manifest from {}: {}", url, e)))?;
+
+        let manifest = ChunkPackManifest::from_json(&body)
+            .map_err(|e| Error::Custom(format!("Invalid manifest from {}: {}", url, e)))?;
+        validate_manifest(&manifest)?;
+        Ok(manifest)
     }
 
     pub async fn download_pack(
@@ -232,7 +394,10 @@ impl PackBatchDownloader {
         remote_url: &str,
         local_dir: &str,
     ) -> Result<Vec<ChunkDownloadResult>> {
-        let downloader = ParallelChunkDownloader::new(self.config.clone(), manifest);
+        let mut downloader = ParallelChunkDownloader::new(self.config.clone(), manifest);
+        if let Some(on_progress) = &self.on_progress {
+            downloader = downloader.with_progress(on_progress.clone());
+        }
 
         let tasks: Vec<ChunkDownloadTask> = manifest
             .chunks
@@ -251,6 +416,7 @@ impl PackBatchDownloader {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::hash;
     use crate::pack::manifest::ChunkMetadata;
 
     #[test]
@@ -317,6 +483,40 @@ mod tests {
         assert!(config.verify_checksums);
     }
 
+    #[test]
+    fn test_decode_chunk() {
+        let content = b"chunk contents".repeat(20);
+        let compressed = ZstdCompressor::default().compress(&content).unwrap();
+        let chunk = ChunkMetadata {
+            hash: hash::hash_bytes(&content),
+            size: content.len() as u64,
+            offset: 0,
+            compressed_size: Some(compressed.len() as u64),
+            compression: Some("zstd".to_string()),
+        };
+
+        let sha256 = Some(HashAlgorithm::Sha256);
+        assert_eq!(decode_chunk(&chunk, &compressed, sha256).unwrap(), content);
+
+        let corrupt = ZstdCompressor::default().compress(b"other").unwrap();
+        assert!(decode_chunk(&chunk, &corrupt, sha256).is_err());
+        assert!(decode_chunk(&chunk, &corrupt, None).is_ok());
+    }
+
+    #[test]
+    fn test_validate_manifest() {
+        let mut manifest = ChunkPackManifest::new("pack-001".to_string());
+        manifest.add_chunk("hash1".to_string(), 1024, 0, "checksum1".to_string());
+        assert!(validate_manifest(&manifest).is_ok());
+
+        manifest.chunk_count = 2;
+        assert!(validate_manifest(&manifest).is_err());
+
+        manifest.chunk_count = 1;
+        manifest.total_size = 10;
+        assert!(validate_manifest(&manifest).is_err());
+    }
+
     #[tokio::test]
     async fn test_parallel_downloader_creation() {
         let manifest = ChunkPackManifest::new("test-pack".to_string());
@@ -1,4 +1,5 @@
-use crate::core::commit::Commit;
+use crate::core::commit::CommitMetadata;
+use crate::core::hash::HashAlgorithm;
 use crate::core::store::{Blob, Tree};
 use serde::{Deserialize, Serialize};
 
@@ -6,6 +7,18 @@ use serde::{Deserialize, Serialize};
 ///
 /// All transports use the same message format (JSON over HTTP/HTTPS, binary over SSH)
 
+/// Entity tag for a set of branch heads, sent by fetch as `ETag` and
+/// compared against the client's `If-None-Match`
+pub fn branches_etag(branches: &std::collections::HashMap<String, String>) -> String {
+    let mut sorted: Vec<_> = branches.iter().collect();
+    sorted.sort();
+    let listing: String = sorted
+        .into_iter()
+        .map(|(name, head)| format!("{} {}\n", name, head))
+        .collect();
+    format!("\"{}\"", crate::core::hash::hash_str(&listing))
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PushRequest {
     /// Repository name
@@ -13,13 +26,20 @@ pub struct PushRequest {
     /// Branch name
     pub branch: String,
     /// Commit objects being pushed
-    pub commits: Vec<Commit>,
+    pub commits: Vec<CommitMetadata>,
     /// Blob objects being pushed
     pub blobs: Vec<Blob>,
     /// Tree objects being pushed
     pub trees: Vec<Tree>,
     /// Current branch head
     pub head: String,
+    /// How the pushed objects are addressed; must match the remote's
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
+    /// Allow a non-fast-forward update, discarding the remote's commits
+    /// that are not in `head`; needs admin access
+    #[serde(default)]
+    pub force: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,6 +60,9 @@ pub struct PullRequest {
     pub branch: String,
     /// Current known head
     pub current_head: Option<String>,
+    /// Only report what would be transferred
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,7 +70,7 @@ pub struct PullResponse {
     /// Success indicator
     pub success: bool,
     /// Commit objects to apply
-    pub commits: Vec<Commit>,
+    pub commits: Vec<CommitMetadata>,
     /// Blob objects to apply
     pub blobs: Vec<Blob>,
     /// Tree objects to apply
@@ -56,6 +79,9 @@ pub struct PullResponse {
     pub head: String,
     /// Status message
     pub message: String,
+    /// Summary of the objects, sent instead of them on a dry run
+    #[serde(default)]
+    pub preview: Option<TransferPreview>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -64,28 +90,64 @@ pub struct FetchRequest {
     pub repo: String,
     /// Fetch all branches or specific branch
     pub branch: Option<String>,
+    /// Commits the client already has; their history is not sent again
+    #[serde(default)]
+    pub known: Vec<String>,
+    /// Only report what would be transferred
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Branch heads the client last saw from this remote; branches still at
+    /// these heads are left out of the response
+    #[serde(default)]
+    pub tracking: std::collections::HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FetchResponse {
     /// Success indicator
     pub success: bool,
-    /// All available branches and their heads
+    /// Branches that are new or have moved since `tracking`, with their heads
     pub branches: std::collections::HashMap<String, String>,
+    /// Branches in `tracking` that the remote no longer has
+    #[serde(default)]
+    pub removed: Vec<String>,
+    /// Commit objects reachable from the branches
+    #[serde(default)]
+    pub commits: Vec<CommitMetadata>,
+    /// Blob objects reachable from the branches
+    #[serde(default)]
+    pub blobs: Vec<Blob>,
+    /// Tree objects reachable from the branches
+    #[serde(default)]
+    pub trees: Vec<Tree>,
     /// Status message
     pub message: String,
+    /// Summary of the objects, sent instead of them on a dry run
+    #[serde(default)]
+    pub preview: Option<TransferPreview>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CloneRequest {
     /// Repository name
     pub repo: String,
+    /// Send file contents too; parallel clones download them separately
+    #[serde(default = "default_true")]
+    pub include_blobs: bool,
+    /// Only send this branch and its history; `HEAD` means the server's
+    /// default branch
+    #[serde(default)]
+    pub branch: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CloneResponse {
     /// All commit objects
-    pub commits: Vec<Commit>,
+    pub commits: Vec<CommitMetadata>,
     /// All blob objects
     pub blobs: Vec<Blob>,
     /// All tree objects
@@ -94,6 +156,48 @@ pub struct CloneResponse {
     pub branches: std::collections::HashMap<String, String>,
     /// Default branch
     pub default_branch: String,
+    /// How the repository addresses objects; clones must match it
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
+}
+
+/// What a sync would transfer, without the objects themselves
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TransferPreview {
+    /// Commits that would be transferred, newest first
+    pub commits: Vec<CommitMetadata>,
+    pub trees: usize,
+    pub blobs: usize,
+    /// Estimated payload size in bytes
+    pub bytes: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChallengeRequest {
+    /// Registered public key that will sign the challenge
+    pub public_key: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChallengeResponse {
+    /// One-time value to sign
+    pub nonce: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionRequest {
+    pub public_key: String,
+    pub nonce: String,
+    /// Signature over the challenge message for `nonce`
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionResponse {
+    /// Bearer token for the session
+    pub token: String,
+    /// Seconds until the token expires
+    pub expires_in: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
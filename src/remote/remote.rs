@@ -1,6 +1,10 @@
 use crate::core::database::MugDb;
 use crate::core::error::Result;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Key prefix of remote-tracking refs in the `refs` tree
+const TRACKING_PREFIX: &str = "refs/remotes/";
 
 /// Remote configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -69,12 +73,73 @@ impl RemoteManager {
         Ok(())
     }
 
-    /// Remove a remote
+    /// Remove a remote along with its remote-tracking refs
     pub fn remove(&self, name: &str) -> Result<()> {
         self.db.delete("remotes", name)?;
+        for (key, _) in self.db.scan("refs", format!("{}{}/", TRACKING_PREFIX, name))? {
+            self.db.delete("refs", key)?;
+        }
         Ok(())
     }
 
+    /// Record fetched branch heads as `refs/remotes/<remote>/<branch>`.
+    ///
+    /// With `prune`, refs for branches the remote no longer has are dropped.
+    pub fn update_tracking_refs(
+        &self,
+        remote: &str,
+        branches: &HashMap<String, String>,
+        prune: bool,
+    ) -> Result<()> {
+        let prefix = format!("{}{}/", TRACKING_PREFIX, remote);
+        if prune {
+            for (key, _) in self.db.scan("refs", &prefix)? {
+                let branch = String::from_utf8_lossy(&key[prefix.len()..]).to_string();
+                if !branches.contains_key(&branch) {
+                    self.db.delete("refs", key)?;
+                }
+            }
+        }
+        for (branch, head) in branches {
+            self.db
+                .set("refs", format!("{}{}", prefix, branch), head.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Remote-tracking branches as `(<remote>/<branch>, commit)`, sorted by name
+    pub fn tracking_branches(&self) -> Result<Vec<(String, String)>> {
+        let mut branches: Vec<(String, String)> = self
+            .db
+            .scan("refs", TRACKING_PREFIX)?
+            .into_iter()
+            .map(|(key, value)| {
+                (
+                    String::from_utf8_lossy(&key[TRACKING_PREFIX.len()..]).to_string(),
+                    String::from_utf8_lossy(&value).to_string(),
+                )
+            })
+            .collect();
+        branches.sort();
+        Ok(branches)
+    }
+
+    /// Last-fetched branch heads of one remote, keyed by branch name
+    pub fn remote_heads(&self, remote: &str) -> Result<HashMap<String, String>> {
+        let prefix = format!("{}{}/", TRACKING_PREFIX, remote);
+        Ok(self
+            .db
+            .scan("refs", &prefix)?
+            .into_iter()
+            .map(|(key, value)| {
+                (
+                    String::from_utf8_lossy(&key[prefix.len()..]).to_string(),
+                    String::from_utf8_lossy(&value).to_string(),
+                )
+            })
+            .collect())
+    }
+
     /// Get a remote by name
     pub fn get(&self, name: &str) -> Result<Option<Remote>> {
         match self.db.get("remotes", name)? {
@@ -162,6 +227,59 @@ impl RemoteManager {
         }
     }
 
+    /// Store the bearer token used to authenticate with a remote
+    pub fn set_token(&self, name: &str, token: &str) -> Result<()> {
+        if self.get(name)?.is_none() {
+            return Err(crate::core::error::Error::Custom(format!(
+                "Remote '{}' not found",
+                name
+            )));
+        }
+
+        self.db
+            .set("config", format!("remote.{}.token", name), token.as_bytes())?;
+        self.db.delete("config", format!("remote.{}.key", name))?;
+        Ok(())
+    }
+
+    /// Authenticate with a remote by signing a challenge with this public
+    /// key's private half instead of sending a stored token
+    pub fn set_key(&self, name: &str, public_key: &str) -> Result<()> {
+        if self.get(name)?.is_none() {
+            return Err(crate::core::error::Error::Custom(format!(
+                "Remote '{}' not found",
+                name
+            )));
+        }
+
+        self.db
+            .set("config", format!("remote.{}.key", name), public_key.as_bytes())?;
+        self.db.delete("config", format!("remote.{}.token", name))?;
+        Ok(())
+    }
+
+    /// Public key used to authenticate with a remote, if set
+    pub fn key(&self, name: &str) -> Result<Option<String>> {
+        match self.db.get("config", format!("remote.{}.key", name))? {
+            Some(data) => Ok(Some(String::from_utf8(data)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Token for a remote: `MUG_TOKEN` if set, else `remote.<name>.token`
+    pub fn token(&self, name: &str) -> Result<Option<String>> {
+        if let Ok(token) = std::env::var("MUG_TOKEN") {
+            if !token.is_empty() {
+                return Ok(Some(token));
+            }
+        }
+
+        match self.db.get("config", format!("remote.{}.token", name))? {
+            Some(data) => Ok(Some(String::from_utf8(data)?)),
+            None => Ok(None),
+        }
+    }
+
     /// Check if a remote supports fetch
     pub fn can_fetch(&self, name: &str) -> Result<bool> {
         Ok(self.get(name)?.map(|r| r.fetch).unwrap_or(false))
@@ -293,6 +411,33 @@ mod tests {
         assert!(remote.is_none());
     }
 
+    #[test]
+    fn test_tracking_refs() {
+        let dir = TempDir::new().unwrap();
+        let db = MugDb::new(dir.path().join("db")).unwrap();
+        let manager = RemoteManager::new(db);
+        manager.add("origin", "https://example.com/repo").unwrap();
+
+        let mut branches = HashMap::new();
+        branches.insert("main".to_string(), "c1".to_string());
+        branches.insert("dev".to_string(), "c2".to_string());
+        manager.update_tracking_refs("origin", &branches, true).unwrap();
+        assert_eq!(
+            manager.tracking_branches().unwrap(),
+            vec![
+                ("origin/dev".to_string(), "c2".to_string()),
+                ("origin/main".to_string(), "c1".to_string()),
+            ]
+        );
+
+        branches.remove("dev");
+        manager.update_tracking_refs("origin", &branches, true).unwrap();
+        assert_eq!(manager.tracking_branches().unwrap().len(), 1);
+
+        manager.remove("origin").unwrap();
+        assert!(manager.tracking_branches().unwrap().is_empty());
+    }
+
     #[test]
     fn test_remote_manager_fetch_push() {
         let dir = TempDir::new().unwrap();
@@ -312,4 +457,30 @@ mod tests {
         manager.set_push("origin", false).unwrap();
         assert!(!manager.can_push("origin").unwrap());
     }
+
+    #[test]
+    fn test_remote_manager_token() {
+        let dir = TempDir::new().unwrap();
+        let db = MugDb::new(dir.path().join("db")).unwrap();
+        let manager = RemoteManager::new(db);
+
+        assert!(manager.set_token("origin", "secret").is_err());
+        manager
+            .add("origin", "https://github.com/user/repo.git")
+            .unwrap();
+        manager.set_token("origin", "secret").unwrap();
+
+        if std::env::var("MUG_TOKEN").is_err() {
+            assert_eq!(manager.token("origin").unwrap(), Some("secret".to_string()));
+        }
+
+        // A key replaces the stored token, and a token the key
+        manager.set_key("origin", "public-key").unwrap();
+        assert_eq!(manager.key("origin").unwrap(), Some("public-key".to_string()));
+        if std::env::var("MUG_TOKEN").is_err() {
+            assert_eq!(manager.token("origin").unwrap(), None);
+        }
+        manager.set_token("origin", "secret").unwrap();
+        assert_eq!(manager.key("origin").unwrap(), None);
+    }
 }
@@ -1,11 +1,113 @@
-use crate::core::auth::ServerAuth;
+use crate::core::auth::{Role, ServerAuth};
 use crate::core::error::Result;
-use crate::remote::protocol::{CloneResponse, FetchResponse, PullResponse, PushResponse};
-use crate::remote::git_compat;
+use crate::core::hash::HashAlgorithm;
+use crate::remote::protocol::{
+    branches_etag, ChallengeRequest, ChallengeResponse, CloneResponse, FetchResponse, PullResponse,
+    PushResponse, SessionRequest, SessionResponse,
+};
+use crate::remote::{chunk_pack, git_compat};
+use crate::remote::objects::ObjectSet;
+use crate::core::branch::BranchManager;
+use crate::core::commit::{CommitLog, CommitMetadata};
 use crate::core::repo::Repository;
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::{InternalError, JsonPayloadError};
+use actix_web::middleware::Next;
 use actix_web::{App, HttpRequest, HttpResponse, HttpServer, middleware, web};
+use futures::StreamExt;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Request limits enforced by the server
+#[derive(Debug, Clone, Copy)]
+pub struct ServerLimits {
+    /// Maximum JSON request body size in bytes
+    pub max_body_bytes: usize,
+    /// Maximum size of one large file upload in bytes
+    pub max_store_bytes: u64,
+    /// Requests allowed per token (or client address) each minute; 0 disables
+    pub requests_per_minute: u32,
+}
+
+impl Default for ServerLimits {
+    fn default() -> Self {
+        ServerLimits {
+            max_body_bytes: 64 * 1024 * 1024,
+            max_store_bytes: 1024 * 1024 * 1024,
+            requests_per_minute: 120,
+        }
+    }
+}
+
+/// Fixed-window request counter keyed by client
+pub struct RateLimiter {
+    limit: u32,
+    window: Duration,
+    counts: Mutex<WindowCounts>,
+}
+
+/// Requests per client in its current window, and when expired windows
+/// were last dropped
+struct WindowCounts {
+    clients: HashMap<String, (Instant, u32)>,
+    swept: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(limit: u32, window: Duration) -> Self {
+        RateLimiter {
+            limit,
+            window,
+            counts: Mutex::new(WindowCounts {
+                clients: HashMap::new(),
+                swept: Instant::now(),
+            }),
+        }
+    }
+
+    /// Count a request for `key`, returning false once it is over the limit
+    pub fn check(&self, key: &str) -> bool {
+        if self.limit == 0 {
+            return true;
+        }
+        let now = Instant::now();
+        let mut counts = self.counts.lock().unwrap();
+        // Forget clients whose window has ended, at most once per window
+        if now.duration_since(counts.swept) >= self.window {
+            let window = self.window;
+            counts.clients.retain(|_, (start, _)| now.duration_since(*start) < window);
+            counts.swept = now;
+        }
+        let entry = counts.clients.entry(key.to_string()).or_insert((now, 0));
+        if now.duration_since(entry.0) >= self.window {
+            *entry = (now, 0);
+        }
+        entry.1 += 1;
+        entry.1 <= self.limit
+    }
+}
+
+/// How long a push waits for another push to the same repository to finish
+const PUSH_LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Write locks keyed by repository name, so ref updates to one repository
+/// happen one at a time
+#[derive(Default)]
+pub struct RepoLocks {
+    locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl RepoLocks {
+    /// Wait up to `timeout` for the lock on `repo`, or `None` if it is
+    /// still held
+    pub async fn acquire(&self, repo: &str, timeout: Duration) -> Option<tokio::sync::OwnedMutexGuard<()>> {
+        let lock = self.locks.lock().unwrap().entry(repo.to_string()).or_default().clone();
+        tokio::time::timeout(timeout, lock.lock_owned()).await.ok()
+    }
+}
 
 /// MUG server state
 pub struct ServerState {
@@ -13,6 +115,28 @@ pub struct ServerState {
     pub repos_dir: PathBuf,
     /// Authentication manager
     pub auth: Arc<Mutex<ServerAuth>>,
+    /// Held while a repository's refs are being written
+    pub write_locks: RepoLocks,
+}
+
+impl ServerState {
+    pub fn new(repos_dir: PathBuf, auth: Arc<Mutex<ServerAuth>>) -> Self {
+        ServerState {
+            repos_dir,
+            auth,
+            write_locks: RepoLocks::default(),
+        }
+    }
+
+    /// Take the write lock on `repo`, or produce the 409 response if another
+    /// write holds it past the timeout
+    async fn lock_repo(&self, repo: &str) -> std::result::Result<tokio::sync::OwnedMutexGuard<()>, HttpResponse> {
+        self.write_locks.acquire(repo, PUSH_LOCK_TIMEOUT).await.ok_or_else(|| {
+            HttpResponse::Conflict().json(serde_json::json!({
+                "error": format!("Repository '{}' is busy: another update is in progress", repo)
+            }))
+        })
+    }
 }
 
 /// Extract and validate token from request
@@ -29,6 +153,54 @@ fn extract_token(req: &HttpRequest) -> Option<String> {
         })
 }
 
+/// Check the request's token holds `required` on the repository, or
+/// produce the 401 or 403 response naming the role needed
+fn check_access(
+    state: &ServerState,
+    req: &HttpRequest,
+    repo_name: &str,
+    required: Role,
+) -> std::result::Result<(), HttpResponse> {
+    let token = extract_token(req).ok_or_else(|| {
+        HttpResponse::Unauthorized()
+            .json(serde_json::json!({"error": "Missing authorization token"}))
+    })?;
+    let mut auth = state.auth.lock().unwrap();
+    if !matches!(auth.verify(&token, repo_name, required), Ok(true)) {
+        return Err(HttpResponse::Forbidden().json(serde_json::json!({
+            "error": format!("Permission denied: {} access to '{}' required", required, repo_name),
+            "required_role": required,
+        })));
+    }
+    Ok(())
+}
+
+/// Whether `ancestor` is reachable from `head`, following the parents of
+/// the pushed commits and of those the repository already has
+fn reaches(repo: &Repository, pushed: &[CommitMetadata], head: &str, ancestor: &str) -> bool {
+    let pushed: HashMap<&str, &CommitMetadata> = pushed.iter().map(|c| (c.id.as_str(), c)).collect();
+    let commit_log = CommitLog::new(repo.get_db().clone());
+    let mut seen = HashSet::new();
+    let mut queue = vec![head.to_string()];
+    while let Some(id) = queue.pop() {
+        if id == ancestor {
+            return true;
+        }
+        if !seen.insert(id.clone()) {
+            continue;
+        }
+        match pushed.get(id.as_str()) {
+            Some(commit) => queue.extend(commit.parents()),
+            None => {
+                if let Ok(commit) = commit_log.get_commit(&id) {
+                    queue.extend(commit.parents());
+                }
+            }
+        }
+    }
+    false
+}
+
 /// Push endpoint: POST /repo/{name}/push
 async fn push_handler(
     state: web::Data<ServerState>,
@@ -38,72 +210,82 @@ async fn push_handler(
 ) -> HttpResponse {
     let repo_name = path.into_inner();
 
-    // Extract and validate token
-    let token = match extract_token(&req) {
-        Some(t) => t,
-        None => {
-            return HttpResponse::Unauthorized()
-                .json(serde_json::json!({"error": "Missing authorization token"}));
-        }
-    };
-
-    // Verify permission
-    let auth = state.auth.lock().unwrap();
-    match auth.verify(&token, &repo_name, "write") {
-        Ok(true) => {}
-        _ => {
-            return HttpResponse::Forbidden()
-                .json(serde_json::json!({"error": "Permission denied"}));
-        }
+    if let Err(response) = check_access(&state, &req, &repo_name, Role::Write) {
+        return response;
     }
-    drop(auth);
+    // Serialize pushes so concurrent ref updates cannot overwrite each other
+    let _lock = match state.lock_repo(&repo_name).await {
+        Ok(lock) => lock,
+        Err(response) => return response,
+    };
 
-    // Get or create repository
+    // Repositories are only created through POST /repos/{name}
     let repo_path = state.repos_dir.join(&repo_name);
-    let repo =
-        match Repository::open(&repo_path) {
-            Ok(r) => r,
-            Err(_) => {
-                // Try to initialize if doesn't exist
-                match Repository::init(&repo_path) {
-                    Ok(r) => r,
-                    Err(e) => return HttpResponse::InternalServerError().json(
-                        serde_json::json!({"error": format!("Failed to initialize repo: {}", e)}),
-                    ),
-                }
-            }
-        };
-
-    // Process push: Store blobs, trees, and commits
-    for blob in &body.blobs {
-        if let Err(e) = repo.get_store().store_blob(&blob.content) {
-            return HttpResponse::InternalServerError().json(
-                serde_json::json!({"error": format!("Failed to store blob: {}", e)}),
-            );
+    let repo = match Repository::open(&repo_path) {
+        Ok(r) => r,
+        Err(e) => {
+            return HttpResponse::NotFound()
+                .json(serde_json::json!({"error": format!("Repository not found: {}", e)}));
         }
-    }
+    };
+    // A new repository takes on the pusher's algorithm
+    let repo = match repo.adopt_hash_algorithm(body.hash_algorithm) {
+        Ok(r) => r,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({"error": format!(
+                "Repository uses {}, push uses {}: {}",
+                repo.hash_algorithm(),
+                body.hash_algorithm,
+                e
+            )}));
+        }
+    };
 
-    for tree in &body.trees {
-        if let Err(e) = repo.get_store().store_tree(tree.entries.clone()) {
-            return HttpResponse::InternalServerError().json(
-                serde_json::json!({"error": format!("Failed to store tree: {}", e)}),
-            );
+    // Moving a branch anywhere but forward discards history, which needs
+    // an explicit force push from an admin. Checked before anything is
+    // stored, so a rejected push leaves no objects behind.
+    let branch_manager = BranchManager::new(repo.get_db().clone());
+    let current = branch_manager
+        .get_branch(&body.branch)
+        .ok()
+        .flatten()
+        .map(|b| b.commit_id)
+        .filter(|id| !id.is_empty());
+    if let Some(current) = current {
+        let fast_forward = reaches(&repo, &body.commits, &body.head, &current);
+        if !fast_forward && !body.force {
+            return HttpResponse::Conflict().json(serde_json::json!({
+                "error": format!(
+                    "Rejected non-fast-forward push to '{}': fetch and merge first, or push with --force",
+                    body.branch
+                ),
+            }));
+        }
+        if !fast_forward && check_access(&state, &req, &repo_name, Role::Admin).is_err() {
+            return HttpResponse::Forbidden().json(serde_json::json!({
+                "error": format!(
+                    "Permission denied: non-fast-forward push to '{}' requires admin access to '{}'",
+                    body.branch, repo_name
+                ),
+                "required_role": Role::Admin,
+            }));
         }
     }
 
-    // Store commits in database
-    for commit in &body.commits {
-        if let Ok(serialized) = serde_json::to_vec(commit) {
-            if let Err(e) = repo.get_db().set("commits", &commit.id, &serialized) {
-                return HttpResponse::InternalServerError().json(
-                    serde_json::json!({"error": format!("Failed to store commit: {}", e)}),
-                );
-            }
-        }
+    // Process push: Store blobs, trees, and commits
+    let objects = ObjectSet {
+        commits: body.commits.clone(),
+        trees: body.trees.clone(),
+        blobs: body.blobs.clone(),
+    };
+    if let Err(e) = objects.store(&repo) {
+        return HttpResponse::InternalServerError().json(
+            serde_json::json!({"error": format!("Failed to store objects: {}", e)}),
+        );
     }
 
     // Update branch reference
-    if let Err(e) = repo.get_db().set("branches", body.branch.as_bytes(), &body.head.as_bytes()) {
+    if let Err(e) = branch_manager.update_branch_with_reason(&body.branch, body.head.clone(), "push") {
         return HttpResponse::InternalServerError().json(
             serde_json::json!({"error": format!("Failed to update branch: {}", e)}),
         );
@@ -125,25 +307,9 @@ async fn pull_handler(
 ) -> HttpResponse {
     let repo_name = path.into_inner();
 
-    // Extract and validate token
-    let token = match extract_token(&req) {
-        Some(t) => t,
-        None => {
-            return HttpResponse::Unauthorized()
-                .json(serde_json::json!({"error": "Missing authorization token"}));
-        }
-    };
-
-    // Verify permission
-    let auth = state.auth.lock().unwrap();
-    match auth.verify(&token, &repo_name, "read") {
-        Ok(true) => {}
-        _ => {
-            return HttpResponse::Forbidden()
-                .json(serde_json::json!({"error": "Permission denied"}));
-        }
+    if let Err(response) = check_access(&state, &req, &repo_name, Role::Read) {
+        return response;
     }
-    drop(auth);
 
     let repo_path = state.repos_dir.join(&repo_name);
     let repo = match Repository::open(&repo_path) {
@@ -158,14 +324,29 @@ async fn pull_handler(
     let branch_name = &body.branch;
     
     match gather_branch_objects(&repo, branch_name, &body.current_head) {
-        Ok((commits, blobs, trees, head)) => {
+        Ok((objects, head)) if body.dry_run => match objects.preview() {
+            Ok(preview) => HttpResponse::Ok().json(PullResponse {
+                success: true,
+                commits: vec![],
+                blobs: vec![],
+                trees: vec![],
+                head,
+                message: "Dry run".to_string(),
+                preview: Some(preview),
+            }),
+            Err(e) => HttpResponse::InternalServerError().json(
+                serde_json::json!({"error": format!("Failed to gather objects: {}", e)}),
+            ),
+        },
+        Ok((objects, head)) => {
             HttpResponse::Ok().json(PullResponse {
                 success: true,
-                commits,
-                blobs,
-                trees,
+                commits: objects.commits,
+                blobs: objects.blobs,
+                trees: objects.trees,
                 head,
                 message: "Pull successful".to_string(),
+                preview: None,
             })
         }
         Err(e) => {
@@ -185,25 +366,9 @@ async fn fetch_handler(
 ) -> HttpResponse {
     let repo_name = path.into_inner();
 
-    // Extract and validate token
-    let token = match extract_token(&req) {
-        Some(t) => t,
-        None => {
-            return HttpResponse::Unauthorized()
-                .json(serde_json::json!({"error": "Missing authorization token"}));
-        }
-    };
-
-    // Verify permission
-    let auth = state.auth.lock().unwrap();
-    match auth.verify(&token, &repo_name, "read") {
-        Ok(true) => {}
-        _ => {
-            return HttpResponse::Forbidden()
-                .json(serde_json::json!({"error": "Permission denied"}));
-        }
+    if let Err(response) = check_access(&state, &req, &repo_name, Role::Read) {
+        return response;
     }
-    drop(auth);
 
     let repo_path = state.repos_dir.join(&repo_name);
     let repo = match Repository::open(&repo_path) {
@@ -214,13 +379,63 @@ async fn fetch_handler(
         }
     };
 
-    // Gather branches and their heads
-    match gather_all_branches(&repo, body.branch.as_deref()) {
-        Ok(branches) => {
-            HttpResponse::Ok().json(FetchResponse {
+    let branches = match fetch_branches(&repo, body.branch.as_deref()) {
+        Ok(branches) => branches,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": format!("Failed to fetch branches: {}", e)}));
+        }
+    };
+
+    // Nothing to send if the client's last-seen heads are still current
+    let etag = branches_etag(&branches);
+    let client_etag = req.headers().get("If-None-Match").and_then(|h| h.to_str().ok());
+    if client_etag.is_some_and(|tags| tags.split(',').any(|tag| tag.trim() == etag)) {
+        return HttpResponse::NotModified().insert_header(("ETag", etag)).finish();
+    }
+
+    // Otherwise send only the branches that moved and the objects the
+    // client is missing
+    let changed: HashMap<String, String> = branches
+        .iter()
+        .filter(|(name, head)| body.tracking.get(*name) != Some(*head))
+        .map(|(name, head)| (name.clone(), head.clone()))
+        .collect();
+    let mut removed: Vec<String> = body
+        .tracking
+        .keys()
+        .filter(|name| !branches.contains_key(*name))
+        .cloned()
+        .collect();
+    removed.sort();
+
+    let heads: Vec<String> = changed.values().cloned().collect();
+    match gather_fetch_objects(&repo, &heads, &body.known) {
+        Ok(objects) if body.dry_run => match objects.preview() {
+            Ok(preview) => HttpResponse::Ok().insert_header(("ETag", etag)).json(FetchResponse {
                 success: true,
-                branches,
+                branches: changed,
+                removed,
+                commits: vec![],
+                blobs: vec![],
+                trees: vec![],
+                message: "Dry run".to_string(),
+                preview: Some(preview),
+            }),
+            Err(e) => HttpResponse::InternalServerError().json(
+                serde_json::json!({"error": format!("Failed to fetch branches: {}", e)}),
+            ),
+        },
+        Ok(objects) => {
+            HttpResponse::Ok().insert_header(("ETag", etag)).json(FetchResponse {
+                success: true,
+                branches: changed,
+                removed,
+                commits: objects.commits,
+                blobs: objects.blobs,
+                trees: objects.trees,
                 message: "Fetch successful".to_string(),
+                preview: None,
             })
         }
         Err(e) => {
@@ -236,29 +451,13 @@ async fn clone_handler(
     state: web::Data<ServerState>,
     path: web::Path<String>,
     req: HttpRequest,
-    _body: web::Json<crate::remote::protocol::CloneRequest>,
+    body: web::Json<crate::remote::protocol::CloneRequest>,
 ) -> HttpResponse {
     let repo_name = path.into_inner();
 
-    // Extract and validate token
-    let token = match extract_token(&req) {
-        Some(t) => t,
-        None => {
-            return HttpResponse::Unauthorized()
-                .json(serde_json::json!({"error": "Missing authorization token"}));
-        }
-    };
-
-    // Verify permission
-    let auth = state.auth.lock().unwrap();
-    match auth.verify(&token, &repo_name, "read") {
-        Ok(true) => {}
-        _ => {
-            return HttpResponse::Forbidden()
-                .json(serde_json::json!({"error": "Permission denied"}));
-        }
+    if let Err(response) = check_access(&state, &req, &repo_name, Role::Read) {
+        return response;
     }
-    drop(auth);
 
     let repo_path = state.repos_dir.join(&repo_name);
     let repo = match Repository::open(&repo_path) {
@@ -270,16 +469,21 @@ async fn clone_handler(
     };
 
     // Gather all commits, blobs, trees, and branches for complete clone
-    match gather_complete_repository(&repo) {
+    match gather_complete_repository(&repo, body.branch.as_deref()) {
         Ok((commits, blobs, trees, branches, default_branch)) => {
+            let blobs = if body.include_blobs { blobs } else { Vec::new() };
             HttpResponse::Ok().json(CloneResponse {
                 commits,
                 blobs,
                 trees,
                 branches,
                 default_branch,
+                hash_algorithm: repo.hash_algorithm(),
             })
         }
+        Err(e @ crate::core::error::Error::BranchNotFound(_)) => {
+            HttpResponse::NotFound().json(serde_json::json!({"error": e.to_string()}))
+        }
         Err(e) => {
             HttpResponse::InternalServerError().json(
                 serde_json::json!({"error": format!("Failed to gather repository: {}", e)}),
@@ -288,34 +492,207 @@ async fn clone_handler(
     }
 }
 
-/// Migrate Git repository to MUG
-async fn migrate_from_git(
+/// Chunk manifest endpoint: GET /repo/{name}/manifest (also `manifest.json`)
+async fn manifest_handler(
     state: web::Data<ServerState>,
     path: web::Path<String>,
     req: HttpRequest,
-    body: web::Json<serde_json::Value>,
 ) -> HttpResponse {
     let repo_name = path.into_inner();
 
-    // Extract and validate token
-    let token = match extract_token(&req) {
-        Some(t) => t,
-        None => {
-            return HttpResponse::Unauthorized()
-                .json(serde_json::json!({"error": "Missing authorization token"}));
+    if let Err(response) = check_access(&state, &req, &repo_name, Role::Read) {
+        return response;
+    }
+
+    let repo_path = state.repos_dir.join(&repo_name);
+    let repo = match Repository::open(&repo_path) {
+        Ok(r) => r,
+        Err(e) => {
+            return HttpResponse::NotFound()
+                .json(serde_json::json!({"error": format!("Repository not found: {}", e)}));
         }
     };
 
-    // Verify write permission
-    let auth = state.auth.lock().unwrap();
-    match auth.verify(&token, &repo_name, "write") {
-        Ok(true) => {}
-        _ => {
-            return HttpResponse::Forbidden()
-                .json(serde_json::json!({"error": "Permission denied"}));
+    match chunk_pack::ensure_chunk_pack(&repo) {
+        Ok(manifest) => HttpResponse::Ok().json(manifest),
+        Err(e) => HttpResponse::InternalServerError().json(
+            serde_json::json!({"error": format!("Failed to build chunk pack: {}", e)}),
+        ),
+    }
+}
+
+/// Chunk endpoint: GET /repo/{name}/chunk/{hash}, returning compressed bytes
+async fn chunk_handler(
+    state: web::Data<ServerState>,
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let (repo_name, hash) = path.into_inner();
+
+    if let Err(response) = check_access(&state, &req, &repo_name, Role::Read) {
+        return response;
+    }
+
+    let repo_path = state.repos_dir.join(&repo_name);
+    let repo = match Repository::open(&repo_path) {
+        Ok(r) => r,
+        Err(e) => {
+            return HttpResponse::NotFound()
+                .json(serde_json::json!({"error": format!("Repository not found: {}", e)}));
         }
+    };
+
+    match chunk_pack::read_chunk(&repo, &hash) {
+        Ok(data) => HttpResponse::Ok()
+            .content_type("application/octet-stream")
+            .body(data),
+        Err(crate::core::error::Error::ObjectNotFound(_)) => HttpResponse::NotFound()
+            .json(serde_json::json!({"error": format!("Chunk not found: {}", hash)})),
+        Err(e) => HttpResponse::InternalServerError()
+            .json(serde_json::json!({"error": format!("Failed to read chunk: {}", e)})),
+    }
+}
+
+/// Where a repository's large files are kept on the server
+fn large_file_path(repo: &Repository, hash: &str) -> PathBuf {
+    repo.root().join(".mug").join("large").join(hash)
+}
+
+/// Check the token on a large file request, open the repository and
+/// validate the hash, or produce the error response
+fn open_large_file_repo(
+    state: &ServerState,
+    req: &HttpRequest,
+    repo_name: &str,
+    hash: &str,
+    required: Role,
+) -> std::result::Result<Repository, HttpResponse> {
+    check_access(state, req, repo_name, required)?;
+
+    let repo = Repository::open(state.repos_dir.join(repo_name)).map_err(|e| {
+        HttpResponse::NotFound()
+            .json(serde_json::json!({"error": format!("Repository not found: {}", e)}))
+    })?;
+    if hash.is_empty() || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": format!("Invalid object hash: {}", hash)})));
+    }
+    Ok(repo)
+}
+
+/// Large file upload: PUT /repo/{name}/store/{hash}
+async fn store_upload_handler(
+    state: web::Data<ServerState>,
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+    mut payload: web::Payload,
+) -> HttpResponse {
+    let (repo_name, hash) = path.into_inner();
+    let repo = match open_large_file_repo(&state, &req, &repo_name, &hash, Role::Write) {
+        Ok(repo) => repo,
+        Err(response) => return response,
+    };
+
+    let max_store_bytes = req
+        .app_data::<web::Data<ServerLimits>>()
+        .map_or(ServerLimits::default().max_store_bytes, |limits| limits.max_store_bytes);
+
+    // Stream the upload to a temporary file beside its destination, so a
+    // large file is never held in memory
+    let target = large_file_path(&repo, &hash);
+    let temp = target
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| tempfile::NamedTempFile::new_in(target.parent().unwrap_or(repo.root())));
+    let mut temp = match temp {
+        Ok(temp) => temp,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": format!("Failed to store large file: {}", e)}));
+        }
+    };
+    let mut size = 0u64;
+    while let Some(chunk) = payload.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                return HttpResponse::BadRequest()
+                    .json(serde_json::json!({"error": format!("Failed to read upload: {}", e)}));
+            }
+        };
+        size += chunk.len() as u64;
+        if size > max_store_bytes {
+            return HttpResponse::PayloadTooLarge().json(serde_json::json!({
+                "error": format!("Upload exceeds the {} byte limit", max_store_bytes)
+            }));
+        }
+        if let Err(e) = std::io::Write::write_all(&mut temp, &chunk) {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": format!("Failed to store large file: {}", e)}));
+        }
+    }
+
+    let hashed = std::fs::File::open(temp.path())
+        .map_err(crate::core::error::Error::from)
+        .and_then(|file| repo.hash_algorithm().hash_blob_reader(std::io::BufReader::new(file), size));
+    match hashed {
+        Ok(actual) if actual == hash => {}
+        Ok(_) => {
+            return HttpResponse::BadRequest()
+                .json(serde_json::json!({"error": format!("Content does not match {}", hash)}));
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": format!("Failed to hash upload: {}", e)}));
+        }
+    }
+
+    match temp.persist(&target) {
+        Ok(_) => HttpResponse::Ok().json(serde_json::json!({"hash": hash, "size": size})),
+        Err(e) => HttpResponse::InternalServerError()
+            .json(serde_json::json!({"error": format!("Failed to store large file: {}", e)})),
+    }
+}
+
+/// Large file download: GET /repo/{name}/store/{hash}
+async fn store_download_handler(
+    state: web::Data<ServerState>,
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let (repo_name, hash) = path.into_inner();
+    let repo = match open_large_file_repo(&state, &req, &repo_name, &hash, Role::Read) {
+        Ok(repo) => repo,
+        Err(response) => return response,
+    };
+
+    match std::fs::read(large_file_path(&repo, &hash)) {
+        Ok(data) => HttpResponse::Ok()
+            .content_type("application/octet-stream")
+            .body(data),
+        Err(_) => HttpResponse::NotFound()
+            .json(serde_json::json!({"error": format!("Large file not found: {}", hash)})),
+    }
+}
+
+/// Migrate Git repository to MUG
+async fn migrate_from_git(
+    state: web::Data<ServerState>,
+    path: web::Path<String>,
+    req: HttpRequest,
+    body: web::Json<serde_json::Value>,
+) -> HttpResponse {
+    let repo_name = path.into_inner();
+
+    // Migrating creates the repository, so it is held to the same rules as
+    // POST /repos/{name}
+    if !valid_repo_name(&repo_name) {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": format!("Invalid repository name '{}'", repo_name)}));
+    }
+    if let Err(response) = check_access(&state, &req, &repo_name, Role::Admin) {
+        return response;
     }
-    drop(auth);
 
     // Get Git path from request
     let git_path = match body.get("git_path") {
@@ -326,10 +703,21 @@ async fn migrate_from_git(
         }
     };
 
+    let algorithm = match body.get("hash_algorithm").and_then(|v| v.as_str()) {
+        Some(name) => match name.parse::<HashAlgorithm>() {
+            Ok(algorithm) => algorithm,
+            Err(e) => {
+                return HttpResponse::BadRequest()
+                    .json(serde_json::json!({"error": e.to_string()}));
+            }
+        },
+        None => HashAlgorithm::default(),
+    };
+
     let mug_path = state.repos_dir.join(&repo_name);
 
     // Perform migration
-    match git_compat::migrate_git_to_mug(&git_path, mug_path.to_str().unwrap_or("")) {
+    match git_compat::migrate_git_to_mug(&git_path, mug_path.to_str().unwrap_or(""), algorithm) {
         Ok(message) => {
             HttpResponse::Ok().json(serde_json::json!({
                 "success": true,
@@ -345,29 +733,128 @@ async fn migrate_from_git(
     }
 }
 
+/// Key challenge: POST /auth/challenge, issuing a nonce for a registered
+/// public key to sign
+async fn challenge_handler(
+    state: web::Data<ServerState>,
+    body: web::Json<ChallengeRequest>,
+) -> HttpResponse {
+    let mut auth = state.auth.lock().unwrap();
+    match auth.issue_challenge(&body.public_key) {
+        Ok(nonce) => HttpResponse::Ok().json(ChallengeResponse { nonce }),
+        Err(e) => HttpResponse::Forbidden().json(serde_json::json!({"error": e.to_string()})),
+    }
+}
+
+/// Key session: POST /auth/session, exchanging a signed challenge for a
+/// short-lived bearer token with the key's permissions
+async fn session_handler(
+    state: web::Data<ServerState>,
+    body: web::Json<SessionRequest>,
+) -> HttpResponse {
+    let mut auth = state.auth.lock().unwrap();
+    match auth.answer_challenge(&body.public_key, &body.nonce, &body.signature) {
+        Ok(token) => HttpResponse::Ok().json(SessionResponse {
+            token,
+            expires_in: ServerAuth::session_ttl(),
+        }),
+        Err(e) => HttpResponse::Unauthorized().json(serde_json::json!({"error": e.to_string()})),
+    }
+}
+
 /// Health check
 async fn health() -> HttpResponse {
     HttpResponse::Ok().json(serde_json::json!({"status": "ok"}))
 }
 
+/// JSON extractor config that rejects oversized bodies with 413
+pub fn json_config(max_body_bytes: usize) -> web::JsonConfig {
+    web::JsonConfig::default()
+        .limit(max_body_bytes)
+        .error_handler(move |err, _req| {
+            let response = match &err {
+                JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } => {
+                    HttpResponse::PayloadTooLarge().json(serde_json::json!({
+                        "error": format!("Request body exceeds the {} byte limit", max_body_bytes)
+                    }))
+                }
+                _ => HttpResponse::BadRequest().json(serde_json::json!({"error": err.to_string()})),
+            };
+            InternalError::from_response(err, response).into()
+        })
+}
+
+/// Reject clients that exceed the per-minute request budget with 429
+async fn rate_limit<B: MessageBody>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> std::result::Result<ServiceResponse<EitherBody<B>>, actix_web::Error> {
+    if let Some(limiter) = req.app_data::<web::Data<RateLimiter>>() {
+        // Count by token only once it is known to be real, so made-up
+        // tokens share their client address's budget
+        let state = req.app_data::<web::Data<ServerState>>();
+        let token = extract_token(req.request()).filter(|token| {
+            state.is_some_and(|state| matches!(state.auth.lock().unwrap().knows(token), Ok(true)))
+        });
+        let key = match token {
+            Some(token) => format!("token:{}", token),
+            None => format!("addr:{}", req.peer_addr().map(|addr| addr.ip().to_string()).unwrap_or_default()),
+        };
+        if !limiter.check(&key) {
+            let response = HttpResponse::TooManyRequests()
+                .json(serde_json::json!({"error": "Rate limit exceeded, try again later"}));
+            return Ok(req.into_response(response).map_into_right_body());
+        }
+    }
+    next.call(req).await.map(ServiceResponse::map_into_left_body)
+}
+
+/// Location of the persisted token store under the repositories directory
+pub fn tokens_path(repos_dir: &std::path::Path) -> PathBuf {
+    repos_dir.join(".mug-server").join("tokens.json")
+}
+
 /// Start HTTP server
-pub async fn run_server(repos_dir: PathBuf, host: &str, port: u16) -> Result<()> {
-    let auth = Arc::new(Mutex::new(ServerAuth::new()));
+pub async fn run_server(
+    repos_dir: PathBuf,
+    host: &str,
+    port: u16,
+    limits: ServerLimits,
+) -> Result<()> {
+    let auth = Arc::new(Mutex::new(ServerAuth::open(tokens_path(&repos_dir))?));
 
-    let state = web::Data::new(ServerState { repos_dir, auth });
+    let state = web::Data::new(ServerState::new(repos_dir, auth));
+    let limiter = web::Data::new(RateLimiter::new(
+        limits.requests_per_minute,
+        Duration::from_secs(60),
+    ));
 
     println!("Starting MUG HTTP server on {}:{}", host, port);
 
     HttpServer::new(move || {
         App::new()
             .app_data(state.clone())
+            .app_data(limiter.clone())
+            .app_data(web::Data::new(limits))
+            .app_data(json_config(limits.max_body_bytes))
+            .wrap(middleware::from_fn(rate_limit))
             .wrap(middleware::Logger::default())
             .route("/health", web::get().to(health))
+            .route("/auth/challenge", web::post().to(challenge_handler))
+            .route("/auth/session", web::post().to(session_handler))
+            .route("/repos", web::get().to(list_repos_handler))
+            .route("/repos/{name}", web::post().to(create_repo_handler))
             .route("/repo/{name}/push", web::post().to(push_handler))
             .route("/repo/{name}/pull", web::post().to(pull_handler))
             .route("/repo/{name}/fetch", web::post().to(fetch_handler))
             .route("/repo/{name}/clone", web::post().to(clone_handler))
+            .route("/repo/{name}/manifest", web::get().to(manifest_handler))
+            .route("/repo/{name}/manifest.json", web::get().to(manifest_handler))
+            .route("/repo/{name}/chunk/{hash}", web::get().to(chunk_handler))
+            .route("/repo/{name}/store/{hash}", web::put().to(store_upload_handler))
+            .route("/repo/{name}/store/{hash}", web::get().to(store_download_handler))
             .route("/repo/{name}/list-branches", web::get().to(list_branches_handler))
+            .route("/repo/{name}/branch/{branch}", web::delete().to(delete_branch_handler))
             .route("/repo/{name}/info", web::get().to(repo_info_handler))
             .route("/repo/{name}/migrate-from-git", web::post().to(migrate_from_git))
     })
@@ -382,126 +869,91 @@ pub async fn run_server(repos_dir: PathBuf, host: &str, port: u16) -> Result<()>
 fn gather_branch_objects(
     repo: &Repository,
     branch: &str,
-    _current_head: &Option<String>,
-) -> Result<(Vec<crate::core::commit::Commit>, Vec<crate::core::store::Blob>, Vec<crate::core::store::Tree>, String)> {
-    // Get commits for branch
-    let commits = repo.log()?
-        .into_iter()
-        .map(|log_line| {
-            // Parse log line to extract commit info
-            let parts: Vec<&str> = log_line.lines().collect();
-            let id = parts.first().map(|s| s.to_string()).unwrap_or_default();
-            crate::core::commit::Commit {
-                id,
-                tree_hash: String::new(),
-                parent: None,
-                author: String::new(),
-                message: String::new(),
-                timestamp: String::new(),
-            }
-        })
-        .collect();
-    
-    // Gather blobs from repository
-    // Full implementation would require iterating through .mug/objects directory
-    // and deserializing blob objects. For now, return empty as placeholder.
-    let blobs = Vec::new();
-    
-    // Gather trees from repository
-    // Full implementation would require querying object store for tree objects
-    // and deserializing them. For now, return empty as placeholder.
-    let trees = Vec::new();
-    
-    let head = format!("refs/heads/{}", branch);
+    current_head: &Option<String>,
+) -> Result<(ObjectSet, String)> {
+    let head = BranchManager::new(repo.get_db().clone())
+        .get_branch(branch)?
+        .map(|b| b.commit_id)
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| crate::core::error::Error::BranchNotFound(branch.to_string()))?;
 
-    Ok((commits, blobs, trees, head))
+    // Skip history the client already has
+    let commit_log = CommitLog::new(repo.get_db().clone());
+    let known = match current_head {
+        Some(id) if commit_log.get_commit(id).is_ok() => commit_log.ancestors(id)?,
+        _ => HashSet::new(),
+    };
+    let objects = ObjectSet::collect(repo, std::slice::from_ref(&head), &known)?;
+
+    Ok((objects, head))
 }
 
-/// Gather all branches and their heads
-fn gather_all_branches(
+/// Branch heads, or just `specific_branch`'s
+fn fetch_branches(
     repo: &Repository,
     specific_branch: Option<&str>,
-) -> Result<std::collections::HashMap<String, String>> {
-    let mut branches = std::collections::HashMap::new();
-
-    // Fetch all branches from repository
-    let all_branches = repo.branches()?;
-    
-    if let Some(filter) = specific_branch {
-        // Return only the specific branch if requested
-        if all_branches.contains(&filter.to_string()) {
-            // Get the head commit for this branch
-            let log = repo.log()?;
-            let head = log.first()
-                .and_then(|l| l.lines().next())
-                .map(|s| s.to_string())
-                .unwrap_or_else(|| "HEAD".to_string());
-            branches.insert(filter.to_string(), head);
-        }
-    } else {
-        // Return all branches with their heads
-        let log = repo.log()?;
-        let head = log.first()
-            .and_then(|l| l.lines().next())
-            .map(|s| s.to_string())
-            .unwrap_or_else(|| "HEAD".to_string());
-        
-        for branch in all_branches {
-            branches.insert(branch, head.clone());
+) -> Result<HashMap<String, String>> {
+    let branches: HashMap<String, String> = BranchManager::new(repo.get_db().clone())
+        .list_branches()?
+        .into_iter()
+        .filter(|b| !b.commit_id.is_empty())
+        .filter(|b| specific_branch.is_none_or(|name| b.name == name))
+        .map(|b| (b.name, b.commit_id))
+        .collect();
+    if let Some(name) = specific_branch {
+        if branches.is_empty() {
+            return Err(crate::core::error::Error::BranchNotFound(name.to_string()));
         }
     }
-
     Ok(branches)
 }
 
+/// Objects reachable from `heads` that are not in the history of `known`
+fn gather_fetch_objects(repo: &Repository, heads: &[String], known: &[String]) -> Result<ObjectSet> {
+    let commit_log = CommitLog::new(repo.get_db().clone());
+    let mut have = HashSet::new();
+    for id in known {
+        if commit_log.get_commit(id).is_ok() {
+            have.extend(commit_log.ancestors(id)?);
+        }
+    }
+    ObjectSet::collect(repo, heads, &have)
+}
+
 /// Gather complete repository for clone
 fn gather_complete_repository(
     repo: &Repository,
+    only_branch: Option<&str>,
 ) -> Result<(
-    Vec<crate::core::commit::Commit>,
+    Vec<CommitMetadata>,
     Vec<crate::core::store::Blob>,
     Vec<crate::core::store::Tree>,
     std::collections::HashMap<String, String>,
     String,
 )> {
+    // Get default branch
+    let default_branch = repo.current_branch()?
+        .unwrap_or_else(|| "main".to_string());
+    let only_branch = only_branch.map(|name| if name == "HEAD" { default_branch.as_str() } else { name });
+
     // Fetch all commits, blobs, trees, and branches
-    let log = repo.log()?;
-    
-    let head = log.first()
-        .and_then(|l| l.lines().next())
-        .map(|s| s.to_string())
-        .unwrap_or_else(|| "HEAD".to_string());
-    
-    let commits = log
+    let mut branches: std::collections::HashMap<String, String> = BranchManager::new(repo.get_db().clone())
+        .list_branches()?
         .into_iter()
-        .map(|log_line| {
-            let parts: Vec<&str> = log_line.lines().collect();
-            let id = parts.first().map(|s| s.to_string()).unwrap_or_default();
-            crate::core::commit::Commit {
-                id,
-                tree_hash: String::new(),
-                parent: None,
-                author: String::new(),
-                message: String::new(),
-                timestamp: String::new(),
-            }
-        })
+        .filter(|b| !b.commit_id.is_empty())
+        .map(|b| (b.name, b.commit_id))
         .collect();
-    
-    let blobs = Vec::new(); // Placeholder for blob gathering
-    let trees = Vec::new(); // Placeholder for tree gathering
-    
-    // Get all branches
-    let all_branches = repo.branches()?;
-    let mut branches = std::collections::HashMap::new();
-    
-    for branch in all_branches {
-        branches.insert(branch, head.clone());
+    if let Some(name) = only_branch {
+        branches.retain(|branch, _| branch == name);
+        if branches.is_empty() {
+            return Err(crate::core::error::Error::BranchNotFound(name.to_string()));
+        }
     }
-    
-    // Get default branch
-    let default_branch = repo.current_branch()?
-        .unwrap_or_else(|| "main".to_string());
+    let default_branch = only_branch.map_or(default_branch.clone(), str::to_string);
+
+    let heads: Vec<String> = branches.values().cloned().collect();
+    let objects = ObjectSet::collect(repo, &heads, &HashSet::new())?;
+    let (commits, blobs, trees) = (objects.commits, objects.blobs, objects.trees);
 
     Ok((commits, blobs, trees, branches, default_branch))
 }
@@ -514,25 +966,9 @@ async fn list_branches_handler(
 ) -> HttpResponse {
     let repo_name = path.into_inner();
 
-    // Extract and validate token
-    let token = match extract_token(&req) {
-        Some(t) => t,
-        None => {
-            return HttpResponse::Unauthorized()
-                .json(serde_json::json!({"error": "Missing authorization token"}));
-        }
-    };
-
-    // Verify permission
-    let auth = state.auth.lock().unwrap();
-    match auth.verify(&token, &repo_name, "read") {
-        Ok(true) => {}
-        _ => {
-            return HttpResponse::Forbidden()
-                .json(serde_json::json!({"error": "Permission denied"}));
-        }
+    if let Err(response) = check_access(&state, &req, &repo_name, Role::Read) {
+        return response;
     }
-    drop(auth);
 
     let repo_path = state.repos_dir.join(&repo_name);
     match Repository::open(&repo_path) {
@@ -561,33 +997,55 @@ async fn list_branches_handler(
     }
 }
 
-/// Get repository information
-async fn repo_info_handler(
+/// Delete a branch: DELETE /repo/{name}/branch/{branch} (admin only)
+async fn delete_branch_handler(
     state: web::Data<ServerState>,
-    path: web::Path<String>,
+    path: web::Path<(String, String)>,
     req: HttpRequest,
 ) -> HttpResponse {
-    let repo_name = path.into_inner();
+    let (repo_name, branch) = path.into_inner();
+
+    if let Err(response) = check_access(&state, &req, &repo_name, Role::Admin) {
+        return response;
+    }
+    let _lock = match state.lock_repo(&repo_name).await {
+        Ok(lock) => lock,
+        Err(response) => return response,
+    };
 
-    // Extract and validate token
-    let token = match extract_token(&req) {
-        Some(t) => t,
-        None => {
-            return HttpResponse::Unauthorized()
-                .json(serde_json::json!({"error": "Missing authorization token"}));
+    let repo = match Repository::open(state.repos_dir.join(&repo_name)) {
+        Ok(repo) => repo,
+        Err(e) => {
+            return HttpResponse::NotFound()
+                .json(serde_json::json!({"error": format!("Repository not found: {}", e)}));
         }
     };
+    if repo.current_branch().ok().flatten().as_deref() == Some(branch.as_str()) {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": format!("Cannot delete the default branch '{}'", branch)}));
+    }
 
-    // Verify permission
-    let auth = state.auth.lock().unwrap();
-    match auth.verify(&token, &repo_name, "read") {
-        Ok(true) => {}
-        _ => {
-            return HttpResponse::Forbidden()
-                .json(serde_json::json!({"error": "Permission denied"}));
+    match BranchManager::new(repo.get_db().clone()).delete_branch(&branch) {
+        Ok(()) => HttpResponse::Ok().json(serde_json::json!({"success": true, "deleted": branch})),
+        Err(e @ crate::core::error::Error::BranchNotFound(_)) => {
+            HttpResponse::NotFound().json(serde_json::json!({"error": e.to_string()}))
         }
+        Err(e) => HttpResponse::InternalServerError()
+            .json(serde_json::json!({"error": format!("Failed to delete branch: {}", e)})),
+    }
+}
+
+/// Get repository information
+async fn repo_info_handler(
+    state: web::Data<ServerState>,
+    path: web::Path<String>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let repo_name = path.into_inner();
+
+    if let Err(response) = check_access(&state, &req, &repo_name, Role::Read) {
+        return response;
     }
-    drop(auth);
 
     let repo_path = state.repos_dir.join(&repo_name);
     match Repository::open(&repo_path) {
@@ -608,6 +1066,72 @@ async fn repo_info_handler(
     }
 }
 
+/// List repositories: GET /repos, showing only those the caller can read
+async fn list_repos_handler(state: web::Data<ServerState>, req: HttpRequest) -> HttpResponse {
+    let Some(token) = extract_token(&req) else {
+        return HttpResponse::Unauthorized()
+            .json(serde_json::json!({"error": "Missing authorization token"}));
+    };
+
+    let entries = match std::fs::read_dir(&state.repos_dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": format!("Failed to list repositories: {}", e)}));
+        }
+    };
+    let mut auth = state.auth.lock().unwrap();
+    let mut repos = Vec::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !valid_repo_name(&name) || !entry.path().join(".mug").is_dir() {
+            continue;
+        }
+        if let Ok(Some(role)) = auth.role(&token, &name) {
+            repos.push(serde_json::json!({"name": name, "role": role}));
+        }
+    }
+    repos.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+
+    HttpResponse::Ok().json(serde_json::json!({"success": true, "repos": repos}))
+}
+
+/// Create a repository: POST /repos/{name} (admin only)
+async fn create_repo_handler(
+    state: web::Data<ServerState>,
+    path: web::Path<String>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let repo_name = path.into_inner();
+
+    if !valid_repo_name(&repo_name) {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": format!("Invalid repository name '{}'", repo_name)}));
+    }
+    if let Err(response) = check_access(&state, &req, &repo_name, Role::Admin) {
+        return response;
+    }
+
+    let repo_path = state.repos_dir.join(&repo_name);
+    if repo_path.exists() {
+        return HttpResponse::Conflict()
+            .json(serde_json::json!({"error": format!("Repository '{}' already exists", repo_name)}));
+    }
+    match Repository::init(&repo_path) {
+        Ok(_) => HttpResponse::Created().json(serde_json::json!({"success": true, "name": repo_name})),
+        Err(e) => HttpResponse::InternalServerError()
+            .json(serde_json::json!({"error": format!("Failed to create repository: {}", e)})),
+    }
+}
+
+/// Whether `name` can name a repository directly under the base directory.
+/// Hidden names are reserved for server state such as `.mug-server`.
+fn valid_repo_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -617,4 +1141,531 @@ mod tests {
         // Mock request would require more setup
         // This is a placeholder for actual tests
     }
+
+    #[test]
+    fn test_rate_limiter_window() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        assert!(limiter.check("a"));
+        assert!(limiter.check("a"));
+        assert!(!limiter.check("a"));
+        assert!(limiter.check("b"));
+
+        let unlimited = RateLimiter::new(0, Duration::from_secs(60));
+        assert!((0..10).all(|_| unlimited.check("a")));
+
+        // Expired windows are dropped rather than kept forever
+        let short = RateLimiter::new(1, Duration::from_millis(10));
+        assert!(short.check("a") && short.check("b"));
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(short.check("c"));
+        assert_eq!(short.counts.lock().unwrap().clients.len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_unverified_tokens_share_the_address_budget() {
+        use crate::core::auth::Permission;
+        use actix_web::http::StatusCode;
+        use actix_web::test;
+
+        let mut auth = ServerAuth::new();
+        auth.add_token("real".to_string(), "dev".to_string(), vec![Permission::Read("proj".to_string())]);
+        let state = web::Data::new(ServerState::new(std::env::temp_dir(), Arc::new(Mutex::new(auth))));
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .app_data(web::Data::new(RateLimiter::new(2, Duration::from_secs(60))))
+                .wrap(middleware::from_fn(rate_limit))
+                .route("/health", web::get().to(health)),
+        )
+        .await;
+        let get = |token: &str| {
+            test::TestRequest::get()
+                .uri("/health")
+                .peer_addr("10.0.0.1:1234".parse().unwrap())
+                .insert_header(("Authorization", format!("Bearer {}", token)))
+                .to_request()
+        };
+
+        assert!(test::call_service(&app, get("bogus-1")).await.status().is_success());
+        assert!(test::call_service(&app, get("bogus-2")).await.status().is_success());
+        assert_eq!(test::call_service(&app, get("bogus-3")).await.status(), StatusCode::TOO_MANY_REQUESTS);
+        // A real token has its own budget
+        assert!(test::call_service(&app, get("real")).await.status().is_success());
+    }
+
+    #[actix_web::test]
+    async fn test_repo_locks_serialize_writes_per_repo() {
+        let locks = RepoLocks::default();
+        let wait = Duration::from_millis(20);
+        let held = locks.acquire("proj", wait).await.unwrap();
+        assert!(locks.acquire("proj", wait).await.is_none());
+        assert!(locks.acquire("other", wait).await.is_some());
+        drop(held);
+        assert!(locks.acquire("proj", wait).await.is_some());
+    }
+
+    #[actix_web::test]
+    async fn test_oversized_body_is_rejected() {
+        use actix_web::test;
+
+        async fn echo(body: web::Json<serde_json::Value>) -> HttpResponse {
+            HttpResponse::Ok().json(body.into_inner())
+        }
+
+        let app = test::init_service(
+            App::new()
+                .app_data(json_config(16))
+                .route("/echo", web::post().to(echo)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/echo")
+            .set_json(serde_json::json!({"payload": "x".repeat(64)}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::PAYLOAD_TOO_LARGE);
+
+        let req = test::TestRequest::post()
+            .uri("/echo")
+            .set_json(serde_json::json!({"a": 1}))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+    }
+
+    #[actix_web::test]
+    async fn test_key_sign_in_grants_repo_access() {
+        use crate::core::auth::{challenge_message, Permission};
+        use crate::core::crypto::CryptoKey;
+        use actix_web::test;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path().join("proj")).unwrap();
+        std::fs::write(dir.path().join("proj/a.txt"), "a").unwrap();
+        repo.add("a.txt").unwrap();
+        repo.commit("Tester".to_string(), "First".to_string()).unwrap();
+        // Release the database so the handler can open it
+        drop(repo);
+
+        let (key, public_key) = CryptoKey::generate().unwrap();
+        let mut auth = ServerAuth::new();
+        auth.add_key(&public_key, "laptop".to_string(), vec![Permission::Read("proj".to_string())])
+            .unwrap();
+        let state = web::Data::new(ServerState::new(dir.path().to_path_buf(), Arc::new(Mutex::new(auth))));
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .route("/auth/challenge", web::post().to(challenge_handler))
+                .route("/auth/session", web::post().to(session_handler))
+                .route("/repo/{name}/list-branches", web::get().to(list_branches_handler)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/auth/challenge")
+            .set_json(ChallengeRequest { public_key: public_key.clone() })
+            .to_request();
+        let challenge: ChallengeResponse = test::call_and_read_body_json(&app, req).await;
+
+        let req = test::TestRequest::post()
+            .uri("/auth/session")
+            .set_json(SessionRequest {
+                public_key: public_key.clone(),
+                signature: key.sign(&challenge_message(&challenge.nonce)).unwrap(),
+                nonce: challenge.nonce.clone(),
+            })
+            .to_request();
+        let session: SessionResponse = test::call_and_read_body_json(&app, req).await;
+
+        let req = test::TestRequest::get()
+            .uri("/repo/proj/list-branches")
+            .insert_header(("Authorization", format!("Bearer {}", session.token)))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        // The nonce itself is not a credential
+        let req = test::TestRequest::get()
+            .uri("/repo/proj/list-branches")
+            .insert_header(("Authorization", format!("Bearer {}", challenge.nonce)))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), actix_web::http::StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn test_history_rewrites_need_admin() {
+        use crate::core::auth::Permission;
+        use crate::remote::protocol::PushRequest;
+        use actix_web::http::StatusCode;
+        use actix_web::test;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path().join("proj")).unwrap();
+        let file = dir.path().join("proj/a.txt");
+        std::fs::write(&file, "1").unwrap();
+        repo.add("a.txt").unwrap();
+        let first = repo.commit("Tester".to_string(), "First".to_string()).unwrap();
+        std::fs::write(&file, "2").unwrap();
+        repo.add("a.txt").unwrap();
+        repo.commit("Tester".to_string(), "Second".to_string()).unwrap();
+        repo.create_branch("topic".to_string()).unwrap();
+        drop(repo);
+
+        let mut auth = ServerAuth::new();
+        auth.add_token("writer-token".to_string(), "dev".to_string(), vec![Permission::Write("proj".to_string())]);
+        auth.add_token("admin-token".to_string(), "lead".to_string(), vec![Permission::Admin("proj".to_string())]);
+        let state = web::Data::new(ServerState::new(dir.path().to_path_buf(), Arc::new(Mutex::new(auth))));
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .route("/repo/{name}/push", web::post().to(push_handler))
+                .route("/repo/{name}/branch/{branch}", web::delete().to(delete_branch_handler)),
+        )
+        .await;
+
+        // Moving main back to its first commit
+        let rewind = PushRequest {
+            repo: "proj".to_string(),
+            branch: "main".to_string(),
+            commits: vec![],
+            blobs: vec![],
+            trees: vec![],
+            head: first.clone(),
+            hash_algorithm: HashAlgorithm::default(),
+            force: false,
+        };
+        let push = |token: &str, force: bool| {
+            test::TestRequest::post()
+                .uri("/repo/proj/push")
+                .insert_header(("Authorization", format!("Bearer {}", token)))
+                .set_json(PushRequest { force, ..rewind.clone() })
+                .to_request()
+        };
+
+        // Without --force nobody rewinds the branch, admins included
+        for token in ["writer-token", "admin-token"] {
+            let resp = test::call_service(&app, push(token, false)).await;
+            assert_eq!(resp.status(), StatusCode::CONFLICT);
+            let body: serde_json::Value = test::read_body_json(resp).await;
+            assert!(body["error"].as_str().unwrap().contains("--force"));
+        }
+        // A rejected push stores none of the objects it carried
+        let other_dir = tempfile::TempDir::new().unwrap();
+        let other = Repository::init(other_dir.path()).unwrap();
+        std::fs::write(other_dir.path().join("a.txt"), "diverged").unwrap();
+        other.add("a.txt").unwrap();
+        let diverged = other.commit("Tester".to_string(), "Diverged".to_string()).unwrap();
+        let objects = ObjectSet::collect(&other, std::slice::from_ref(&diverged), &HashSet::new()).unwrap();
+        let diverging = PushRequest {
+            commits: objects.commits,
+            trees: objects.trees,
+            blobs: objects.blobs.clone(),
+            head: diverged.clone(),
+            ..rewind.clone()
+        };
+        for force in [false, true] {
+            let req = test::TestRequest::post()
+                .uri("/repo/proj/push")
+                .insert_header(("Authorization", "Bearer writer-token"))
+                .set_json(PushRequest { force, ..diverging.clone() })
+                .to_request();
+            assert!(!test::call_service(&app, req).await.status().is_success());
+        }
+        let repo = Repository::open(dir.path().join("proj")).unwrap();
+        assert!(!repo.get_store().has_object(&objects.blobs[0].hash));
+        assert!(repo.get_db().get("COMMITS", &diverged).unwrap().is_none());
+        drop(repo);
+
+        let resp = test::call_service(&app, push("writer-token", true)).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["required_role"], "admin");
+        assert!(body["error"].as_str().unwrap().contains("non-fast-forward"));
+        assert!(test::call_service(&app, push("admin-token", true)).await.status().is_success());
+
+        let delete = |token: &str| {
+            test::TestRequest::delete()
+                .uri("/repo/proj/branch/topic")
+                .insert_header(("Authorization", format!("Bearer {}", token)))
+                .to_request()
+        };
+        let resp = test::call_service(&app, delete("writer-token")).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "Permission denied: admin access to 'proj' required");
+        assert!(test::call_service(&app, delete("admin-token")).await.status().is_success());
+        assert_eq!(test::call_service(&app, delete("admin-token")).await.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_list_and_create_repos() {
+        use crate::core::auth::Permission;
+        use actix_web::http::StatusCode;
+        use actix_web::test;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        for name in ["docs", "secret"] {
+            Repository::init(dir.path().join(name)).unwrap();
+        }
+        std::fs::create_dir(dir.path().join("not-a-repo")).unwrap();
+
+        let mut auth = ServerAuth::new();
+        auth.add_token(
+            "token".to_string(),
+            "dev".to_string(),
+            vec![Permission::Read("docs".to_string()), Permission::Admin("app".to_string())],
+        );
+        let state = web::Data::new(ServerState::new(dir.path().to_path_buf(), Arc::new(Mutex::new(auth))));
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .route("/repos", web::get().to(list_repos_handler))
+                .route("/repos/{name}", web::post().to(create_repo_handler)),
+        )
+        .await;
+        let authed = |req: test::TestRequest| {
+            req.insert_header(("Authorization", "Bearer token")).to_request()
+        };
+
+        let create = |name: &str| authed(test::TestRequest::post().uri(&format!("/repos/{}", name)));
+        assert_eq!(test::call_service(&app, create("docs")).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(test::call_service(&app, create(".mug-server")).await.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(test::call_service(&app, create("app")).await.status(), StatusCode::CREATED);
+        assert_eq!(test::call_service(&app, create("app")).await.status(), StatusCode::CONFLICT);
+        assert!(Repository::open(dir.path().join("app")).is_ok());
+
+        let body: serde_json::Value =
+            test::call_and_read_body_json(&app, authed(test::TestRequest::get().uri("/repos"))).await;
+        assert_eq!(
+            body["repos"],
+            serde_json::json!([{"name": "app", "role": "admin"}, {"name": "docs", "role": "read"}])
+        );
+
+        let req = test::TestRequest::get().uri("/repos").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn test_push_and_migrate_do_not_create_repos_without_admin() {
+        use crate::core::auth::Permission;
+        use crate::remote::protocol::PushRequest;
+        use actix_web::http::StatusCode;
+        use actix_web::test;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut auth = ServerAuth::new();
+        auth.add_token("writer".to_string(), "dev".to_string(), vec![Permission::Write("new".to_string())]);
+        let state = web::Data::new(ServerState::new(dir.path().to_path_buf(), Arc::new(Mutex::new(auth))));
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .route("/repo/{name}/push", web::post().to(push_handler))
+                .route("/repo/{name}/migrate-from-git", web::post().to(migrate_from_git)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/repo/new/push")
+            .insert_header(("Authorization", "Bearer writer"))
+            .set_json(PushRequest {
+                repo: "new".to_string(),
+                branch: "main".to_string(),
+                commits: vec![],
+                blobs: vec![],
+                trees: vec![],
+                head: String::new(),
+                hash_algorithm: HashAlgorithm::default(),
+                force: false,
+            })
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+
+        let migrate = |name: &str| {
+            test::TestRequest::post()
+                .uri(&format!("/repo/{}/migrate-from-git", name))
+                .insert_header(("Authorization", "Bearer writer"))
+                .set_json(serde_json::json!({"git_path": dir.path().join("git")}))
+                .to_request()
+        };
+        assert_eq!(test::call_service(&app, migrate("new")).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(test::call_service(&app, migrate(".mug-server")).await.status(), StatusCode::BAD_REQUEST);
+        assert!(!dir.path().join("new").exists());
+    }
+
+    #[actix_web::test]
+    async fn test_push_refuses_blobs_with_unsafe_ids() {
+        use crate::core::auth::Permission;
+        use crate::core::store::Blob;
+        use crate::core::store_manager::LargeFilePointer;
+        use crate::remote::protocol::PushRequest;
+        use actix_web::test;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        Repository::init(dir.path().join("proj")).unwrap();
+        let mut auth = ServerAuth::new();
+        auth.add_token("writer".to_string(), "dev".to_string(), vec![Permission::Write("proj".to_string())]);
+        let state = web::Data::new(ServerState::new(dir.path().to_path_buf(), Arc::new(Mutex::new(auth))));
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .route("/repo/{name}/push", web::post().to(push_handler)),
+        )
+        .await;
+
+        // A pointer whose id matches the claimed hash used to pass as valid
+        let hash = "../../escaped".to_string();
+        let content = LargeFilePointer { hash: hash.clone(), size: 1 }.to_bytes();
+        let req = test::TestRequest::post()
+            .uri("/repo/proj/push")
+            .insert_header(("Authorization", "Bearer writer"))
+            .set_json(PushRequest {
+                repo: "proj".to_string(),
+                branch: "main".to_string(),
+                commits: vec![],
+                blobs: vec![Blob { hash, size: content.len() as u64, content }],
+                trees: vec![],
+                head: String::new(),
+                hash_algorithm: HashAlgorithm::default(),
+                force: false,
+            })
+            .to_request();
+        assert!(!test::call_service(&app, req).await.status().is_success());
+        assert!(!dir.path().join("proj/escaped").exists());
+        assert!(!dir.path().join("proj/.mug/escaped").exists());
+    }
+
+    #[actix_web::test]
+    async fn test_fetch_sends_only_changed_branches() {
+        use crate::core::auth::Permission;
+        use crate::remote::protocol::FetchRequest;
+        use actix_web::http::StatusCode;
+        use actix_web::test;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path().join("proj")).unwrap();
+        let file = dir.path().join("proj/a.txt");
+        std::fs::write(&file, "1").unwrap();
+        repo.add("a.txt").unwrap();
+        let first = repo.commit("Tester".to_string(), "First".to_string()).unwrap();
+        repo.create_branch("topic".to_string()).unwrap();
+        repo.create_branch("old".to_string()).unwrap();
+        drop(repo);
+
+        let mut auth = ServerAuth::new();
+        auth.add_token("token".to_string(), "dev".to_string(), vec![Permission::Read("proj".to_string())]);
+        let state = web::Data::new(ServerState::new(dir.path().to_path_buf(), Arc::new(Mutex::new(auth))));
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .route("/repo/{name}/fetch", web::post().to(fetch_handler)),
+        )
+        .await;
+        let fetch = |tracking: HashMap<String, String>| {
+            let etag = branches_etag(&tracking);
+            let known = tracking.values().cloned().collect();
+            test::TestRequest::post()
+                .uri("/repo/proj/fetch")
+                .insert_header(("Authorization", "Bearer token"))
+                .insert_header(("If-None-Match", etag))
+                .set_json(FetchRequest {
+                    repo: "proj".to_string(),
+                    branch: None,
+                    known,
+                    dry_run: false,
+                    tracking,
+                })
+                .to_request()
+        };
+
+        let resp = test::call_service(&app, fetch(HashMap::new())).await;
+        let etag = resp.headers().get("ETag").unwrap().to_str().unwrap().to_string();
+        let body: FetchResponse = test::read_body_json(resp).await;
+        assert_eq!(body.branches.len(), 3);
+        assert!(!body.commits.is_empty());
+        assert_eq!(branches_etag(&body.branches), etag);
+
+        let resp = test::call_service(&app, fetch(body.branches.clone())).await;
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+        let repo = Repository::open(dir.path().join("proj")).unwrap();
+        std::fs::write(&file, "2").unwrap();
+        repo.add("a.txt").unwrap();
+        let second = repo.commit("Tester".to_string(), "Second".to_string()).unwrap();
+        BranchManager::new(repo.get_db().clone()).delete_branch("old").unwrap();
+        drop(repo);
+
+        let mut tracking = body.branches;
+        tracking.insert("gone".to_string(), first.clone());
+        let body: FetchResponse = test::call_and_read_body_json(&app, fetch(tracking)).await;
+        assert_eq!(body.branches, HashMap::from([("main".to_string(), second)]));
+        assert_eq!(body.removed, vec!["gone".to_string(), "old".to_string()]);
+        assert_eq!(body.commits.len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_store_upload_is_size_limited() {
+        use crate::core::auth::Permission;
+        use actix_web::http::StatusCode;
+        use actix_web::test;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path().join("proj")).unwrap();
+        let algorithm = repo.hash_algorithm();
+        drop(repo);
+
+        let mut auth = ServerAuth::new();
+        auth.add_token("token".to_string(), "dev".to_string(), vec![Permission::Write("proj".to_string())]);
+        let state = web::Data::new(ServerState::new(dir.path().to_path_buf(), Arc::new(Mutex::new(auth))));
+        let limits = ServerLimits { max_store_bytes: 16, ..ServerLimits::default() };
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .app_data(web::Data::new(limits))
+                .route("/repo/{name}/store/{hash}", web::put().to(store_upload_handler)),
+        )
+        .await;
+        let upload = |content: &[u8]| {
+            test::TestRequest::put()
+                .uri(&format!("/repo/proj/store/{}", algorithm.hash_blob(content)))
+                .insert_header(("Authorization", "Bearer token"))
+                .set_payload(content.to_vec())
+                .to_request()
+        };
+
+        assert_eq!(test::call_service(&app, upload(&[b'x'; 17])).await.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(test::call_service(&app, upload(b"small file")).await.status().is_success());
+        let stored = dir.path().join("proj/.mug/large").join(algorithm.hash_blob(b"small file"));
+        assert_eq!(std::fs::read(stored).unwrap(), b"small file");
+        assert_eq!(std::fs::read_dir(dir.path().join("proj/.mug/large")).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_gather_single_branch() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        std::fs::write(dir.path().join("a.txt"), "a").unwrap();
+        repo.add("a.txt").unwrap();
+        repo.commit("Tester".to_string(), "First".to_string()).unwrap();
+        repo.create_branch("stale".to_string()).unwrap();
+        repo.checkout("stale".to_string()).unwrap();
+        std::fs::write(dir.path().join("b.txt"), "b").unwrap();
+        repo.add("b.txt").unwrap();
+        repo.commit("Tester".to_string(), "Stale work".to_string()).unwrap();
+        repo.checkout("main".to_string()).unwrap();
+
+        let (commits, blobs, _, branches, default) = gather_complete_repository(&repo, None).unwrap();
+        assert_eq!((commits.len(), blobs.len(), branches.len()), (2, 2, 2));
+
+        let (commits, blobs, _, branches, default_only) =
+            gather_complete_repository(&repo, Some("HEAD")).unwrap();
+        assert_eq!((commits.len(), blobs.len()), (1, 1));
+        assert_eq!(branches.keys().collect::<Vec<_>>(), vec!["main"]);
+        assert_eq!(default_only, default);
+
+        let (_, _, _, branches, default) = gather_complete_repository(&repo, Some("stale")).unwrap();
+        assert_eq!((branches.len(), default.as_str()), (1, "stale"));
+        assert!(matches!(
+            gather_complete_repository(&repo, Some("missing")),
+            Err(crate::core::error::Error::BranchNotFound(_))
+        ));
+    }
 }
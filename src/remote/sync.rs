@@ -1,9 +1,19 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-
-use crate::remote::client::build_remote_client;
-use crate::core::error::Result;
+use std::sync::{Arc, Mutex};
+
+use crate::clone::{CloneConfig, ParallelCloner};
+use crate::core::branch::BranchManager;
+use crate::core::database::MugDb;
+use crate::core::hooks::{HookManager, HookType};
+use crate::core::resume::{Operation, OperationManager, OperationStatus, OperationType};
+use crate::remote::client::{build_remote_client, remote_token, RemoteClient, Transfer, TransferCallback};
+use crate::remote::objects::ObjectSet;
+use crate::remote::protocol::TransferPreview;
+use crate::core::error::{Error, Result};
 use crate::core::repo::Repository;
+use crate::ui::ProgressReporter;
 
 /// Represents a remote repository with its objects
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -54,15 +64,134 @@ impl SyncResult {
 /// Handles push/pull operations with remote repositories
 pub struct SyncManager {
     repo: Repository,
+    progress: bool,
 }
 
 impl SyncManager {
     pub fn new(repo: Repository) -> Self {
-        SyncManager { repo }
+        SyncManager {
+            repo,
+            progress: false,
+        }
+    }
+
+    /// Draw progress bars while transferring
+    pub fn with_progress(mut self, enabled: bool) -> Self {
+        self.progress = enabled;
+        self
+    }
+
+    /// A client for `remote`, with the reporter its progress is drawn by.
+    /// Progress is also recorded on `tracker`, if given.
+    async fn client(
+        &self,
+        remote: &crate::remote::Remote,
+        tracker: Option<&Tracker>,
+    ) -> Result<(RemoteClient, Option<ProgressReporter>)> {
+        let reporter = self.progress.then(ProgressReporter::new);
+        let mut callbacks: Vec<TransferCallback> = Vec::new();
+        callbacks.extend(reporter.as_ref().map(transfer_callback));
+        callbacks.extend(tracker.map(Tracker::callback));
+
+        let client = build_remote_client(remote).await?;
+        if callbacks.is_empty() {
+            return Ok((client, reporter));
+        }
+        let client = client.with_progress(Arc::new(move |phase, progress| {
+            for callback in &callbacks {
+                callback(phase, progress);
+            }
+        }));
+        Ok((client, reporter))
     }
 
-    /// Push commits to remote repository
-    pub async fn push(&self, remote_name: &str, branch: &str) -> Result<SyncResult> {
+    /// Continue an interrupted or failed push, fetch or clone recorded by
+    /// the operation tracker, reusing its operation record
+    pub async fn resume(&self, operation: &Operation) -> Result<SyncResult> {
+        if operation.status == OperationStatus::Completed {
+            return Err(Error::Custom(format!(
+                "Operation {} has already completed",
+                operation.id
+            )));
+        }
+        let param = |key: &str| {
+            operation.state.metadata.get(key).cloned().ok_or_else(|| {
+                Error::Custom(format!("Operation {} has no '{}' to resume with", operation.id, key))
+            })
+        };
+
+        match operation.op_type {
+            OperationType::Push => {
+                let (remote, branch) = (param("remote")?, param("branch")?);
+                let force = operation.state.metadata.contains_key("force");
+                self.push_tracked(&remote, &branch, false, force, Some(&operation.id)).await
+            }
+            OperationType::Fetch => {
+                self.fetch_tracked(&param("remote")?, false, Some(&operation.id)).await
+            }
+            OperationType::Clone => {
+                let mut config = CloneConfig::new(&param("url")?, &self.repo.root_path().to_string_lossy());
+                config.progress = self.progress;
+                config.branch = operation.state.metadata.get("branch").cloned();
+                config.single_branch = operation.state.metadata.contains_key("single_branch");
+                let tracker = Tracker::start(
+                    self.repo.get_db(),
+                    OperationType::Clone,
+                    HashMap::new(),
+                    Some(&operation.id),
+                )?;
+                let result = Self::clone_objects(&self.repo, config, &tracker).await;
+                tracker.finish(&result)?;
+                result
+            }
+            ref other => Err(Error::Custom(format!(
+                "Operation type '{}' can't be resumed by sync",
+                other.as_str()
+            ))),
+        }
+    }
+
+    /// Push commits to remote repository. With `dry_run`, report what would
+    /// be sent without contacting the remote. `force` asks the server to
+    /// accept a non-fast-forward update.
+    pub async fn push(&self, remote_name: &str, branch: &str, dry_run: bool, force: bool) -> Result<SyncResult> {
+        self.push_tracked(remote_name, branch, dry_run, force, None).await
+    }
+
+    /// Push, recording the run as an operation (continuing `resume` if set)
+    /// unless it is a dry run
+    async fn push_tracked(
+        &self,
+        remote_name: &str,
+        branch: &str,
+        dry_run: bool,
+        force: bool,
+        resume: Option<&str>,
+    ) -> Result<SyncResult> {
+        if dry_run {
+            return self.push_with(remote_name, branch, true, force, None).await;
+        }
+        let mut params = HashMap::from([
+            ("remote".to_string(), remote_name.to_string()),
+            ("branch".to_string(), branch.to_string()),
+        ]);
+        if force {
+            params.insert("force".to_string(), "true".to_string());
+        }
+        let tracker = Tracker::start(self.repo.get_db(), OperationType::Push, params, resume)?;
+        let result = self.push_with(remote_name, branch, false, force, Some(&tracker)).await;
+        tracker.finish(&result)?;
+        result
+    }
+
+    async fn push_with(
+        &self,
+        remote_name: &str,
+        branch: &str,
+        dry_run: bool,
+        force: bool,
+        tracker: Option<&Tracker>,
+    ) -> Result<SyncResult> {
         // Get remote configuration
         let remote_manager = crate::remote::RemoteManager::new(self.repo.get_db().clone());
         let remote = remote_manager.get(remote_name)?.ok_or_else(|| {
@@ -76,11 +205,39 @@ impl SyncManager {
         }
 
         // Build HTTP client and send push
-        let client = build_remote_client(&remote).await?;
-        match client.push(&remote, &self.repo, branch, "").await {
-            Ok(response) => {
+        let token = remote_token(&self.repo, &remote).await?;
+        let (client, reporter) = self.client(&remote, tracker).await?;
+        if dry_run {
+            return Ok(match client.preview_push(&remote, &self.repo, branch)? {
+                Some(preview) => {
+                    let target = format!("{}/{}", remote.name, branch);
+                    dry_run_result("push", "to", &target, &preview, true)
+                }
+                None => SyncResult::failed("No commits to push".to_string()),
+            });
+        }
+
+        // pre-push hooks get the remote name, its URL and the branch, and can abort
+        let hooks = self
+            .repo
+            .hooks_enabled()
+            .then(|| HookManager::new(self.repo.common_root()))
+            .transpose()?;
+        let hook_args = [remote.name.as_str(), remote.url.as_str(), branch];
+        if let Some(hooks) = &hooks {
+            hooks.trigger_strict(HookType::PrePush, &hook_args)?;
+        }
+
+        let pushed = client.push(&remote, &self.repo, branch, &token, force).await;
+        if let Some(reporter) = reporter {
+            reporter.finish();
+        }
+        match pushed {
+            Ok(Transfer { response, bytes: bytes_transferred }) => {
                 if response.success {
-                    let bytes_transferred = commits.iter().map(|c| c.len()).sum::<usize>();
+                    if let Some(hooks) = &hooks {
+                        hooks.trigger(HookType::PostPush, &hook_args)?;
+                    }
                     Ok(SyncResult::success(
                         format!(
                             "Pushed {} commits to {}/{} ({})",
@@ -101,8 +258,9 @@ impl SyncManager {
         }
     }
 
-    /// Pull commits from remote repository
-    pub async fn pull(&self, remote_name: &str, branch: &str) -> Result<SyncResult> {
+    /// Pull commits from remote repository. With `dry_run`, report what
+    /// would be received without applying anything.
+    pub async fn pull(&self, remote_name: &str, branch: &str, dry_run: bool) -> Result<SyncResult> {
         // Get remote configuration
         let remote_manager = crate::remote::RemoteManager::new(self.repo.get_db().clone());
         let remote = remote_manager.get(remote_name)?.ok_or_else(|| {
@@ -110,17 +268,25 @@ impl SyncManager {
         })?;
 
         // Build HTTP client and send pull
-        let client = build_remote_client(&remote).await?;
-        match client.pull(&remote, &self.repo, branch, "").await {
-            Ok(response) => {
-                if response.success {
-                    let bytes = response.commits.len() * 256; // Estimate bytes per commit
+        let token = remote_token(&self.repo, &remote).await?;
+        let (client, reporter) = self.client(&remote, None).await?;
+        let pulled = client.pull(&remote, &self.repo, branch, &token, dry_run).await;
+        if let Some(reporter) = reporter {
+            reporter.finish();
+        }
+        match pulled {
+            Ok(Transfer { response, bytes }) => {
+                if let (true, Some(preview)) = (response.success, &response.preview) {
+                    let target = format!("{}/{}", remote.name, branch);
+                    Ok(dry_run_result("pull", "from", &target, preview, false))
+                } else if response.success {
                     Ok(SyncResult::success(
                         format!(
-                            "Pulled {} commits from {}/{}",
+                            "Pulled {} commits from {}/{} ({})",
                             response.commits.len(),
                             remote.name,
-                            branch
+                            branch,
+                            format_bytes(bytes)
                         ),
                         0,
                         response.commits.len(),
@@ -134,23 +300,88 @@ impl SyncManager {
         }
     }
 
-    /// Fetch commits from remote (without merging)
-    pub async fn fetch(&self, remote_name: &str) -> Result<SyncResult> {
+    /// Fetch commits from remote (without merging), recording the remote's
+    /// branch heads as `refs/remotes/<remote>/<branch>`. With `dry_run`,
+    /// report what would be received without storing anything.
+    pub async fn fetch(&self, remote_name: &str, dry_run: bool) -> Result<SyncResult> {
+        self.fetch_tracked(remote_name, dry_run, None).await
+    }
+
+    /// Fetch, recording the run as an operation (continuing `resume` if set)
+    /// unless it is a dry run
+    async fn fetch_tracked(
+        &self,
+        remote_name: &str,
+        dry_run: bool,
+        resume: Option<&str>,
+    ) -> Result<SyncResult> {
+        if dry_run {
+            return self.fetch_with(remote_name, true, None).await;
+        }
+        let params = HashMap::from([("remote".to_string(), remote_name.to_string())]);
+        let tracker = Tracker::start(self.repo.get_db(), OperationType::Fetch, params, resume)?;
+        let result = self.fetch_with(remote_name, false, Some(&tracker)).await;
+        tracker.finish(&result)?;
+        result
+    }
+
+    async fn fetch_with(
+        &self,
+        remote_name: &str,
+        dry_run: bool,
+        tracker: Option<&Tracker>,
+    ) -> Result<SyncResult> {
         let remote_manager = crate::remote::RemoteManager::new(self.repo.get_db().clone());
         let remote = remote_manager.get(remote_name)?.ok_or_else(|| {
             crate::core::error::Error::Custom(format!("Remote '{}' not found", remote_name))
         })?;
 
         // Build HTTP client and send fetch
-        let client = build_remote_client(&remote).await?;
-        match client.fetch(&remote, None, "").await {
-            Ok(response) => {
-                if response.success {
-                    let bytes = response.branches.len() * 256; // Estimate bytes
+        let token = remote_token(&self.repo, &remote).await?;
+        let (client, reporter) = self.client(&remote, tracker).await?;
+        let fetched = client.fetch(&remote, &self.repo, None, &token, dry_run).await;
+        if let Some(reporter) = reporter {
+            reporter.finish();
+        }
+        match fetched {
+            Ok(Transfer { response, bytes }) => {
+                if let (true, Some(preview)) = (response.success, &response.preview) {
+                    Ok(dry_run_result("fetch", "from", &remote.name, preview, false))
+                } else if response.success && response.branches.is_empty() && response.removed.is_empty() {
+                    Ok(SyncResult::success(
+                        format!("Already up to date with {}", remote.name),
+                        0,
+                        0,
+                        bytes,
+                    ))
+                } else if response.success {
+                    let objects = ObjectSet {
+                        commits: response.commits,
+                        trees: response.trees,
+                        blobs: response.blobs,
+                    };
+                    if let Some(tracker) = tracker {
+                        tracker.step("Storing objects")?;
+                    }
+                    objects.store(&self.repo)?;
+                    if let Some(tracker) = tracker {
+                        tracker.step("Updating refs")?;
+                    }
+                    // Only moved branches are sent, so apply them to the heads
+                    // already tracked
+                    let mut heads = remote_manager.remote_heads(&remote.name)?;
+                    for branch in &response.removed {
+                        heads.remove(branch);
+                    }
+                    heads.extend(response.branches.clone());
+                    remote_manager.update_tracking_refs(&remote.name, &heads, true)?;
+                    self.repo.get_db().flush()?;
+
                     Ok(SyncResult::success(
                         format!(
-                            "Fetched {} branches from {} ({})",
+                            "Fetched {} updated branch{} from {} ({})",
                             response.branches.len(),
+                            if response.branches.len() == 1 { "" } else { "es" },
                             remote.name,
                             format_bytes(bytes)
                         ),
@@ -166,30 +397,136 @@ impl SyncManager {
         }
     }
 
-    /// Clone a remote repository (minimal implementation)
-    pub fn clone(remote_url: &str, destination: Option<&str>) -> Result<()> {
+    /// Clone a remote repository: fetch every object and branch, then check
+    /// out the default branch. `jobs` bounds the parallel downloads and
+    /// `progress` draws progress bars while downloading.
+    pub async fn clone(
+        remote_url: &str,
+        destination: Option<&str>,
+        jobs: Option<usize>,
+        branch: Option<&str>,
+        single_branch: bool,
+        progress: bool,
+    ) -> Result<SyncResult> {
         // Extract repo name from URL
         let repo_name = extract_repo_name(remote_url).unwrap_or_else(|| "repository".to_string());
 
         let target_dir = destination.unwrap_or(&repo_name);
+        if fs::read_dir(target_dir).map(|mut d| d.next().is_some()).unwrap_or(false) {
+            return Err(crate::core::error::Error::Custom(format!(
+                "Destination '{}' already exists and is not empty",
+                target_dir
+            )));
+        }
 
         // Create directory
         fs::create_dir_all(target_dir)?;
 
+        let mut config = CloneConfig::new(remote_url, target_dir);
+        config.progress = progress;
+        config.branch = branch.map(str::to_string);
+        config.single_branch = single_branch;
+        if let Some(jobs) = jobs {
+            config.num_workers = jobs;
+        }
+
+        let result = Self::clone_into(config).await;
+        if result.is_err() {
+            // Don't leave a half-initialized repository behind
+            let _ = fs::remove_dir_all(target_dir);
+        }
+        result
+    }
+
+    async fn clone_into(config: CloneConfig) -> Result<SyncResult> {
+        let remote_url = config.url.clone();
+        let target_dir = config.path.clone();
+
         // Initialize repository
-        Repository::init(target_dir)?;
+        let repo = Repository::init(&target_dir)?;
 
         // Add remote
-        let repo = Repository::open(target_dir)?;
         let remote_manager = crate::remote::RemoteManager::new(repo.get_db().clone());
-        remote_manager.add("origin", remote_url)?;
+        remote_manager.add("origin", &remote_url)?;
+        let remote = remote_manager.get("origin")?.ok_or_else(|| {
+            crate::core::error::Error::Custom("Remote 'origin' not found".to_string())
+        })?;
 
-        eprintln!(
-            "Cloned repository to {} (origin: {})",
-            target_dir, remote_url
-        );
+        let mut params = HashMap::from([
+            ("remote".to_string(), remote.name.clone()),
+            ("url".to_string(), remote_url.clone()),
+        ]);
+        if let Some(branch) = &config.branch {
+            params.insert("branch".to_string(), branch.clone());
+        }
+        if config.single_branch {
+            params.insert("single_branch".to_string(), "true".to_string());
+        }
+        let tracker = Tracker::start(repo.get_db(), OperationType::Clone, params, None)?;
+        let result = Self::clone_objects(&repo, config, &tracker).await;
+        tracker.finish(&result)?;
+        result
+    }
 
-        Ok(())
+    /// Download every object of `origin` into `repo`, recreate the remote's
+    /// branches and check out its default branch
+    async fn clone_objects(
+        repo: &Repository,
+        config: CloneConfig,
+        tracker: &Tracker,
+    ) -> Result<SyncResult> {
+        let remote_url = config.url.clone();
+        let target_dir = config.path.clone();
+        let remote_manager = crate::remote::RemoteManager::new(repo.get_db().clone());
+        let remote = remote_manager.get("origin")?.ok_or_else(|| {
+            crate::core::error::Error::Custom("Remote 'origin' not found".to_string())
+        })?;
+
+        let token = remote_token(repo, &remote).await?;
+        let checkout = config.branch.clone();
+        build_remote_client(&remote).await?;
+        let (response, stats) = ParallelCloner::new(config)
+            .with_progress(tracker.callback())
+            .fetch(repo, &remote, &token)
+            .await?;
+
+        let checkout = checkout.unwrap_or_else(|| response.default_branch.clone());
+        if !response.branches.contains_key(&checkout) {
+            return Err(Error::BranchNotFound(checkout));
+        }
+
+        // Recreate the remote's branches
+        tracker.step("Checking out")?;
+        let branch_manager = BranchManager::new(repo.get_db().clone());
+        let operation = format!("clone: from {}", remote_url);
+        for (name, head) in &response.branches {
+            branch_manager.update_branch_with_reason(name, head.clone(), &operation)?;
+        }
+        if !response.branches.contains_key("main") {
+            branch_manager.delete_branch("main")?;
+        }
+
+        // Check out the requested or default branch
+        branch_manager.set_head(checkout.clone())?;
+        let (files, modes) = repo.snapshot_with_modes(&response.branches[&checkout])?;
+        repo.update_working_tree(&HashMap::new(), &files, &modes)?;
+        repo.get_db().flush()?;
+
+        Ok(SyncResult::success(
+            format!(
+                "Cloned {} into {} ({} commits, {} objects, {} in {:.2}s, {}/s)",
+                remote_url,
+                target_dir,
+                stats.commits,
+                stats.objects,
+                format_bytes(stats.bytes as usize),
+                stats.elapsed.as_secs_f64(),
+                format_bytes(stats.throughput() as usize)
+            ),
+            0,
+            stats.commits,
+            stats.bytes as usize,
+        ))
     }
 
     /// Get remote info
@@ -224,8 +561,138 @@ impl SyncManager {
     }
 }
 
+/// Records a sync as a resumable operation, so interrupted runs show up in
+/// `mug resume list` and can be continued
+#[derive(Clone)]
+struct Tracker {
+    db: MugDb,
+    id: String,
+    /// Current step, so progress only rewrites it when the phase changes
+    step: Arc<Mutex<String>>,
+}
+
+impl Tracker {
+    /// Create the operation, or mark `resume` as running again
+    fn start(
+        db: &MugDb,
+        op_type: OperationType,
+        params: HashMap<String, String>,
+        resume: Option<&str>,
+    ) -> Result<Self> {
+        let manager = OperationManager::new(db.clone());
+        let (id, step) = match resume {
+            Some(id) => {
+                manager.update_status(id, OperationStatus::Running)?;
+                let step = manager.get(id)?.map(|op| op.state.current_step);
+                (id.to_string(), step.unwrap_or_default())
+            }
+            None => {
+                let op = manager.create(op_type, String::new(), params)?;
+                (op.id, op.state.current_step)
+            }
+        };
+        db.flush()?;
+        Ok(Tracker {
+            db: db.clone(),
+            id,
+            step: Arc::new(Mutex::new(step)),
+        })
+    }
+
+    /// Move to `step`, keeping the previous step as the checkpoint
+    fn step(&self, step: &str) -> Result<()> {
+        let mut current = self.step.lock().unwrap();
+        if *current == step {
+            return Ok(());
+        }
+        let previous = std::mem::replace(&mut *current, step.to_string());
+        OperationManager::new(self.db.clone()).update_checkpoint(&self.id, previous, step.to_string(), None)
+    }
+
+    /// Progress callback recording the phase and totals on the operation
+    fn callback(&self) -> TransferCallback {
+        let tracker = self.clone();
+        Arc::new(move |phase, progress| {
+            let _ = tracker.step(phase);
+            let _ = OperationManager::new(tracker.db.clone()).update_progress(
+                &tracker.id,
+                progress.processed,
+                progress.total,
+                progress.bytes_processed,
+                progress.total_bytes,
+            );
+        })
+    }
+
+    /// Mark the operation completed or failed according to `result`
+    fn finish(&self, result: &Result<SyncResult>) -> Result<()> {
+        let manager = OperationManager::new(self.db.clone());
+        match result {
+            Ok(sync) if sync.success => {
+                self.step("Done")?;
+                manager.complete(&self.id)?;
+            }
+            Ok(sync) => manager.fail(&self.id, &sync.message)?,
+            Err(e) => manager.fail(&self.id, &e.to_string())?,
+        }
+        self.db.flush()
+    }
+}
+
+/// Describe the commits and objects a dry run would transfer
+fn dry_run_result(
+    verb: &str,
+    direction: &str,
+    target: &str,
+    preview: &TransferPreview,
+    sending: bool,
+) -> SyncResult {
+    let commits = preview.commits.len();
+    if commits == 0 {
+        return SyncResult::success(
+            format!("Would {} nothing {} {}: already up to date", verb, direction, target),
+            0,
+            0,
+            0,
+        );
+    }
+
+    let mut message = format!(
+        "Would {} {} commit{} {} {} ({} trees, {} blobs, ~{})",
+        verb,
+        commits,
+        if commits == 1 { "" } else { "s" },
+        direction,
+        target,
+        preview.trees,
+        preview.blobs,
+        format_bytes(preview.bytes)
+    );
+    for commit in &preview.commits {
+        let id = commit.id.get(..8).unwrap_or(&commit.id);
+        let summary = commit.message.lines().next().unwrap_or("");
+        message.push_str(&format!("\n  {} {}", id, summary));
+    }
+
+    let (sent, received) = if sending { (commits, 0) } else { (0, commits) };
+    SyncResult::success(message, sent, received, preview.bytes)
+}
+
+/// Progress callback drawing a transfer's bytes on `reporter`
+pub(crate) fn transfer_callback(reporter: &ProgressReporter) -> TransferCallback {
+    let reporter = reporter.clone();
+    std::sync::Arc::new(move |phase, progress| {
+        let done = progress.bytes_processed;
+        let detail = match progress.total_bytes {
+            Some(total) => format!("{}/{}", format_bytes(done as usize), format_bytes(total as usize)),
+            None => format_bytes(done as usize),
+        };
+        reporter.update(phase, done, progress.total_bytes.unwrap_or(0), &detail);
+    })
+}
+
 /// Helper function to format bytes
-fn format_bytes(bytes: usize) -> String {
+pub(crate) fn format_bytes(bytes: usize) -> String {
     if bytes < 1024 {
         format!("{}B", bytes)
     } else if bytes < 1024 * 1024 {
@@ -282,6 +749,74 @@ mod tests {
         assert_eq!(format_bytes(1024 * 1024), "1.00MB");
     }
 
+    #[test]
+    fn test_dry_run_result() {
+        let repo_dir = TempDir::new().unwrap();
+        let repo = Repository::init(repo_dir.path()).unwrap();
+        fs::write(repo_dir.path().join("a.txt"), "hello").unwrap();
+        repo.add("a.txt").unwrap();
+        let id = repo.commit("Tester".to_string(), "First\n\nBody".to_string()).unwrap();
+        let head = vec![id.clone()];
+        let objects = ObjectSet::collect(&repo, &head, &Default::default()).unwrap();
+        let preview = objects.preview().unwrap();
+
+        let result = dry_run_result("push", "to", "origin/main", &preview, true);
+        assert_eq!(result.commits_sent, 1);
+        assert_eq!(result.bytes_transferred, preview.bytes);
+        let mut lines = result.message.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            format!(
+                "Would push 1 commit to origin/main (1 trees, 1 blobs, ~{})",
+                format_bytes(preview.bytes)
+            )
+        );
+        assert_eq!(lines.next().unwrap(), format!("  {} First", &id[..8]));
+
+        let empty = dry_run_result("fetch", "from", "origin", &TransferPreview::default(), false);
+        assert_eq!(empty.message, "Would fetch nothing from origin: already up to date");
+    }
+
+    #[test]
+    fn test_tracker_records_operation() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let manager = OperationManager::new(repo.get_db().clone());
+        let params = HashMap::from([("remote".to_string(), "origin".to_string())]);
+
+        let tracker = Tracker::start(repo.get_db(), OperationType::Fetch, params, None).unwrap();
+        let progress = crate::core::resume::OperationProgress {
+            processed: 5,
+            total: Some(10),
+            bytes_processed: 5,
+            total_bytes: Some(10),
+        };
+        tracker.callback()("Receiving objects", &progress);
+        let op = manager.find(&tracker.id).unwrap().unwrap();
+        assert_eq!(op.status, OperationStatus::Running);
+        assert_eq!(op.state.current_step, "Receiving objects");
+        assert_eq!(op.progress.percentage(), Some(50.0));
+        assert_eq!(op.state.metadata["remote"], "origin");
+
+        tracker.finish(&Err(Error::Custom("connection reset".to_string()))).unwrap();
+        let op = manager.get(&tracker.id).unwrap().unwrap();
+        assert_eq!(op.status, OperationStatus::Failed);
+        assert_eq!(op.state.error_message.as_deref(), Some("connection reset"));
+
+        // Resuming reuses the record and picks up where it stopped
+        let resumed =
+            Tracker::start(repo.get_db(), OperationType::Fetch, HashMap::new(), Some(&op.id)).unwrap();
+        assert_eq!(resumed.id, op.id);
+        assert_eq!(manager.get(&op.id).unwrap().unwrap().status, OperationStatus::Running);
+        resumed
+            .finish(&Ok(SyncResult::success("done".to_string(), 0, 1, 10)))
+            .unwrap();
+        let op = manager.get(&op.id).unwrap().unwrap();
+        assert_eq!(op.status, OperationStatus::Completed);
+        assert_eq!(op.state.checkpoint, "Receiving objects");
+        assert_eq!(manager.list(None).unwrap().len(), 1);
+    }
+
     #[test]
     fn test_extract_repo_name() {
         assert_eq!(
@@ -0,0 +1,210 @@
+/// Shell completion: scripts for each shell that ask `mug __complete` for
+/// candidates, so branch, tag and remote names come from the repository
+use clap::Command;
+
+use crate::core::error::{Error, Result};
+use crate::core::repo::Repository;
+use crate::core::tag::TagManager;
+use crate::remote::RemoteManager;
+
+/// Shells `mug completions` can write a script for
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+/// Completion script for `shell`, to be sourced from its startup file
+pub fn script(shell: &str) -> Result<&'static str> {
+    match shell {
+        "bash" => Ok(BASH),
+        "zsh" => Ok(ZSH),
+        "fish" => Ok(FISH),
+        _ => Err(Error::Custom(format!(
+            "Unsupported shell '{}' (supported: {})",
+            shell,
+            SHELLS.join(", ")
+        ))),
+    }
+}
+
+// With no candidates, bash and zsh fall back to completing file names
+const BASH: &str = r#"_mug() {
+    local IFS=$'\n'
+    COMPREPLY=($(mug __complete -- "${COMP_WORDS[@]:1:COMP_CWORD}" 2>/dev/null))
+}
+complete -o default -F _mug mug
+"#;
+
+const ZSH: &str = r#"_mug() {
+    local -a candidates
+    candidates=(${(f)"$(mug __complete -- "${(@)words[2,CURRENT]}" 2>/dev/null)"})
+    if (( ${#candidates} )); then
+        compadd -a candidates
+    else
+        _files
+    fi
+}
+compdef _mug mug
+"#;
+
+const FISH: &str = r#"function __mug_complete
+    set -l tokens (commandline -opc)
+    set -e tokens[1]
+    set -l current (commandline -ct)
+    mug __complete -- $tokens "$current" 2>/dev/null
+end
+complete -c mug -a '(__mug_complete)'
+"#;
+
+/// Candidates for the last of `words`, the command line after `mug` up to
+/// the word being completed. Subcommands and flags come from `cli`; branch,
+/// tag and remote names from `repo` when run inside one.
+pub fn candidates(cli: &Command, repo: Option<&Repository>, words: &[String]) -> Result<Vec<String>> {
+    let (partial, before) = match words.split_last() {
+        Some((partial, before)) => (partial.as_str(), before),
+        None => ("", &[][..]),
+    };
+
+    // Walk the finished words to find the subcommand and how many of its
+    // positional arguments have been given
+    let mut command = cli;
+    let mut top_level = None;
+    let mut positional = 0;
+    let mut words = before.iter();
+    while let Some(word) = words.next() {
+        if let Some(flag) = word.strip_prefix('-') {
+            if takes_value(command, flag) {
+                words.next();
+            }
+        } else if let Some(sub) = command.find_subcommand(word).filter(|_| positional == 0) {
+            command = sub;
+            top_level.get_or_insert(sub.get_name());
+        } else {
+            positional += 1;
+        }
+    }
+
+    let mut found: Vec<String> = if partial.starts_with('-') {
+        command
+            .get_arguments()
+            .filter(|arg| !arg.is_hide_set())
+            .filter_map(|arg| arg.get_long())
+            .map(|long| format!("--{}", long))
+            .collect()
+    } else if command.has_subcommands() && positional == 0 {
+        command
+            .get_subcommands()
+            .filter(|sub| !sub.is_hide_set())
+            .flat_map(|sub| std::iter::once(sub.get_name()).chain(sub.get_visible_aliases()))
+            .map(str::to_string)
+            .collect()
+    } else {
+        match (top_level, repo) {
+            (Some(name), Some(repo)) => repo_names(repo, name, positional)?,
+            _ => Vec::new(),
+        }
+    };
+    found.retain(|candidate| candidate.starts_with(partial));
+    found.sort();
+    found.dedup();
+    Ok(found)
+}
+
+/// Whether `flag` (a word without its leading `-`) takes its value from
+/// the next word
+fn takes_value(command: &Command, flag: &str) -> bool {
+    let arg = match flag.strip_prefix('-') {
+        Some(long) if !long.contains('=') => command.get_arguments().find(|arg| arg.get_long() == Some(long)),
+        Some(_) => None,
+        None if flag.chars().count() == 1 => {
+            let short = flag.chars().next();
+            command.get_arguments().find(|arg| arg.get_short() == short)
+        }
+        None => None,
+    };
+    arg.is_some_and(|arg| arg.get_action().takes_values())
+}
+
+/// Names from the repository that suit positional argument `position` of
+/// top-level command `command`
+fn repo_names(repo: &Repository, command: &str, position: usize) -> Result<Vec<String>> {
+    let branches = || repo.branches();
+    let tags = || -> Result<Vec<String>> {
+        Ok(TagManager::new(repo.get_db().clone()).list()?.into_iter().map(|tag| tag.name).collect())
+    };
+    let remotes = || -> Result<Vec<String>> {
+        Ok(RemoteManager::new(repo.get_db().clone())
+            .list()?
+            .into_iter()
+            .map(|remote| remote.name)
+            .collect())
+    };
+
+    match (command, position) {
+        ("checkout" | "switch" | "merge" | "rebase" | "bookmark", 0) => branches(),
+        ("tag", 0) | ("delete-tag", 0) => tags(),
+        ("tag", 1) => branches(),
+        ("inspect", 0) => Ok(tags()?.into_iter().chain(branches()?).collect()),
+        ("push" | "pull", 0) | ("fetch", 0) => remotes(),
+        ("push" | "pull", 1) => branches(),
+        _ => Ok(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, ArgAction};
+    use tempfile::TempDir;
+
+    fn cli() -> Command {
+        Command::new("mug")
+            .arg(Arg::new("json").long("json").action(ArgAction::SetTrue))
+            .subcommand(Command::new("checkout").arg(Arg::new("branch")))
+            .subcommand(Command::new("inspect").visible_alias("show").arg(Arg::new("commit")))
+            .subcommand(
+                Command::new("push")
+                    .arg(Arg::new("remote"))
+                    .arg(Arg::new("branch"))
+                    .arg(Arg::new("dry-run").long("dry-run").action(ArgAction::SetTrue)),
+            )
+            .subcommand(
+                Command::new("tag")
+                    .arg(Arg::new("name"))
+                    .arg(Arg::new("message").short('m').long("message")),
+            )
+            .subcommand(Command::new("__complete").hide(true))
+    }
+
+    fn complete(repo: &Repository, line: &str) -> Vec<String> {
+        let mut words: Vec<String> = line.split(' ').map(str::to_string).collect();
+        words.remove(0);
+        candidates(&cli(), Some(repo), &words).unwrap()
+    }
+
+    #[test]
+    fn test_candidates_come_from_cli_and_repository() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        std::fs::write(dir.path().join("file.txt"), "content").unwrap();
+        repo.add("file.txt").unwrap();
+        let id = repo.commit("Tester".to_string(), "Initial".to_string()).unwrap();
+        repo.create_branch("feature".to_string()).unwrap();
+        TagManager::new(repo.get_db().clone()).create("v1.0".to_string(), id).unwrap();
+        RemoteManager::new(repo.get_db().clone()).add("origin", "http://localhost:1").unwrap();
+
+        assert_eq!(complete(&repo, "mug "), vec!["checkout", "inspect", "push", "show", "tag"]);
+        assert_eq!(complete(&repo, "mug --json ch"), vec!["checkout"]);
+        assert_eq!(complete(&repo, "mug checkout "), vec!["feature", "main"]);
+        assert_eq!(complete(&repo, "mug checkout f"), vec!["feature"]);
+        assert!(complete(&repo, "mug checkout main ").is_empty());
+        assert_eq!(complete(&repo, "mug show "), vec!["feature", "main", "v1.0"]);
+        assert_eq!(complete(&repo, "mug tag -m note "), vec!["v1.0"]);
+        assert_eq!(complete(&repo, "mug push "), vec!["origin"]);
+        assert_eq!(complete(&repo, "mug push --dry-run origin m"), vec!["main"]);
+        assert_eq!(complete(&repo, "mug push --d"), vec!["--dry-run"]);
+    }
+
+    #[test]
+    fn test_unknown_shell_is_refused() {
+        assert!(script("bash").unwrap().contains("__complete"));
+        assert!(script("powershell").is_err());
+    }
+}
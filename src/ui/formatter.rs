@@ -1,5 +1,6 @@
 /// Unicode output formatter for beautiful terminal output with colors
 use colored::Colorize;
+use serde::Serialize;
 use std::fmt::Write;
 
 pub struct UnicodeFormatter {
@@ -7,14 +8,28 @@ pub struct UnicodeFormatter {
     pub use_colors: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct CommitInfo {
     pub hash: String,
     pub author: String,
     pub date: String,
     pub message: String,
+    pub parents: Vec<String>,
     pub is_head: bool,
     pub branch: Option<String>,
+    /// Signature check result, shown when present
+    pub signature: Option<String>,
+}
+
+impl CommitInfo {
+    /// `<short-hash> <subject>`, as printed by `log --oneline`
+    pub fn oneline(&self) -> String {
+        format!(
+            "{} {}",
+            crate::core::hash::short_hash(&self.hash),
+            self.message.lines().next().unwrap_or("")
+        )
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -23,13 +38,25 @@ pub struct DiffHunk {
     pub added: usize,
     pub removed: usize,
     pub lines: Vec<DiffLine>,
+    /// Binary content, shown only as "Binary files differ"
+    pub binary: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum DiffLine {
     Added(String),
     Removed(String),
     Context(String),
+    /// A line edited in place, split into the words that changed
+    Changed(Vec<InlineSpan>),
+}
+
+/// Part of a changed line in a word diff
+#[derive(Debug, Clone, PartialEq)]
+pub enum InlineSpan {
+    Same(String),
+    Added(String),
+    Removed(String),
 }
 
 #[derive(Debug, Clone)]
@@ -126,7 +153,9 @@ impl UnicodeFormatter {
             // Main commit line
             let short_hash = &commit.hash[..8.min(commit.hash.len())];
             let hash_colored = self.colorize(short_hash, "yellow");
-            let message_colored = self.colorize(&commit.message, "white").bold().to_string();
+            let mut message_lines = commit.message.lines();
+            let subject = message_lines.next().unwrap_or("");
+            let message_colored = self.colorize(subject, "white").bold().to_string();
 
             writeln!(
                 &mut output,
@@ -150,6 +179,21 @@ impl UnicodeFormatter {
             let date_value = self.colorize(&commit.date, "white");
             writeln!(&mut output, "{}  {} {}", pipe, date_label, date_value).unwrap();
 
+            if let Some(ref signature) = commit.signature {
+                let signature_label = self.colorize("Signature:", "bright_cyan");
+                let signature_value = self.colorize(signature, "white");
+                writeln!(&mut output, "{}  {} {}", pipe, signature_label, signature_value).unwrap();
+            }
+
+            // Message body, if any
+            let body: Vec<&str> = message_lines.skip_while(|l| l.trim().is_empty()).collect();
+            if !body.is_empty() {
+                writeln!(&mut output, "{}", pipe).unwrap();
+                for line in body {
+                    writeln!(&mut output, "{}    {}", pipe, line).unwrap();
+                }
+            }
+
             // Separator
             if !is_last {
                 writeln!(&mut output, "{}", pipe).unwrap();
@@ -359,6 +403,16 @@ impl UnicodeFormatter {
             )
             .unwrap();
 
+            if hunk.binary {
+                writeln!(
+                    &mut output,
+                    "Binary files a/{} and b/{} differ\n",
+                    &hunk.file, &hunk.file
+                )
+                .unwrap();
+                continue;
+            }
+
             writeln!(
                 &mut output,
                 "{}",
@@ -412,6 +466,9 @@ impl UnicodeFormatter {
                         )
                         .unwrap();
                     }
+                    DiffLine::Changed(spans) => {
+                        writeln!(&mut output, "~{}", self.format_inline_spans(spans)).unwrap();
+                    }
                 }
             }
 
@@ -421,6 +478,23 @@ impl UnicodeFormatter {
         output
     }
 
+    /// A word-diffed line: removed words red and added words green, or
+    /// wrapped in `[-...-]` and `{+...+}` without colors
+    fn format_inline_spans(&self, spans: &[InlineSpan]) -> String {
+        // Colors can also be switched off globally, e.g. when piped
+        let colors = self.use_colors && colored::control::SHOULD_COLORIZE.should_colorize();
+        spans
+            .iter()
+            .map(|span| match span {
+                InlineSpan::Same(text) => text.clone(),
+                InlineSpan::Added(text) if colors => text.bright_green().underline().to_string(),
+                InlineSpan::Removed(text) if colors => text.red().strikethrough().to_string(),
+                InlineSpan::Added(text) => format!("{{+{}+}}", text),
+                InlineSpan::Removed(text) => format!("[-{}-]", text),
+            })
+            .collect()
+    }
+
     pub fn format_merge_conflict(&self, file: &str, ours: &str, theirs: &str) -> String {
         let mut output = String::new();
 
@@ -594,7 +668,7 @@ impl UnicodeFormatter {
                         ("-", "delete mode 100644".to_string(), "bright_red")
                     }
                     FileMode::Renamed(old_name) => {
-                        (">", format!("rename {} -> {}", old_name, &file.path), "magenta")
+                        (">", format!("rename {} ->", old_name), "magenta")
                     }
                 };
 
@@ -622,16 +696,20 @@ mod tests {
                 author: "Test Author".to_string(),
                 date: "2025-12-29".to_string(),
                 message: "Update docs".to_string(),
+                parents: vec!["def5678901234".to_string()],
                 is_head: true,
                 branch: Some("main".to_string()),
+                signature: None,
             },
             CommitInfo {
                 hash: "def5678901234".to_string(),
                 author: "Another Author".to_string(),
                 date: "2025-12-28".to_string(),
                 message: "Add feature".to_string(),
+                parents: vec![],
                 is_head: false,
                 branch: None,
+                signature: None,
             },
         ];
 
@@ -647,6 +725,24 @@ mod tests {
         assert!(bar.contains("50%"));
     }
 
+    #[test]
+    fn test_format_word_diff_without_colors() {
+        let formatter = UnicodeFormatter::new(false, false);
+        let hunk = DiffHunk {
+            file: "notes.txt".to_string(),
+            added: 1,
+            removed: 1,
+            lines: vec![DiffLine::Changed(vec![
+                InlineSpan::Same("the ".to_string()),
+                InlineSpan::Removed("quick".to_string()),
+                InlineSpan::Added("slow".to_string()),
+            ])],
+            binary: false,
+        };
+        let output = formatter.format_diff(&[hunk]);
+        assert!(output.contains("~the [-quick-]{+slow+}\n"));
+    }
+
     #[test]
     fn test_format_status() {
         let formatter = UnicodeFormatter::new(true, true);
@@ -667,14 +763,51 @@ mod tests {
             author: "Author".to_string(),
             date: "2025-12-29".to_string(),
             message: "Message".to_string(),
+            parents: vec![],
             is_head: true,
             branch: None,
+            signature: None,
         }];
 
         let output = formatter.format_log(&commits);
         assert!(output.contains("Message"));
     }
 
+    #[test]
+    fn test_commit_oneline() {
+        let commit = CommitInfo {
+            hash: "5166ce38-9bbd-4c6c-aed3-9613f4821afb".to_string(),
+            author: "Author".to_string(),
+            date: "2025-12-29".to_string(),
+            message: "Subject\n\nBody".to_string(),
+            parents: vec![],
+            is_head: true,
+            branch: None,
+            signature: None,
+        };
+        assert_eq!(commit.oneline(), "5166ce3 Subject");
+    }
+
+    #[test]
+    fn test_format_log_multiline_message() {
+        let formatter = UnicodeFormatter::new(false, false);
+        let commits = vec![CommitInfo {
+            hash: "abc1234".to_string(),
+            author: "Author".to_string(),
+            date: "2025-12-29".to_string(),
+            message: "Subject\n\nFirst body line\nSecond body line".to_string(),
+            parents: vec![],
+            is_head: true,
+            branch: None,
+            signature: None,
+        }];
+
+        let output = formatter.format_log(&commits);
+        assert!(output.contains("* abc1234 Subject\n"));
+        assert!(output.contains("|    First body line\n"));
+        assert!(output.contains("|    Second body line\n"));
+    }
+
     #[test]
     fn test_format_error() {
         let formatter = UnicodeFormatter::new(true, true);
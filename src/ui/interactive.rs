@@ -1,88 +1,248 @@
-/// Interactive branch selector - inline with shell visible
-use colored::Colorize;
-use std::io::{self, Write};
+/// Interactive branch selector with incremental fuzzy filtering
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    Frame, Terminal,
+};
+use std::io::{self, IsTerminal};
 
+use crate::core::error::{Error, Result};
+use crate::ui::terminal::TerminalGuard;
+
+/// How well `query` matches `candidate` as a case-insensitive subsequence,
+/// lower being better, or `None` if it does not match. Matches that start
+/// early and keep their characters together score best.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<usize> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    for wanted in query.to_lowercase().chars() {
+        let found = candidate[next..].iter().position(|&c| c == wanted)?;
+        // The first character's offset counts too, so prefixes rank first
+        score += found;
+        next += found + 1;
+    }
+    Some(score)
+}
+
+/// Branch list narrowed by a typed query
 pub struct BranchSelector {
     branches: Vec<String>,
     current: String,
+    query: String,
+    /// Indices into `branches` that match the query, best first
+    visible: Vec<usize>,
+    /// Position in `visible`
+    selected: usize,
 }
 
 impl BranchSelector {
     pub fn new(branches: Vec<String>, current: String) -> Self {
-        BranchSelector {
+        let mut selector = BranchSelector {
             branches,
             current,
+            query: String::new(),
+            visible: Vec::new(),
+            selected: 0,
+        };
+        selector.refilter();
+        selector.selected = selector
+            .visible
+            .iter()
+            .position(|&i| selector.branches[i] == selector.current)
+            .unwrap_or(0);
+        selector
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Branches matching the query, best match first
+    pub fn visible(&self) -> Vec<&str> {
+        self.visible.iter().map(|&i| self.branches[i].as_str()).collect()
+    }
+
+    pub fn push_char(&mut self, c: char) {
+        self.query.push(c);
+        self.refilter();
+    }
+
+    pub fn pop_char(&mut self) {
+        self.query.pop();
+        self.refilter();
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.visible.len() {
+            self.selected += 1;
         }
     }
 
-    pub fn display_with_numbers(&self) {
-        println!();
-        println!("{}", "Select a branch:".bright_cyan().bold());
-        
-        for (idx, branch) in self.branches.iter().enumerate() {
-            let number = (idx + 1).to_string().bright_yellow().bold();
-            
-            if branch == &self.current {
-                println!("  {} {} {} {}", 
-                    number,
-                    "●".bright_green(),
-                    branch.bright_green().bold(),
-                    "(current)".bright_green().italic()
-                );
-            } else {
-                println!("  {} {} {}",
-                    number,
-                    "○".cyan(),
-                    branch.white()
-                );
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// The highlighted branch, if any branch matches
+    pub fn selected_branch(&self) -> Option<&str> {
+        self.visible
+            .get(self.selected)
+            .map(|&i| self.branches[i].as_str())
+    }
+
+    fn refilter(&mut self) {
+        let mut scored: Vec<(usize, usize)> = self
+            .branches
+            .iter()
+            .enumerate()
+            .filter_map(|(i, branch)| fuzzy_score(&self.query, branch).map(|score| (score, i)))
+            .collect();
+        // Stable, so equally good matches keep their listed order
+        scored.sort_by_key(|&(score, _)| score);
+        self.visible = scored.into_iter().map(|(_, i)| i).collect();
+        self.selected = 0;
+    }
+
+    /// Run the selector; returns `None` if the user cancelled
+    pub fn run(mut self) -> Result<Option<String>> {
+        let tui_error = |e: io::Error| Error::Custom(e.to_string());
+        // Restores the terminal however the selector exits
+        let _guard = TerminalGuard::new()?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout())).map_err(tui_error)?;
+        loop {
+            terminal.draw(|f| ui(f, &self)).map_err(tui_error)?;
+
+            if let Event::Key(key) = event::read().map_err(tui_error)? {
+                let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                match key.code {
+                    KeyCode::Esc => return Ok(None),
+                    KeyCode::Char('c') if ctrl => return Ok(None),
+                    KeyCode::Enter => return Ok(self.selected_branch().map(str::to_string)),
+                    KeyCode::Up => self.select_prev(),
+                    KeyCode::Down => self.select_next(),
+                    KeyCode::Char('p') if ctrl => self.select_prev(),
+                    KeyCode::Char('n') if ctrl => self.select_next(),
+                    KeyCode::Backspace => self.pop_char(),
+                    KeyCode::Char(c) if !ctrl => self.push_char(c),
+                    _ => {}
+                }
             }
         }
-        println!();
-    }
-
-    pub fn prompt_user(&self) -> Option<String> {
-        // Display branches with numbers
-        self.display_with_numbers();
-        
-        // Prompt user
-        print!("{} ", "Enter branch number or name (or press Enter to skip):".bright_cyan());
-        io::stdout().flush().ok()?;
-        
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).ok()?;
-        
-        let input = input.trim();
-        
-        if input.is_empty() {
-            return None;
-        }
-        
-        // Try parsing as number
-        if let Ok(num) = input.parse::<usize>() {
-            if num > 0 && num <= self.branches.len() {
-                return Some(self.branches[num - 1].clone());
+    }
+}
+
+fn ui(f: &mut Frame, selector: &BranchSelector) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([Constraint::Length(3), Constraint::Min(5), Constraint::Length(3)].as_ref())
+        .split(f.size());
+
+    let query = Paragraph::new(Line::from(vec![
+        Span::styled("> ", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+        Span::raw(selector.query()),
+    ]))
+    .block(Block::default().title("Filter").borders(Borders::ALL));
+    f.render_widget(query, chunks[0]);
+
+    let items: Vec<ListItem> = selector
+        .visible()
+        .into_iter()
+        .map(|branch| {
+            if branch == selector.current {
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        format!("● {}", branch),
+                        Style::default().fg(Color::Green).add_modifier(Modifier::BOLD),
+                    ),
+                    Span::styled(" (current)", Style::default().fg(Color::Green)),
+                ]))
             } else {
-                println!("{}", "Invalid number!".red());
-                return None;
+                ListItem::new(Line::from(format!("○ {}", branch)))
             }
-        }
-        
-        // Try matching by name
-        if let Some(matched) = self.branches.iter().find(|b| b.contains(input) || input.contains(b.as_str())) {
-            return Some(matched.clone());
-        }
-        
-        println!("{}", "Branch not found!".red());
-        None
-    }
+        })
+        .collect();
+    let title = format!("Branches ({}/{})", selector.visible.len(), selector.branches.len());
+    let list = List::new(items)
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .highlight_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD));
+    let mut state = ListState::default();
+    state.select(selector.selected_branch().map(|_| selector.selected));
+    f.render_stateful_widget(list, chunks[1], &mut state);
+
+    let help = Paragraph::new(Line::from(vec![
+        Span::raw("Type to filter  "),
+        Span::styled("↑/↓", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+        Span::raw(" Move  "),
+        Span::styled("Enter", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+        Span::raw(" Switch  "),
+        Span::styled("Esc", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+        Span::raw(" Cancel"),
+    ]))
+    .block(Block::default().borders(Borders::ALL));
+    f.render_widget(help, chunks[2]);
 }
 
-/// Simple interactive branch selector with inline display
+/// Let the user pick a branch, filtering as they type.
+///
+/// Returns `None` without prompting when stdin is not a terminal, so
+/// scripts and CI never block waiting for input.
 pub fn select_branch_interactive(branches: Vec<String>, current: String) -> Option<String> {
-    if branches.is_empty() {
+    if branches.is_empty() || !io::stdin().is_terminal() {
         return None;
     }
 
-    let selector = BranchSelector::new(branches, current);
-    selector.prompt_user()
+    BranchSelector::new(branches, current).run().ok().flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selector() -> BranchSelector {
+        let branches = ["main", "feature/login", "feature/logout", "fix/lint"]
+            .iter()
+            .map(|b| b.to_string())
+            .collect();
+        BranchSelector::new(branches, "feature/logout".to_string())
+    }
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert_eq!(fuzzy_score("", "main"), Some(0));
+        assert_eq!(fuzzy_score("MAI", "main"), Some(0));
+        assert!(fuzzy_score("fl", "feature/login").is_some());
+        assert!(fuzzy_score("lf", "feature/login").is_none());
+        assert!(fuzzy_score("fix", "fix/lint") < fuzzy_score("fix", "feature/fix"));
+    }
+
+    #[test]
+    fn test_selector_starts_on_current_branch() {
+        let selector = selector();
+        assert_eq!(selector.selected_branch(), Some("feature/logout"));
+        assert_eq!(selector.visible().len(), 4);
+    }
+
+    #[test]
+    fn test_typing_narrows_and_navigation_stays_in_filter() {
+        let mut selector = selector();
+        for c in "flo".chars() {
+            selector.push_char(c);
+        }
+        assert_eq!(selector.visible(), vec!["feature/login", "feature/logout"]);
+        assert_eq!(selector.selected_branch(), Some("feature/login"));
+
+        selector.select_next();
+        selector.select_next();
+        assert_eq!(selector.selected_branch(), Some("feature/logout"));
+
+        selector.push_char('x');
+        assert_eq!(selector.selected_branch(), None);
+        selector.pop_char();
+        assert_eq!(selector.visible().len(), 2);
+    }
 }
@@ -1,5 +1,11 @@
+pub mod completion;
 pub mod formatter;
 pub mod interactive;
+pub mod pager;
+pub mod progress;
+pub mod terminal;
 
-pub use formatter::{UnicodeFormatter, CommitInfo, DiffHunk, DiffLine, CommitStats, FileChange, FileMode};
+pub use formatter::{UnicodeFormatter, CommitInfo, DiffHunk, DiffLine, InlineSpan, CommitStats, FileChange, FileMode};
 pub use interactive::{BranchSelector, select_branch_interactive};
+pub use progress::{ProgressBar, ProgressReporter};
+pub use terminal::TerminalGuard;
@@ -0,0 +1,77 @@
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+use crate::core::error::Result;
+
+/// Pager used when `$PAGER` is unset; `-R` keeps colors intact
+const DEFAULT_PAGER: &str = "less -R";
+
+/// The pager command to run, or `None` when paging is disabled.
+///
+/// `$PAGER` wins over the default; an empty value or `cat` disables paging.
+pub fn pager_command(pager_env: Option<&str>) -> Option<Vec<String>> {
+    let command = pager_env.unwrap_or(DEFAULT_PAGER).trim();
+    if command.is_empty() || command == "cat" {
+        return None;
+    }
+    Some(command.split_whitespace().map(String::from).collect())
+}
+
+/// Print `text` through a pager when stdout is a terminal and `enabled` is
+/// set, falling back to plain output when piped or the pager can't start
+pub fn page(text: &str, enabled: bool) -> Result<()> {
+    let command = if enabled && io::stdout().is_terminal() {
+        pager_command(env::var("PAGER").ok().as_deref())
+    } else {
+        None
+    };
+    let Some(command) = command else {
+        return print_all(text);
+    };
+
+    let mut child = match Command::new(&command[0])
+        .args(&command[1..])
+        // Let `less` exit at once when everything fits on one screen
+        .env("LESS", env::var("LESS").unwrap_or_else(|_| "FRX".to_string()))
+        .stdin(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(_) => return print_all(text),
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // The user quitting the pager early closes the pipe; that's not an error
+        match stdin.write_all(text.as_bytes()) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e.into()),
+            _ => {}
+        }
+    }
+    child.wait()?;
+    Ok(())
+}
+
+fn print_all(text: &str) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    match stdout.write_all(text.as_bytes()) {
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pager_command() {
+        assert_eq!(
+            pager_command(None),
+            Some(vec!["less".to_string(), "-R".to_string()])
+        );
+        assert_eq!(pager_command(Some("more")), Some(vec!["more".to_string()]));
+        assert_eq!(pager_command(Some("")), None);
+        assert_eq!(pager_command(Some("cat")), None);
+    }
+}
@@ -0,0 +1,144 @@
+use std::io::{self, IsTerminal, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::ui::formatter::UnicodeFormatter;
+
+/// Percentage step between lines when stdout isn't a terminal
+const PLAIN_STEP: u64 = 10;
+
+/// A live progress bar for one phase of a long operation.
+///
+/// On a terminal the bar is redrawn in place; otherwise a plain percentage
+/// line is printed each time another 10% completes.
+pub struct ProgressBar {
+    label: String,
+    formatter: UnicodeFormatter,
+    tty: bool,
+    /// Last percentage printed in plain mode
+    last_percent: Option<u64>,
+    drawn: bool,
+}
+
+impl ProgressBar {
+    pub fn new(label: &str) -> Self {
+        Self::with_tty(label, io::stdout().is_terminal())
+    }
+
+    fn with_tty(label: &str, tty: bool) -> Self {
+        ProgressBar {
+            label: label.to_string(),
+            formatter: UnicodeFormatter::new(tty, tty),
+            tty,
+            last_percent: None,
+            drawn: false,
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Show `current` out of `total`, with `detail` (e.g. "3/10 chunks")
+    /// after the bar
+    pub fn update(&mut self, current: u64, total: u64, detail: &str) {
+        if let Some(line) = self.render(current, total, detail) {
+            let mut stdout = io::stdout().lock();
+            let _ = if self.tty {
+                write!(stdout, "\r\x1b[K{}", line)
+            } else {
+                writeln!(stdout, "{}", line)
+            };
+            let _ = stdout.flush();
+            self.drawn = true;
+        }
+    }
+
+    /// End the bar's line
+    pub fn finish(&mut self) {
+        if self.tty && self.drawn {
+            println!();
+        }
+        self.drawn = false;
+    }
+
+    /// The text to print for this update, or `None` if nothing changed
+    /// enough to be worth printing
+    fn render(&mut self, current: u64, total: u64, detail: &str) -> Option<String> {
+        let percent = (current.min(total) * 100).checked_div(total).unwrap_or(0);
+        if self.tty {
+            let bar = self.formatter.format_progress_bar(current.min(total), total);
+            return Some(format!("{}: {} ({})", self.label, bar, detail));
+        }
+
+        let step = percent / PLAIN_STEP * PLAIN_STEP;
+        if total == 0 || self.last_percent.is_some_and(|last| step <= last) {
+            return None;
+        }
+        self.last_percent = Some(step);
+        Some(format!("{}: {}% ({})", self.label, step, detail))
+    }
+}
+
+/// A progress display shared across threads that starts a new bar whenever
+/// the phase label changes
+#[derive(Clone, Default)]
+pub struct ProgressReporter {
+    bar: Arc<Mutex<Option<ProgressBar>>>,
+}
+
+impl ProgressReporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&self, phase: &str, current: u64, total: u64, detail: &str) {
+        let mut bar = self.bar.lock().unwrap();
+        if bar.as_ref().is_none_or(|b| b.label() != phase) {
+            if let Some(previous) = bar.as_mut() {
+                previous.finish();
+            }
+            *bar = Some(ProgressBar::new(phase));
+        }
+        if let Some(bar) = bar.as_mut() {
+            bar.update(current, total, detail);
+        }
+    }
+
+    /// End the current bar, if any
+    pub fn finish(&self) {
+        if let Some(mut bar) = self.bar.lock().unwrap().take() {
+            bar.finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_progress_prints_every_ten_percent() {
+        let mut bar = ProgressBar::with_tty("Receiving objects", false);
+        let lines: Vec<String> = (0..=20)
+            .filter_map(|i| bar.render(i, 20, &format!("{}/20", i)))
+            .collect();
+        assert_eq!(lines.len(), 11);
+        assert_eq!(lines[0], "Receiving objects: 0% (0/20)");
+        assert_eq!(lines[5], "Receiving objects: 50% (10/20)");
+        assert_eq!(lines[10], "Receiving objects: 100% (20/20)");
+
+        // Unknown totals print nothing
+        assert!(ProgressBar::with_tty("x", false).render(5, 0, "").is_none());
+    }
+
+    #[test]
+    fn test_terminal_progress_redraws_bar() {
+        let mut bar = ProgressBar::with_tty("Writing objects", true);
+        let first = bar.render(1, 4, "1/4").unwrap();
+        let second = bar.render(1, 4, "1/4").unwrap();
+        assert_eq!(first, second);
+        assert!(first.starts_with("Writing objects: "));
+        assert!(first.contains("25%"));
+        assert!(first.ends_with("(1/4)"));
+    }
+}
@@ -0,0 +1,59 @@
+/// Terminal setup and teardown shared by the TUIs
+use crossterm::{
+    cursor, execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+
+use crate::core::error::{Error, Result};
+
+/// Puts the terminal in raw mode on an alternate screen with the cursor
+/// hidden, and restores it when dropped, so every exit path (return, error,
+/// or panic) leaves the user's shell usable.
+pub struct TerminalGuard;
+
+static PANIC_HOOK: Once = Once::new();
+/// Whether a guard currently holds the terminal
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+impl TerminalGuard {
+    pub fn new() -> Result<Self> {
+        // The guard only restores the terminal once unwinding reaches it,
+        // after the panic message has gone to the alternate screen and been
+        // lost, so restore it before the message is printed as well
+        PANIC_HOOK.call_once(|| {
+            let default_hook = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                if ACTIVE.load(Ordering::SeqCst) {
+                    restore();
+                }
+                default_hook(info);
+            }));
+        });
+
+        enable_raw_mode().map_err(|e| Error::Custom(e.to_string()))?;
+        // Constructed before the remaining setup so a failure there still
+        // disables raw mode
+        let guard = TerminalGuard;
+        ACTIVE.store(true, Ordering::SeqCst);
+        execute!(io::stdout(), EnterAlternateScreen, cursor::Hide)
+            .map_err(|e| Error::Custom(e.to_string()))?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+/// Leave raw mode and the alternate screen. Nothing useful can be done if
+/// this fails, and it may run while panicking, so errors are ignored.
+fn restore() {
+    ACTIVE.store(false, Ordering::SeqCst);
+    let _ = execute!(io::stdout(), cursor::Show, LeaveAlternateScreen);
+    let _ = disable_raw_mode();
+}